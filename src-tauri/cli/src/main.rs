@@ -0,0 +1,318 @@
+//! Headless command-line interface to the editor's project tools, for
+//! scripts and CI runs without a display.
+//!
+//! Every command prints a JSON result on stdout. The exit code is 0 on
+//! success, 1 when the command found problems (validation errors, EPUB
+//! errors, or unformatted chapters with `--check`), and 2 when it could
+//! not run; the error is then printed on stderr as `{"error": {...}}`.
+
+use std::fs;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use clap::{Parser, Subcommand, ValueEnum};
+use serde::Serialize;
+
+use editor_core::backup::BackupTracker;
+use editor_core::error::{EditorError, EditorResult, ErrorKind};
+use editor_core::export::{self, ChapterForm};
+use editor_core::{
+    docx_import, epub, epub_check, epub_import, format, git, html_parser, manifest, markdown,
+    markdown_import, preview, single_file, site, stats, validate,
+};
+
+#[derive(Parser)]
+#[command(name = "html-editor", version, about = "Batch operations on HTML book projects")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// List chapters in manifest order
+    List {
+        /// Project folder
+        project: PathBuf,
+    },
+    /// Check encoding, reassembly, relative links, and the manifest
+    Validate {
+        project: PathBuf,
+    },
+    /// Normalize line endings and trailing whitespace of every chapter
+    Format {
+        project: PathBuf,
+        /// Report chapters that need formatting without changing them
+        #[arg(long)]
+        check: bool,
+    },
+    /// Count words, characters, and elements per chapter
+    Stats {
+        project: PathBuf,
+    },
+    /// Write every chapter as a complete document, with assets, to a folder
+    Export {
+        project: PathBuf,
+        /// Output folder
+        #[arg(long, short)]
+        out: PathBuf,
+    },
+    /// Write the chapters, in manifest order, as an EPUB 3 book
+    Epub {
+        project: PathBuf,
+        /// Output .epub file
+        #[arg(long, short)]
+        out: PathBuf,
+    },
+    /// Write a chapter, or the whole book, as one HTML file with styles and images embedded
+    SingleFile {
+        project: PathBuf,
+        /// Project-relative chapter to export; the whole book if omitted
+        #[arg(long)]
+        chapter: Option<String>,
+        /// Output .html file
+        #[arg(long, short)]
+        out: PathBuf,
+    },
+    /// Write the chapters as a static website with navigation, rewriting only changed files
+    Site {
+        project: PathBuf,
+        /// Output folder
+        #[arg(long, short)]
+        out: PathBuf,
+    },
+    /// Serve the project on a local port with live reload until interrupted
+    Preview {
+        project: PathBuf,
+        /// Port to listen on; a free one if omitted
+        #[arg(long, default_value_t = 0)]
+        port: u16,
+    },
+    /// Check the packaging, manifest, spine, navigation, and XHTML of an EPUB
+    CheckEpub {
+        /// EPUB file to check
+        book: PathBuf,
+    },
+    /// Unpack an EPUB 2 or 3 book into a new project folder
+    ImportEpub {
+        /// EPUB file to import
+        book: PathBuf,
+        /// Project folder to create; must be empty if it exists
+        project: PathBuf,
+    },
+    /// Import a Word document into a project as new chapters
+    ImportDocx {
+        /// .docx file to import
+        document: PathBuf,
+        project: PathBuf,
+    },
+    /// Convert a folder of Markdown files into the chapters of a new project
+    ImportMarkdown {
+        /// Folder of .md files to convert
+        source: PathBuf,
+        /// Project folder to create; must be empty if it exists
+        project: PathBuf,
+    },
+    /// Convert a Markdown file to an HTML document, front matter included
+    FromMarkdown {
+        /// Markdown file to convert
+        input: PathBuf,
+        /// Output .html file
+        #[arg(long, short)]
+        out: PathBuf,
+    },
+    /// Convert an HTML chapter to Markdown with YAML front matter
+    ToMarkdown {
+        /// HTML chapter to convert
+        input: PathBuf,
+        /// Output .md file
+        #[arg(long, short)]
+        out: PathBuf,
+    },
+    /// Convert chapters between complete documents and body fragments
+    Convert {
+        project: PathBuf,
+        /// Form to convert chapters to
+        #[arg(long, value_enum)]
+        to: Form,
+        /// Write converted chapters here instead of in place
+        #[arg(long, short)]
+        out: Option<PathBuf>,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Form {
+    Document,
+    Fragment,
+}
+
+/// Result of `format`.
+#[derive(Serialize)]
+struct FormatResult {
+    check: bool,
+    changed: Vec<String>,
+}
+
+/// Result of `from-markdown` and `to-markdown`.
+#[derive(Serialize)]
+struct ConvertedFile {
+    written: PathBuf,
+    warnings: Vec<String>,
+}
+
+/// Result of `preview`, printed once the server listens.
+#[derive(Serialize)]
+struct PreviewResult {
+    url: String,
+}
+
+/// Result of `convert`.
+#[derive(Serialize)]
+struct ConvertResult {
+    converted: Vec<String>,
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    match run(cli.command) {
+        Ok(code) => code,
+        Err(err) => {
+            eprintln!("{}", serde_json::json!({ "error": err }));
+            ExitCode::from(2)
+        }
+    }
+}
+
+/// Run a command, print its result, and choose the exit code.
+fn run(command: Command) -> EditorResult<ExitCode> {
+    match command {
+        Command::List { project } => {
+            let mut chapters = manifest::list_chapters(&project)
+                .map_err(|e| EditorError::io("Failed to list chapters", e).with_path(&project))?;
+            git::annotate_status(&project, &mut chapters);
+            print_json(&chapters)?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Command::Validate { project } => {
+            let report = validate::validate_project(&project)
+                .map_err(|e| EditorError::io("Failed to validate project", e).with_path(&project))?;
+            print_json(&report)?;
+            Ok(problems_exit(!report.is_valid()))
+        }
+        Command::Format { project, check } => {
+            let tracker = BackupTracker::new();
+            let changed = format::format_project(&project, check, &tracker)?;
+            let needs_format = check && !changed.is_empty();
+            print_json(&FormatResult { check, changed })?;
+            Ok(problems_exit(needs_format))
+        }
+        Command::Stats { project } => {
+            let stats = stats::project_stats(&project)
+                .map_err(|e| EditorError::io("Failed to count project", e).with_path(&project))?;
+            print_json(&stats)?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Command::Export { project, out } => {
+            let summary = export::export_project(&project, &out)
+                .map_err(|e| EditorError::write("Failed to export project", e).with_path(&out))?;
+            print_json(&summary)?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Command::Epub { project, out } => {
+            let summary = epub::export_epub(&project, &out, None)?;
+            print_json(&summary)?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Command::SingleFile { project, chapter, out } => {
+            let summary = single_file::export_single_file(&project, chapter.as_deref(), &out)?;
+            print_json(&summary)?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Command::Site { project, out } => {
+            let summary = site::export_site(&project, &out)?;
+            print_json(&summary)?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Command::Preview { project, port } => {
+            let server = preview::PreviewServer::start(&project, port)
+                .map_err(|e| EditorError::io("Failed to start the preview server", e).with_path(&project))?;
+            print_json(&PreviewResult { url: server.url() })?;
+            loop {
+                std::thread::park();
+            }
+        }
+        Command::CheckEpub { book } => {
+            let report = epub_check::check_epub(&book)?;
+            print_json(&report)?;
+            Ok(problems_exit(!report.is_valid()))
+        }
+        Command::ImportEpub { book, project } => {
+            let imported = epub_import::import_epub(&book, &project)?;
+            print_json(&imported)?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Command::ImportDocx { document, project } => {
+            let imported = docx_import::import_docx(&document, &project)?;
+            print_json(&imported)?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Command::ImportMarkdown { source, project } => {
+            let imported = markdown_import::import_markdown_folder(&source, &project)?;
+            print_json(&imported)?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Command::FromMarkdown { input, out } => {
+            let markdown = fs::read_to_string(&input)
+                .map_err(|e| EditorError::io("Failed to read Markdown", e).with_path(&input))?;
+            let converted = markdown_import::markdown_to_html(&markdown);
+            fs::write(&out, converted.document())
+                .map_err(|e| EditorError::write("Failed to write HTML", e).with_path(&out))?;
+            print_json(&ConvertedFile { written: out, warnings: converted.warnings })?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Command::ToMarkdown { input, out } => {
+            let html = fs::read_to_string(&input)
+                .map_err(|e| EditorError::io("Failed to read chapter", e).with_path(&input))?;
+            let split = html_parser::split_html(&html);
+            let converted = markdown::html_to_markdown(&split.head_content, &split.body_content);
+            fs::write(&out, &converted.markdown)
+                .map_err(|e| EditorError::write("Failed to write Markdown", e).with_path(&out))?;
+            print_json(&ConvertedFile { written: out, warnings: converted.warnings })?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Command::Convert { project, to, out } => {
+            let form = match to {
+                Form::Document => ChapterForm::Document,
+                Form::Fragment => ChapterForm::Fragment,
+            };
+            let tracker = BackupTracker::new();
+            let converted = export::convert_chapters(&project, form, out.as_deref(), &tracker)?;
+            print_json(&ConvertResult { converted })?;
+            Ok(ExitCode::SUCCESS)
+        }
+    }
+}
+
+/// Exit code 1 if the command found problems.
+fn problems_exit(found: bool) -> ExitCode {
+    if found {
+        ExitCode::from(1)
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+/// Print a value as pretty JSON on stdout.
+/// A closed pipe (as with `| head`) is not an error.
+fn print_json<T: Serialize>(value: &T) -> EditorResult<()> {
+    let text = serde_json::to_string_pretty(value)
+        .map_err(|e| EditorError::new(ErrorKind::Io, format!("Failed to encode result: {}", e)))?;
+    match writeln!(io::stdout().lock(), "{}", text) {
+        Err(e) if e.kind() != io::ErrorKind::BrokenPipe => {
+            Err(EditorError::io("Failed to write result", e))
+        }
+        _ => Ok(()),
+    }
+}
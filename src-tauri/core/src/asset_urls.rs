@@ -0,0 +1,166 @@
+use crate::links;
+
+/// Attributes rewritten to the asset scheme when a chapter is loaded.
+const ASSET_ATTRIBUTES: [&str; 2] = ["src", "poster"];
+
+/// URL prefix of the asset scheme as seen by the webview.
+/// Windows and Android webviews reach custom schemes over http.
+#[cfg(any(windows, target_os = "android"))]
+pub const URL_PREFIX: &str = "http://book.localhost/";
+#[cfg(not(any(windows, target_os = "android")))]
+pub const URL_PREFIX: &str = "book://localhost/";
+
+/// Rewrite relative `src`/`poster` URLs in a chapter body to the asset
+/// scheme so the webview can load them. `chapter` is the chapter's
+/// project-relative path.
+/// The URL text is kept verbatim after the chapter's folder; the webview
+/// resolves `./`, `..` and spaces itself, and `from_asset_urls` can give
+/// back exactly what the author wrote.
+pub fn to_asset_urls(body: &str, chapter: &str) -> String {
+    let base = chapter_base(chapter);
+    let mut replacements: Vec<(usize, usize, String)> = Vec::new();
+    for span in links::attribute_spans(body, &ASSET_ATTRIBUTES) {
+        let url = &body[span.start..span.end];
+        let (path_part, _) = links::split_url(url);
+        if path_part.is_empty() || !links::is_relative_url(path_part) {
+            continue;
+        }
+        if links::resolve_relative(chapter, &links::percent_decode(path_part)).is_some() {
+            replacements.push((span.start, span.end, format!("{}{}", base, url)));
+        }
+    }
+    links::replace_spans(body, replacements)
+}
+
+/// Reverse `to_asset_urls` before saving: asset scheme URLs become
+/// relative to the chapter again. URLs under the chapter's own folder are
+/// restored as written; others (e.g. pasted from another chapter) are
+/// made relative to the chapter.
+pub fn from_asset_urls(body: &str, chapter: &str) -> String {
+    let base = chapter_base(chapter);
+    let mut replacements: Vec<(usize, usize, String)> = Vec::new();
+    for span in links::attribute_spans(body, &ASSET_ATTRIBUTES) {
+        let url = &body[span.start..span.end];
+        if let Some(original) = url.strip_prefix(base.as_str()) {
+            replacements.push((span.start, span.end, original.to_string()));
+            continue;
+        }
+        let Some(rest) = url.strip_prefix(URL_PREFIX) else {
+            continue;
+        };
+        let (path_part, suffix) = links::split_url(rest);
+        let target = links::percent_decode(path_part);
+        let relative = format!("{}{}", links::relative_url(chapter, &target), suffix);
+        replacements.push((span.start, span.end, relative));
+    }
+    links::replace_spans(body, replacements)
+}
+
+/// Asset scheme URL of the folder holding `chapter`, with a trailing slash.
+fn chapter_base(chapter: &str) -> String {
+    match chapter.rsplit_once('/') {
+        Some((dir, _)) if !dir.is_empty() => format!("{}{}/", URL_PREFIX, links::encode_path(dir)),
+        _ => URL_PREFIX.to_string(),
+    }
+}
+
+/// Rewrite relative `url(...)` references in a stylesheet to the asset
+/// scheme. `sheet` is the stylesheet's project-relative path.
+pub fn css_to_asset_urls(css: &str, sheet: &str) -> String {
+    let mut replacements: Vec<(usize, usize, String)> = Vec::new();
+    for (start, end) in css_url_spans(css) {
+        let url = &css[start..end];
+        let (path_part, suffix) = links::split_url(url);
+        if path_part.is_empty() || !links::is_relative_url(path_part) {
+            continue;
+        }
+        if let Some(target) = links::resolve_relative(sheet, &links::percent_decode(path_part)) {
+            let asset_url = format!("{}{}{}", URL_PREFIX, links::encode_path(&target), suffix);
+            replacements.push((start, end, asset_url));
+        }
+    }
+    links::replace_spans(css, replacements)
+}
+
+/// Byte ranges of the URLs inside each `url(...)` of a stylesheet,
+/// excluding surrounding whitespace and quotes.
+pub fn css_url_spans(css: &str) -> Vec<(usize, usize)> {
+    let mut spans = Vec::new();
+    let mut search = 0;
+
+    while let Some(found) = css[search..].find("url(") {
+        let open = search + found + 4;
+        let Some(close) = css[open..].find(')').map(|p| open + p) else {
+            break;
+        };
+        search = close;
+
+        let raw = &css[open..close];
+        let lead = raw.len() - raw.trim_start().len();
+        let trimmed = raw.trim();
+        // Only a closing quote that matches the opening one is dropped
+        let (start, url) = match trimmed.chars().next() {
+            Some(quote @ ('"' | '\'')) => {
+                let inner = &trimmed[1..];
+                (open + lead + 1, inner.strip_suffix(quote).unwrap_or(inner))
+            }
+            _ => (open + lead, trimmed),
+        };
+        spans.push((start, start + url.len()));
+    }
+    spans
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_asset_url_round_trip() {
+        let body = r#"<p><img src="../images/fig 1.png" alt="x"><a href="ch02.html">2</a>
+<img src="https://example.com/remote.png"><video poster="poster.jpg#t"></video></p>"#;
+        let served = to_asset_urls(body, "part1/ch01.html");
+        assert!(served.contains(&format!("src=\"{}part1/../images/fig 1.png\"", URL_PREFIX)));
+        assert!(served.contains(&format!("poster=\"{}part1/poster.jpg#t\"", URL_PREFIX)));
+        assert!(served.contains("href=\"ch02.html\""));
+        assert!(served.contains("src=\"https://example.com/remote.png\""));
+
+        let saved = from_asset_urls(&served, "part1/ch01.html");
+        assert_eq!(saved, body);
+    }
+
+    #[test]
+    fn test_asset_url_load_save_identity() {
+        let urls = [
+            "./img.png", "fig 1.png", "my%20fig.png", "a/../b.png",
+            "images/x.png?v=2", "sub/./deep/y.svg#icon",
+        ];
+        for chapter in ["ch01.html", "part 1/ch01.html"] {
+            for url in urls {
+                let body = format!("<p><img src=\"{}\"></p>", url);
+                let served = to_asset_urls(&body, chapter);
+                assert_ne!(served, body, "{} in {}", url, chapter);
+                assert_eq!(from_asset_urls(&served, chapter), body, "{} in {}", url, chapter);
+            }
+        }
+
+        // An asset URL from another folder is made relative to the chapter
+        let pasted = format!("<img src=\"{}images/a%20b.png\">", URL_PREFIX);
+        assert_eq!(from_asset_urls(&pasted, "part1/ch01.html"), "<img src=\"../images/a%20b.png\">");
+    }
+
+    #[test]
+    fn test_css_asset_urls() {
+        let css = "@font-face { src: url('../fonts/a.woff2') format('woff2'); }\n\
+                   body { background: url(bg.png); }\nh1 { background: url(data:image/png;base64,AA); }";
+        let served = css_to_asset_urls(css, "css/book.css");
+        assert!(served.contains(&format!("url('{}fonts/a.woff2')", URL_PREFIX)));
+        assert!(served.contains(&format!("url({}css/bg.png)", URL_PREFIX)));
+        assert!(served.contains("url(data:image/png;base64,AA)"));
+
+        // An unterminated quote keeps its last character, even when not ASCII
+        let css = "a { b: url(' é) } c { d: url(\"x.png') }";
+        assert_eq!(css_url_spans(css).iter().map(|&(s, e)| &css[s..e]).collect::<Vec<_>>(), vec![" é", "x.png'"]);
+        assert!(css_to_asset_urls(css, "book.css").contains(&format!("url('{}%20é)", URL_PREFIX)));
+    }
+}
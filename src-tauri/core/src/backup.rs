@@ -0,0 +1,112 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::error::{EditorError, EditorResult, ErrorKind};
+use crate::manifest::Manifest;
+use crate::project;
+
+/// Tracks which files have been backed up in this session.
+/// Only the first save per file creates a .bak copy.
+/// Backups go beside the file, or under the manifest's `backup_dir`
+/// (mirroring the project layout) when the file lies in a project.
+pub struct BackupTracker {
+    backed_up: Mutex<HashSet<PathBuf>>,
+}
+
+impl Default for BackupTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BackupTracker {
+    /// Create a new empty tracker.
+    pub fn new() -> Self {
+        BackupTracker {
+            backed_up: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// Create a backup if this file has not been backed up yet in this session.
+    /// Returns Ok(true) if a backup was created, Ok(false) if already backed up.
+    pub fn backup_if_needed(&self, path: &Path, root: Option<&Path>) -> EditorResult<bool> {
+        let canonical = path.to_path_buf();
+        let mut set = self.backed_up.lock()
+            .map_err(|e| EditorError::new(ErrorKind::BackupFailed, format!("Lock error: {}", e)))?;
+
+        if set.contains(&canonical) {
+            return Ok(false);
+        }
+
+        // Only back up if the file exists
+        if path.exists() {
+            let backup_dir = match root {
+                Some(root) => backup_dir_for(root, path)?,
+                None => None,
+            };
+            project::create_backup(path, backup_dir.as_deref())
+                .map_err(|e| {
+                    EditorError::new(ErrorKind::BackupFailed, format!("Backup failed for {}: {}", path.display(), e))
+                        .with_path(path)
+                })?;
+        }
+
+        set.insert(canonical);
+        Ok(true)
+    }
+}
+
+/// Folder for a file's backup under the project's configured backup
+/// directory, mirroring the file's subdirectory. None if no backup
+/// directory is configured or the file lies outside the project.
+/// A backup directory that is not a plain project-relative path is an error.
+fn backup_dir_for(root: &Path, path: &Path) -> EditorResult<Option<PathBuf>> {
+    let Ok(manifest) = Manifest::load(root) else {
+        return Ok(None);
+    };
+    let backup_dir = manifest.backup_dir()
+        .map_err(|e| EditorError::new(ErrorKind::BackupFailed, e.to_string()).with_path(root))?;
+    let (Some(backup_dir), Ok(relative)) = (backup_dir, path.strip_prefix(root)) else {
+        return Ok(None);
+    };
+    let mut dir = root.join(backup_dir);
+    if let Some(parent) = relative.parent() {
+        dir.push(parent);
+    }
+    Ok(Some(dir))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_backup_dir() {
+        let root = std::env::temp_dir()
+            .join(format!("rhe_backup_dir_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("part1")).unwrap();
+        let chapter = root.join("part1/ch01.html");
+        fs::write(&chapter, "<p>1</p>").unwrap();
+
+        let mut manifest = Manifest {
+            backup_dir: Some("backups".to_string()),
+            ..Manifest::default()
+        };
+        manifest.save(&root).unwrap();
+        assert!(BackupTracker::new().backup_if_needed(&chapter, Some(&root)).unwrap());
+        assert!(root.join("backups/part1/ch01.html.bak").is_file());
+
+        // Folders outside the project or hidden are refused
+        for unsafe_dir in ["../outside", "/tmp/backups", ".backups"] {
+            manifest.backup_dir = Some(unsafe_dir.to_string());
+            manifest.save(&root).unwrap();
+            let err = BackupTracker::new().backup_if_needed(&chapter, Some(&root)).unwrap_err();
+            assert_eq!(err.kind, ErrorKind::BackupFailed);
+        }
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
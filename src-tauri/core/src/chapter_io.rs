@@ -0,0 +1,210 @@
+use std::fs;
+use std::path::Path;
+
+use crate::asset_urls;
+use crate::backup::BackupTracker;
+use crate::error::{EditorError, EditorResult, ErrorKind};
+use crate::history;
+use crate::html_parser;
+use crate::manifest::Manifest;
+use crate::project;
+use crate::scope::PathScope;
+use crate::stylesheets::{self, Stylesheet};
+
+/// A chapter prepared for the editor.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ChapterData {
+    pub filename: String,
+    pub body_html: String,
+    /// All stylesheet text concatenated in cascade order
+    pub css: String,
+    /// The individual stylesheets, in cascade order, with their source paths
+    pub stylesheets: Vec<Stylesheet>,
+    pub original_head: String,
+    /// True if the file is a body-only fragment (no <html>/<head>/<body> wrapper)
+    pub is_fragment: bool,
+}
+
+/// Read a chapter in scope and prepare it for the editor: split it,
+/// gather its stylesheets, and point asset URLs at the asset scheme.
+/// `dir` is the project folder, searched for a fallback stylesheet.
+pub fn load_chapter(path: &Path, dir: &Path, scope: &PathScope) -> EditorResult<ChapterData> {
+    let path = &scope.resolve(path)?;
+
+    // Read the raw HTML
+    let raw_html = fs::read_to_string(path)
+        .map_err(|e| EditorError::io(format!("Failed to read {}", path.display()), e).with_path(path))?;
+
+    // Split into head and body (handles both full docs and fragments)
+    let split = html_parser::split_html(&raw_html);
+
+    // Stylesheets linked from the chapter head, falling back to the
+    // project stylesheet for fragments and chapters without links
+    let mut sheets = stylesheets::chapter_stylesheets(path, &split.head_content);
+    if sheets.is_empty() {
+        if let Some(css_path) = project::find_css(dir) {
+            sheets = stylesheets::stylesheet_chain(&css_path);
+        }
+    }
+    // Stylesheets may not pull in files from outside the project
+    sheets.retain(|sheet| scope.resolve(Path::new(&sheet.path)).is_ok());
+
+    // Point relative image, media, and font URLs at the asset scheme
+    let mut body_html = split.body_content;
+    if let Some(chapter) = scope.relative_path(path) {
+        body_html = asset_urls::to_asset_urls(&body_html, &chapter);
+    }
+    for sheet in sheets.iter_mut() {
+        if let Some(sheet_path) = scope.relative_path(Path::new(&sheet.path)) {
+            sheet.css = asset_urls::css_to_asset_urls(&sheet.css, &sheet_path);
+        }
+    }
+
+    let css = sheets.iter()
+        .map(|sheet| sheet.css.as_str())
+        .collect::<Vec<&str>>()
+        .join("\n");
+
+    // Get filename
+    let filename = path.file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or("unknown")
+        .to_string();
+
+    Ok(ChapterData {
+        filename,
+        body_html,
+        css,
+        stylesheets: sheets,
+        original_head: split.head_content,
+        is_fragment: split.is_fragment,
+    })
+}
+
+/// Write edited body HTML back to a chapter file, preserving the original
+/// head and doctype. Creates a .bak backup on the first save per session,
+/// reads the written file back to verify it (restoring the previous content
+/// on a mismatch), and records the saved version in the project history.
+pub fn save_chapter(
+    path: &Path,
+    body_html: &str,
+    original_head: &str,
+    is_fragment: bool,
+    tracker: &BackupTracker,
+    scope: &PathScope,
+) -> EditorResult<()> {
+    let path = &scope.resolve(path)?;
+
+    // Turn asset scheme URLs back into relative URLs
+    let body_html = match scope.relative_path(path) {
+        Some(chapter) => asset_urls::from_asset_urls(body_html, &chapter),
+        None => body_html.to_string(),
+    };
+
+    // Create backup on first save per session
+    tracker.backup_if_needed(path, scope.project_root().as_deref())?;
+
+    // Content before this save, restored if the written file does not verify
+    let previous = fs::read_to_string(path).ok();
+
+    // Build the output content
+    let output = if is_fragment {
+        // Fragment: save body content directly
+        body_html.clone()
+    } else {
+        // Full document: keep the original doctype and reassemble. A file
+        // deleted since it was opened (or recovered) is written as HTML5
+        let doctype = match previous.as_deref() {
+            Some(raw_html) => html_parser::split_html(raw_html).doctype,
+            None => "<!DOCTYPE html>".to_string(),
+        };
+        html_parser::reassemble_html(
+            &doctype,
+            original_head,
+            &body_html,
+            false,
+        )
+    };
+
+    // Write atomically
+    project::atomic_write(path, &output)
+        .map_err(|e| EditorError::write(format!("Failed to write {}", path.display()), e).with_path(path))?;
+
+    // Read the file back and check it holds what was intended
+    if let Err(problem) = verify_written(path, &output, &body_html, original_head, is_fragment) {
+        let rollback = match &previous {
+            Some(content) => project::atomic_write(path, content),
+            None => fs::remove_file(path),
+        };
+        let message = match rollback {
+            Ok(()) => format!(
+                "Save verification failed for {}: {}. The previous content was restored.",
+                path.display(), problem,
+            ),
+            Err(e) => format!(
+                "Save verification failed for {}: {}. Restoring the previous content also failed: {}",
+                path.display(), problem, e,
+            ),
+        };
+        return Err(EditorError::new(ErrorKind::VerifyFailed, message).with_path(path));
+    }
+
+    // Record the saved version in the project history
+    if let (Some(root), Some(chapter)) = (scope.project_root(), scope.relative_path(path)) {
+        let policy = Manifest::load(&root)
+            .map(|m| m.history)
+            .unwrap_or_default();
+        history::record_snapshot(&root, &chapter, &output, &policy)
+            .map_err(|e| {
+                EditorError::io(format!("Saved {}, but failed to record history", path.display()), e)
+                    .with_path(path)
+            })?;
+    }
+
+    Ok(())
+}
+
+/// Re-read a saved chapter and check it against the intended content.
+fn verify_written(
+    path: &Path,
+    output: &str,
+    body_html: &str,
+    original_head: &str,
+    is_fragment: bool,
+) -> Result<(), String> {
+    let written = fs::read(path)
+        .map_err(|e| format!("could not read the file back: {}", e))?;
+    let written = String::from_utf8(written)
+        .map_err(|e| format!("file is not valid UTF-8 at byte {}", e.utf8_error().valid_up_to()))?;
+    if written.len() != output.len() {
+        return Err(format!("expected {} bytes but found {}", output.len(), written.len()));
+    }
+    html_parser::verify_round_trip(&written, body_html, original_head, is_fragment)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_save_recreates_deleted_document() {
+        let dir = std::env::temp_dir()
+            .join(format!("rhe_chapter_io_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("ch01.html");
+
+        let scope = PathScope::new();
+        scope.set_project(&dir).unwrap();
+        let tracker = BackupTracker::new();
+
+        // A full document deleted since it was opened is written from scratch
+        save_chapter(&path, "<p>Recovered</p>", "<title>One</title>", false, &tracker, &scope).unwrap();
+        let saved = fs::read_to_string(&path).unwrap();
+        assert!(saved.starts_with("<!DOCTYPE html>\n<html>"));
+        assert!(saved.contains("<title>One</title>"));
+        assert!(saved.contains("<p>Recovered</p>"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
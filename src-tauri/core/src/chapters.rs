@@ -0,0 +1,281 @@
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use crate::html_parser;
+use crate::manifest::Manifest;
+use crate::project;
+
+/// Project-local folder that receives deleted chapters.
+pub const TRASH_DIR: &str = ".editor-trash";
+
+/// Optional project template for new chapters, in a hidden folder so it
+/// is not listed, exported, or counted as a chapter.
+pub const TEMPLATE_PATH: &str = ".editor/chapter_template.html";
+
+/// Built-in chapter template used when the project has none.
+const DEFAULT_TEMPLATE: &str = "<!DOCTYPE html>
+<html>
+<head>
+<meta charset=\"utf-8\">
+<title>{{title}}</title>
+</head>
+<body>
+<h1>{{title}}</h1>
+</body>
+</html>
+";
+
+/// Create a new chapter from the project template (or the built-in one).
+/// `{{title}}` in the template is replaced with the chapter title.
+/// The chapter is added to the manifest after `after`, or at the end.
+pub fn create_chapter(
+    root: &Path,
+    relative_path: &str,
+    title: &str,
+    after: Option<&str>,
+) -> io::Result<PathBuf> {
+    let target = chapter_path(root, relative_path)?;
+    if target.exists() {
+        return Err(already_exists(relative_path));
+    }
+
+    let template = fs::read_to_string(root.join(TEMPLATE_PATH))
+        .unwrap_or_else(|_| DEFAULT_TEMPLATE.to_string());
+    let content = template.replace("{{title}}", &html_parser::escape_text(title));
+
+    let mut manifest = Manifest::load_for_update(root)?;
+    create_parent(&target)?;
+    fs::write(&target, content)?;
+
+    manifest.insert_after(after, relative_path);
+    manifest.save(root)?;
+    Ok(target)
+}
+
+/// Rename or move a chapter within the project.
+pub fn rename_chapter(root: &Path, from: &str, to: &str) -> io::Result<PathBuf> {
+    let source = chapter_path(root, from)?;
+    let target = chapter_path(root, to)?;
+    if !source.is_file() {
+        return Err(not_found(from));
+    }
+    if target.exists() {
+        return Err(already_exists(to));
+    }
+
+    let mut manifest = Manifest::load_for_update(root)?;
+    create_parent(&target)?;
+    fs::rename(&source, &target)?;
+
+    manifest.rename(from, to);
+    manifest.save(root)?;
+    Ok(target)
+}
+
+/// Copy a chapter to a new path; the copy follows the original in the manifest.
+pub fn duplicate_chapter(root: &Path, from: &str, to: &str) -> io::Result<PathBuf> {
+    let source = chapter_path(root, from)?;
+    let target = chapter_path(root, to)?;
+    if !source.is_file() {
+        return Err(not_found(from));
+    }
+    if target.exists() {
+        return Err(already_exists(to));
+    }
+
+    let mut manifest = Manifest::load_for_update(root)?;
+    create_parent(&target)?;
+    fs::copy(&source, &target)?;
+
+    manifest.insert_after(Some(from), to);
+    manifest.save(root)?;
+    Ok(target)
+}
+
+/// Move a chapter into the project trash folder and drop it from the manifest.
+/// Returns the path of the trashed file.
+pub fn delete_chapter(root: &Path, relative_path: &str) -> io::Result<PathBuf> {
+    let source = chapter_path(root, relative_path)?;
+    if !source.is_file() {
+        return Err(not_found(relative_path));
+    }
+
+    // Keep the subdirectory layout inside the trash; number repeated deletes
+    let mut trashed = root.join(TRASH_DIR).join(relative_path);
+    let mut counter = 1;
+    while trashed.exists() {
+        let name = format!(
+            "{}.{}",
+            Path::new(relative_path).file_name().unwrap_or_default().to_string_lossy(),
+            counter,
+        );
+        trashed = root.join(TRASH_DIR).join(relative_path).with_file_name(name);
+        counter += 1;
+    }
+
+    let mut manifest = Manifest::load_for_update(root)?;
+    create_parent(&trashed)?;
+    fs::rename(&source, &trashed)?;
+
+    manifest.remove(relative_path);
+    manifest.save(root)?;
+    Ok(trashed)
+}
+
+/// Move an asset file or folder within the project.
+pub fn move_asset(root: &Path, from: &str, to: &str) -> io::Result<PathBuf> {
+    let source = project_path(root, from)?;
+    let target = project_path(root, to)?;
+    if !source.exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("File not found: {}", from),
+        ));
+    }
+    if target.exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("File already exists: {}", to),
+        ));
+    }
+
+    create_parent(&target)?;
+    fs::rename(&source, &target)?;
+    Ok(target)
+}
+
+/// Resolve a relative path against the project root.
+/// Rejects empty and absolute paths, `..` components, and paths that
+/// symlinks lead out of the project.
+pub fn project_path(root: &Path, relative_path: &str) -> io::Result<PathBuf> {
+    let rel = Path::new(relative_path);
+    let is_plain = !relative_path.is_empty()
+        && rel.components().all(|c| matches!(c, Component::Normal(_)));
+    if !is_plain {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Not a project-relative path: {}", relative_path),
+        ));
+    }
+    let path = root.join(rel);
+    project::ensure_inside(root, &path)?;
+    Ok(path)
+}
+
+/// Resolve a chapter's relative path against the project root.
+/// Rejects absolute paths, `..` components, and non-HTML extensions.
+pub fn chapter_path(root: &Path, relative_path: &str) -> io::Result<PathBuf> {
+    let path = project_path(root, relative_path)?;
+
+    let ext = path.extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("")
+        .to_lowercase();
+    if ext != "html" && ext != "htm" && ext != "xhtml" {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Not an HTML chapter: {}", relative_path),
+        ));
+    }
+
+    Ok(path)
+}
+
+/// Create the parent directory of a path if needed.
+fn create_parent(path: &Path) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    Ok(())
+}
+
+fn not_found(relative_path: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("Chapter not found: {}", relative_path),
+    )
+}
+
+fn already_exists(relative_path: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::AlreadyExists,
+        format!("Chapter already exists: {}", relative_path),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manifest;
+
+    /// Create an empty scratch project directory for a test.
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir()
+            .join(format!("rhe_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn relative_paths(dir: &Path) -> Vec<String> {
+        manifest::list_chapters(dir).unwrap()
+            .into_iter()
+            .map(|c| c.relative_path)
+            .collect()
+    }
+
+    #[test]
+    fn test_chapter_lifecycle() {
+        let dir = scratch_dir("chapters");
+        fs::write(dir.join("b.html"), "<p>B</p>").unwrap();
+        fs::write(dir.join("a.html"), "<p>A</p>").unwrap();
+
+        fs::create_dir_all(dir.join(".editor")).unwrap();
+        fs::write(dir.join(TEMPLATE_PATH), "<title>{{title}}</title><h1 class=\"chapter\">{{title}}</h1>").unwrap();
+
+        // Seeds the manifest from disk order, then appends; the template is not a chapter
+        create_chapter(&dir, "z.html", "Zed & Co", None).unwrap();
+        assert_eq!(relative_paths(&dir), ["a.html", "b.html", "z.html"]);
+        let content = fs::read_to_string(dir.join("z.html")).unwrap();
+        assert!(content.contains("<title>Zed &amp; Co</title><h1 class=\"chapter\">"));
+
+        // Rename keeps the manifest position
+        rename_chapter(&dir, "a.html", "part1/intro.html").unwrap();
+        assert_eq!(relative_paths(&dir), ["part1/intro.html", "b.html", "z.html"]);
+
+        // Duplicate follows the original
+        duplicate_chapter(&dir, "b.html", "b_copy.html").unwrap();
+        assert_eq!(
+            relative_paths(&dir),
+            ["part1/intro.html", "b.html", "b_copy.html", "z.html"],
+        );
+
+        // Delete moves to the trash, which is not listed
+        let trashed = delete_chapter(&dir, "b.html").unwrap();
+        assert!(trashed.starts_with(dir.join(TRASH_DIR)));
+        assert!(trashed.is_file());
+        assert_eq!(relative_paths(&dir), ["part1/intro.html", "b_copy.html", "z.html"]);
+
+        // Invalid paths are rejected
+        assert!(create_chapter(&dir, "../out.html", "Out", None).is_err());
+        assert!(create_chapter(&dir, "notes.txt", "Notes", None).is_err());
+        assert!(create_chapter(&dir, "z.html", "Again", None).is_err());
+
+        // Folders symlinked out of the project are not written through
+        #[cfg(unix)]
+        {
+            let outside = scratch_dir("chapters_outside");
+            std::os::unix::fs::symlink(&outside, dir.join("linked")).unwrap();
+            let err = create_chapter(&dir, "linked/new.html", "New", None).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+            assert!(duplicate_chapter(&dir, "z.html", "linked/z.html").is_err());
+            assert!(!outside.join("new.html").exists());
+            assert!(!outside.join("z.html").exists());
+            fs::remove_file(dir.join("linked")).unwrap();
+            fs::remove_dir_all(&outside).unwrap();
+        }
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
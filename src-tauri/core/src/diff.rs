@@ -0,0 +1,304 @@
+/// Kind of change in a diff chunk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeKind {
+    Equal,
+    Insert,
+    Delete,
+}
+
+/// A run of consecutive tokens with the same change kind.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct DiffChunk {
+    pub kind: ChangeKind,
+    pub text: String,
+}
+
+/// Line-level diff from `old` to `new`. Each chunk keeps its line endings.
+pub fn diff_lines(old: &str, new: &str) -> Vec<DiffChunk> {
+    let old_lines: Vec<&str> = old.split_inclusive('\n').collect();
+    let new_lines: Vec<&str> = new.split_inclusive('\n').collect();
+    diff_tokens(&old_lines, &new_lines)
+}
+
+/// Word-level diff from `old` to `new`. Tags, words, whitespace runs,
+/// and single punctuation characters are compared as separate tokens.
+pub fn diff_words(old: &str, new: &str) -> Vec<DiffChunk> {
+    diff_tokens(&split_words(old), &split_words(new))
+}
+
+/// Split text into tokens for `diff_words`. Concatenating the tokens
+/// gives back the original text.
+pub fn split_words(text: &str) -> Vec<&str> {
+    let mut tokens: Vec<&str> = Vec::new();
+    let mut chars = text.char_indices().peekable();
+
+    while let Some((start, c)) = chars.next() {
+        let mut end = start + c.len_utf8();
+        if c == '<' {
+            // A whole tag is one token
+            if let Some(close) = text[start..].find('>') {
+                end = start + close + 1;
+                while chars.peek().is_some_and(|(i, _)| *i < end) {
+                    chars.next();
+                }
+            }
+        } else if c.is_whitespace() || c.is_alphanumeric() {
+            let same_class = |next: char| {
+                if c.is_whitespace() { next.is_whitespace() } else { next.is_alphanumeric() }
+            };
+            while let Some((i, next)) = chars.peek().copied() {
+                if !same_class(next) {
+                    break;
+                }
+                end = i + next.len_utf8();
+                chars.next();
+            }
+        }
+        tokens.push(&text[start..end]);
+    }
+    tokens
+}
+
+/// Diff two token sequences, merging runs of the same kind into chunks.
+/// Deletions are reported before insertions at each change.
+pub fn diff_tokens(old: &[&str], new: &[&str]) -> Vec<DiffChunk> {
+    let mut chunks: Vec<DiffChunk> = Vec::new();
+    let mut push = |kind: ChangeKind, text: &str| {
+        match chunks.last_mut() {
+            Some(last) if last.kind == kind => last.text += text,
+            _ => chunks.push(DiffChunk { kind, text: text.to_string() }),
+        }
+    };
+
+    let (mut i, mut j) = (0, 0);
+    for (a, b) in lcs_pairs(old, new) {
+        old[i..a].iter().for_each(|t| push(ChangeKind::Delete, t));
+        new[j..b].iter().for_each(|t| push(ChangeKind::Insert, t));
+        push(ChangeKind::Equal, old[a]);
+        i = a + 1;
+        j = b + 1;
+    }
+    old[i..].iter().for_each(|t| push(ChangeKind::Delete, t));
+    new[j..].iter().for_each(|t| push(ChangeKind::Insert, t));
+    chunks
+}
+
+/// Longest common subsequence of two slices.
+/// Returns matched index pairs (index in `a`, index in `b`) in increasing order.
+/// Uses Myers' divide and conquer diff, so time grows with the input size
+/// times the number of differences and memory only with the input size.
+pub fn lcs_pairs<T: PartialEq>(a: &[T], b: &[T]) -> Vec<(usize, usize)> {
+    let mut pairs = Vec::new();
+    lcs_into(a, b, (0, 0), &mut pairs);
+    pairs
+}
+
+/// Push the LCS pairs of `a` and `b` onto `pairs`, with `offset` added to
+/// each index. Common prefix and suffix are matched directly and the rest
+/// is split at the middle of a shortest edit path.
+fn lcs_into<T: PartialEq>(a: &[T], b: &[T], offset: (usize, usize), pairs: &mut Vec<(usize, usize)>) {
+    let mut prefix = 0;
+    while prefix < a.len() && prefix < b.len() && a[prefix] == b[prefix] {
+        pairs.push((offset.0 + prefix, offset.1 + prefix));
+        prefix += 1;
+    }
+    let mut suffix = 0;
+    while suffix < a.len() - prefix
+        && suffix < b.len() - prefix
+        && a[a.len() - 1 - suffix] == b[b.len() - 1 - suffix]
+    {
+        suffix += 1;
+    }
+
+    let mid_a = &a[prefix..a.len() - suffix];
+    let mid_b = &b[prefix..b.len() - suffix];
+    let mid_offset = (offset.0 + prefix, offset.1 + prefix);
+    if let Some((x, y)) = middle_split(mid_a, mid_b) {
+        lcs_into(&mid_a[..x], &mid_b[..y], mid_offset, pairs);
+        lcs_into(&mid_a[x..], &mid_b[y..], (mid_offset.0 + x, mid_offset.1 + y), pairs);
+    }
+
+    for s in 0..suffix {
+        pairs.push((offset.0 + a.len() - suffix + s, offset.1 + b.len() - suffix + s));
+    }
+}
+
+/// Where a shortest edit path from `a` to `b` crosses its middle, found by
+/// searching from both ends at once. None if the slices have nothing in
+/// common (or either is empty).
+fn middle_split<T: PartialEq>(a: &[T], b: &[T]) -> Option<(usize, usize)> {
+    let (n, m) = (a.len() as isize, b.len() as isize);
+    if n == 0 || m == 0 {
+        return None;
+    }
+    let max_d = (n + m + 1) / 2;
+    let v_offset = max_d;
+    let v_len = 2 * max_d + 2;
+    // Furthest x reached on each diagonal k = x - y, forward and backward
+    let mut forward = vec![-1isize; v_len as usize];
+    let mut backward = vec![-1isize; v_len as usize];
+    forward[(v_offset + 1) as usize] = 0;
+    backward[(v_offset + 1) as usize] = 0;
+    let delta = n - m;
+    // With an odd delta the paths meet on a forward step, else a backward one
+    let meet_forward = delta % 2 != 0;
+    let (mut k1_start, mut k1_end, mut k2_start, mut k2_end) = (0, 0, 0, 0);
+
+    for d in 0..max_d {
+        let mut k1 = -d + k1_start;
+        while k1 <= d - k1_end {
+            let i = (v_offset + k1) as usize;
+            let mut x1 = if k1 == -d || (k1 != d && forward[i - 1] < forward[i + 1]) {
+                forward[i + 1]
+            } else {
+                forward[i - 1] + 1
+            };
+            let mut y1 = x1 - k1;
+            while x1 < n && y1 < m && a[x1 as usize] == b[y1 as usize] {
+                x1 += 1;
+                y1 += 1;
+            }
+            forward[i] = x1;
+            if x1 > n {
+                k1_end += 2;
+            } else if y1 > m {
+                k1_start += 2;
+            } else if meet_forward {
+                let j = v_offset + delta - k1;
+                if (0..v_len).contains(&j) && backward[j as usize] != -1 && x1 >= n - backward[j as usize] {
+                    return Some((x1 as usize, y1 as usize));
+                }
+            }
+            k1 += 2;
+        }
+
+        let mut k2 = -d + k2_start;
+        while k2 <= d - k2_end {
+            let i = (v_offset + k2) as usize;
+            let mut x2 = if k2 == -d || (k2 != d && backward[i - 1] < backward[i + 1]) {
+                backward[i + 1]
+            } else {
+                backward[i - 1] + 1
+            };
+            let mut y2 = x2 - k2;
+            while x2 < n && y2 < m && a[(n - x2 - 1) as usize] == b[(m - y2 - 1) as usize] {
+                x2 += 1;
+                y2 += 1;
+            }
+            backward[i] = x2;
+            if x2 > n {
+                k2_end += 2;
+            } else if y2 > m {
+                k2_start += 2;
+            } else if !meet_forward {
+                let j = v_offset + delta - k2;
+                if (0..v_len).contains(&j) && forward[j as usize] != -1 {
+                    let x1 = forward[j as usize];
+                    let y1 = v_offset + x1 - j;
+                    if x1 >= n - x2 {
+                        return Some((x1 as usize, y1 as usize));
+                    }
+                }
+            }
+            k2 += 2;
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// LCS length from the full dynamic programming table.
+    fn lcs_length(a: &[u32], b: &[u32]) -> usize {
+        let mut table = vec![vec![0; b.len() + 1]; a.len() + 1];
+        for i in 0..a.len() {
+            for j in 0..b.len() {
+                table[i + 1][j + 1] = if a[i] == b[j] { table[i][j] + 1 } else { table[i][j + 1].max(table[i + 1][j]) };
+            }
+        }
+        table[a.len()][b.len()]
+    }
+
+    #[test]
+    fn test_lcs_pairs() {
+        let a = ["a", "b", "c", "d", "e"];
+        let b = ["a", "x", "c", "d", "y", "e"];
+        let pairs = lcs_pairs(&a, &b);
+        assert_eq!(pairs, vec![(0, 0), (2, 2), (3, 3), (4, 5)]);
+
+        // Empty inputs
+        let empty: [&str; 0] = [];
+        assert!(lcs_pairs(&empty, &b).is_empty());
+        assert!(lcs_pairs(&a, &empty).is_empty());
+
+        // Same length as the full table would find, with valid pairs
+        let mut seed = 7u32;
+        let mut next = || {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            (seed >> 16) % 4
+        };
+        for _ in 0..200 {
+            let a: Vec<u32> = (0..next() * 5).map(|_| next()).collect();
+            let b: Vec<u32> = (0..next() * 5).map(|_| next()).collect();
+            let pairs = lcs_pairs(&a, &b);
+            assert!(pairs.iter().all(|&(i, j)| a[i] == b[j]));
+            assert!(pairs.windows(2).all(|w| w[0].0 < w[1].0 && w[0].1 < w[1].1));
+            assert_eq!(pairs.len(), lcs_length(&a, &b));
+        }
+    }
+
+    #[test]
+    fn test_diff_lines() {
+        let chunks = diff_lines("a\nb\nc\n", "a\nB\nc\nd\n");
+        let summary: Vec<(ChangeKind, &str)> = chunks.iter()
+            .map(|c| (c.kind, c.text.as_str()))
+            .collect();
+        assert_eq!(summary, vec![
+            (ChangeKind::Equal, "a\n"),
+            (ChangeKind::Delete, "b\n"),
+            (ChangeKind::Insert, "B\n"),
+            (ChangeKind::Equal, "c\n"),
+            (ChangeKind::Insert, "d\n"),
+        ]);
+    }
+
+    #[test]
+    fn test_diff_words() {
+        let text = "<p class=\"a\">Hello,  world</p>";
+        let tokens = split_words(text);
+        assert_eq!(tokens, vec!["<p class=\"a\">", "Hello", ",", "  ", "world", "</p>"]);
+        assert_eq!(tokens.concat(), text);
+
+        let chunks = diff_words("<p>The quick fox</p>", "<p>The slow fox</p>");
+        let changed: Vec<(ChangeKind, &str)> = chunks.iter()
+            .filter(|c| c.kind != ChangeKind::Equal)
+            .map(|c| (c.kind, c.text.as_str()))
+            .collect();
+        assert_eq!(changed, vec![(ChangeKind::Delete, "quick"), (ChangeKind::Insert, "slow")]);
+    }
+
+    #[test]
+    fn test_diff_words_long_text() {
+        // Edits near both ends of a long chapter, on one line and on many
+        let words: Vec<String> = (0..20_000).map(|i| format!("w{}", i)).collect();
+        for separator in [" ", "\n"] {
+            let old = words.join(separator);
+            let new = old.replacen("w1 ", "one ", 1).replacen("w1\n", "one\n", 1)
+                .replace("w19998", "near-end");
+            let chunks = diff_words(&old, &new);
+            let changed: Vec<&str> = chunks.iter()
+                .filter(|c| c.kind != ChangeKind::Equal)
+                .map(|c| c.text.as_str())
+                .collect();
+            assert_eq!(changed, vec!["w1", "one", "w19998", "near-end"]);
+            let rebuilt: String = chunks.iter()
+                .filter(|c| c.kind != ChangeKind::Delete)
+                .map(|c| c.text.as_str())
+                .collect();
+            assert_eq!(rebuilt, new);
+        }
+    }
+}
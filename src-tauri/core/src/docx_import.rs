@@ -0,0 +1,1618 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, File};
+use std::io::{self, Read, Seek};
+use std::path::{Path, PathBuf};
+
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use zip::ZipArchive;
+
+use crate::epub_import::{extract_entry, malformed, read_entry};
+use crate::error::{EditorError, EditorResult, ErrorKind};
+use crate::export;
+use crate::html_parser::{self, escape_attribute, escape_text};
+use crate::links;
+use crate::manifest::Manifest;
+use crate::project::{self, is_safe_relative};
+
+/// Elements a paragraph style can be mapped to.
+const PARAGRAPH_ELEMENTS: [&str; 10] = ["p", "h1", "h2", "h3", "h4", "h5", "h6", "blockquote", "pre", "aside"];
+
+/// Elements a character style can be mapped to.
+const CHARACTER_ELEMENTS: [&str; 19] = [
+    "span", "em", "strong", "code", "kbd", "cite", "dfn", "mark", "small", "q", "s", "sub",
+    "sup", "var", "samp", "abbr", "b", "i", "u",
+];
+
+/// DOCX import settings, from the `docx` section of the project manifest.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct DocxConfig {
+    /// Word style names mapped to an element and classes, such as
+    /// `blockquote.epigraph` or `dfn`; `.note` keeps the usual element
+    pub styles: BTreeMap<String, String>,
+    /// Headings at this level or above start a new chapter; 0 keeps the
+    /// document in one chapter
+    pub split_level: u8,
+    /// Project-relative folder for extracted images
+    pub assets_dir: String,
+}
+
+impl Default for DocxConfig {
+    fn default() -> Self {
+        DocxConfig {
+            styles: BTreeMap::new(),
+            split_level: 1,
+            assets_dir: "images".to_string(),
+        }
+    }
+}
+
+/// What a DOCX import added to the project.
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct DocxImport {
+    /// New chapters in document order, project-relative
+    pub chapters: Vec<String>,
+    /// Images extracted into the assets folder
+    pub images: Vec<String>,
+    /// Content that was left out or simplified, such as unmapped styles
+    pub warnings: Vec<String>,
+}
+
+/// One chapter of a converted document.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DocxChapter {
+    /// Project-relative file name
+    pub path: String,
+    pub title: String,
+    /// Body fragment
+    pub body: String,
+}
+
+/// A Word document converted to HTML chapters, before anything is written.
+#[derive(Debug, Clone, Default)]
+pub struct ConvertedDocx {
+    pub chapters: Vec<DocxChapter>,
+    /// Zip entries of the images used, with their project-relative targets
+    pub images: Vec<(String, String)>,
+    pub warnings: Vec<String>,
+}
+
+/// Import a Word document into a project as HTML chapters.
+///
+/// Headings come from the paragraph styles (`Heading 1` to `Heading 6`,
+/// `Title`, or an outline level), lists from the document's numbering,
+/// and tables, footnotes, endnotes, and links are kept; direct formatting
+/// other than bold, italic, strikethrough, and superscript or subscript is
+/// dropped. The `docx` section of `manifest.json` maps Word styles to
+/// elements and classes and sets where chapters split and images go. New
+/// chapters are named after the document and added to the end of the
+/// chapter order; existing files are never overwritten. If writing fails
+/// part way, the images, chapters, and folders already created are removed.
+pub fn import_docx(docx_path: &Path, project_dir: &Path) -> EditorResult<DocxImport> {
+    let mut manifest = Manifest::load_for_update(project_dir)
+        .map_err(|e| EditorError::io("Failed to read manifest.json", e).with_path(project_dir))?;
+    let config = manifest.docx.clone().unwrap_or_default();
+    if !is_safe_relative(&config.assets_dir) {
+        return Err(EditorError::new(
+            ErrorKind::InvalidInput,
+            format!("docx.assets_dir {:?} is not a folder inside the project", config.assets_dir),
+        ).with_path(project_dir));
+    }
+
+    let file = File::open(docx_path)
+        .map_err(|e| EditorError::io(format!("Failed to open {}", docx_path.display()), e).with_path(docx_path))?;
+    let mut archive = ZipArchive::new(file).map_err(|e| invalid_docx(docx_path, e))?;
+    let parts = DocxParts::read(&mut archive).map_err(|e| e.with_path(docx_path))?;
+    let stem = docx_path.file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_else(|| "document".to_string());
+    let converted = convert_docx(&parts, &stem, &config);
+    if converted.chapters.is_empty() {
+        return Err(EditorError::new(ErrorKind::InvalidInput, "The document has no text").with_path(docx_path));
+    }
+
+    let mut new_dirs: Vec<PathBuf> = Vec::new();
+    let targets = converted.chapters.iter().map(|c| &c.path)
+        .chain(converted.images.iter().map(|(_, target)| target));
+    for target in targets {
+        let path = project_dir.join(target);
+        if path.exists() {
+            return Err(EditorError::new(
+                ErrorKind::AlreadyExists,
+                format!("{} already exists in the project", target),
+            ).with_path(&path));
+        }
+        // Folders the import will create, outermost first
+        let missing = path.ancestors()
+            .skip(1)
+            .take_while(|dir| *dir != project_dir && !dir.exists())
+            .collect::<Vec<&Path>>();
+        for dir in missing.into_iter().rev() {
+            if !new_dirs.iter().any(|d| d == dir) {
+                new_dirs.push(dir.to_path_buf());
+            }
+        }
+    }
+
+    let mut result = DocxImport { warnings: converted.warnings.clone(), ..DocxImport::default() };
+    let mut created: Vec<PathBuf> = Vec::new();
+    let written = write_import(&mut archive, project_dir, &converted, &mut result, &mut created)
+        .and_then(|()| {
+            manifest.chapters.extend(result.chapters.iter().cloned());
+            manifest.save(project_dir)
+                .map_err(|e| EditorError::write("Failed to write manifest.json", e).with_path(project_dir))
+        });
+    if let Err(e) = written {
+        // Leave the project as it was before the import
+        for path in created.iter().rev() {
+            let _ = fs::remove_file(path);
+        }
+        for dir in new_dirs.iter().rev() {
+            let _ = fs::remove_dir(dir);
+        }
+        return Err(e);
+    }
+    Ok(result)
+}
+
+/// Extract the images and write the chapters of a converted document,
+/// recording each file created in `created` so a failed import can be undone.
+fn write_import<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    project_dir: &Path,
+    converted: &ConvertedDocx,
+    result: &mut DocxImport,
+    created: &mut Vec<PathBuf>,
+) -> EditorResult<()> {
+    for (entry, target) in &converted.images {
+        let path = project_dir.join(target);
+        match extract_entry(archive, entry, &path) {
+            Ok(()) => created.push(path),
+            Err(e) => {
+                // A partly extracted image is removed; a file created by
+                // someone else in the meantime is not
+                if e.kind() != io::ErrorKind::AlreadyExists {
+                    created.push(path.clone());
+                }
+                return Err(EditorError::write(format!("Failed to extract {}", entry), e).with_path(&path));
+            }
+        }
+        result.images.push(target.clone());
+    }
+    for chapter in &converted.chapters {
+        let head = format!("<meta charset=\"utf-8\">\n<title>{}</title>\n", escape_text(&chapter.title));
+        let document = html_parser::reassemble_html("<!DOCTYPE html>", &head, &chapter.body, false);
+        let path = project_dir.join(&chapter.path);
+        project::atomic_write(&path, &document)
+            .map_err(|e| EditorError::write("Failed to write chapter", e).with_path(&path))?;
+        created.push(path);
+        result.chapters.push(chapter.path.clone());
+    }
+    Ok(())
+}
+
+/// Convert the parts of a Word document into chapters named after `stem`.
+/// A stem that is not a plain file name, such as `..`, becomes `document`.
+pub fn convert_docx(parts: &DocxParts, stem: &str, config: &DocxConfig) -> ConvertedDocx {
+    let stem = if is_safe_relative(stem) && !stem.contains(['/', '\\']) { stem } else { "document" };
+    let mut converter = Converter {
+        parts,
+        config,
+        image_dir: format!("{}/{}", config.assets_dir.trim_end_matches('/'), stem),
+        images: Vec::new(),
+        warnings: Vec::new(),
+        counts: HashMap::new(),
+        notes: Vec::new(),
+        part: 0,
+        in_heading: false,
+    };
+
+    // Split the body's blocks at chapter headings
+    let mut groups: Vec<Vec<&Element>> = vec![Vec::new()];
+    if let Some(body) = parts.document.find("body") {
+        for block in body.elements() {
+            let level = match block.name.as_str() {
+                "p" => converter.paragraph_style(block).level,
+                "sectPr" => continue,
+                _ => None,
+            };
+            let splits = level.is_some_and(|level| level <= config.split_level);
+            if splits && groups.last().is_some_and(|group| !group.is_empty()) {
+                groups.push(Vec::new());
+            }
+            groups.last_mut().unwrap().push(block);
+        }
+    }
+
+    let mut bodies = Vec::new();
+    for group in groups {
+        let mut body = converter.blocks(&group);
+        body += &converter.footnotes();
+        if !body.is_empty() {
+            bodies.push(body);
+        }
+    }
+
+    let chapters: Vec<DocxChapter> = bodies.iter().enumerate()
+        .map(|(i, body)| {
+            let path = if bodies.len() == 1 {
+                format!("{}.html", stem)
+            } else {
+                format!("{}-{:02}.html", stem, i + 1)
+            };
+            DocxChapter { title: export::chapter_title(body, stem), path, body: body.clone() }
+        })
+        .collect();
+
+    ConvertedDocx {
+        chapters: link_bookmarks(chapters),
+        images: converter.images,
+        warnings: converter.warnings,
+    }
+}
+
+/// Point links to bookmarks in other chapters at those chapters.
+fn link_bookmarks(mut chapters: Vec<DocxChapter>) -> Vec<DocxChapter> {
+    let ids: Vec<Vec<String>> = chapters.iter()
+        .map(|c| links::attribute_spans(&c.body, &["id"]).iter()
+            .map(|span| c.body[span.start..span.end].to_string())
+            .collect())
+        .collect();
+
+    for i in 0..chapters.len() {
+        let body = &chapters[i].body;
+        let mut replacements = Vec::new();
+        for span in links::attribute_spans(body, &["href"]) {
+            let Some(id) = body[span.start..span.end].strip_prefix('#') else {
+                continue;
+            };
+            if ids[i].iter().any(|own| own == id) {
+                continue;
+            }
+            if let Some(other) = ids.iter().position(|list| list.iter().any(|other| other == id)) {
+                let url = format!("{}#{}", links::encode_path(&chapters[other].path), id);
+                replacements.push((span.start, span.end, escape_attribute(&url)));
+            }
+        }
+        chapters[i].body = links::replace_spans(body, replacements);
+    }
+    chapters
+}
+
+fn invalid_docx(path: &Path, err: impl std::fmt::Display) -> EditorError {
+    EditorError::new(ErrorKind::InvalidInput, format!("Not a readable Word document: {}", err)).with_path(path)
+}
+
+/// An XML element with namespace prefixes removed from its name and
+/// attribute names.
+#[derive(Debug, Clone, Default)]
+struct Element {
+    name: String,
+    attrs: Vec<(String, String)>,
+    children: Vec<Node>,
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Element(Element),
+    Text(String),
+}
+
+impl Element {
+    /// Parse an XML document into its root element.
+    fn parse(xml: &str) -> Result<Element, String> {
+        let mut reader = Reader::from_str(xml);
+        let mut stack = vec![Element::default()];
+        loop {
+            let event = reader.read_event()
+                .map_err(|e| format!("XML error at byte {}: {}", reader.error_position(), e))?;
+            match event {
+                Event::Start(e) => stack.push(Element::start(&e)?),
+                Event::Empty(e) => {
+                    let element = Element::start(&e)?;
+                    stack.last_mut().unwrap().children.push(Node::Element(element));
+                }
+                Event::End(_) => {
+                    let element = stack.pop().unwrap();
+                    let Some(parent) = stack.last_mut() else {
+                        return Err("unbalanced end tag".to_string());
+                    };
+                    parent.children.push(Node::Element(element));
+                }
+                Event::Text(t) => {
+                    let text = t.unescape().map_err(|e| e.to_string())?;
+                    stack.last_mut().unwrap().children.push(Node::Text(text.to_string()));
+                }
+                Event::CData(t) => {
+                    let text = String::from_utf8_lossy(&t).to_string();
+                    stack.last_mut().unwrap().children.push(Node::Text(text));
+                }
+                Event::Eof => break,
+                _ => {}
+            }
+        }
+        let root = stack.pop().filter(|_| stack.is_empty()).ok_or("unclosed element")?;
+        root.children.into_iter()
+            .find_map(|node| match node {
+                Node::Element(element) => Some(element),
+                Node::Text(_) => None,
+            })
+            .ok_or_else(|| "no root element".to_string())
+    }
+
+    fn start(e: &BytesStart) -> Result<Element, String> {
+        let mut attrs = Vec::new();
+        for attr in e.attributes() {
+            let attr = attr.map_err(|e| e.to_string())?;
+            let value = attr.unescape_value().map_err(|e| e.to_string())?;
+            let name = String::from_utf8_lossy(attr.key.local_name().as_ref()).to_string();
+            attrs.push((name, value.to_string()));
+        }
+        Ok(Element {
+            name: String::from_utf8_lossy(e.local_name().as_ref()).to_string(),
+            attrs,
+            children: Vec::new(),
+        })
+    }
+
+    fn attr(&self, name: &str) -> Option<&str> {
+        self.attrs.iter().find(|(key, _)| key == name).map(|(_, value)| value.as_str())
+    }
+
+    fn elements(&self) -> impl Iterator<Item = &Element> {
+        self.children.iter().filter_map(|node| match node {
+            Node::Element(element) => Some(element),
+            Node::Text(_) => None,
+        })
+    }
+
+    fn child(&self, name: &str) -> Option<&Element> {
+        self.elements().find(|element| element.name == name)
+    }
+
+    /// The first descendant with this name, depth first.
+    fn find(&self, name: &str) -> Option<&Element> {
+        self.elements().find_map(|element| {
+            if element.name == name {
+                Some(element)
+            } else {
+                element.find(name)
+            }
+        })
+    }
+
+    /// The `val` attribute of a child property element.
+    fn val(&self, name: &str) -> Option<&str> {
+        self.child(name).and_then(|child| child.attr("val"))
+    }
+
+    /// True if an on/off property is present and not turned off.
+    fn flag(&self, name: &str) -> bool {
+        self.child(name).is_some_and(|child| {
+            !matches!(child.attr("val"), Some("0" | "false" | "off" | "none"))
+        })
+    }
+
+    /// Text of all descendants.
+    fn text(&self) -> String {
+        let mut text = String::new();
+        for node in &self.children {
+            match node {
+                Node::Text(t) => text += t,
+                Node::Element(element) => text += &element.text(),
+            }
+        }
+        text
+    }
+}
+
+/// A paragraph or character style from `styles.xml`.
+#[derive(Debug, Clone, Default)]
+struct Style {
+    name: String,
+    based_on: Option<String>,
+    /// Defined by the author rather than built into Word
+    custom: bool,
+    /// Outline level of the style's paragraphs, 0-based
+    outline: Option<u8>,
+    /// List numbering of the style's paragraphs: numId and level
+    numbering: Option<(String, u8)>,
+}
+
+/// List formats from `numbering.xml`.
+#[derive(Debug, Clone, Default)]
+struct Numbering {
+    /// numId to abstractNumId
+    nums: HashMap<String, String>,
+    /// Level formats and starts by abstractNumId and level
+    levels: HashMap<(String, u8), (String, u32)>,
+    /// Start overrides by numId and level
+    overrides: HashMap<(String, u8), u32>,
+}
+
+impl Numbering {
+    fn parse(root: &Element) -> Numbering {
+        let mut numbering = Numbering::default();
+        for element in root.elements() {
+            match element.name.as_str() {
+                "abstractNum" => {
+                    let id = element.attr("abstractNumId").unwrap_or_default();
+                    for lvl in element.elements().filter(|e| e.name == "lvl") {
+                        let level = lvl.attr("ilvl").and_then(|l| l.parse().ok()).unwrap_or(0);
+                        let format = lvl.val("numFmt").unwrap_or("decimal").to_string();
+                        let start = lvl.val("start").and_then(|s| s.parse().ok()).unwrap_or(1);
+                        numbering.levels.insert((id.to_string(), level), (format, start));
+                    }
+                }
+                "num" => {
+                    let id = element.attr("numId").unwrap_or_default().to_string();
+                    if let Some(abstract_id) = element.val("abstractNumId") {
+                        numbering.nums.insert(id.clone(), abstract_id.to_string());
+                    }
+                    for over in element.elements().filter(|e| e.name == "lvlOverride") {
+                        let level = over.attr("ilvl").and_then(|l| l.parse().ok()).unwrap_or(0);
+                        if let Some(start) = over.val("startOverride").and_then(|s| s.parse().ok()) {
+                            numbering.overrides.insert((id.clone(), level), start);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        numbering
+    }
+
+    /// Number format and first number of a list level; bullets if unknown.
+    fn level(&self, num_id: &str, level: u8) -> (&str, u32) {
+        let (format, start) = self.nums.get(num_id)
+            .and_then(|abstract_id| self.levels.get(&(abstract_id.clone(), level)))
+            .map(|(format, start)| (format.as_str(), *start))
+            .unwrap_or(("bullet", 1));
+        let start = self.overrides.get(&(num_id.to_string(), level)).copied().unwrap_or(start);
+        (format, start)
+    }
+}
+
+/// Relationships of one part by id.
+type Relationships = HashMap<String, Relationship>;
+
+/// The parts of a Word document the converter reads.
+#[derive(Debug, Clone, Default)]
+pub struct DocxParts {
+    document: Element,
+    /// Relationships of the main document, footnotes, and endnotes
+    rels: [Relationships; 3],
+    styles: HashMap<String, Style>,
+    numbering: Numbering,
+    /// Footnote and endnote bodies by part (1 or 2) and id
+    notes: HashMap<(usize, String), Element>,
+}
+
+impl DocxParts {
+    /// Read and parse the document parts from a `.docx` archive.
+    pub fn read<R: Read + Seek>(archive: &mut ZipArchive<R>) -> EditorResult<DocxParts> {
+        let document_path = part_of_type(&relationships(archive, "")?, "/officeDocument")
+            .unwrap_or_else(|| "word/document.xml".to_string());
+        let document = parse_part(archive, &document_path)?;
+
+        let mut parts = DocxParts { document, ..DocxParts::default() };
+        let document_rels = relationships(archive, &document_path)?;
+        let part_path = |kind: &str| part_of_type(&document_rels, kind);
+        if let Some(path) = part_path("/styles") {
+            parts.styles = parse_styles(&parse_part(archive, &path)?);
+        }
+        if let Some(path) = part_path("/numbering") {
+            parts.numbering = Numbering::parse(&parse_part(archive, &path)?);
+        }
+        for (part, kind) in [(1, "/footnotes"), (2, "/endnotes")] {
+            let Some(path) = part_path(kind) else {
+                continue;
+            };
+            let root = parse_part(archive, &path)?;
+            for note in root.elements() {
+                let normal = note.attr("type").is_none_or(|t| t == "normal");
+                if let (true, Some(id)) = (normal, note.attr("id")) {
+                    parts.notes.insert((part, id.to_string()), note.clone());
+                }
+            }
+            parts.rels[part] = relationship_map(relationships(archive, &path)?);
+        }
+        parts.rels[0] = relationship_map(document_rels);
+        Ok(parts)
+    }
+}
+
+fn parse_part<R: Read + Seek>(archive: &mut ZipArchive<R>, path: &str) -> EditorResult<Element> {
+    Element::parse(&read_entry(archive, path)?).map_err(|e| malformed(format!("{}: {}", path, e)))
+}
+
+/// One entry of a part's `_rels` file.
+#[derive(Debug, Clone)]
+struct Relationship {
+    id: String,
+    /// Relationship type URI
+    kind: String,
+    /// Zip path of an internal part, or the URL of an external target
+    target: String,
+    external: bool,
+}
+
+/// Zip path of the first internal part with a relationship type ending in `kind`.
+fn part_of_type(rels: &[Relationship], kind: &str) -> Option<String> {
+    rels.iter()
+        .find(|rel| rel.kind.ends_with(kind) && !rel.external)
+        .map(|rel| rel.target.clone())
+}
+
+fn relationship_map(rels: Vec<Relationship>) -> Relationships {
+    rels.into_iter().map(|rel| (rel.id.clone(), rel)).collect()
+}
+
+/// Relationships from the `_rels` file of a part, with internal targets
+/// resolved to zip paths. Empty if the part has none.
+fn relationships<R: Read + Seek>(archive: &mut ZipArchive<R>, part: &str) -> EditorResult<Vec<Relationship>> {
+    let (folder, name) = part.rsplit_once('/').unwrap_or(("", part));
+    let rels_path = if folder.is_empty() {
+        format!("_rels/{}.rels", name)
+    } else {
+        format!("{}/_rels/{}.rels", folder, name)
+    };
+    if archive.by_name(&rels_path).is_err() {
+        return Ok(Vec::new());
+    }
+    let root = parse_part(archive, &rels_path)?;
+    Ok(root.elements()
+        .filter(|e| e.name == "Relationship")
+        .filter_map(|e| {
+            let target = e.attr("Target")?;
+            let external = e.attr("TargetMode") == Some("External");
+            let target = if external {
+                target.to_string()
+            } else if let Some(absolute) = target.strip_prefix('/') {
+                absolute.to_string()
+            } else {
+                links::resolve_relative(part, &links::percent_decode(target))?
+            };
+            Some(Relationship {
+                id: e.attr("Id")?.to_string(),
+                kind: e.attr("Type").unwrap_or_default().to_string(),
+                target,
+                external,
+            })
+        })
+        .collect())
+}
+
+fn parse_styles(root: &Element) -> HashMap<String, Style> {
+    root.elements()
+        .filter(|e| e.name == "style")
+        .filter_map(|e| {
+            let id = e.attr("styleId")?.to_string();
+            let props = e.child("pPr");
+            let numbering = props.and_then(|p| p.child("numPr")).and_then(|num| {
+                let num_id = num.val("numId")?;
+                let level = num.val("ilvl").and_then(|l| l.parse().ok()).unwrap_or(0);
+                Some((num_id.to_string(), level))
+            });
+            let style = Style {
+                name: e.val("name").unwrap_or(&id).to_string(),
+                based_on: e.val("basedOn").map(String::from),
+                custom: e.attr("customStyle").is_some_and(|c| c == "1" || c == "true"),
+                outline: props.and_then(|p| p.val("outlineLvl")).and_then(|l| l.parse().ok()),
+                numbering,
+            };
+            Some((id, style))
+        })
+        .collect()
+}
+
+/// How a paragraph is written: its element, classes, and heading level.
+struct ParagraphStyle {
+    /// Style name, or the style id if the style is not defined
+    name: String,
+    tag: String,
+    classes: Vec<String>,
+    level: Option<u8>,
+}
+
+/// A table cell with its first grid column and column span.
+type TableCell<'e> = (&'e Element, usize, usize);
+
+/// A converted paragraph or table, before lists and quotes are grouped.
+enum Block {
+    Paragraph {
+        tag: String,
+        classes: Vec<String>,
+        id: Option<String>,
+        /// numId and level of a list item
+        list: Option<(String, u8)>,
+        html: String,
+    },
+    Html(String),
+}
+
+/// Writes inline content, opening and closing formatting elements only
+/// where the formatting of neighboring runs differs.
+#[derive(Default)]
+struct InlineWriter {
+    html: String,
+    /// Open tags, outermost first, such as `strong` or `a href="..."`
+    open: Vec<String>,
+}
+
+impl InlineWriter {
+    fn set_format(&mut self, format: &[String]) {
+        let common = self.open.iter().zip(format).take_while(|(a, b)| a == b).count();
+        while self.open.len() > common {
+            let tag = self.open.pop().unwrap();
+            let name = tag.split(' ').next().unwrap_or_default();
+            self.html += &format!("</{}>", name);
+        }
+        for tag in &format[common..] {
+            self.html += &format!("<{}>", tag);
+            self.open.push(tag.clone());
+        }
+    }
+
+    fn text(&mut self, format: &[String], text: &str) {
+        if !text.is_empty() {
+            self.set_format(format);
+            self.html += &escape_text(text);
+        }
+    }
+
+    fn raw(&mut self, format: &[String], html: &str) {
+        self.set_format(format);
+        self.html += html;
+    }
+
+    fn finish(mut self) -> String {
+        self.set_format(&[]);
+        self.html.trim().to_string()
+    }
+}
+
+struct Converter<'a> {
+    parts: &'a DocxParts,
+    config: &'a DocxConfig,
+    /// Project-relative folder for this document's images
+    image_dir: String,
+    images: Vec<(String, String)>,
+    warnings: Vec<String>,
+    /// Items seen so far per list and level, for `start` numbers
+    counts: HashMap<(String, u8), u32>,
+    /// Notes referenced in the current chapter, by part and id
+    notes: Vec<(usize, String)>,
+    /// Part being converted: 0 document, 1 footnotes, 2 endnotes
+    part: usize,
+    in_heading: bool,
+}
+
+impl Converter<'_> {
+    fn warn_once(&mut self, warning: String) {
+        if !self.warnings.contains(&warning) {
+            self.warnings.push(warning);
+        }
+    }
+
+    /// The configured mapping of a style, by name or id.
+    fn mapping(&self, name: &str, id: &str) -> Option<(Option<String>, Vec<String>)> {
+        let selector = self.config.styles.iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name) || key.as_str() == id)
+            .map(|(_, selector)| selector)?;
+        let mut parts = selector.trim().split('.');
+        let element = parts.next().filter(|e| !e.is_empty()).map(|e| e.to_ascii_lowercase());
+        let classes = parts.filter(|c| !c.is_empty()).map(String::from).collect();
+        Some((element, classes))
+    }
+
+    fn paragraph_style(&mut self, p: &Element) -> ParagraphStyle {
+        let props = p.child("pPr");
+        let id = props.and_then(|pr| pr.val("pStyle")).unwrap_or("Normal");
+        let style = self.parts.styles.get(id);
+        let name = style.map_or(id, |s| s.name.as_str()).to_string();
+
+        let mut level = props.and_then(|pr| pr.val("outlineLvl")).and_then(|l| l.parse::<u8>().ok());
+        let mut builtin = None;
+        let mut current = style;
+        for _ in 0..10 {
+            let Some(s) = current else {
+                break;
+            };
+            let lower = s.name.to_ascii_lowercase();
+            if let Some(n) = lower.strip_prefix("heading ").and_then(|n| n.parse::<u8>().ok()) {
+                level = level.or(n.checked_sub(1));
+            }
+            match lower.as_str() {
+                "title" => level = level.or(Some(0)),
+                "quote" | "intense quote" => builtin = builtin.or(Some("blockquote")),
+                "html preformatted" => builtin = builtin.or(Some("pre")),
+                _ => {}
+            }
+            level = level.or(s.outline);
+            current = s.based_on.as_ref().and_then(|b| self.parts.styles.get(b));
+        }
+        // Outline level 9 is body text
+        let level = level.filter(|l| *l < 9).map(|l| (l + 1).min(6));
+
+        let mut tag = match (level, builtin) {
+            (Some(level), _) => format!("h{}", level),
+            (None, Some(tag)) => tag.to_string(),
+            (None, None) => "p".to_string(),
+        };
+        let mut classes = Vec::new();
+        match self.mapping(&name, id) {
+            Some((element, mapped_classes)) => {
+                classes = mapped_classes;
+                match element {
+                    Some(element) if PARAGRAPH_ELEMENTS.contains(&element.as_str()) => tag = element,
+                    Some(element) => self.warn_once(format!(
+                        "Style \"{}\" maps to <{}>, which paragraphs cannot become; <{}> was used",
+                        name, element, tag,
+                    )),
+                    None => {}
+                }
+            }
+            None if style.is_some_and(|s| s.custom) && level.is_none() && builtin.is_none() => {
+                self.warn_once(format!("Paragraph style \"{}\" has no mapping; its paragraphs are plain <p>", name));
+            }
+            None => {}
+        }
+        let level = tag.strip_prefix('h').and_then(|n| n.parse().ok());
+        ParagraphStyle { name, tag, classes, level }
+    }
+
+    /// Convert a sequence of body elements to HTML.
+    fn blocks(&mut self, elements: &[&Element]) -> String {
+        let mut blocks = Vec::new();
+        for element in elements {
+            self.block(element, &mut blocks);
+        }
+        self.render_blocks(&blocks)
+    }
+
+    fn block(&mut self, element: &Element, blocks: &mut Vec<Block>) {
+        match element.name.as_str() {
+            "p" => {
+                if let Some(block) = self.paragraph(element) {
+                    blocks.push(block);
+                }
+            }
+            "tbl" => {
+                let table = self.table(element);
+                blocks.push(Block::Html(table));
+            }
+            "sdt" => {
+                let gallery = element.child("sdtPr")
+                    .and_then(|pr| pr.find("docPartGallery"))
+                    .and_then(|g| g.attr("val"))
+                    .unwrap_or_default();
+                if gallery.contains("Table of Contents") {
+                    self.warn_once(toc_warning());
+                } else if let Some(content) = element.child("sdtContent") {
+                    for child in content.elements() {
+                        self.block(child, blocks);
+                    }
+                }
+            }
+            "customXml" | "ins" | "smartTag" => {
+                for child in element.elements() {
+                    self.block(child, blocks);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn paragraph(&mut self, p: &Element) -> Option<Block> {
+        let style = self.paragraph_style(p);
+        let lower = style.name.to_ascii_lowercase();
+        if lower.starts_with("toc ") || lower == "toc heading" {
+            self.warn_once(toc_warning());
+            return None;
+        }
+
+        let mut list = None;
+        if !style.tag.starts_with('h') {
+            let props = p.child("pPr");
+            list = props.and_then(|pr| pr.child("numPr")).and_then(|num| {
+                let level = num.val("ilvl").and_then(|l| l.parse().ok()).unwrap_or(0);
+                Some((num.val("numId")?.to_string(), level))
+            });
+            let mut current = props.and_then(|pr| pr.val("pStyle")).and_then(|id| self.parts.styles.get(id));
+            for _ in 0..10 {
+                let Some(s) = current.filter(|_| list.is_none()) else {
+                    break;
+                };
+                list = s.numbering.clone();
+                current = s.based_on.as_ref().and_then(|b| self.parts.styles.get(b));
+            }
+            // numId 0 removes numbering inherited from the style
+            list = list.filter(|(num_id, _)| num_id != "0");
+        }
+
+        self.in_heading = style.level.is_some();
+        let html = self.inline_content(p);
+        self.in_heading = false;
+        let id = p.elements()
+            .filter(|e| e.name == "bookmarkStart")
+            .filter_map(|e| e.attr("name"))
+            .find(|name| !name.starts_with('_'))
+            .map(String::from);
+        if html.is_empty() && id.is_none() {
+            return None;
+        }
+        Some(Block::Paragraph { tag: style.tag, classes: style.classes, id, list, html })
+    }
+
+    /// Write blocks, grouping list items into nested lists and consecutive
+    /// quote or preformatted paragraphs into one element.
+    fn render_blocks(&mut self, blocks: &[Block]) -> String {
+        let mut out = String::new();
+        let mut i = 0;
+        while i < blocks.len() {
+            let run = |same: &dyn Fn(&Block) -> bool| {
+                blocks[i..].iter().take_while(|b| same(b)).count()
+            };
+            match &blocks[i] {
+                Block::Paragraph { list: Some(_), .. } => {
+                    let n = run(&|b| matches!(b, Block::Paragraph { list: Some(_), .. }));
+                    out += &self.list(&blocks[i..i + n]);
+                    i += n;
+                }
+                Block::Paragraph { tag, classes, id, .. } if tag == "blockquote" || tag == "pre" => {
+                    let n = run(&|b| matches!(
+                        b,
+                        Block::Paragraph { tag: t, classes: c, list: None, .. } if t == tag && c == classes
+                    ));
+                    let group = blocks[i..i + n].iter().filter_map(|b| match b {
+                        Block::Paragraph { id, html, .. } => Some((id.as_deref(), html.as_str())),
+                        Block::Html(_) => None,
+                    });
+                    let inner = if tag == "pre" {
+                        group.map(|(_, html)| html).collect::<Vec<_>>().join("\n")
+                    } else {
+                        group.map(|(id, html)| format!("\n{}", element_html("p", &[], id, html))).collect::<String>() + "\n"
+                    };
+                    let id = if tag == "pre" { id.as_deref() } else { None };
+                    out += &element_html(tag, classes, id, &inner);
+                    out.push('\n');
+                    i += n;
+                }
+                Block::Paragraph { tag, classes, id, html, .. } => {
+                    out += &element_html(tag, classes, id.as_deref(), html);
+                    out.push('\n');
+                    i += 1;
+                }
+                Block::Html(html) => {
+                    out += html;
+                    i += 1;
+                }
+            }
+        }
+        out
+    }
+
+    /// Write list items as nested `<ul>` and `<ol>` elements.
+    fn list(&mut self, items: &[Block]) -> String {
+        let mut out = String::new();
+        // Open lists, each with an open <li>: element and numId
+        let mut stack: Vec<(&str, String)> = Vec::new();
+        for item in items {
+            let Block::Paragraph { classes, id, list: Some((num_id, level)), html, .. } = item else {
+                continue;
+            };
+            // Never nest more than one level below the open lists
+            let depth = (*level as usize + 1).min(stack.len() + 1);
+            let level = (depth - 1) as u8;
+
+            let count = self.counts.entry((num_id.clone(), level)).or_insert(0);
+            *count += 1;
+            let count = *count;
+            self.counts.retain(|(n, l), _| n != num_id || *l <= level);
+
+            while stack.len() > depth
+                || (stack.len() == depth && stack.last().is_some_and(|(_, n)| n != num_id))
+            {
+                let (tag, _) = stack.pop().unwrap();
+                out += &format!("</li>\n</{}>\n", tag);
+            }
+            if stack.len() == depth {
+                out += "</li>\n";
+            }
+            while stack.len() < depth {
+                let (format, start) = self.parts.numbering.level(num_id, stack.len() as u8);
+                let (tag, mut attrs) = match format {
+                    "bullet" | "none" => ("ul", String::new()),
+                    "lowerLetter" => ("ol", " type=\"a\"".to_string()),
+                    "upperLetter" => ("ol", " type=\"A\"".to_string()),
+                    "lowerRoman" => ("ol", " type=\"i\"".to_string()),
+                    "upperRoman" => ("ol", " type=\"I\"".to_string()),
+                    _ => ("ol", String::new()),
+                };
+                let first = start + count - 1;
+                if tag == "ol" && first != 1 {
+                    attrs += &format!(" start=\"{}\"", first);
+                }
+                if !stack.is_empty() {
+                    out.push('\n');
+                }
+                out += &format!("<{}{}>\n", tag, attrs);
+                stack.push((tag, num_id.clone()));
+            }
+
+            let class = if classes.is_empty() {
+                String::new()
+            } else {
+                format!(" class=\"{}\"", escape_attribute(&classes.join(" ")))
+            };
+            let id = id.as_ref().map(|id| format!(" id=\"{}\"", escape_attribute(id))).unwrap_or_default();
+            out += &format!("<li{}{}>{}", class, id, html);
+        }
+        while let Some((tag, _)) = stack.pop() {
+            out += &format!("</li>\n</{}>\n", tag);
+        }
+        out
+    }
+
+    fn table(&mut self, tbl: &Element) -> String {
+        // Cells of each row with their first grid column and span
+        let rows: Vec<(&Element, Vec<TableCell>)> = tbl.elements()
+            .filter(|e| e.name == "tr")
+            .map(|tr| {
+                let mut column = tr.child("trPr")
+                    .and_then(|pr| pr.val("gridBefore"))
+                    .and_then(|n| n.parse().ok())
+                    .unwrap_or(0);
+                let cells = tr.elements()
+                    .filter(|e| e.name == "tc")
+                    .map(|tc| {
+                        let span = tc.child("tcPr")
+                            .and_then(|pr| pr.val("gridSpan"))
+                            .and_then(|n| n.parse().ok())
+                            .unwrap_or(1usize)
+                            .max(1);
+                        column += span;
+                        (tc, column - span, span)
+                    })
+                    .collect();
+                (tr, cells)
+            })
+            .collect();
+        let merge = |tc: &Element| tc.child("tcPr")
+            .and_then(|pr| pr.child("vMerge"))
+            .map(|m| m.attr("val").unwrap_or("continue").to_string());
+
+        let class = tbl.child("tblPr")
+            .and_then(|pr| pr.val("tblStyle"))
+            .and_then(|id| {
+                let name = self.parts.styles.get(id).map_or(id, |s| s.name.as_str());
+                self.mapping(name, id)
+            })
+            .map(|(_, classes)| classes)
+            .filter(|classes| !classes.is_empty())
+            .map(|classes| format!(" class=\"{}\"", escape_attribute(&classes.join(" "))))
+            .unwrap_or_default();
+
+        let header_rows = rows.iter()
+            .take_while(|(tr, _)| tr.child("trPr").is_some_and(|pr| pr.flag("tblHeader")))
+            .count();
+        let mut out = format!("<table{}>\n", class);
+        for (r, (_, cells)) in rows.iter().enumerate() {
+            if r == 0 && header_rows > 0 {
+                out += "<thead>\n";
+            }
+            if r == header_rows {
+                out += "<tbody>\n";
+            }
+            out += "<tr>\n";
+            for &(tc, column, span) in cells {
+                match merge(tc).as_deref() {
+                    Some("restart") | None => {}
+                    Some(_) => continue,
+                }
+                // A restarted vertical merge spans the continuing cells below
+                let mut row_span = 1;
+                if merge(tc).is_some() {
+                    row_span += rows[r + 1..].iter()
+                        .take_while(|(_, below)| below.iter().any(|&(cell, col, _)| {
+                            col == column && merge(cell).is_some_and(|m| m != "restart")
+                        }))
+                        .count();
+                }
+                let tag = if r < header_rows { "th" } else { "td" };
+                let mut attrs = String::new();
+                if span > 1 {
+                    attrs += &format!(" colspan=\"{}\"", span);
+                }
+                if row_span > 1 {
+                    attrs += &format!(" rowspan=\"{}\"", row_span);
+                }
+                let children: Vec<&Element> = tc.elements().collect();
+                let mut content = self.blocks(&children);
+                // A single plain paragraph is written without its <p>
+                if let Some(inner) = content.strip_prefix("<p>").and_then(|c| c.strip_suffix("</p>\n")) {
+                    if !inner.contains("<p>") {
+                        content = inner.to_string();
+                    }
+                }
+                out += &format!("<{tag}{attrs}>{}</{tag}>\n", content.trim_end());
+            }
+            out += "</tr>\n";
+            if r + 1 == header_rows {
+                out += "</thead>\n";
+            }
+        }
+        if rows.len() > header_rows {
+            out += "</tbody>\n";
+        }
+        out + "</table>\n"
+    }
+
+    fn inline_content(&mut self, element: &Element) -> String {
+        let mut writer = InlineWriter::default();
+        self.inlines(element, &[], &mut writer);
+        writer.finish()
+    }
+
+    fn inlines(&mut self, element: &Element, base: &[String], writer: &mut InlineWriter) {
+        for child in element.elements() {
+            match child.name.as_str() {
+                "r" => self.run(child, base, writer),
+                "hyperlink" => {
+                    let url = child.attr("id").and_then(|id| self.parts.rels[self.part].get(id)).map(|rel| &rel.target);
+                    let anchor = child.attr("anchor").filter(|a| !a.starts_with('_'));
+                    let href = match (url, anchor) {
+                        (Some(url), Some(anchor)) => Some(format!("{}#{}", url, anchor)),
+                        (Some(url), None) => Some(url.clone()),
+                        (None, Some(anchor)) => Some(format!("#{}", anchor)),
+                        (None, None) => None,
+                    };
+                    let mut format = base.to_vec();
+                    if let Some(href) = href {
+                        format.push(format!("a href=\"{}\"", escape_attribute(&href)));
+                    }
+                    self.inlines(child, &format, writer);
+                }
+                "ins" | "smartTag" | "customXml" | "fldSimple" | "sdtContent" => self.inlines(child, base, writer),
+                "sdt" => {
+                    if let Some(content) = child.child("sdtContent") {
+                        self.inlines(content, base, writer);
+                    }
+                }
+                "oMath" | "oMathPara" => self.warn_once("Equations were left out".to_string()),
+                _ => {}
+            }
+        }
+    }
+
+    fn run(&mut self, r: &Element, base: &[String], writer: &mut InlineWriter) {
+        let mut format = base.to_vec();
+        for tag in self.run_format(r.child("rPr")) {
+            if !format.contains(&tag) {
+                format.push(tag);
+            }
+        }
+        self.run_content(r, base, &format, writer);
+    }
+
+    fn run_content(&mut self, r: &Element, base: &[String], format: &[String], writer: &mut InlineWriter) {
+        for child in r.elements() {
+            match child.name.as_str() {
+                "t" => writer.text(format, &child.text()),
+                "tab" | "ptab" => writer.text(format, " "),
+                "noBreakHyphen" => writer.text(format, "-"),
+                "br" | "cr" if !matches!(child.attr("type"), Some("page" | "column")) => {
+                    writer.raw(format, "<br>");
+                }
+                "drawing" | "pict" | "object" => {
+                    if let Some(img) = self.image(child) {
+                        writer.raw(format, &img);
+                    }
+                }
+                "footnoteReference" | "endnoteReference" => {
+                    let part = if child.name == "footnoteReference" { 1 } else { 2 };
+                    if let Some(id) = child.attr("id") {
+                        let reference = self.note_reference(part, id);
+                        writer.raw(base, &reference);
+                    }
+                }
+                "AlternateContent" => {
+                    if let Some(choice) = child.child("Choice") {
+                        self.run_content(choice, base, format, writer);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Formatting elements for a run: its mapped character style, then
+    /// bold, italic, strikethrough, and vertical alignment.
+    fn run_format(&mut self, props: Option<&Element>) -> Vec<String> {
+        let mut format = Vec::new();
+        let Some(props) = props else {
+            return format;
+        };
+        if let Some(id) = props.val("rStyle") {
+            let style = self.parts.styles.get(id);
+            let name = style.map_or(id, |s| s.name.as_str()).to_string();
+            match self.mapping(&name, id) {
+                Some((element, classes)) => {
+                    let element = match element {
+                        Some(element) if CHARACTER_ELEMENTS.contains(&element.as_str()) => element,
+                        Some(element) => {
+                            self.warn_once(format!(
+                                "Style \"{}\" maps to <{}>, which text cannot become; <span> was used",
+                                name, element,
+                            ));
+                            "span".to_string()
+                        }
+                        None => "span".to_string(),
+                    };
+                    if classes.is_empty() {
+                        if element != "span" {
+                            format.push(element);
+                        }
+                    } else {
+                        format.push(format!("{} class=\"{}\"", element, escape_attribute(&classes.join(" "))));
+                    }
+                }
+                None => match name.to_ascii_lowercase().as_str() {
+                    "strong" => format.push("strong".to_string()),
+                    "emphasis" => format.push("em".to_string()),
+                    "html code" | "html typewriter" => format.push("code".to_string()),
+                    _ if style.is_some_and(|s| s.custom) => self.warn_once(format!(
+                        "Character style \"{}\" has no mapping and was dropped",
+                        name,
+                    )),
+                    _ => {}
+                },
+            }
+        }
+        if props.flag("b") && !self.in_heading {
+            format.push("strong".to_string());
+        }
+        if props.flag("i") {
+            format.push("em".to_string());
+        }
+        if props.flag("strike") || props.flag("dstrike") {
+            format.push("s".to_string());
+        }
+        match props.val("vertAlign") {
+            Some("superscript") => format.push("sup".to_string()),
+            Some("subscript") => format.push("sub".to_string()),
+            _ => {}
+        }
+        format
+    }
+
+    /// An `<img>` for a drawing, extracting the image into the assets folder.
+    fn image(&mut self, drawing: &Element) -> Option<String> {
+        let id = drawing.find("blip")
+            .and_then(|blip| blip.attr("embed").or(blip.attr("link")))
+            .or_else(|| drawing.find("imagedata").and_then(|data| data.attr("id")))?;
+        let Some(Relationship { target, external, .. }) = self.parts.rels[self.part].get(id).cloned() else {
+            self.warn_once(format!("Image {} is not in the document and was left out", id));
+            return None;
+        };
+        let alt = drawing.find("docPr")
+            .and_then(|pr| pr.attr("descr").filter(|d| !d.is_empty()).or(pr.attr("title")))
+            .unwrap_or_default()
+            .to_string();
+
+        let src = if external {
+            target.clone()
+        } else {
+            let name = target.rsplit('/').next().unwrap_or(&target);
+            let project_path = format!("{}/{}", self.image_dir, name);
+            if !self.images.iter().any(|(entry, _)| entry == &target) {
+                self.images.push((target.clone(), project_path.clone()));
+            }
+            project_path
+        };
+        if alt.is_empty() {
+            let name = src.rsplit('/').next().unwrap_or(&src).to_string();
+            self.warn_once(format!("Image {} has no alt text", name));
+        }
+        Some(format!("<img src=\"{}\" alt=\"{}\">", escape_attribute(&links::encode_path(&src)), escape_attribute(&alt)))
+    }
+
+    /// A numbered reference to a footnote or endnote, numbered per chapter.
+    fn note_reference(&mut self, part: usize, id: &str) -> String {
+        let key = (part, id.to_string());
+        if !self.parts.notes.contains_key(&key) {
+            self.warn_once(format!("Note {} is referenced but missing", id));
+            return String::new();
+        }
+        let n = match self.notes.iter().position(|k| k == &key) {
+            Some(i) => i + 1,
+            None => {
+                self.notes.push(key);
+                self.notes.len()
+            }
+        };
+        format!(
+            "<sup class=\"footnote-ref\"><a href=\"#fn-{n}\" id=\"fnref-{n}\" role=\"doc-noteref\">{n}</a></sup>",
+        )
+    }
+
+    /// The notes referenced in the current chapter, as a footnotes section.
+    fn footnotes(&mut self) -> String {
+        let mut out = String::new();
+        let notes = std::mem::take(&mut self.notes);
+        for (i, key) in notes.iter().enumerate() {
+            let n = i + 1;
+            let note = &self.parts.notes[key];
+            let children: Vec<&Element> = note.elements().collect();
+            let part = std::mem::replace(&mut self.part, key.0);
+            let mut content = self.blocks(&children);
+            self.part = part;
+
+            let backref = format!(" <a href=\"#fnref-{n}\" class=\"footnote-backref\" role=\"doc-backlink\">&#8617;</a>");
+            match content.rfind("</p>") {
+                Some(end) if content[end..].trim() == "</p>" => content.insert_str(end, &backref),
+                _ => content += &format!("<p>{}</p>\n", backref.trim_start()),
+            }
+            out += &format!("<li id=\"fn-{}\">\n{}</li>\n", n, content);
+        }
+        if out.is_empty() {
+            return out;
+        }
+        format!("<section class=\"footnotes\" role=\"doc-endnotes\">\n<ol>\n{}</ol>\n</section>\n", out)
+    }
+}
+
+fn toc_warning() -> String {
+    "The document's table of contents was left out; exports generate their own".to_string()
+}
+
+/// An element with optional classes and id around inline HTML.
+fn element_html(tag: &str, classes: &[String], id: Option<&str>, html: &str) -> String {
+    let mut attrs = String::new();
+    if !classes.is_empty() {
+        attrs += &format!(" class=\"{}\"", escape_attribute(&classes.join(" ")));
+    }
+    if let Some(id) = id {
+        attrs += &format!(" id=\"{}\"", escape_attribute(id));
+    }
+    format!("<{tag}{attrs}>{html}</{tag}>")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use zip::write::SimpleFileOptions;
+
+    const W: &str = "xmlns:w=\"http://schemas.openxmlformats.org/wordprocessingml/2006/main\" \
+        xmlns:r=\"http://schemas.openxmlformats.org/officeDocument/2006/relationships\" \
+        xmlns:wp=\"http://schemas.openxmlformats.org/drawingml/2006/wordprocessingDrawing\" \
+        xmlns:a=\"http://schemas.openxmlformats.org/drawingml/2006/main\"";
+
+    fn write_test_docx(path: &Path, body: &str) {
+        let rels = "http://schemas.openxmlformats.org/officeDocument/2006/relationships";
+        let entries = [
+            ("_rels/.rels".to_string(), format!(
+                "<Relationships><Relationship Id=\"rId1\" Type=\"{}/officeDocument\" Target=\"word/document.xml\"/></Relationships>",
+                rels,
+            )),
+            ("word/_rels/document.xml.rels".to_string(), format!(
+                "<Relationships>\
+                 <Relationship Id=\"rId1\" Type=\"{0}/styles\" Target=\"styles.xml\"/>\
+                 <Relationship Id=\"rId2\" Type=\"{0}/numbering\" Target=\"numbering.xml\"/>\
+                 <Relationship Id=\"rId3\" Type=\"{0}/footnotes\" Target=\"footnotes.xml\"/>\
+                 <Relationship Id=\"rId4\" Type=\"{0}/hyperlink\" Target=\"https://example.com/?a=1&amp;b=2\" TargetMode=\"External\"/>\
+                 <Relationship Id=\"rId5\" Type=\"{0}/image\" Target=\"media/image1.png\"/>\
+                 </Relationships>",
+                rels,
+            )),
+            ("word/document.xml".to_string(), format!("<w:document {}><w:body>{}<w:sectPr/></w:body></w:document>", W, body)),
+            ("word/styles.xml".to_string(), format!(
+                "<w:styles {}>\
+                 <w:style w:type=\"paragraph\" w:styleId=\"Title\"><w:name w:val=\"Title\"/></w:style>\
+                 <w:style w:type=\"paragraph\" w:styleId=\"Heading1\"><w:name w:val=\"heading 1\"/></w:style>\
+                 <w:style w:type=\"paragraph\" w:styleId=\"Heading2\"><w:name w:val=\"heading 2\"/><w:basedOn w:val=\"Heading1\"/></w:style>\
+                 <w:style w:type=\"paragraph\" w:customStyle=\"1\" w:styleId=\"Epigraph\"><w:name w:val=\"Epigraph\"/></w:style>\
+                 <w:style w:type=\"paragraph\" w:customStyle=\"1\" w:styleId=\"Sidebar\"><w:name w:val=\"Sidebar\"/></w:style>\
+                 <w:style w:type=\"character\" w:customStyle=\"1\" w:styleId=\"Term\"><w:name w:val=\"Term\"/></w:style>\
+                 </w:styles>",
+                W,
+            )),
+            ("word/numbering.xml".to_string(), format!(
+                "<w:numbering {}>\
+                 <w:abstractNum w:abstractNumId=\"0\">\
+                 <w:lvl w:ilvl=\"0\"><w:start w:val=\"1\"/><w:numFmt w:val=\"bullet\"/></w:lvl>\
+                 <w:lvl w:ilvl=\"1\"><w:start w:val=\"1\"/><w:numFmt w:val=\"lowerLetter\"/></w:lvl>\
+                 </w:abstractNum>\
+                 <w:abstractNum w:abstractNumId=\"1\"><w:lvl w:ilvl=\"0\"><w:start w:val=\"1\"/><w:numFmt w:val=\"decimal\"/></w:lvl></w:abstractNum>\
+                 <w:num w:numId=\"1\"><w:abstractNumId w:val=\"0\"/></w:num>\
+                 <w:num w:numId=\"2\"><w:abstractNumId w:val=\"1\"/></w:num>\
+                 </w:numbering>",
+                W,
+            )),
+            ("word/footnotes.xml".to_string(), format!(
+                "<w:footnotes {}>\
+                 <w:footnote w:type=\"separator\" w:id=\"-1\"><w:p><w:r><w:separator/></w:r></w:p></w:footnote>\
+                 <w:footnote w:id=\"1\"><w:p><w:r><w:footnoteRef/></w:r><w:r><w:t xml:space=\"preserve\"> Seen in </w:t></w:r>\
+                 <w:r><w:rPr><w:i/></w:rPr><w:t>May</w:t></w:r><w:r><w:t>.</w:t></w:r></w:p></w:footnote>\
+                 </w:footnotes>",
+                W,
+            )),
+        ];
+        let mut zip = zip::ZipWriter::new(File::create(path).unwrap());
+        for (name, content) in &entries {
+            zip.start_file(name.as_str(), SimpleFileOptions::default()).unwrap();
+            zip.write_all(content.as_bytes()).unwrap();
+        }
+        zip.start_file("word/media/image1.png", SimpleFileOptions::default()).unwrap();
+        zip.write_all(b"png").unwrap();
+        zip.finish().unwrap();
+    }
+
+    fn p(style: &str, runs: &str) -> String {
+        format!("<w:p><w:pPr><w:pStyle w:val=\"{}\"/></w:pPr>{}</w:p>", style, runs)
+    }
+
+    fn item(num_id: u32, level: u32, text: &str) -> String {
+        format!(
+            "<w:p><w:pPr><w:numPr><w:ilvl w:val=\"{}\"/><w:numId w:val=\"{}\"/></w:numPr></w:pPr><w:r><w:t>{}</w:t></w:r></w:p>",
+            level, num_id, text,
+        )
+    }
+
+    fn cell(props: &str, text: &str) -> String {
+        format!("<w:tc><w:tcPr>{}</w:tcPr><w:p><w:r><w:t>{}</w:t></w:r></w:p></w:tc>", props, text)
+    }
+
+    fn convert(name: &str, body: &str, config: &DocxConfig) -> ConvertedDocx {
+        let path = std::env::temp_dir().join(format!("rhe_docx_{}_{}.docx", name, std::process::id()));
+        write_test_docx(&path, body);
+        let mut archive = ZipArchive::new(File::open(&path).unwrap()).unwrap();
+        let parts = DocxParts::read(&mut archive).unwrap();
+        fs::remove_file(&path).unwrap();
+        convert_docx(&parts, "doc", config)
+    }
+
+    fn text(runs: &str) -> String {
+        format!("<w:r><w:t>{}</w:t></w:r>", runs)
+    }
+
+    #[test]
+    fn test_import_docx() {
+        let dir = std::env::temp_dir().join(format!("rhe_docx_import_{}", std::process::id()));
+        let project = dir.join("book");
+        fs::create_dir_all(&project).unwrap();
+        fs::write(project.join("intro.html"), "<h1>Intro</h1>\n").unwrap();
+        let manifest = Manifest {
+            docx: Some(DocxConfig {
+                styles: BTreeMap::from([
+                    ("Epigraph".to_string(), "blockquote.epigraph".to_string()),
+                    ("term".to_string(), "dfn".to_string()),
+                ]),
+                ..DocxConfig::default()
+            }),
+            ..Manifest::default()
+        };
+        manifest.save(&project).unwrap();
+
+        let body = [
+            p("Title", "<w:r><w:rPr><w:b/></w:rPr><w:t>Field Guide</w:t></w:r>"),
+            p("Normal", "<w:r><w:rPr><w:b/><w:color w:val=\"FF0000\"/></w:rPr><w:t xml:space=\"preserve\">Bold </w:t></w:r>\
+                <w:r><w:rPr><w:b/><w:i/></w:rPr><w:t>both</w:t></w:r><w:r><w:t xml:space=\"preserve\">, a </w:t></w:r>\
+                <w:r><w:rPr><w:rStyle w:val=\"Term\"/></w:rPr><w:t>lek</w:t></w:r>\
+                <w:r><w:rPr><w:vertAlign w:val=\"superscript\"/></w:rPr><w:footnoteReference w:id=\"1\"/></w:r>\
+                <w:hyperlink r:id=\"rId4\"><w:r><w:t>site</w:t></w:r></w:hyperlink><w:r><w:t xml:space=\"preserve\"> and </w:t></w:r>\
+                <w:hyperlink w:anchor=\"birds\"><w:r><w:t>birds</w:t></w:r></w:hyperlink>"),
+            p("Epigraph", "<w:r><w:t>First line</w:t></w:r>"),
+            p("Epigraph", "<w:r><w:t>Second line</w:t></w:r>"),
+            p("Sidebar", "<w:r><w:t>Aside text</w:t></w:r>"),
+            p("Normal", ""),
+            item(1, 0, "Wings"),
+            item(1, 1, "Long"),
+            item(1, 0, "Feet"),
+            p("Heading1", "<w:bookmarkStart w:id=\"0\" w:name=\"birds\"/><w:r><w:t>Birds</w:t></w:r><w:bookmarkEnd w:id=\"0\"/>"),
+            item(2, 0, "One"),
+            item(2, 0, "Two"),
+            p("Normal", "<w:r><w:drawing><wp:inline><wp:docPr w:id=\"1\" name=\"Picture\" descr=\"Map\"/>\
+                <a:graphic><a:graphicData><a:blip r:embed=\"rId5\"/></a:graphicData></a:graphic></wp:inline></w:drawing></w:r>"),
+            item(2, 0, "Three"),
+            format!(
+                "<w:tbl><w:tblPr/><w:tr><w:trPr><w:tblHeader/></w:trPr>{}{}</w:tr><w:tr>{}{}{}</w:tr><w:tr>{}{}{}</w:tr></w:tbl>",
+                cell("<w:gridSpan w:val=\"2\"/>", "Bird"), cell("", "Count"),
+                cell("<w:vMerge w:val=\"restart\"/>", "Wren"), cell("", "a"), cell("", "3"),
+                cell("<w:vMerge/>", ""), cell("", "b"), cell("", "4"),
+            ),
+        ].concat();
+        let docx = dir.join("guide.docx");
+        write_test_docx(&docx, &body);
+
+        let imported = import_docx(&docx, &project).unwrap();
+        assert_eq!(imported.chapters, vec!["guide-01.html", "guide-02.html"]);
+        assert_eq!(imported.images, vec!["images/guide/image1.png"]);
+        assert_eq!(imported.warnings, vec!["Paragraph style \"Sidebar\" has no mapping; its paragraphs are plain <p>"]);
+        assert_eq!(Manifest::load(&project).unwrap().chapters, vec!["intro.html", "guide-01.html", "guide-02.html"]);
+        assert_eq!(fs::read(project.join("images/guide/image1.png")).unwrap(), b"png");
+
+        let one = fs::read_to_string(project.join("guide-01.html")).unwrap();
+        assert!(one.contains("<title>Field Guide</title>"));
+        assert!(one.contains(
+            "<h1>Field Guide</h1>\n\
+             <p><strong>Bold <em>both</em></strong>, a <dfn>lek</dfn>\
+             <sup class=\"footnote-ref\"><a href=\"#fn-1\" id=\"fnref-1\" role=\"doc-noteref\">1</a></sup>\
+             <a href=\"https://example.com/?a=1&amp;b=2\">site</a> and <a href=\"guide-02.html#birds\">birds</a></p>\n\
+             <blockquote class=\"epigraph\">\n<p>First line</p>\n<p>Second line</p>\n</blockquote>\n\
+             <p>Aside text</p>\n\
+             <ul>\n<li>Wings\n<ol type=\"a\">\n<li>Long</li>\n</ol>\n</li>\n<li>Feet</li>\n</ul>\n\
+             <section class=\"footnotes\" role=\"doc-endnotes\">\n<ol>\n<li id=\"fn-1\">\n\
+             <p>Seen in <em>May</em>. <a href=\"#fnref-1\" class=\"footnote-backref\" role=\"doc-backlink\">&#8617;</a></p>\n\
+             </li>\n</ol>\n</section>\n"
+        ));
+        let two = fs::read_to_string(project.join("guide-02.html")).unwrap();
+        assert!(two.contains(
+            "<h1 id=\"birds\">Birds</h1>\n<ol>\n<li>One</li>\n<li>Two</li>\n</ol>\n\
+             <p><img src=\"images/guide/image1.png\" alt=\"Map\"></p>\n<ol start=\"3\">\n<li>Three</li>\n</ol>\n\
+             <table>\n<thead>\n<tr>\n<th colspan=\"2\">Bird</th>\n<th>Count</th>\n</tr>\n</thead>\n\
+             <tbody>\n<tr>\n<td rowspan=\"2\">Wren</td>\n<td>a</td>\n<td>3</td>\n</tr>\n\
+             <tr>\n<td>b</td>\n<td>4</td>\n</tr>\n</tbody>\n</table>\n"
+        ));
+        assert!(!one.contains("style=") && !two.contains("style="));
+
+        // Existing chapters are not overwritten
+        let again = import_docx(&docx, &project).unwrap_err();
+        assert!(matches!(again.kind, ErrorKind::AlreadyExists));
+
+        // A file name that is only dots does not put images in the project root
+        let dots = dir.join("...docx");
+        fs::copy(&docx, &dots).unwrap();
+        let imported = import_docx(&dots, &project).unwrap();
+        assert_eq!(imported.chapters, vec!["document-01.html", "document-02.html"]);
+        assert_eq!(imported.images, vec!["images/document/image1.png"]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_style_mapping() {
+        let config = DocxConfig {
+            styles: BTreeMap::from([
+                ("Epigraph".to_string(), "blockquote.epigraph".to_string()),
+                ("Sidebar".to_string(), ".note".to_string()),
+                ("Term".to_string(), "dfn.term".to_string()),
+            ]),
+            ..DocxConfig::default()
+        };
+        let body = [
+            p("Heading2", &text("Sub")),
+            p("Epigraph", &text("Quoted")),
+            p("Sidebar", &text("Aside")),
+            p("Normal", "<w:r><w:rPr><w:rStyle w:val=\"Term\"/></w:rPr><w:t>lek</w:t></w:r>"),
+        ].concat();
+        let converted = convert("styles", &body, &config);
+        assert_eq!(converted.chapters.len(), 1);
+        assert_eq!(
+            converted.chapters[0].body,
+            "<h2>Sub</h2>\n<blockquote class=\"epigraph\">\n<p>Quoted</p>\n</blockquote>\n\
+             <p class=\"note\">Aside</p>\n<p><dfn class=\"term\">lek</dfn></p>\n",
+        );
+        assert!(converted.warnings.is_empty());
+
+        let unmapped = convert("unmapped", &p("Sidebar", &text("Aside")), &DocxConfig::default());
+        assert_eq!(unmapped.warnings, vec!["Paragraph style \"Sidebar\" has no mapping; its paragraphs are plain <p>"]);
+    }
+
+    #[test]
+    fn test_list_numbering() {
+        let body = [
+            item(2, 0, "One"),
+            item(1, 0, "Dot"),
+            item(1, 1, "Sub a"),
+            item(1, 1, "Sub b"),
+            item(2, 0, "Two"),
+            p("Normal", &text("Between")),
+            item(2, 0, "Three"),
+        ].concat();
+        let converted = convert("lists", &body, &DocxConfig::default());
+        // Each list keeps counting across interruptions
+        assert_eq!(
+            converted.chapters[0].body,
+            "<ol>\n<li>One</li>\n</ol>\n\
+             <ul>\n<li>Dot\n<ol type=\"a\">\n<li>Sub a</li>\n<li>Sub b</li>\n</ol>\n</li>\n</ul>\n\
+             <ol start=\"2\">\n<li>Two</li>\n</ol>\n<p>Between</p>\n<ol start=\"3\">\n<li>Three</li>\n</ol>\n",
+        );
+    }
+
+    #[test]
+    fn test_tables() {
+        let body = format!(
+            "<w:tbl><w:tblPr/><w:tr><w:trPr><w:tblHeader/></w:trPr>{}{}</w:tr><w:tr>{}{}{}</w:tr><w:tr>{}{}{}</w:tr><w:tr>{}{}</w:tr></w:tbl>",
+            cell("<w:gridSpan w:val=\"2\"/>", "Name"), cell("", "Size"),
+            cell("<w:vMerge w:val=\"restart\"/>", "Wren"), cell("", "a"), cell("", "1"),
+            cell("<w:vMerge/>", ""), cell("", "b"), cell("", "2"),
+            cell("<w:gridSpan w:val=\"2\"/>", "Total"), cell("", "3"),
+        );
+        let converted = convert("tables", &body, &DocxConfig::default());
+        assert_eq!(
+            converted.chapters[0].body,
+            "<table>\n<thead>\n<tr>\n<th colspan=\"2\">Name</th>\n<th>Size</th>\n</tr>\n</thead>\n\
+             <tbody>\n<tr>\n<td rowspan=\"2\">Wren</td>\n<td>a</td>\n<td>1</td>\n</tr>\n\
+             <tr>\n<td>b</td>\n<td>2</td>\n</tr>\n\
+             <tr>\n<td colspan=\"2\">Total</td>\n<td>3</td>\n</tr>\n</tbody>\n</table>\n",
+        );
+    }
+
+    #[test]
+    fn test_footnotes() {
+        let body = [
+            p("Heading1", &text("One")),
+            p("Normal", "<w:r><w:t>Noted</w:t></w:r><w:r><w:footnoteReference w:id=\"1\"/></w:r>"),
+            p("Heading1", &text("Two")),
+            p("Normal", &text("Plain")),
+        ].concat();
+        let converted = convert("notes", &body, &DocxConfig::default());
+        assert_eq!(converted.chapters.len(), 2);
+        // Notes end the chapter that refers to them
+        assert_eq!(
+            converted.chapters[0].body,
+            "<h1>One</h1>\n<p>Noted<sup class=\"footnote-ref\"><a href=\"#fn-1\" id=\"fnref-1\" role=\"doc-noteref\">1</a></sup></p>\n\
+             <section class=\"footnotes\" role=\"doc-endnotes\">\n<ol>\n<li id=\"fn-1\">\n\
+             <p>Seen in <em>May</em>. <a href=\"#fnref-1\" class=\"footnote-backref\" role=\"doc-backlink\">&#8617;</a></p>\n\
+             </li>\n</ol>\n</section>\n",
+        );
+        assert_eq!(converted.chapters[1].body, "<h1>Two</h1>\n<p>Plain</p>\n");
+    }
+
+    #[test]
+    fn test_image_extraction() {
+        let drawing = |descr: &str| format!(
+            "<w:r><w:drawing><wp:inline><wp:docPr w:id=\"1\" name=\"Picture\"{}/>\
+             <a:graphic><a:graphicData><a:blip r:embed=\"rId5\"/></a:graphicData></a:graphic></wp:inline></w:drawing></w:r>",
+            descr,
+        );
+        let config = DocxConfig { assets_dir: "media/".to_string(), ..DocxConfig::default() };
+        let body = [p("Normal", &drawing(" descr=\"Map &amp; key\"")), p("Normal", &drawing(""))].concat();
+        let converted = convert("images", &body, &config);
+        assert_eq!(converted.images, vec![("word/media/image1.png".to_string(), "media/doc/image1.png".to_string())]);
+        // The image is extracted once however often it is used
+        assert_eq!(
+            converted.chapters[0].body,
+            "<p><img src=\"media/doc/image1.png\" alt=\"Map &amp; key\"></p>\n<p><img src=\"media/doc/image1.png\" alt=\"\"></p>\n",
+        );
+        assert_eq!(converted.warnings, vec!["Image image1.png has no alt text"]);
+    }
+
+    #[test]
+    fn test_import_docx_cleans_up_on_failure() {
+        let dir = std::env::temp_dir().join(format!("rhe_docx_cleanup_{}", std::process::id()));
+        let project = dir.join("book");
+        fs::create_dir_all(&project).unwrap();
+        fs::write(project.join("intro.html"), "<h1>Intro</h1>\n").unwrap();
+        Manifest { chapters: vec!["intro.html".to_string()], ..Manifest::default() }.save(&project).unwrap();
+        let manifest = fs::read_to_string(project.join("manifest.json")).unwrap();
+        // A folder at the manifest's temp path makes saving it fail last
+        fs::create_dir_all(project.join(".manifest.json.tmp/keep")).unwrap();
+
+        let body = [
+            p("Heading1", &text("One")),
+            p("Heading1", &text("Two")),
+            p("Normal", "<w:r><w:drawing><wp:inline><wp:docPr w:id=\"1\" name=\"Picture\" descr=\"Map\"/>\
+                <a:graphic><a:graphicData><a:blip r:embed=\"rId5\"/></a:graphicData></a:graphic></wp:inline></w:drawing></w:r>"),
+        ].concat();
+        let docx = dir.join("guide.docx");
+        write_test_docx(&docx, &body);
+
+        assert!(import_docx(&docx, &project).is_err());
+        assert!(!project.join("guide-01.html").exists());
+        assert!(!project.join("guide-02.html").exists());
+        assert!(!project.join("images").exists());
+        assert_eq!(fs::read_to_string(project.join("manifest.json")).unwrap(), manifest);
+        assert!(project.join("intro.html").exists());
+
+        // With the way clear, the same import succeeds
+        fs::remove_dir_all(project.join(".manifest.json.tmp")).unwrap();
+        let imported = import_docx(&docx, &project).unwrap();
+        assert_eq!(imported.chapters, vec!["guide-01.html", "guide-02.html"]);
+        assert!(project.join("images/guide/image1.png").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,618 @@
+use std::collections::{HashSet, VecDeque};
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+use crate::asset_urls;
+use crate::error::{EditorError, EditorResult};
+use crate::export;
+use crate::html_parser;
+use crate::links;
+use crate::manifest::{self, Manifest};
+use crate::media_type;
+use crate::project;
+use crate::recovery;
+use crate::stylesheets;
+use crate::xhtml::{self, Heading};
+
+/// Content of the `mimetype` entry, stored first and uncompressed.
+pub const MIMETYPE: &str = "application/epub+zip";
+
+/// Folder inside the EPUB holding the package document and content.
+pub const CONTENT_DIR: &str = "OEBPS";
+
+/// Package document filename inside `CONTENT_DIR`.
+pub const PACKAGE_FILENAME: &str = "content.opf";
+
+/// Path of the container document that points at the package document.
+pub const CONTAINER_PATH: &str = "META-INF/container.xml";
+
+/// URL attributes scanned for resources a chapter needs.
+const RESOURCE_ATTRIBUTES: [&str; 5] = ["href", "src", "poster", "data", "xlink:href"];
+
+/// Book metadata written to the package document.
+/// Empty fields fall back to defaults when exporting.
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct EpubMetadata {
+    /// Book title; the project folder name if empty
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub title: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub creators: Vec<String>,
+    /// BCP 47 language tag; `en` if empty
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub language: String,
+    /// Unique identifier such as an ISBN or `urn:uuid:`; derived from the
+    /// project path and title if empty
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub identifier: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub publisher: Option<String>,
+    /// Publication date, `YYYY` or `YYYY-MM-DD`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub date: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rights: Option<String>,
+    /// Project-relative path of the cover image
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cover_image: Option<String>,
+}
+
+/// What an EPUB export wrote.
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct EpubSummary {
+    pub path: String,
+    /// Chapters in spine order, project-relative
+    pub chapters: Vec<String>,
+    /// Stylesheets, images, fonts, and other files included
+    pub resources: Vec<String>,
+    /// Missing or unusable references, which were left out
+    pub warnings: Vec<String>,
+}
+
+/// A file going into the EPUB.
+struct Item {
+    /// Project-relative path, also the path under `CONTENT_DIR`
+    href: String,
+    media_type: &'static str,
+    properties: Vec<&'static str>,
+    content: ItemContent,
+}
+
+enum ItemContent {
+    Text(String),
+    File,
+}
+
+/// Export the project's chapters, in manifest order, as an EPUB 3 file.
+/// Chapters are converted to XHTML and keep their project-relative paths,
+/// so links between them and to assets stay valid. Stylesheets, images,
+/// fonts, and other files they reference (including through CSS `url()`
+/// and `@import`) are included. The navigation document lists each
+/// chapter and its next level of headings.
+/// `metadata` overrides the `epub` section of the project manifest.
+pub fn export_epub(
+    root: &Path,
+    output: &Path,
+    metadata: Option<&EpubMetadata>,
+) -> EditorResult<EpubSummary> {
+    let project_manifest = Manifest::load(root)
+        .map_err(|e| EditorError::io("Failed to read the project manifest", e).with_path(root))?;
+    let metadata = metadata.cloned()
+        .or(project_manifest.epub)
+        .unwrap_or_default();
+    let metadata = with_defaults(root, metadata);
+
+    let chapters = manifest::list_chapters(root)
+        .map_err(|e| EditorError::io("Failed to list chapters", e).with_path(root))?;
+    let chapter_paths: HashSet<String> = chapters.iter()
+        .map(|c| c.relative_path.replace('\\', "/"))
+        .collect();
+
+    let mut summary = EpubSummary {
+        path: output.to_string_lossy().to_string(),
+        ..EpubSummary::default()
+    };
+    let mut items: Vec<Item> = Vec::new();
+    let mut toc: Vec<(String, String, Vec<Heading>)> = Vec::new();
+    let mut wanted: VecDeque<(String, String)> = VecDeque::new();
+
+    for chapter in &chapters {
+        let doc = chapter.relative_path.replace('\\', "/");
+        let raw = fs::read_to_string(&chapter.path)
+            .map_err(|e| EditorError::io(format!("Failed to read {}", chapter.path), e).with_path(&chapter.path))?;
+        let (content, title, headings) = chapter_xhtml(root, &doc, &raw, &metadata.language);
+
+        let mut properties = Vec::new();
+        if content.contains("<svg") {
+            properties.push("svg");
+        }
+        if content.contains("<script") {
+            properties.push("scripted");
+        }
+        for span in links::attribute_spans(&content, &RESOURCE_ATTRIBUTES) {
+            let url = &content[span.start..span.end];
+            if url.starts_with("http://") || url.starts_with("https://") {
+                if span.name != "href" && !properties.contains(&"remote-resources") {
+                    properties.push("remote-resources");
+                }
+                continue;
+            }
+            wanted.push_back((doc.clone(), url.to_string()));
+        }
+
+        items.push(Item {
+            href: doc.clone(),
+            media_type: "application/xhtml+xml",
+            properties,
+            content: ItemContent::Text(content),
+        });
+        toc.push((doc.clone(), title, headings));
+        summary.chapters.push(doc);
+    }
+
+    // Cover image, then everything the chapters reference
+    let mut included: HashSet<String> = chapter_paths.clone();
+    let mut cover = None;
+    if let Some(cover_path) = &metadata.cover_image {
+        let cover_path = cover_path.replace('\\', "/");
+        let full_path = root.join(&cover_path);
+        if full_path.is_file() && project::ensure_inside(root, &full_path).is_err() {
+            summary.warnings.push(format!("Cover image {} is outside the project", cover_path));
+        } else if full_path.is_file() {
+            included.insert(cover_path.clone());
+            items.push(Item {
+                media_type: media_type::media_type(Path::new(&cover_path)),
+                href: cover_path.clone(),
+                properties: vec!["cover-image"],
+                content: ItemContent::File,
+            });
+            summary.resources.push(cover_path.clone());
+            cover = Some(cover_path);
+        } else {
+            summary.warnings.push(format!("Cover image {} not found", cover_path));
+        }
+    }
+
+    while let Some((from, url)) = wanted.pop_front() {
+        if !links::is_relative_url(&url) {
+            continue;
+        }
+        let (url_path, _) = links::split_url(&url);
+        if url_path.is_empty() {
+            continue;
+        }
+        let Some(target) = links::resolve_relative(&from, &links::percent_decode(url_path)) else {
+            summary.warnings.push(format!("{}: {} is outside the project", from, url));
+            continue;
+        };
+        if included.contains(&target) {
+            continue;
+        }
+        let target_path = root.join(&target);
+        if !target_path.is_file() {
+            summary.warnings.push(format!("{}: {} not found", from, url));
+            continue;
+        }
+        // A symlink may point anywhere; only files in the project are packed
+        if project::ensure_inside(root, &target_path).is_err() {
+            summary.warnings.push(format!("{}: {} is outside the project", from, url));
+            continue;
+        }
+        included.insert(target.clone());
+
+        let media = media_type::media_type(&target_path);
+        if media == "text/css" {
+            if let Ok(css) = fs::read_to_string(&target_path) {
+                for (start, end) in asset_urls::css_url_spans(&css) {
+                    wanted.push_back((target.clone(), css[start..end].to_string()));
+                }
+                for import in stylesheets::css_imports(&css) {
+                    wanted.push_back((target.clone(), import));
+                }
+            }
+        }
+        items.push(Item {
+            href: target.clone(),
+            media_type: media,
+            properties: Vec::new(),
+            content: ItemContent::File,
+        });
+        summary.resources.push(target);
+    }
+
+    let nav_href = free_name(&included, "nav", "xhtml");
+    let nav = nav_document(&metadata, &toc);
+    let package = package_document(&metadata, &items, &nav_href, cover.as_deref(), &summary.chapters);
+
+    write_epub(root, output, &package, &nav_href, &nav, &items)
+        .map_err(|e| EditorError::write(format!("Failed to write {}", output.display()), e).with_path(output))?;
+    Ok(summary)
+}
+
+/// Fill in the title, language, and identifier if they are empty.
+fn with_defaults(root: &Path, mut metadata: EpubMetadata) -> EpubMetadata {
+    if metadata.title.trim().is_empty() {
+        let folder = fs::canonicalize(root).unwrap_or_else(|_| root.to_path_buf());
+        metadata.title = folder.file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| "Untitled".to_string());
+    }
+    if metadata.language.trim().is_empty() {
+        metadata.language = "en".to_string();
+    }
+    if metadata.identifier.trim().is_empty() {
+        let seed = format!("{}\n{}", root.display(), metadata.title);
+        let high = recovery::fnv1a(seed.as_bytes());
+        let low = recovery::fnv1a(format!("{}\n", seed).as_bytes());
+        // Shaped as a version 4 UUID so readers accept it
+        metadata.identifier = format!(
+            "urn:uuid:{:08x}-{:04x}-4{:03x}-8{:03x}-{:012x}",
+            high >> 32,
+            (high >> 16) & 0xffff,
+            high & 0xfff,
+            (low >> 48) & 0xfff,
+            low & 0xffff_ffff_ffff,
+        );
+    }
+    metadata
+}
+
+/// Convert one chapter to an XHTML content document.
+/// Returns the document, its title, and its headings.
+fn chapter_xhtml(root: &Path, doc: &str, raw: &str, lang: &str) -> (String, String, Vec<Heading>) {
+    let split = html_parser::split_html(raw);
+    let (body, headings) = xhtml::fragment_to_xhtml(&split.body_content);
+
+    let head_title = export::head_title(&split.head_content);
+    let stem = Path::new(doc).file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("Chapter");
+    let title = head_title.unwrap_or_else(|| export::chapter_title(&split.body_content, stem));
+
+    // Stylesheet links and inline styles from the head; the project
+    // stylesheet for chapters that link none
+    let mut hrefs = stylesheets::stylesheet_hrefs(&split.head_content);
+    if hrefs.is_empty() {
+        if let Some(css_path) = project::find_css(root) {
+            let css = css_path.strip_prefix(root)
+                .unwrap_or(&css_path)
+                .to_string_lossy()
+                .replace('\\', "/");
+            hrefs.push(links::relative_url(doc, &css));
+        }
+    }
+    let mut head = String::new();
+    for href in hrefs {
+        head += &format!(
+            "<link rel=\"stylesheet\" type=\"text/css\" href=\"{}\"/>\n",
+            html_parser::escape_attribute(&href),
+        );
+    }
+    for style in stylesheets::head_styles(&split.head_content) {
+        head += &format!("<style>{}</style>\n", html_parser::escape_text(&style));
+    }
+
+    (xhtml::document(&title, lang, &head, &body), title, headings)
+}
+
+/// `stem.ext`, or `stem-2.ext` and so on if that path is taken.
+fn free_name(taken: &HashSet<String>, stem: &str, ext: &str) -> String {
+    let mut name = format!("{}.{}", stem, ext);
+    let mut n = 2;
+    while taken.contains(&name) {
+        name = format!("{}-{}.{}", stem, n, ext);
+        n += 1;
+    }
+    name
+}
+
+/// The EPUB 3 navigation document: one entry per chapter, titled by its
+/// first top-level heading, with the next heading level nested below.
+fn nav_document(metadata: &EpubMetadata, toc: &[(String, String, Vec<Heading>)]) -> String {
+    let mut body = String::from("<nav epub:type=\"toc\" id=\"toc\">\n<h1>Contents</h1>\n<ol>\n");
+    for (href, title, headings) in toc {
+        let href = links::encode_path(href);
+        let top = headings.iter().map(|h| h.level).min();
+        body += &format!(
+            "<li><a href=\"{}\">{}</a>",
+            html_parser::escape_attribute(&href),
+            html_parser::escape_text(title),
+        );
+
+        let sub: Vec<&Heading> = match top {
+            Some(top) => headings.iter()
+                .filter(|h| h.level == top + 1 && !h.text.is_empty())
+                .collect(),
+            None => Vec::new(),
+        };
+        if !sub.is_empty() {
+            body += "\n<ol>\n";
+            for heading in sub {
+                body += &format!(
+                    "<li><a href=\"{}#{}\">{}</a></li>\n",
+                    html_parser::escape_attribute(&href),
+                    html_parser::escape_attribute(&heading.id),
+                    html_parser::escape_text(&heading.text),
+                );
+            }
+            body += "</ol>\n";
+        }
+        body += "</li>\n";
+    }
+    body += "</ol>\n</nav>\n";
+    xhtml::document(&metadata.title, &metadata.language, "", &body)
+}
+
+/// The OPF package document: metadata, manifest, and spine.
+fn package_document(
+    metadata: &EpubMetadata,
+    items: &[Item],
+    nav_href: &str,
+    cover: Option<&str>,
+    spine: &[String],
+) -> String {
+    let text = |value: &str| html_parser::escape_text(value);
+    let mut opf = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    opf += "<package xmlns=\"http://www.idpf.org/2007/opf\" version=\"3.0\" unique-identifier=\"book-id\">\n";
+    opf += "<metadata xmlns:dc=\"http://purl.org/dc/elements/1.1/\">\n";
+    opf += &format!("<dc:identifier id=\"book-id\">{}</dc:identifier>\n", text(&metadata.identifier));
+    opf += &format!("<dc:title>{}</dc:title>\n", text(&metadata.title));
+    opf += &format!("<dc:language>{}</dc:language>\n", text(&metadata.language));
+    for creator in &metadata.creators {
+        opf += &format!("<dc:creator>{}</dc:creator>\n", text(creator));
+    }
+    let optional = [
+        ("publisher", &metadata.publisher),
+        ("date", &metadata.date),
+        ("description", &metadata.description),
+        ("rights", &metadata.rights),
+    ];
+    for (name, value) in optional {
+        if let Some(value) = value {
+            opf += &format!("<dc:{}>{}</dc:{}>\n", name, text(value), name);
+        }
+    }
+    opf += &format!("<meta property=\"dcterms:modified\">{}</meta>\n", utc_timestamp(now_secs()));
+
+    // Item ids follow manifest order; the cover also gets the EPUB 2 meta
+    let id_of = |index: usize| format!("item-{}", index + 1);
+    if let Some(cover) = cover {
+        if let Some(index) = items.iter().position(|item| item.href == cover) {
+            opf += &format!("<meta name=\"cover\" content=\"{}\"/>\n", id_of(index));
+        }
+    }
+    opf += "</metadata>\n<manifest>\n";
+
+    opf += &format!(
+        "<item id=\"nav\" href=\"{}\" media-type=\"application/xhtml+xml\" properties=\"nav\"/>\n",
+        html_parser::escape_attribute(&links::encode_path(nav_href)),
+    );
+    for (index, item) in items.iter().enumerate() {
+        opf += &format!(
+            "<item id=\"{}\" href=\"{}\" media-type=\"{}\"",
+            id_of(index),
+            html_parser::escape_attribute(&links::encode_path(&item.href)),
+            item.media_type,
+        );
+        if !item.properties.is_empty() {
+            opf += &format!(" properties=\"{}\"", item.properties.join(" "));
+        }
+        opf += "/>\n";
+    }
+    opf += "</manifest>\n<spine>\n";
+    for href in spine {
+        if let Some(index) = items.iter().position(|item| &item.href == href) {
+            opf += &format!("<itemref idref=\"{}\"/>\n", id_of(index));
+        }
+    }
+    opf += "</spine>\n</package>\n";
+    opf
+}
+
+/// The container document pointing at the package document.
+fn container_document() -> String {
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <container version=\"1.0\" xmlns=\"urn:oasis:names:tc:opendocument:xmlns:container\">\n\
+         <rootfiles>\n\
+         <rootfile full-path=\"{}/{}\" media-type=\"application/oebps-package+xml\"/>\n\
+         </rootfiles>\n\
+         </container>\n",
+        CONTENT_DIR, PACKAGE_FILENAME,
+    )
+}
+
+/// Write the zip to a temp file beside `output`, then rename it into place.
+fn write_epub(
+    root: &Path,
+    output: &Path,
+    package: &str,
+    nav_href: &str,
+    nav: &str,
+    items: &[Item],
+) -> io::Result<()> {
+    let tmp_path = project::temp_path(output);
+    let result = write_zip(root, &tmp_path, package, nav_href, nav, items)
+        .and_then(|()| fs::rename(&tmp_path, output));
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+    }
+    result
+}
+
+fn write_zip(
+    root: &Path,
+    path: &Path,
+    package: &str,
+    nav_href: &str,
+    nav: &str,
+    items: &[Item],
+) -> io::Result<()> {
+    let mut zip = ZipWriter::new(File::create(path)?);
+    let stored = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+    let deflated = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    // The mimetype must come first and uncompressed
+    zip.start_file("mimetype", stored).map_err(io::Error::other)?;
+    zip.write_all(MIMETYPE.as_bytes())?;
+
+    zip.start_file(CONTAINER_PATH, deflated).map_err(io::Error::other)?;
+    zip.write_all(container_document().as_bytes())?;
+    zip.start_file(format!("{}/{}", CONTENT_DIR, PACKAGE_FILENAME), deflated).map_err(io::Error::other)?;
+    zip.write_all(package.as_bytes())?;
+    zip.start_file(format!("{}/{}", CONTENT_DIR, nav_href), deflated).map_err(io::Error::other)?;
+    zip.write_all(nav.as_bytes())?;
+
+    for item in items {
+        zip.start_file(format!("{}/{}", CONTENT_DIR, item.href), deflated).map_err(io::Error::other)?;
+        match &item.content {
+            ItemContent::Text(text) => zip.write_all(text.as_bytes())?,
+            ItemContent::File => {
+                let mut file = File::open(root.join(&item.href))?;
+                io::copy(&mut file, &mut zip)?;
+            }
+        }
+    }
+
+    zip.finish().map_err(io::Error::other)?.sync_all()
+}
+
+/// Seconds since the Unix epoch.
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Format seconds since the epoch as `YYYY-MM-DDThh:mm:ssZ`.
+pub fn utc_timestamp(secs: u64) -> String {
+    let days = (secs / 86_400) as i64;
+    let rem = secs % 86_400;
+
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year, month, day, rem / 3600, rem % 3600 / 60, rem % 60,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    #[test]
+    fn test_utc_timestamp() {
+        assert_eq!(utc_timestamp(0), "1970-01-01T00:00:00Z");
+        assert_eq!(utc_timestamp(951_782_400), "2000-02-29T00:00:00Z");
+        assert_eq!(utc_timestamp(1_792_367_999), "2026-10-18T23:59:59Z");
+    }
+
+    #[test]
+    fn test_export_epub() {
+        let root = std::env::temp_dir().join(format!("rhe_epub_{}", std::process::id()));
+        fs::create_dir_all(root.join("fonts")).unwrap();
+        fs::create_dir_all(root.join("images")).unwrap();
+        fs::write(root.join("book.css"), "@font-face { src: url(fonts/serif.woff2); }").unwrap();
+        fs::write(root.join("fonts/serif.woff2"), b"font").unwrap();
+        fs::write(root.join("images/cover.jpg"), b"jpeg").unwrap();
+        fs::write(root.join("images/map.png"), b"png").unwrap();
+        fs::write(
+            root.join("ch01.html"),
+            "<h1>Arrival</h1><p>Text<br><img src=\"images/map.png\"></p><h2>Port</h2>\
+             <p><a href=\"ch02.html\">On</a> <img src=\"images/lost.png\"></p>",
+        ).unwrap();
+        fs::write(root.join("ch02.html"), "<h1>Departure</h1>").unwrap();
+        fs::write(root.join("manifest.json"), r#"{"chapters": ["ch02.html", "ch01.html"]}"#).unwrap();
+
+        let output = root.join("book.epub");
+        let metadata = EpubMetadata {
+            title: "Voyage & Return".to_string(),
+            creators: vec!["A. Writer".to_string()],
+            cover_image: Some("images/cover.jpg".to_string()),
+            ..EpubMetadata::default()
+        };
+        let summary = export_epub(&root, &output, Some(&metadata)).unwrap();
+        assert_eq!(summary.chapters, vec!["ch02.html", "ch01.html"]);
+        assert_eq!(summary.resources, vec!["images/cover.jpg", "book.css", "images/map.png", "fonts/serif.woff2"]);
+        assert_eq!(summary.warnings, vec!["ch01.html: images/lost.png not found"]);
+
+        let mut archive = zip::ZipArchive::new(File::open(&output).unwrap()).unwrap();
+        let first = archive.by_index(0).unwrap();
+        assert_eq!(first.name(), "mimetype");
+        assert_eq!(first.compression(), CompressionMethod::Stored);
+        drop(first);
+
+        let read = |archive: &mut zip::ZipArchive<File>, name: &str| {
+            let mut text = String::new();
+            archive.by_name(name).unwrap().read_to_string(&mut text).unwrap();
+            text
+        };
+        let opf = read(&mut archive, "OEBPS/content.opf");
+        assert!(opf.contains("<dc:title>Voyage &amp; Return</dc:title>"));
+        assert!(opf.contains("<dc:identifier id=\"book-id\">urn:uuid:"));
+        assert!(opf.contains("properties=\"cover-image\""));
+        assert!(opf.contains("<item id=\"nav\" href=\"nav.xhtml\""));
+        assert!(opf.contains("<spine>\n<itemref idref=\"item-1\"/>\n<itemref idref=\"item-2\"/>\n</spine>"));
+
+        let nav = read(&mut archive, "OEBPS/nav.xhtml");
+        assert!(nav.contains("<li><a href=\"ch01.html\">Arrival</a>\n<ol>\n<li><a href=\"ch01.html#heading-2\">Port</a></li>"));
+
+        let ch01 = read(&mut archive, "OEBPS/ch01.html");
+        assert!(ch01.contains("<link rel=\"stylesheet\" type=\"text/css\" href=\"book.css\"/>"));
+        assert!(ch01.contains("Text<br/><img src=\"images/map.png\"/>"));
+        assert!(archive.by_name("OEBPS/fonts/serif.woff2").is_ok());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_export_epub_skips_links_outside() {
+        let base = std::env::temp_dir().join(format!("rhe_epub_links_{}", std::process::id()));
+        let root = base.join("book");
+        fs::create_dir_all(root.join("images")).unwrap();
+        fs::write(base.join("secret.png"), b"secret").unwrap();
+        fs::write(root.join("images/real.png"), b"png").unwrap();
+        std::os::unix::fs::symlink(base.join("secret.png"), root.join("images/secret.png")).unwrap();
+        std::os::unix::fs::symlink("real.png", root.join("images/alias.png")).unwrap();
+        fs::write(
+            root.join("ch01.html"),
+            "<h1>One</h1><img src=\"images/secret.png\"><img src=\"images/alias.png\">",
+        ).unwrap();
+
+        let metadata = EpubMetadata {
+            title: "Links".to_string(),
+            cover_image: Some("images/secret.png".to_string()),
+            ..EpubMetadata::default()
+        };
+        let summary = export_epub(&root, &base.join("book.epub"), Some(&metadata)).unwrap();
+        // Links inside the project are packed, links out of it are not
+        assert_eq!(summary.resources, vec!["images/alias.png"]);
+        assert_eq!(summary.warnings, vec![
+            "Cover image images/secret.png is outside the project",
+            "ch01.html: images/secret.png is outside the project",
+        ]);
+
+        fs::remove_dir_all(&base).unwrap();
+    }
+}
@@ -0,0 +1,698 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use zip::{CompressionMethod, ZipArchive};
+
+use crate::asset_urls;
+use crate::epub::{CONTAINER_PATH, MIMETYPE};
+use crate::epub_import::{self, Package, PackageItem};
+use crate::error::{EditorError, EditorResult};
+use crate::links;
+use crate::stylesheets;
+
+/// How serious an issue is. Reading systems may reject books with errors;
+/// warnings are worth fixing but do not break the book.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// What is wrong with an EPUB file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IssueKind {
+    /// The file is not a readable zip archive
+    Archive,
+    /// `mimetype` is missing, not the first entry, compressed, or wrong
+    Mimetype,
+    /// `META-INF/container.xml` is missing or names no package document
+    Container,
+    /// The package document is missing, malformed, or of an unknown version
+    Package,
+    /// Required metadata is missing or malformed
+    Metadata,
+    /// A manifest item is duplicated, incomplete, or its file is missing
+    Manifest,
+    /// A spine entry is unknown, repeated, or not a content document
+    Spine,
+    /// The navigation document or NCX is missing or has no usable table of contents
+    Navigation,
+    /// A content document or the navigation document is not well-formed XML
+    NotWellFormed,
+    /// Content links to a file that is not in the manifest
+    MissingResource,
+    /// A file in the archive is not in the manifest
+    UnlistedFile,
+    /// A manifest item that nothing in the book uses
+    UnusedResource,
+}
+
+/// One issue found in an EPUB.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct EpubIssue {
+    pub severity: Severity,
+    pub kind: IssueKind,
+    /// Path inside the archive of the file with the issue
+    pub path: Option<String>,
+    pub message: String,
+}
+
+/// Result of checking an EPUB.
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct EpubCheckReport {
+    pub path: String,
+    /// Package `version` attribute, if the package document was read
+    pub version: Option<String>,
+    /// Number of content and navigation documents parsed
+    pub documents_checked: usize,
+    pub issues: Vec<EpubIssue>,
+}
+
+impl EpubCheckReport {
+    /// True if no issue is an error.
+    pub fn is_valid(&self) -> bool {
+        !self.issues.iter().any(|issue| issue.severity == Severity::Error)
+    }
+
+    fn error(&mut self, kind: IssueKind, path: Option<&str>, message: impl Into<String>) {
+        self.push(Severity::Error, kind, path, message.into());
+    }
+
+    fn warning(&mut self, kind: IssueKind, path: Option<&str>, message: impl Into<String>) {
+        self.push(Severity::Warning, kind, path, message.into());
+    }
+
+    fn push(&mut self, severity: Severity, kind: IssueKind, path: Option<&str>, message: String) {
+        self.issues.push(EpubIssue {
+            severity,
+            kind,
+            path: path.map(str::to_string),
+            message,
+        });
+    }
+}
+
+/// Attributes that link a content document to another file.
+const LINK_ATTRIBUTES: [&str; 6] = ["href", "src", "poster", "data", "xlink:href", "srcset"];
+
+/// Media types that may appear in the spine.
+const CONTENT_TYPES: [&str; 3] = ["application/xhtml+xml", "image/svg+xml", "application/x-dtbook+xml"];
+
+/// Check the structure of an EPUB 2 or 3 file: the `mimetype` entry, the
+/// container and package documents, manifest and spine consistency,
+/// files missing from or unlisted in the manifest, the navigation
+/// document, well-formedness of every XHTML and SVG document, and the
+/// required metadata.
+///
+/// This covers the packaging checks of epubcheck, not its schema
+/// validation of content. Returns an error only if the file cannot be
+/// opened; everything else is reported as an issue.
+pub fn check_epub(path: &Path) -> EditorResult<EpubCheckReport> {
+    let file = File::open(path)
+        .map_err(|e| EditorError::io(format!("Failed to open {}", path.display()), e).with_path(path))?;
+    let mut report = EpubCheckReport {
+        path: path.to_string_lossy().to_string(),
+        ..EpubCheckReport::default()
+    };
+    let mut archive = match ZipArchive::new(file) {
+        Ok(archive) => archive,
+        Err(e) => {
+            report.error(IssueKind::Archive, None, format!("Not a zip archive: {}", e));
+            return Ok(report);
+        }
+    };
+
+    check_mimetype(&mut archive, &mut report);
+
+    let files: Vec<String> = archive.file_names()
+        .filter(|name| !name.ends_with('/'))
+        .map(str::to_string)
+        .collect();
+
+    let opf_path = match epub_import::read_entry(&mut archive, CONTAINER_PATH)
+        .and_then(|container| epub_import::container_rootfile(&container))
+    {
+        Ok(opf_path) => opf_path,
+        Err(e) => {
+            report.error(IssueKind::Container, Some(CONTAINER_PATH), e.message);
+            return Ok(report);
+        }
+    };
+    let package = match epub_import::read_entry(&mut archive, &opf_path)
+        .and_then(|opf| {
+            check_well_formed(&opf).map_err(epub_import::malformed)?;
+            Package::parse(&opf)
+        })
+    {
+        Ok(package) => package,
+        Err(e) => {
+            report.error(IssueKind::Package, Some(&opf_path), e.message);
+            return Ok(report);
+        }
+    };
+    report.version = Some(package.version.clone());
+    let epub3 = package.version.starts_with('3');
+    if package.version != "2.0" && !epub3 {
+        report.error(IssueKind::Package, Some(&opf_path), format!("Unknown package version \"{}\"", package.version));
+    }
+
+    check_metadata(&package, &opf_path, epub3, &mut report);
+
+    // Archive path of every local manifest item
+    let items = check_manifest(&package, &opf_path, &files, &mut report);
+    let item_of_path: HashMap<&str, &PackageItem> = items.iter()
+        .map(|(item, path)| (path.as_str(), *item))
+        .collect();
+    let mut used: HashSet<String> = HashSet::new();
+
+    check_spine(&package, &opf_path, epub3, &items, &mut report, &mut used);
+
+    // Parse every XHTML and SVG document, then follow the links in them
+    // and in stylesheets
+    let mut nav_parsed = false;
+    for (item, item_path) in &items {
+        if !files.contains(item_path) {
+            continue;
+        }
+        let is_nav = item.properties.iter().any(|p| p == "nav");
+        let is_xml = matches!(item.media_type.as_str(), "application/xhtml+xml" | "image/svg+xml");
+        let is_css = item.media_type == "text/css";
+        if !is_xml && !is_css {
+            continue;
+        }
+        let text = match epub_import::read_entry(&mut archive, item_path) {
+            Ok(text) => text,
+            Err(e) => {
+                let kind = if is_xml { IssueKind::NotWellFormed } else { IssueKind::Manifest };
+                report.error(kind, Some(item_path), e.message);
+                continue;
+            }
+        };
+
+        let urls = if is_css {
+            let mut urls: Vec<String> = asset_urls::css_url_spans(&text).into_iter()
+                .map(|(start, end)| text[start..end].to_string())
+                .collect();
+            urls.extend(stylesheets::css_imports(&text));
+            urls
+        } else {
+            report.documents_checked += 1;
+            match scan_document(&text) {
+                Ok(scan) => {
+                    if is_nav {
+                        nav_parsed = true;
+                        check_nav(&scan, item_path, &item_of_path, &mut report);
+                    }
+                    scan.links
+                }
+                Err(e) => {
+                    report.error(IssueKind::NotWellFormed, Some(item_path), e);
+                    continue;
+                }
+            }
+        };
+
+        for url in urls {
+            let (url_path, _) = links::split_url(&url);
+            if url_path.is_empty() || !links::is_relative_url(url_path) {
+                continue;
+            }
+            let target = links::resolve_relative(item_path, &links::percent_decode(url_path));
+            match target {
+                Some(target) if item_of_path.contains_key(target.as_str()) => {
+                    used.insert(target);
+                }
+                Some(target) if files.contains(&target) => report.error(
+                    IssueKind::MissingResource,
+                    Some(item_path),
+                    format!("{} links to {}, which is not in the manifest", item_path, target),
+                ),
+                _ => report.error(
+                    IssueKind::MissingResource,
+                    Some(item_path),
+                    format!("{} links to {}, which is not in the book", item_path, url),
+                ),
+            }
+        }
+    }
+
+    if epub3 {
+        check_nav_item(&package, &opf_path, nav_parsed, &mut report);
+    }
+
+    // Files the manifest does not list
+    for name in &files {
+        let container_file = name == "mimetype" || name.starts_with("META-INF/") || name == &opf_path;
+        if !container_file && !item_of_path.contains_key(name.as_str()) {
+            report.warning(IssueKind::UnlistedFile, Some(name), format!("{} is not in the manifest", name));
+        }
+    }
+
+    // Manifest items nothing uses
+    let cover = package.cover_item().map(|item| item.id.as_str());
+    let ncx = package.spine_toc.as_deref();
+    for (item, item_path) in &items {
+        let special = item.properties.iter().any(|p| p == "nav")
+            || Some(item.id.as_str()) == cover
+            || Some(item.id.as_str()) == ncx;
+        if !special && !used.contains(item_path) && files.contains(item_path) {
+            report.warning(IssueKind::UnusedResource, Some(item_path), format!("{} is not used by the book", item_path));
+        }
+    }
+
+    Ok(report)
+}
+
+/// `mimetype` must be the first entry, stored uncompressed and without an
+/// extra field, and hold exactly `application/epub+zip`.
+fn check_mimetype(archive: &mut ZipArchive<File>, report: &mut EpubCheckReport) {
+    let first_is_mimetype = archive.by_index(0).map(|entry| entry.name() == "mimetype").unwrap_or(false);
+    if !first_is_mimetype {
+        let message = if archive.index_for_name("mimetype").is_some() {
+            "mimetype is not the first file in the archive"
+        } else {
+            "mimetype file is missing"
+        };
+        report.error(IssueKind::Mimetype, Some("mimetype"), message);
+        return;
+    }
+
+    let Ok(mut entry) = archive.by_index(0) else {
+        return;
+    };
+    if entry.compression() != CompressionMethod::Stored {
+        report.error(IssueKind::Mimetype, Some("mimetype"), "mimetype is compressed");
+    }
+    if entry.extra_data().is_some_and(|extra| !extra.is_empty()) {
+        report.error(IssueKind::Mimetype, Some("mimetype"), "mimetype has an extra field");
+    }
+    let mut content = String::new();
+    if entry.read_to_string(&mut content).is_err() || content != MIMETYPE {
+        report.error(IssueKind::Mimetype, Some("mimetype"), format!("mimetype must contain exactly {}", MIMETYPE));
+    }
+}
+
+/// Title, language, and the unique identifier are required; EPUB 3 also
+/// needs a `dcterms:modified` UTC timestamp.
+fn check_metadata(package: &Package, opf_path: &str, epub3: bool, report: &mut EpubCheckReport) {
+    let opf = Some(opf_path);
+    let metadata = &package.metadata;
+    if metadata.title.is_empty() {
+        report.error(IssueKind::Metadata, opf, "dc:title is missing");
+    }
+    if metadata.language.is_empty() {
+        report.error(IssueKind::Metadata, opf, "dc:language is missing");
+    }
+    if metadata.identifier.is_empty() {
+        report.error(IssueKind::Metadata, opf, "dc:identifier is missing");
+    }
+    match &package.unique_identifier {
+        None => report.error(IssueKind::Metadata, opf, "package has no unique-identifier attribute"),
+        Some(id) if !package.identifier_ids.iter().any(|i| i.as_deref() == Some(id)) => report.error(
+            IssueKind::Metadata,
+            opf,
+            format!("unique-identifier \"{}\" names no dc:identifier", id),
+        ),
+        Some(_) => {}
+    }
+    if epub3 {
+        match &package.modified {
+            None => report.error(IssueKind::Metadata, opf, "dcterms:modified is missing"),
+            Some(modified) if !is_utc_timestamp(modified) => report.error(
+                IssueKind::Metadata,
+                opf,
+                format!("dcterms:modified \"{}\" is not of the form CCYY-MM-DDThh:mm:ssZ", modified),
+            ),
+            Some(_) => {}
+        }
+    }
+}
+
+/// Check ids, media types, and files of manifest items. Returns each
+/// local item with its archive path.
+fn check_manifest<'a>(
+    package: &'a Package,
+    opf_path: &str,
+    files: &[String],
+    report: &mut EpubCheckReport,
+) -> Vec<(&'a PackageItem, String)> {
+    let opf = Some(opf_path);
+    let mut ids = HashSet::new();
+    let mut paths = HashSet::new();
+    let mut items = Vec::new();
+
+    for item in &package.items {
+        if item.id.is_empty() {
+            report.error(IssueKind::Manifest, opf, format!("Item {} has no id", item.href));
+        } else if !ids.insert(item.id.as_str()) {
+            report.error(IssueKind::Manifest, opf, format!("Item id {} is used more than once", item.id));
+        }
+        if item.media_type.is_empty() {
+            report.error(IssueKind::Manifest, opf, format!("Item {} has no media-type", item.id));
+        }
+        let (href_path, fragment) = links::split_url(&item.href);
+        if href_path.is_empty() || !fragment.is_empty() {
+            report.error(IssueKind::Manifest, opf, format!("Item {} has an invalid href \"{}\"", item.id, item.href));
+            continue;
+        }
+        if !links::is_relative_url(href_path) {
+            // Remote resources are allowed for audio, video, and fonts
+            continue;
+        }
+        let Some(path) = links::resolve_relative(opf_path, &links::percent_decode(href_path)) else {
+            report.error(IssueKind::Manifest, opf, format!("Item {} points outside the book", item.id));
+            continue;
+        };
+        if !paths.insert(path.clone()) {
+            report.error(IssueKind::Manifest, opf, format!("{} is listed more than once", path));
+            continue;
+        }
+        if !files.contains(&path) {
+            report.error(IssueKind::Manifest, Some(&path), format!("{} is listed but missing from the book", path));
+        }
+        items.push((item, path));
+    }
+    items
+}
+
+/// Every spine entry must name a distinct content document; EPUB 2 also
+/// needs an NCX named by the spine's `toc` attribute.
+fn check_spine(
+    package: &Package,
+    opf_path: &str,
+    epub3: bool,
+    items: &[(&PackageItem, String)],
+    report: &mut EpubCheckReport,
+    used: &mut HashSet<String>,
+) {
+    let opf = Some(opf_path);
+    if package.spine.is_empty() {
+        report.error(IssueKind::Spine, opf, "The spine is empty");
+    }
+    let mut seen = HashSet::new();
+    for entry in &package.spine {
+        if !seen.insert(entry.idref.as_str()) {
+            report.error(IssueKind::Spine, opf, format!("Spine lists {} more than once", entry.idref));
+            continue;
+        }
+        let Some(item) = package.item(&entry.idref) else {
+            report.error(IssueKind::Spine, opf, format!("Spine entry {} is not in the manifest", entry.idref));
+            continue;
+        };
+        if !CONTENT_TYPES.contains(&item.media_type.as_str()) {
+            report.error(
+                IssueKind::Spine,
+                opf,
+                format!("Spine entry {} is {}, not a content document", entry.idref, item.media_type),
+            );
+        }
+        if let Some((_, path)) = items.iter().find(|(i, _)| i.id == item.id) {
+            used.insert(path.clone());
+        }
+    }
+
+    match package.spine_toc.as_deref() {
+        Some(toc) => match package.item(toc) {
+            Some(item) if item.media_type == "application/x-dtbncx+xml" => {}
+            Some(_) => report.error(IssueKind::Navigation, opf, format!("Spine toc {} is not an NCX", toc)),
+            None => report.error(IssueKind::Navigation, opf, format!("Spine toc {} is not in the manifest", toc)),
+        },
+        None if !epub3 => report.error(IssueKind::Navigation, opf, "EPUB 2 spine has no toc attribute"),
+        None => {}
+    }
+}
+
+/// EPUB 3 needs exactly one XHTML navigation document with a toc.
+fn check_nav_item(package: &Package, opf_path: &str, nav_parsed: bool, report: &mut EpubCheckReport) {
+    let navs: Vec<&PackageItem> = package.items.iter()
+        .filter(|item| item.properties.iter().any(|p| p == "nav"))
+        .collect();
+    match navs.as_slice() {
+        [] => report.error(IssueKind::Navigation, Some(opf_path), "No manifest item has the nav property"),
+        [nav] if nav.media_type != "application/xhtml+xml" => report.error(
+            IssueKind::Navigation,
+            Some(opf_path),
+            format!("Navigation document {} is not XHTML", nav.href),
+        ),
+        [nav] if !nav_parsed => report.error(
+            IssueKind::Navigation,
+            Some(opf_path),
+            format!("Navigation document {} could not be checked", nav.href),
+        ),
+        [_] => {}
+        _ => report.error(IssueKind::Navigation, Some(opf_path), "More than one item has the nav property"),
+    }
+}
+
+/// The navigation document needs a `<nav epub:type="toc">` with a list
+/// whose links point at manifest items.
+fn check_nav(
+    scan: &DocumentScan,
+    nav_path: &str,
+    item_of_path: &HashMap<&str, &PackageItem>,
+    report: &mut EpubCheckReport,
+) {
+    let nav = Some(nav_path);
+    if !scan.toc_nav {
+        report.error(IssueKind::Navigation, nav, "No <nav epub:type=\"toc\"> element");
+        return;
+    }
+    if !scan.toc_list {
+        report.error(IssueKind::Navigation, nav, "The toc nav has no <ol> list");
+    }
+    if scan.toc_links.is_empty() {
+        report.error(IssueKind::Navigation, nav, "The toc nav has no links");
+    }
+    for url in &scan.toc_links {
+        let (url_path, _) = links::split_url(url);
+        let target = links::resolve_relative(nav_path, &links::percent_decode(url_path));
+        if !target.is_some_and(|t| item_of_path.contains_key(t.as_str())) {
+            report.error(IssueKind::Navigation, nav, format!("The toc links to {}, which is not in the manifest", url));
+        }
+    }
+}
+
+/// Links and table of contents found in an XHTML or SVG document.
+#[derive(Debug, Default)]
+struct DocumentScan {
+    /// Values of link attributes, one per URL
+    links: Vec<String>,
+    /// True if there is a `<nav>` with `epub:type` including `toc`
+    toc_nav: bool,
+    /// True if that nav contains an `<ol>`
+    toc_list: bool,
+    /// `href`s of the links inside that nav
+    toc_links: Vec<String>,
+}
+
+/// Parse a document as XML, failing on the first well-formedness error,
+/// and collect its links.
+fn scan_document(text: &str) -> Result<DocumentScan, String> {
+    let mut reader = xml_reader(text);
+    let mut scan = DocumentScan::default();
+    // Named HTML entities are only defined when a DTD is referenced
+    let mut has_dtd = false;
+    let mut stack: Vec<String> = Vec::new();
+    let mut roots = 0;
+    // Depth of the toc nav on the stack, while inside it
+    let mut toc_depth: Option<usize> = None;
+
+    loop {
+        let event = reader.read_event()
+            .map_err(|e| format!("line {}: {}", line_at(text, reader.error_position()), e))?;
+        let position = reader.buffer_position();
+        let is_empty = matches!(event, Event::Empty(_));
+        match event {
+            Event::DocType(d) => has_dtd = String::from_utf8_lossy(&d).contains("PUBLIC"),
+            Event::Start(e) | Event::Empty(e) => {
+                if stack.is_empty() {
+                    roots += 1;
+                    if roots > 1 {
+                        return Err(format!("line {}: more than one root element", line_at(text, position)));
+                    }
+                }
+                let name = String::from_utf8_lossy(e.local_name().as_ref()).to_string();
+                for attribute in e.attributes() {
+                    let attribute = attribute.map_err(|e| format!("line {}: {}", line_at(text, position), e))?;
+                    let value = if has_dtd {
+                        attribute.unescape_value_with(|_| Some(""))
+                    } else {
+                        attribute.unescape_value()
+                    };
+                    let value = value.map_err(|e| format!("line {}: {}", line_at(text, position), e))?;
+                    let key = String::from_utf8_lossy(attribute.key.as_ref()).to_string();
+                    if key == "srcset" {
+                        scan.links.extend(value.split(',').filter_map(|c| c.split_whitespace().next().map(str::to_string)));
+                    } else if LINK_ATTRIBUTES.contains(&key.as_str()) {
+                        if name == "a" && key == "href" && toc_depth.is_some() {
+                            scan.toc_links.push(value.to_string());
+                        }
+                        scan.links.push(value.to_string());
+                    }
+                    if name == "nav" && key == "epub:type" && value.split_whitespace().any(|t| t == "toc") && !scan.toc_nav {
+                        scan.toc_nav = true;
+                        toc_depth = Some(stack.len());
+                    }
+                }
+                if name == "ol" && toc_depth.is_some() {
+                    scan.toc_list = true;
+                }
+                if is_empty {
+                    if toc_depth == Some(stack.len()) {
+                        toc_depth = None;
+                    }
+                } else {
+                    stack.push(name);
+                }
+            }
+            Event::End(_) => {
+                stack.pop();
+                if toc_depth == Some(stack.len()) {
+                    toc_depth = None;
+                }
+            }
+            Event::Text(t) => {
+                if stack.is_empty() {
+                    if !t.iter().all(u8::is_ascii_whitespace) {
+                        return Err(format!("line {}: text outside the root element", line_at(text, position)));
+                    }
+                } else if !has_dtd {
+                    t.unescape().map_err(|e| format!("line {}: {}", line_at(text, position), e))?;
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    if let Some(open) = stack.last() {
+        return Err(format!("<{}> is not closed", open));
+    }
+    if roots == 0 {
+        return Err("No root element".to_string());
+    }
+    Ok(scan)
+}
+
+/// Check that a document is well-formed XML.
+fn check_well_formed(text: &str) -> Result<(), String> {
+    scan_document(text).map(|_| ())
+}
+
+/// An XML reader that reports mismatched end tags and bad comments.
+fn xml_reader(text: &str) -> Reader<&[u8]> {
+    let mut reader = Reader::from_str(text);
+    let config = reader.config_mut();
+    config.check_end_names = true;
+    config.check_comments = true;
+    reader
+}
+
+/// 1-based line number of a byte offset.
+fn line_at(text: &str, offset: u64) -> usize {
+    let offset = (offset as usize).min(text.len());
+    text.as_bytes()[..offset].iter().filter(|&&b| b == b'\n').count() + 1
+}
+
+/// True for a `CCYY-MM-DDThh:mm:ssZ` timestamp.
+fn is_utc_timestamp(value: &str) -> bool {
+    let bytes = value.as_bytes();
+    bytes.len() == 20
+        && bytes.iter().enumerate().all(|(i, &b)| match i {
+            4 | 7 => b == b'-',
+            10 => b == b'T',
+            13 | 16 => b == b':',
+            19 => b == b'Z',
+            _ => b.is_ascii_digit(),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::epub;
+    use std::fs;
+    use std::io::Write;
+    use zip::write::SimpleFileOptions;
+
+    #[test]
+    fn test_check_epub() {
+        let dir = std::env::temp_dir().join(format!("rhe_epub_check_{}", std::process::id()));
+        let project = dir.join("project");
+        fs::create_dir_all(project.join("images")).unwrap();
+        fs::write(project.join("ch1.html"), "<h1>One</h1><p><img src=\"images/a.png\" alt=\"\"></p>\n").unwrap();
+        fs::write(project.join("ch2.html"), "<h1>Two</h1><p><a href=\"ch1.html#x\">Back</a></p>\n").unwrap();
+        fs::write(project.join("images/a.png"), "png").unwrap();
+
+        // A book written by the exporter is valid
+        let good = dir.join("good.epub");
+        epub::export_epub(&project, &good, None).unwrap();
+        let report = check_epub(&good).unwrap();
+        assert!(report.is_valid(), "{:?}", report.issues);
+        assert_eq!(report.version.as_deref(), Some("3.0"));
+        assert_eq!(report.documents_checked, 3);
+
+        // A hand-made book with one of each problem
+        let opf = r#"<?xml version="1.0"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="3.0" unique-identifier="uid">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+    <dc:identifier id="other">x</dc:identifier>
+    <dc:title>Bad</dc:title>
+  </metadata>
+  <manifest>
+    <item id="nav" href="nav.xhtml" media-type="application/xhtml+xml" properties="nav"/>
+    <item id="c1" href="c1.xhtml" media-type="application/xhtml+xml"/>
+    <item id="gone" href="gone.png" media-type="image/png"/>
+    <item id="css" href="style.css" media-type="text/css"/>
+  </manifest>
+  <spine><itemref idref="c1"/><itemref idref="missing"/></spine>
+</package>"#;
+        let bad = dir.join("bad.epub");
+        let mut zip = zip::ZipWriter::new(File::create(&bad).unwrap());
+        let deflated = SimpleFileOptions::default();
+        for (name, content) in [
+            ("mimetype", MIMETYPE),
+            (CONTAINER_PATH, r#"<container xmlns="urn:oasis:names:tc:opendocument:xmlns:container"><rootfiles><rootfile full-path="OPS/p.opf"/></rootfiles></container>"#),
+            ("OPS/p.opf", opf),
+            ("OPS/nav.xhtml", "<html xmlns:epub=\"http://www.idpf.org/2007/ops\"><body><nav epub:type=\"toc\"><ol><li><a href=\"c2.xhtml\">2</a></li></ol></nav></body></html>"),
+            ("OPS/c1.xhtml", "<html><body><p>One&nbsp;<img src=\"extra.png\"/></p></body></html>"),
+            ("OPS/style.css", "p { background: url(bg.png) }"),
+            ("OPS/extra.png", "png"),
+        ] {
+            zip.start_file(name, deflated).unwrap();
+            zip.write_all(content.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap();
+
+        let report = check_epub(&bad).unwrap();
+        assert!(!report.is_valid());
+        let found: Vec<(Severity, IssueKind, Option<&str>)> = report.issues.iter()
+            .map(|issue| (issue.severity, issue.kind, issue.path.as_deref()))
+            .collect();
+        use IssueKind::*;
+        use Severity::*;
+        let opf_path = Some("OPS/p.opf");
+        assert_eq!(found, vec![
+            (Error, Mimetype, Some("mimetype")),
+            (Error, Metadata, opf_path),
+            (Error, Metadata, opf_path),
+            (Error, Metadata, opf_path),
+            (Error, Manifest, Some("OPS/gone.png")),
+            (Error, Spine, opf_path),
+            (Error, Navigation, Some("OPS/nav.xhtml")),
+            (Error, MissingResource, Some("OPS/nav.xhtml")),
+            (Error, NotWellFormed, Some("OPS/c1.xhtml")),
+            (Error, MissingResource, Some("OPS/style.css")),
+            (Warning, UnlistedFile, Some("OPS/extra.png")),
+            (Warning, UnusedResource, Some("OPS/style.css")),
+        ]);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
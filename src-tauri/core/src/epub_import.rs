@@ -0,0 +1,469 @@
+use std::fs::{self, File};
+use std::io::{self, Read, Seek};
+use std::path::Path;
+
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use zip::ZipArchive;
+
+use crate::epub::{EpubMetadata, CONTAINER_PATH};
+use crate::error::{EditorError, EditorResult, ErrorKind};
+use crate::links;
+use crate::manifest::Manifest;
+use crate::project::is_safe_relative;
+
+/// A resource declared in the package manifest.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct PackageItem {
+    pub id: String,
+    /// `href` as written in the package document
+    pub href: String,
+    pub media_type: String,
+    pub properties: Vec<String>,
+}
+
+/// A spine entry.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct SpineItem {
+    pub idref: String,
+    /// False for `linear="no"` entries
+    pub linear: bool,
+}
+
+/// The parts of an OPF package document the editor uses.
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct Package {
+    /// `version` attribute of `<package>`, such as `2.0` or `3.0`
+    pub version: String,
+    /// `unique-identifier` attribute of `<package>`
+    pub unique_identifier: Option<String>,
+    /// Ids of the `<dc:identifier>` elements, in order
+    pub identifier_ids: Vec<Option<String>>,
+    /// Dublin Core metadata; `cover_image` is left unset
+    pub metadata: EpubMetadata,
+    /// `dcterms:modified` value, required by EPUB 3
+    pub modified: Option<String>,
+    /// Item id named by `<meta name="cover">` (EPUB 2)
+    pub cover_id: Option<String>,
+    pub items: Vec<PackageItem>,
+    pub spine: Vec<SpineItem>,
+    /// `toc` attribute of `<spine>`: the NCX item id (EPUB 2)
+    pub spine_toc: Option<String>,
+}
+
+impl Package {
+    /// Parse an OPF package document.
+    pub fn parse(xml: &str) -> EditorResult<Package> {
+        let mut reader = Reader::from_str(xml);
+        reader.config_mut().trim_text(true);
+
+        let mut package = Package::default();
+        let mut section = "";
+        // Metadata element whose text is being read, with its id
+        let mut field: Option<(String, Option<String>, Option<String>)> = None;
+        let mut text = String::new();
+
+        loop {
+            let event = reader.read_event()
+                .map_err(|e| malformed(format!("XML error at byte {}: {}", reader.error_position(), e)))?;
+            let is_empty = matches!(event, Event::Empty(_));
+            match event {
+                Event::Start(e) | Event::Empty(e) if field.is_none() => {
+                    let name = local_name(&e);
+                    match (section, name.as_str()) {
+                        (_, "package") => {
+                            package.version = attr(&e, "version").unwrap_or_default();
+                            package.unique_identifier = attr(&e, "unique-identifier");
+                        }
+                        (_, "metadata") => section = "metadata",
+                        (_, "manifest") => section = "manifest",
+                        (_, "spine") => {
+                            section = "spine";
+                            package.spine_toc = attr(&e, "toc");
+                        }
+                        ("metadata", "meta") => {
+                            if attr(&e, "name").as_deref() == Some("cover") {
+                                package.cover_id = attr(&e, "content");
+                            }
+                            field = Some((name, attr(&e, "id"), attr(&e, "property")));
+                            text.clear();
+                        }
+                        ("metadata", _) => {
+                            field = Some((name, attr(&e, "id"), None));
+                            text.clear();
+                        }
+                        ("manifest", "item") => package.items.push(PackageItem {
+                            id: attr(&e, "id").unwrap_or_default(),
+                            href: attr(&e, "href").unwrap_or_default(),
+                            media_type: attr(&e, "media-type").unwrap_or_default(),
+                            properties: attr(&e, "properties")
+                                .map(|p| p.split_whitespace().map(str::to_string).collect())
+                                .unwrap_or_default(),
+                        }),
+                        ("spine", "itemref") => package.spine.push(SpineItem {
+                            idref: attr(&e, "idref").unwrap_or_default(),
+                            linear: attr(&e, "linear").as_deref() != Some("no"),
+                        }),
+                        _ => {}
+                    }
+                    if is_empty {
+                        if let Some(done) = field.take() {
+                            package.set_field(done, "");
+                        }
+                    }
+                }
+                Event::Text(t) if field.is_some() => {
+                    let value = t.unescape().map_err(|e| malformed(e.to_string()))?;
+                    text += &value;
+                }
+                Event::CData(t) if field.is_some() => {
+                    text += &String::from_utf8_lossy(&t);
+                }
+                Event::End(e) => {
+                    let name = String::from_utf8_lossy(e.local_name().as_ref()).to_string();
+                    if let Some(current) = field.take() {
+                        if current.0 == name {
+                            package.set_field(current, text.trim());
+                        } else {
+                            // Markup nested inside a metadata element
+                            field = Some(current);
+                        }
+                    } else if matches!(name.as_str(), "metadata" | "manifest" | "spine") {
+                        section = "";
+                    }
+                }
+                Event::Eof => break,
+                _ => {}
+            }
+        }
+        Ok(package)
+    }
+
+    /// Record the text of a metadata element.
+    fn set_field(&mut self, (name, id, property): (String, Option<String>, Option<String>), value: &str) {
+        let value = value.to_string();
+        let metadata = &mut self.metadata;
+        match name.as_str() {
+            "title" if metadata.title.is_empty() => metadata.title = value,
+            "creator" => metadata.creators.push(value),
+            "language" if metadata.language.is_empty() => metadata.language = value,
+            "identifier" => {
+                let unique = id.is_some() && id == self.unique_identifier;
+                if unique || metadata.identifier.is_empty() {
+                    metadata.identifier = value;
+                }
+                self.identifier_ids.push(id);
+            }
+            "publisher" => metadata.publisher = Some(value),
+            "date" => metadata.date = Some(value),
+            "description" => metadata.description = Some(value),
+            "rights" => metadata.rights = Some(value),
+            "meta" if property.as_deref() == Some("dcterms:modified") => self.modified = Some(value),
+            _ => {}
+        }
+    }
+
+    /// The manifest item with an id.
+    pub fn item(&self, id: &str) -> Option<&PackageItem> {
+        self.items.iter().find(|item| item.id == id)
+    }
+
+    /// The EPUB 3 navigation document item.
+    pub fn nav_item(&self) -> Option<&PackageItem> {
+        self.items.iter().find(|item| item.properties.iter().any(|p| p == "nav"))
+    }
+
+    /// The cover image item, from `cover-image` or the EPUB 2 cover meta.
+    pub fn cover_item(&self) -> Option<&PackageItem> {
+        self.items.iter()
+            .find(|item| item.properties.iter().any(|p| p == "cover-image"))
+            .or_else(|| self.cover_id.as_deref().and_then(|id| self.item(id)))
+    }
+}
+
+/// What an EPUB import wrote.
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct EpubImport {
+    pub project_dir: String,
+    /// Chapters in spine order, project-relative
+    pub chapters: Vec<String>,
+    /// Stylesheets, images, fonts, and other files extracted
+    pub resources: Vec<String>,
+    pub metadata: EpubMetadata,
+    /// Entries that were skipped, such as paths outside the package folder
+    pub warnings: Vec<String>,
+}
+
+/// Unpack an EPUB 2 or 3 file into a new project folder.
+///
+/// Every manifest item is extracted at its path relative to the package
+/// document, so chapters, stylesheets, and assets keep the relative links
+/// between them. The spine order becomes the chapter order and the book
+/// metadata and cover are stored in `manifest.json`, so the project can be
+/// exported again with the same structure. The navigation document and
+/// NCX are not extracted; EPUB export generates a new one.
+/// Entry paths are checked so nothing is written outside `project_dir`,
+/// which must be empty or not exist yet.
+pub fn import_epub(epub_path: &Path, project_dir: &Path) -> EditorResult<EpubImport> {
+    let file = File::open(epub_path)
+        .map_err(|e| EditorError::io(format!("Failed to open {}", epub_path.display()), e).with_path(epub_path))?;
+    let mut archive = ZipArchive::new(file).map_err(|e| invalid_epub(epub_path, e))?;
+
+    let container = read_entry(&mut archive, CONTAINER_PATH).map_err(|e| e.with_path(epub_path))?;
+    let opf_path = container_rootfile(&container).map_err(|e| e.with_path(epub_path))?;
+    let opf = read_entry(&mut archive, &opf_path).map_err(|e| e.with_path(epub_path))?;
+    let package = Package::parse(&opf)
+        .map_err(|e| malformed(format!("{}: {}", opf_path, e.message)).with_path(epub_path))?;
+
+    ensure_empty_dir(project_dir)?;
+
+    // Item paths are relative to the package document
+    let mut result = EpubImport {
+        project_dir: project_dir.to_string_lossy().to_string(),
+        ..EpubImport::default()
+    };
+    let skipped: Vec<&str> = package.nav_item().into_iter()
+        .chain(package.spine_toc.as_deref().and_then(|id| package.item(id)))
+        .map(|item| item.id.as_str())
+        .collect();
+
+    let mut chapter_of_item: Vec<(String, String)> = Vec::new();
+    for item in &package.items {
+        if skipped.contains(&item.id.as_str()) {
+            continue;
+        }
+        let (href_path, _) = links::split_url(&item.href);
+        if !links::is_relative_url(href_path) {
+            // Remote resources stay remote
+            continue;
+        }
+        let Some(relative) = links::resolve_relative(&opf_path, &links::percent_decode(href_path)) else {
+            result.warnings.push(format!("{} is outside the EPUB", item.href));
+            continue;
+        };
+        let Some(project_relative) = relative_to_package(&opf_path, &relative) else {
+            result.warnings.push(format!("{} is outside the package folder and was skipped", relative));
+            continue;
+        };
+        if !is_safe_relative(&project_relative) {
+            result.warnings.push(format!("{} is not a safe file path and was skipped", relative));
+            continue;
+        }
+
+        let target = project_dir.join(&project_relative);
+        match extract_entry(&mut archive, &relative, &target) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                result.warnings.push(format!("{} is listed but missing from the EPUB", relative));
+                continue;
+            }
+            Err(e) => {
+                return Err(EditorError::write(format!("Failed to extract {}", relative), e).with_path(&target));
+            }
+        }
+        chapter_of_item.push((item.id.clone(), project_relative.clone()));
+        if !package.spine.iter().any(|s| s.idref == item.id) {
+            result.resources.push(project_relative);
+        }
+    }
+
+    for spine_item in &package.spine {
+        let extracted = chapter_of_item.iter().find(|(id, _)| id == &spine_item.idref);
+        match extracted {
+            Some((_, path)) => result.chapters.push(path.clone()),
+            None => result.warnings.push(format!("Spine entry {} has no extracted item", spine_item.idref)),
+        }
+    }
+
+    let mut metadata = package.metadata.clone();
+    metadata.cover_image = package.cover_item()
+        .and_then(|cover| chapter_of_item.iter().find(|(id, _)| id == &cover.id))
+        .map(|(_, path)| path.clone());
+
+    let manifest = Manifest {
+        chapters: result.chapters.clone(),
+        epub: Some(metadata.clone()),
+        ..Manifest::default()
+    };
+    manifest.save(project_dir)
+        .map_err(|e| EditorError::write("Failed to write manifest.json", e).with_path(project_dir))?;
+
+    result.metadata = metadata;
+    Ok(result)
+}
+
+/// The package document path from `META-INF/container.xml`.
+pub fn container_rootfile(container: &str) -> EditorResult<String> {
+    let mut reader = Reader::from_str(container);
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(e)) | Ok(Event::Empty(e)) if local_name(&e) == "rootfile" => {
+                let media_type = attr(&e, "media-type");
+                if media_type.is_none() || media_type.as_deref() == Some("application/oebps-package+xml") {
+                    if let Some(path) = attr(&e, "full-path") {
+                        return Ok(path);
+                    }
+                }
+            }
+            Ok(Event::Eof) => return Err(malformed("container.xml names no package document")),
+            Err(e) => return Err(malformed(format!("container.xml: {}", e))),
+            _ => {}
+        }
+    }
+}
+
+/// Read a zip entry as UTF-8 text.
+pub fn read_entry<R: Read + Seek>(archive: &mut ZipArchive<R>, name: &str) -> EditorResult<String> {
+    let mut entry = archive.by_name(name).map_err(|_| malformed(format!("{} is missing", name)))?;
+    let mut text = String::new();
+    entry.read_to_string(&mut text).map_err(|e| malformed(format!("{}: {}", name, e)))?;
+    Ok(text)
+}
+
+/// Copy a zip entry to a new file.
+pub(crate) fn extract_entry<R: Read + Seek>(archive: &mut ZipArchive<R>, name: &str, target: &Path) -> io::Result<()> {
+    let mut entry = archive.by_name(name).map_err(|e| match e {
+        zip::result::ZipError::FileNotFound => io::Error::new(io::ErrorKind::NotFound, name.to_string()),
+        other => io::Error::other(other),
+    })?;
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut out = fs::OpenOptions::new().write(true).create_new(true).open(target)?;
+    io::copy(&mut entry, &mut out)?;
+    Ok(())
+}
+
+/// A zip path relative to the folder of the package document, or None
+/// if it lies outside that folder.
+fn relative_to_package(opf_path: &str, path: &str) -> Option<String> {
+    match opf_path.rfind('/') {
+        Some(end) => path.strip_prefix(&opf_path[..=end]).map(str::to_string),
+        None => Some(path.to_string()),
+    }
+}
+
+/// Refuse to import into a folder that already has files.
+pub(crate) fn ensure_empty_dir(dir: &Path) -> EditorResult<()> {
+    match fs::read_dir(dir) {
+        Ok(mut entries) => {
+            if entries.next().is_some() {
+                return Err(EditorError::new(
+                    ErrorKind::AlreadyExists,
+                    format!("{} is not empty", dir.display()),
+                ).with_path(dir));
+            }
+            Ok(())
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => fs::create_dir_all(dir)
+            .map_err(|e| EditorError::write("Failed to create the project folder", e).with_path(dir)),
+        Err(e) => Err(EditorError::io("Failed to read the project folder", e).with_path(dir)),
+    }
+}
+
+fn invalid_epub(path: &Path, err: impl std::fmt::Display) -> EditorError {
+    EditorError::new(ErrorKind::InvalidInput, format!("Not a readable EPUB: {}", err)).with_path(path)
+}
+
+/// Error for a malformed part of a zip package.
+pub(crate) fn malformed(message: impl Into<String>) -> EditorError {
+    EditorError::new(ErrorKind::InvalidInput, message)
+}
+
+/// Local name of an element, without its namespace prefix.
+fn local_name(e: &BytesStart) -> String {
+    String::from_utf8_lossy(e.local_name().as_ref()).to_string()
+}
+
+/// Unescaped value of an attribute, matched by its full name.
+fn attr(e: &BytesStart, name: &str) -> Option<String> {
+    e.attributes()
+        .flatten()
+        .find(|a| a.key.as_ref() == name.as_bytes())
+        .and_then(|a| a.unescape_value().ok().map(|v| v.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use zip::write::SimpleFileOptions;
+
+    /// Write an EPUB 2 style archive with the given extra entries.
+    fn write_test_epub(path: &Path, entries: &[(&str, &str)]) {
+        let mut zip = zip::ZipWriter::new(File::create(path).unwrap());
+        let options = SimpleFileOptions::default();
+        for (name, content) in entries {
+            zip.start_file(*name, options).unwrap();
+            zip.write_all(content.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap();
+    }
+
+    #[test]
+    fn test_import_epub() {
+        let dir = std::env::temp_dir().join(format!("rhe_epub_import_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let epub_path = dir.join("book.epub");
+        let opf = r#"<?xml version="1.0"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="2.0" unique-identifier="uid">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/" xmlns:opf="http://www.idpf.org/2007/opf">
+    <dc:identifier id="isbn">978</dc:identifier>
+    <dc:identifier id="uid">urn:uuid:1234</dc:identifier>
+    <dc:title>Salt &amp; Sea</dc:title>
+    <dc:creator opf:role="aut">A. Writer</dc:creator>
+    <dc:language>en-GB</dc:language>
+    <meta name="cover" content="cover"/>
+  </metadata>
+  <manifest>
+    <item id="ncx" href="toc.ncx" media-type="application/x-dtbncx+xml"/>
+    <item id="c2" href="text/chapter%202.xhtml" media-type="application/xhtml+xml"/>
+    <item id="c1" href="text/ch1.xhtml" media-type="application/xhtml+xml"/>
+    <item id="css" href="styles/book.css" media-type="text/css"/>
+    <item id="cover" href="images/cover.jpg" media-type="image/jpeg"/>
+    <item id="evil" href="../../escape.txt" media-type="text/plain"/>
+    <item id="gone" href="images/gone.png" media-type="image/png"/>
+  </manifest>
+  <spine toc="ncx">
+    <itemref idref="c1"/>
+    <itemref idref="c2" linear="no"/>
+  </spine>
+</package>"#;
+        write_test_epub(&epub_path, &[
+            ("mimetype", "application/epub+zip"),
+            ("META-INF/container.xml", r#"<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container"><rootfiles><rootfile full-path="OPS/package.opf" media-type="application/oebps-package+xml"/></rootfiles></container>"#),
+            ("OPS/package.opf", opf),
+            ("OPS/toc.ncx", "<ncx/>"),
+            ("OPS/text/ch1.xhtml", "<html><body><p>One</p></body></html>"),
+            ("OPS/text/chapter 2.xhtml", "<html><body><p>Two</p></body></html>"),
+            ("OPS/styles/book.css", "p {}"),
+            ("OPS/images/cover.jpg", "jpeg"),
+            ("../escape.txt", "no"),
+        ]);
+
+        let project = dir.join("project");
+        let imported = import_epub(&epub_path, &project).unwrap();
+        assert_eq!(imported.chapters, vec!["text/ch1.xhtml", "text/chapter 2.xhtml"]);
+        assert_eq!(imported.resources, vec!["styles/book.css", "images/cover.jpg"]);
+        assert_eq!(imported.warnings.len(), 2);
+        assert_eq!(imported.metadata.title, "Salt & Sea");
+        assert_eq!(imported.metadata.identifier, "urn:uuid:1234");
+        assert_eq!(imported.metadata.creators, vec!["A. Writer"]);
+        assert_eq!(imported.metadata.language, "en-GB");
+        assert_eq!(imported.metadata.cover_image.as_deref(), Some("images/cover.jpg"));
+
+        assert!(project.join("text/chapter 2.xhtml").is_file());
+        assert!(!project.join("toc.ncx").exists());
+        assert!(!dir.join("escape.txt").exists());
+
+        let manifest = Manifest::load(&project).unwrap();
+        assert_eq!(manifest.chapters, imported.chapters);
+        assert_eq!(manifest.epub.unwrap().title, "Salt & Sea");
+
+        // A second import into the same folder is refused
+        let err = import_epub(&epub_path, &project).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::AlreadyExists);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,121 @@
+use std::fmt;
+use std::io;
+use std::path::Path;
+
+/// What went wrong, so the frontend can choose a response
+/// (retry, save elsewhere, reopen the project, and so on).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    NotFound,
+    PermissionDenied,
+    AlreadyExists,
+    /// Bad arguments, names, or file content
+    InvalidInput,
+    /// Path outside the opened project and picked files
+    OutsideScope,
+    /// A command needs an open project
+    NoProject,
+    /// The user closed a dialog without choosing
+    Cancelled,
+    /// Another instance holds the chapter lock
+    Locked,
+    BackupFailed,
+    WriteFailed,
+    /// The saved file did not read back as intended
+    VerifyFailed,
+    Git,
+    /// Any other I/O error
+    Io,
+}
+
+/// Error returned by editor commands. Serialized to the frontend as
+/// `{ kind, path, message }`.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct EditorError {
+    pub kind: ErrorKind,
+    /// The file or folder involved, if any
+    pub path: Option<String>,
+    /// Human-readable description, shown to the user
+    pub message: String,
+}
+
+/// Result type used across the backend.
+pub type EditorResult<T> = Result<T, EditorError>;
+
+impl EditorError {
+    /// Create an error of a given kind.
+    pub fn new(kind: ErrorKind, message: impl Into<String>) -> Self {
+        EditorError {
+            kind,
+            path: None,
+            message: message.into(),
+        }
+    }
+
+    /// Attach the path the error concerns.
+    pub fn with_path(mut self, path: impl AsRef<Path>) -> Self {
+        self.path = Some(path.as_ref().to_string_lossy().to_string());
+        self
+    }
+
+    /// Wrap an I/O error; the kind follows the I/O error kind.
+    /// `context` describes the failed action, e.g. "Failed to read ch01.html".
+    pub fn io(context: impl fmt::Display, err: io::Error) -> Self {
+        let kind = match err.kind() {
+            io::ErrorKind::NotFound => ErrorKind::NotFound,
+            io::ErrorKind::PermissionDenied => ErrorKind::PermissionDenied,
+            io::ErrorKind::AlreadyExists => ErrorKind::AlreadyExists,
+            io::ErrorKind::InvalidInput | io::ErrorKind::InvalidData => ErrorKind::InvalidInput,
+            _ => ErrorKind::Io,
+        };
+        EditorError::new(kind, format!("{}: {}", context, err))
+    }
+
+    /// Wrap an I/O error from writing a file. Errors other than missing
+    /// folders and permissions (full disk, read-only filesystem) become
+    /// `WriteFailed`.
+    pub fn write(context: impl fmt::Display, err: io::Error) -> Self {
+        let mut error = EditorError::io(context, err);
+        if !matches!(error.kind, ErrorKind::NotFound | ErrorKind::PermissionDenied) {
+            error.kind = ErrorKind::WriteFailed;
+        }
+        error
+    }
+
+    /// Wrap a git error.
+    pub fn git(context: impl fmt::Display, err: git2::Error) -> Self {
+        EditorError::new(ErrorKind::Git, format!("{}: {}", context, err.message()))
+    }
+}
+
+impl fmt::Display for EditorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for EditorError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_kinds() {
+        let err = EditorError::io("Failed to read ch01.html", io::Error::from(io::ErrorKind::NotFound))
+            .with_path("/book/ch01.html");
+        assert_eq!(err.kind, ErrorKind::NotFound);
+        assert_eq!(err.path.as_deref(), Some("/book/ch01.html"));
+        assert!(err.message.starts_with("Failed to read ch01.html: "));
+
+        let full = io::Error::other("No space left on device");
+        assert_eq!(EditorError::write("Failed to write", full).kind, ErrorKind::WriteFailed);
+        let denied = io::Error::from(io::ErrorKind::PermissionDenied);
+        assert_eq!(EditorError::write("Failed to write", denied).kind, ErrorKind::PermissionDenied);
+
+        let json = serde_json::to_value(EditorError::new(ErrorKind::OutsideScope, "x")).unwrap();
+        assert_eq!(json["kind"], "outside_scope");
+        assert!(json["path"].is_null());
+    }
+}
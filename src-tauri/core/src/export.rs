@@ -0,0 +1,256 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use scraper::{Html, Selector};
+
+use crate::backup::BackupTracker;
+use crate::error::{EditorError, EditorResult};
+use crate::html_parser;
+use crate::links;
+use crate::project;
+
+/// The form a chapter file is stored in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChapterForm {
+    /// A complete document with doctype, head, and body
+    Document,
+    /// Body content only
+    Fragment,
+}
+
+/// Files written by a project export.
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct ExportSummary {
+    /// Chapters written as complete documents, project-relative
+    pub chapters: Vec<String>,
+    /// Stylesheets, images, and other files copied unchanged
+    pub assets: Vec<String>,
+}
+
+/// Text of the first heading in a chapter body, or `fallback` if it has none.
+pub fn chapter_title(body_html: &str, fallback: &str) -> String {
+    let fragment = Html::parse_fragment(body_html);
+    let selector = Selector::parse("h1, h2, h3, h4, h5, h6").unwrap();
+    fragment.select(&selector)
+        .map(|heading| heading.text().collect::<String>())
+        .map(|text| text.split_whitespace().collect::<Vec<_>>().join(" "))
+        .find(|text| !text.is_empty())
+        .unwrap_or_else(|| fallback.to_string())
+}
+
+/// The non-empty `<title>` of a document head.
+pub fn head_title(head: &str) -> Option<String> {
+    let document = Html::parse_document(&format!("<html><head>{}</head></html>", head));
+    let selector = Selector::parse("title").unwrap();
+    document.select(&selector)
+        .map(|title| title.text().collect::<String>().split_whitespace().collect::<Vec<_>>().join(" "))
+        .find(|text| !text.is_empty())
+}
+
+/// A chapter as a complete HTML document. Full documents are returned as
+/// they are; body fragments are wrapped in a document whose head has a
+/// title from the first heading and links the project stylesheet.
+/// `doc` is the chapter's project-relative path.
+pub fn standalone_document(root: &Path, doc: &str, raw: &str) -> String {
+    let split = html_parser::split_html(raw);
+    if !split.is_fragment {
+        return raw.to_string();
+    }
+
+    let stem = Path::new(doc).file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("Chapter");
+    let title = chapter_title(&split.body_content, stem);
+
+    let mut head = String::from("<meta charset=\"utf-8\">\n");
+    head += &format!("<title>{}</title>\n", html_parser::escape_text(&title));
+    if let Some(css_path) = project::find_css(root) {
+        let css = css_path.strip_prefix(root)
+            .unwrap_or(&css_path)
+            .to_string_lossy()
+            .replace('\\', "/");
+        let href = links::relative_url(doc, &css);
+        head += &format!("<link rel=\"stylesheet\" href=\"{}\">\n", html_parser::escape_attribute(&href));
+    }
+    html_parser::reassemble_html("<!DOCTYPE html>", &head, &split.body_content, false)
+}
+
+/// The body of a chapter, dropping any document wrapper.
+pub fn body_fragment(raw: &str) -> String {
+    let split = html_parser::split_html(raw);
+    let mut body = split.body_content.trim_matches('\n').to_string();
+    body.push('\n');
+    body
+}
+
+/// Convert every chapter of a project to `form`. Chapters are written to
+/// the same relative path under `out`, or rewritten in place (with a
+/// `.bak` backup through the tracker) if `out` is None. Returns the
+/// project-relative paths of chapters whose form changed.
+pub fn convert_chapters(
+    root: &Path,
+    form: ChapterForm,
+    out: Option<&Path>,
+    tracker: &BackupTracker,
+) -> EditorResult<Vec<String>> {
+    let chapters = project::list_html_files(root)
+        .map_err(|e| EditorError::io("Failed to list chapters", e).with_path(root))?;
+
+    let mut converted = Vec::new();
+    for chapter in chapters {
+        let path = Path::new(&chapter.path);
+        let relative = chapter.relative_path.replace('\\', "/");
+        let raw = fs::read_to_string(path)
+            .map_err(|e| EditorError::io(format!("Failed to read {}", chapter.path), e).with_path(path))?;
+
+        let is_fragment = html_parser::split_html(&raw).is_fragment;
+        let output = match form {
+            ChapterForm::Document if is_fragment => Some(standalone_document(root, &relative, &raw)),
+            ChapterForm::Fragment if !is_fragment => Some(body_fragment(&raw)),
+            _ => None,
+        };
+        // Chapters already in the requested form are only copied to `out`
+        if output.is_none() && out.is_none() {
+            continue;
+        }
+
+        let target = match out {
+            Some(out) => {
+                let target = out.join(&relative);
+                if let Some(parent) = target.parent() {
+                    fs::create_dir_all(parent)
+                        .map_err(|e| EditorError::write("Failed to create output folder", e).with_path(parent))?;
+                }
+                target
+            }
+            None => {
+                tracker.backup_if_needed(path, Some(root))?;
+                path.to_path_buf()
+            }
+        };
+        project::atomic_write(&target, output.as_deref().unwrap_or(&raw))
+            .map_err(|e| EditorError::write(format!("Failed to write {}", target.display()), e).with_path(&target))?;
+        if output.is_some() {
+            converted.push(relative);
+        }
+    }
+    Ok(converted)
+}
+
+/// Export a project to `out`: every chapter as a complete document and
+/// every other file copied at the same relative path. Hidden files,
+/// symlinks, `.bak` backups, and `out` itself (if inside the project)
+/// are skipped.
+pub fn export_project(root: &Path, out: &Path) -> io::Result<ExportSummary> {
+    fs::create_dir_all(out)?;
+    let skip = fs::canonicalize(out)?;
+    let mut summary = ExportSummary::default();
+    export_dir(root, root, out, &skip, &mut summary)?;
+    summary.chapters.sort();
+    summary.assets.sort();
+    Ok(summary)
+}
+
+/// Recursively export one folder of the project.
+fn export_dir(
+    root: &Path,
+    dir: &Path,
+    out: &Path,
+    skip: &Path,
+    summary: &mut ExportSummary,
+) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        let file_type = entry.file_type()?;
+        if entry.file_name().to_string_lossy().starts_with('.') || file_type.is_symlink() {
+            continue;
+        }
+        if file_type.is_dir() {
+            if fs::canonicalize(&path)? != skip {
+                export_dir(root, &path, out, skip, summary)?;
+            }
+            continue;
+        }
+
+        let ext = path.extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or("")
+            .to_lowercase();
+        if ext == "bak" {
+            continue;
+        }
+
+        let relative = path.strip_prefix(root)
+            .unwrap_or(&path)
+            .to_string_lossy()
+            .replace('\\', "/");
+        let target = out.join(&relative);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        if ext == "html" || ext == "htm" || ext == "xhtml" {
+            let raw = fs::read_to_string(&path)?;
+            project::atomic_write(&target, &standalone_document(root, &relative, &raw))?;
+            summary.chapters.push(relative);
+        } else {
+            fs::copy(&path, &target)?;
+            summary.assets.push(relative);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_export_project() {
+        let root = std::env::temp_dir().join(format!("rhe_export_{}", std::process::id()));
+        let out = root.join("build");
+        fs::create_dir_all(root.join("part1")).unwrap();
+        fs::write(root.join("book.css"), "p { margin: 0; }").unwrap();
+        fs::write(root.join("part1/ch01.html"), "<h1>The <em>First</em> Day</h1>\n<p>Text</p>").unwrap();
+        fs::write(root.join("part1/ch01.html.bak"), "old").unwrap();
+        let full = "<!DOCTYPE html>\n<html>\n<head>\n<title>Two</title>\n</head>\n<body>\n<p>Two</p>\n</body>\n</html>\n";
+        fs::write(root.join("ch02.html"), full).unwrap();
+
+        // Symlinks, to files or folders, are not exported
+        #[cfg(unix)]
+        {
+            let outside = root.with_file_name(format!("rhe_export_outside_{}", std::process::id()));
+            fs::create_dir_all(&outside).unwrap();
+            fs::write(outside.join("secret.txt"), "secret").unwrap();
+            std::os::unix::fs::symlink(&outside, root.join("linked")).unwrap();
+            std::os::unix::fs::symlink(outside.join("secret.txt"), root.join("secret.txt")).unwrap();
+        }
+
+        let summary = export_project(&root, &out).unwrap();
+        assert_eq!(summary.chapters, vec!["ch02.html", "part1/ch01.html"]);
+        assert_eq!(summary.assets, vec!["book.css"]);
+
+        let ch01 = fs::read_to_string(out.join("part1/ch01.html")).unwrap();
+        assert!(ch01.starts_with("<!DOCTYPE html>\n<html>"));
+        assert!(ch01.contains("<title>The First Day</title>"));
+        assert!(ch01.contains("<link rel=\"stylesheet\" href=\"../book.css\">"));
+        assert_eq!(fs::read_to_string(out.join("ch02.html")).unwrap(), full);
+        assert!(!out.join("build").exists());
+
+        assert_eq!(body_fragment(full), "<p>Two</p>\n");
+
+        // Converting in place only touches chapters in the other form
+        fs::remove_dir_all(&out).unwrap();
+        let tracker = BackupTracker::new();
+        let converted = convert_chapters(&root, ChapterForm::Fragment, None, &tracker).unwrap();
+        assert_eq!(converted, vec!["ch02.html"]);
+        assert_eq!(fs::read_to_string(root.join("ch02.html")).unwrap(), "<p>Two</p>\n");
+        assert!(root.join("ch02.html.bak").exists());
+        fs::remove_dir_all(&root).unwrap();
+        #[cfg(unix)]
+        fs::remove_dir_all(root.with_file_name(format!("rhe_export_outside_{}", std::process::id()))).unwrap();
+    }
+}
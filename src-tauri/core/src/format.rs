@@ -0,0 +1,145 @@
+use std::fs;
+use std::path::Path;
+
+use crate::backup::BackupTracker;
+use crate::error::{EditorError, EditorResult};
+use crate::project;
+
+/// Elements whose text is whitespace-sensitive and left untouched.
+const PRESERVED_ELEMENTS: [&str; 3] = ["pre", "textarea", "script"];
+
+/// Normalize whitespace in an HTML file without changing its markup:
+/// CRLF and CR line endings become LF, trailing spaces and tabs are
+/// removed (except inside `<pre>`, `<textarea>`, and `<script>`), and the
+/// file ends with exactly one newline.
+pub fn format_html(raw: &str) -> String {
+    let text = raw.replace("\r\n", "\n").replace('\r', "\n");
+    let mut out = String::with_capacity(text.len());
+    let mut open: Option<&str> = None;
+
+    for line in text.split('\n') {
+        let lower = line.to_ascii_lowercase();
+        // Track preserved elements opening and closing on this line
+        let mut pos = 0;
+        loop {
+            match open {
+                Some(tag) => match lower[pos..].find(&format!("</{}", tag)) {
+                    Some(found) => {
+                        pos += found + tag.len() + 2;
+                        open = None;
+                    }
+                    None => break,
+                },
+                None => {
+                    let next = PRESERVED_ELEMENTS.iter()
+                        .filter_map(|tag| find_open_tag(&lower[pos..], tag).map(|i| (i, *tag)))
+                        .min_by_key(|(i, _)| *i);
+                    match next {
+                        Some((found, tag)) => {
+                            pos += found + tag.len() + 1;
+                            open = Some(tag);
+                        }
+                        None => break,
+                    }
+                }
+            }
+        }
+
+        // A line ending inside a preserved element keeps its trailing whitespace
+        if open.is_some() {
+            out += line;
+        } else {
+            out += line.trim_end_matches([' ', '\t']);
+        }
+        out.push('\n');
+    }
+
+    let trimmed = out.trim_end_matches('\n').len();
+    out.truncate(trimmed);
+    if !out.is_empty() {
+        out.push('\n');
+    }
+    out
+}
+
+/// Byte offset of an opening tag such as `<pre>` or `<pre class="x">`.
+fn find_open_tag(lower: &str, tag: &str) -> Option<usize> {
+    let needle = format!("<{}", tag);
+    let mut start = 0;
+    while let Some(found) = lower[start..].find(&needle) {
+        let at = start + found;
+        let after = lower[at + needle.len()..].chars().next();
+        if matches!(after, None | Some('>' | '/' | ' ' | '\t' | '\n')) {
+            return Some(at);
+        }
+        start = at + needle.len();
+    }
+    None
+}
+
+/// Format every chapter of a project in place, with a `.bak` backup
+/// through the tracker. With `check` set nothing is written.
+/// Returns the project-relative paths of chapters that changed (or would).
+pub fn format_project(root: &Path, check: bool, tracker: &BackupTracker) -> EditorResult<Vec<String>> {
+    let chapters = project::list_html_files(root)
+        .map_err(|e| EditorError::io("Failed to list chapters", e).with_path(root))?;
+
+    let mut changed = Vec::new();
+    for chapter in chapters {
+        let path = Path::new(&chapter.path);
+        let raw = fs::read_to_string(path)
+            .map_err(|e| EditorError::io(format!("Failed to read {}", chapter.path), e).with_path(path))?;
+        let formatted = format_html(&raw);
+        if formatted == raw {
+            continue;
+        }
+        if !check {
+            tracker.backup_if_needed(path, Some(root))?;
+            project::atomic_write(path, &formatted)
+                .map_err(|e| EditorError::write(format!("Failed to write {}", chapter.path), e).with_path(path))?;
+        }
+        changed.push(chapter.relative_path);
+    }
+    Ok(changed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_html() {
+        let raw = "<p>One  \r\n<pre>code   \r\n  more  </pre>   \r\n<p>Two\t\n\n\n";
+        assert_eq!(
+            format_html(raw),
+            "<p>One\n<pre>code   \n  more  </pre>\n<p>Two\n",
+        );
+        // Tags sharing a prefix with a preserved element are not preserved
+        assert_eq!(format_html("<preview>a  \nb  "), "<preview>a\nb\n");
+        assert_eq!(format_html(""), "");
+        // Already formatted text is unchanged
+        let formatted = format_html(raw);
+        assert_eq!(format_html(&formatted), formatted);
+    }
+
+    #[test]
+    fn test_format_project() {
+        let root = std::env::temp_dir().join(format!("rhe_format_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join("ch01.html"), "<p>One  \r\n").unwrap();
+        fs::write(root.join("ch02.html"), "<p>Two</p>\n").unwrap();
+
+        let tracker = BackupTracker::new();
+        assert_eq!(format_project(&root, true, &tracker).unwrap(), ["ch01.html"]);
+        assert!(!root.join("ch01.html.bak").exists());
+
+        // Formatting in place backs up the changed chapter first
+        assert_eq!(format_project(&root, false, &tracker).unwrap(), ["ch01.html"]);
+        assert_eq!(fs::read_to_string(root.join("ch01.html")).unwrap(), "<p>One\n");
+        assert_eq!(fs::read_to_string(root.join("ch01.html.bak")).unwrap(), "<p>One  \r\n");
+        assert!(!root.join("ch02.html.bak").exists());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,304 @@
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use git2::{IndexEntry, IndexTime, Oid, Signature, Sort, Status};
+
+/// Repository handle returned by `open_repository`.
+pub use git2::Repository;
+
+use crate::diff::{self, DiffChunk};
+use crate::project::{self, ChapterMeta};
+
+/// Git status of a chapter file, relative to HEAD.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GitStatus {
+    Clean,
+    Modified,
+    /// New file staged in the index
+    Added,
+    /// New file not yet tracked
+    Untracked,
+    Deleted,
+    Conflicted,
+    Ignored,
+}
+
+/// A commit touching a chapter.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct CommitInfo {
+    pub id: String,
+    pub short_id: String,
+    pub summary: String,
+    pub author: String,
+    /// Commit time in seconds since the Unix epoch
+    pub time: i64,
+}
+
+/// Open the repository containing a project directory, if any.
+pub fn open_repository(dir: &Path) -> Option<Repository> {
+    Repository::discover(dir).ok()
+        .filter(|repo| repo.workdir().is_some())
+}
+
+/// Fill in the git status of listed chapters.
+/// Chapters outside a repository keep a status of None.
+pub fn annotate_status(dir: &Path, chapters: &mut [ChapterMeta]) {
+    let Some(repo) = open_repository(dir) else {
+        return;
+    };
+    for chapter in chapters.iter_mut() {
+        chapter.git_status = file_status(&repo, Path::new(&chapter.path)).ok();
+    }
+}
+
+/// Git status of one file in the working tree.
+pub fn file_status(repo: &Repository, path: &Path) -> Result<GitStatus, git2::Error> {
+    let relative = workdir_relative(repo, path)?;
+    let status = repo.status_file(&relative)?;
+    Ok(map_status(status))
+}
+
+fn map_status(status: Status) -> GitStatus {
+    if status.is_conflicted() {
+        GitStatus::Conflicted
+    } else if status.is_ignored() {
+        GitStatus::Ignored
+    } else if status.is_index_new() {
+        GitStatus::Added
+    } else if status.is_wt_new() {
+        GitStatus::Untracked
+    } else if status.is_index_deleted() || status.is_wt_deleted() {
+        GitStatus::Deleted
+    } else if status.is_empty() {
+        GitStatus::Clean
+    } else {
+        GitStatus::Modified
+    }
+}
+
+/// Commit the given chapter files, and only those, on top of HEAD.
+/// Other staged changes in the index are left staged and uncommitted.
+/// Files missing from disk are committed as deletions.
+pub fn commit_files(repo: &Repository, paths: &[PathBuf], message: &str) -> Result<CommitInfo, git2::Error> {
+    if paths.is_empty() {
+        return Err(git2::Error::from_str("No chapters selected to commit"));
+    }
+    if message.trim().is_empty() {
+        return Err(git2::Error::from_str("Commit message is empty"));
+    }
+
+    let parent = match repo.head() {
+        Ok(head) => Some(head.peel_to_commit()?),
+        Err(e) if e.code() == git2::ErrorCode::UnbornBranch => None,
+        Err(e) => return Err(e),
+    };
+
+    // Build the new tree from HEAD plus the selected files only
+    let mut tree_index = git2::Index::new()?;
+    if let Some(parent) = &parent {
+        tree_index.read_tree(&parent.tree()?)?;
+    }
+    let mut repo_index = repo.index()?;
+    for path in paths {
+        let relative = workdir_relative(repo, path)?;
+        if path.exists() {
+            let id = repo.blob_path(path)?;
+            tree_index.add(&file_entry(&relative, id, path)?)?;
+            repo_index.add_path(&relative)?;
+        } else {
+            tree_index.remove_path(&relative)?;
+            repo_index.remove_path(&relative)?;
+        }
+    }
+    let tree = repo.find_tree(tree_index.write_tree_to(repo)?)?;
+
+    let signature = repo.signature().map_err(|_| {
+        git2::Error::from_str("Set git user.name and user.email before committing")
+    })?;
+    let parents: Vec<&git2::Commit> = parent.iter().collect();
+    let id = repo.commit(Some("HEAD"), &signature, &signature, message, &tree, &parents)?;
+
+    // Keep the index in step so the committed files show as clean
+    repo_index.write()?;
+    commit_info(&repo.find_commit(id)?)
+}
+
+/// Commits that changed a file, newest first.
+pub fn file_log(repo: &Repository, path: &Path, limit: usize) -> Result<Vec<CommitInfo>, git2::Error> {
+    let relative = workdir_relative(repo, path)?;
+    let mut walk = repo.revwalk()?;
+    if walk.push_head().is_err() {
+        // No commits yet
+        return Ok(Vec::new());
+    }
+    walk.set_sorting(Sort::TIME)?;
+
+    let mut log: Vec<CommitInfo> = Vec::new();
+    for id in walk {
+        let commit = repo.find_commit(id?)?;
+        let current = blob_id(&commit, &relative);
+        let previous = match commit.parent(0) {
+            Ok(parent) => blob_id(&parent, &relative),
+            Err(_) => None,
+        };
+        if current != previous {
+            log.push(commit_info(&commit)?);
+            if log.len() >= limit {
+                break;
+            }
+        }
+    }
+    Ok(log)
+}
+
+/// Word-level diff from the file at HEAD to the working copy.
+pub fn diff_head(repo: &Repository, path: &Path) -> Result<Vec<DiffChunk>, git2::Error> {
+    let relative = workdir_relative(repo, path)?;
+    let old = match repo.head().and_then(|head| head.peel_to_commit()) {
+        Ok(commit) => file_at(repo, &commit, &relative).unwrap_or_default(),
+        Err(_) => String::new(),
+    };
+    let new = fs::read_to_string(path).unwrap_or_default();
+    Ok(diff::diff_words(&old, &new))
+}
+
+/// Write the content a file had at a commit over the working copy.
+/// Returns the restored content.
+pub fn revert_file(repo: &Repository, path: &Path, commit_id: &str) -> Result<String, git2::Error> {
+    let relative = workdir_relative(repo, path)?;
+    let commit = repo.revparse_single(commit_id)?.peel_to_commit()?;
+    let content = file_at(repo, &commit, &relative).ok_or_else(|| {
+        git2::Error::from_str(&format!("{} does not exist at {}", relative.display(), commit_id))
+    })?;
+    project::atomic_write(path, &content)
+        .map_err(|e| git2::Error::from_str(&format!("Failed to write {}: {}", path.display(), e)))?;
+    Ok(content)
+}
+
+/// Text of a file at a commit, if it exists there.
+fn file_at(repo: &Repository, commit: &git2::Commit, relative: &Path) -> Option<String> {
+    let id = blob_id(commit, relative)?;
+    let blob = repo.find_blob(id).ok()?;
+    Some(String::from_utf8_lossy(blob.content()).to_string())
+}
+
+/// Blob id of a file at a commit, if it exists there.
+fn blob_id(commit: &git2::Commit, relative: &Path) -> Option<Oid> {
+    let entry = commit.tree().ok()?.get_path(relative).ok()?;
+    Some(entry.id())
+}
+
+/// Index entry for a working tree file with a known blob id.
+fn file_entry(relative: &Path, id: Oid, path: &Path) -> Result<IndexEntry, git2::Error> {
+    let meta = fs::metadata(path)
+        .map_err(|e| git2::Error::from_str(&format!("Failed to read {}: {}", path.display(), e)))?;
+    let path_bytes = relative_slash_path(relative).into_bytes();
+
+    #[cfg(unix)]
+    let executable = std::os::unix::fs::PermissionsExt::mode(&meta.permissions()) & 0o111 != 0;
+    #[cfg(not(unix))]
+    let executable = false;
+
+    Ok(IndexEntry {
+        ctime: IndexTime::new(0, 0),
+        mtime: IndexTime::new(0, 0),
+        dev: 0,
+        ino: 0,
+        mode: if executable { 0o100755 } else { 0o100644 },
+        uid: 0,
+        gid: 0,
+        file_size: meta.len() as u32,
+        id,
+        flags: path_bytes.len().min(0xfff) as u16,
+        flags_extended: 0,
+        path: path_bytes,
+    })
+}
+
+/// Path of a file relative to the repository working tree.
+/// The file may not exist (deleted chapters), but its folder must.
+fn workdir_relative(repo: &Repository, path: &Path) -> Result<PathBuf, git2::Error> {
+    let workdir = repo.workdir()
+        .and_then(|dir| fs::canonicalize(dir).ok())
+        .ok_or_else(|| git2::Error::from_str("Repository has no working tree"))?;
+    let canonical = fs::canonicalize(path).ok().or_else(|| {
+        let parent = fs::canonicalize(path.parent()?).ok()?;
+        Some(parent.join(path.file_name()?))
+    });
+    canonical
+        .and_then(|p| p.strip_prefix(&workdir).ok().map(Path::to_path_buf))
+        .ok_or_else(|| git2::Error::from_str(&format!("{} is not in the repository", path.display())))
+}
+
+/// Relative path with `/` separators, as git stores it.
+fn relative_slash_path(relative: &Path) -> String {
+    relative.components()
+        .filter_map(|c| match c {
+            Component::Normal(name) => Some(name.to_string_lossy().to_string()),
+            _ => None,
+        })
+        .collect::<Vec<String>>()
+        .join("/")
+}
+
+fn commit_info(commit: &git2::Commit) -> Result<CommitInfo, git2::Error> {
+    let id = commit.id().to_string();
+    let short_id = commit.as_object().short_id()?.as_str().unwrap_or_default().to_string();
+    let author: Signature = commit.author();
+    Ok(CommitInfo {
+        id,
+        short_id,
+        summary: commit.summary().unwrap_or_default().to_string(),
+        author: author.name().unwrap_or_default().to_string(),
+        time: commit.time().seconds(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_commit_log_diff_revert() {
+        let dir = std::env::temp_dir()
+            .join(format!("rhe_git_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("part1")).unwrap();
+        let repo = Repository::init(&dir).unwrap();
+        let mut config = repo.config().unwrap();
+        config.set_str("user.name", "Test").unwrap();
+        config.set_str("user.email", "test@example.com").unwrap();
+
+        let ch01 = dir.join("part1/ch01.html");
+        let ch02 = dir.join("ch02.html");
+        fs::write(&ch01, "<p>First draft</p>").unwrap();
+        fs::write(&ch02, "<p>Other</p>").unwrap();
+        assert_eq!(file_status(&repo, &ch01).unwrap(), GitStatus::Untracked);
+
+        // Only the selected chapter is committed
+        let first = commit_files(&repo, std::slice::from_ref(&ch01), "Add chapter 1").unwrap();
+        assert_eq!(file_status(&repo, &ch01).unwrap(), GitStatus::Clean);
+        assert_eq!(file_status(&repo, &ch02).unwrap(), GitStatus::Untracked);
+
+        fs::write(&ch01, "<p>Second draft</p>").unwrap();
+        assert_eq!(file_status(&repo, &ch01).unwrap(), GitStatus::Modified);
+        let changed: Vec<String> = diff_head(&repo, &ch01).unwrap().into_iter()
+            .filter(|c| c.kind != diff::ChangeKind::Equal)
+            .map(|c| c.text)
+            .collect();
+        assert_eq!(changed, vec!["First", "Second"]);
+
+        commit_files(&repo, &[ch01.clone(), ch02.clone()], "Revise").unwrap();
+        let log = file_log(&repo, &ch01, 10).unwrap();
+        assert_eq!(log.len(), 2);
+        assert_eq!(log[1].summary, "Add chapter 1");
+        assert_eq!(file_log(&repo, &ch02, 10).unwrap().len(), 1);
+
+        let restored = revert_file(&repo, &ch01, &first.id).unwrap();
+        assert_eq!(restored, "<p>First draft</p>");
+        assert_eq!(file_status(&repo, &ch01).unwrap(), GitStatus::Modified);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
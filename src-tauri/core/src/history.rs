@@ -0,0 +1,238 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::chapters;
+use crate::project;
+
+/// Project-local folder holding saved versions of each chapter.
+pub const HISTORY_DIR: &str = ".editor-history";
+
+const MILLIS_PER_DAY: u64 = 24 * 60 * 60 * 1000;
+
+/// How many saved versions of a chapter to keep.
+/// The newest version is always kept.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct RetentionPolicy {
+    /// Maximum versions kept per chapter (0 = unlimited)
+    #[serde(default = "default_max_versions")]
+    pub max_versions: usize,
+    /// Versions older than this many days are pruned (0 = never)
+    #[serde(default = "default_max_age_days")]
+    pub max_age_days: u64,
+}
+
+fn default_max_versions() -> usize {
+    50
+}
+
+fn default_max_age_days() -> u64 {
+    30
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        RetentionPolicy {
+            max_versions: default_max_versions(),
+            max_age_days: default_max_age_days(),
+        }
+    }
+}
+
+/// A saved version of a chapter.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct VersionInfo {
+    /// Version identifier (the save time in milliseconds, as a string)
+    pub id: String,
+    /// Save time in milliseconds since the Unix epoch
+    pub timestamp_ms: u64,
+    pub size: u64,
+}
+
+/// Record a version of a chapter and prune old versions.
+/// Nothing is recorded if the content matches the newest version.
+pub fn record_snapshot(
+    root: &Path,
+    relative_path: &str,
+    content: &str,
+    policy: &RetentionPolicy,
+) -> io::Result<Option<VersionInfo>> {
+    let dir = version_dir(root, relative_path)?;
+    let versions = list_versions(root, relative_path)?;
+    if let Some(newest) = versions.first() {
+        if fs::read_to_string(dir.join(version_filename(newest.timestamp_ms)))? == content {
+            return Ok(None);
+        }
+    }
+
+    // Keep ids unique and increasing even for saves in the same millisecond
+    let mut timestamp_ms = now_ms();
+    if let Some(newest) = versions.first() {
+        timestamp_ms = timestamp_ms.max(newest.timestamp_ms + 1);
+    }
+
+    fs::create_dir_all(&dir)?;
+    let path = dir.join(version_filename(timestamp_ms));
+    project::atomic_write(&path, content)?;
+
+    prune(root, relative_path, policy)?;
+    Ok(Some(VersionInfo {
+        id: timestamp_ms.to_string(),
+        timestamp_ms,
+        size: content.len() as u64,
+    }))
+}
+
+/// Saved versions of a chapter, newest first.
+pub fn list_versions(root: &Path, relative_path: &str) -> io::Result<Vec<VersionInfo>> {
+    let dir = version_dir(root, relative_path)?;
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+
+    let mut versions: Vec<VersionInfo> = Vec::new();
+    for entry in fs::read_dir(&dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        let Some(timestamp_ms) = name.strip_suffix(".html").and_then(|n| n.parse::<u64>().ok()) else {
+            continue;
+        };
+        versions.push(VersionInfo {
+            id: timestamp_ms.to_string(),
+            timestamp_ms,
+            size: entry.metadata()?.len(),
+        });
+    }
+
+    versions.sort_by_key(|v| std::cmp::Reverse(v.timestamp_ms));
+    Ok(versions)
+}
+
+/// Read the full content of a saved version.
+pub fn read_version(root: &Path, relative_path: &str, id: &str) -> io::Result<String> {
+    fs::read_to_string(version_path(root, relative_path, id)?)
+}
+
+/// Restore a saved version over the chapter file.
+/// The current content is recorded first, so the restore can be undone.
+pub fn restore_version(
+    root: &Path,
+    relative_path: &str,
+    id: &str,
+    policy: &RetentionPolicy,
+) -> io::Result<String> {
+    let content = read_version(root, relative_path, id)?;
+    let chapter = chapters::chapter_path(root, relative_path)?;
+
+    if let Ok(current) = fs::read_to_string(&chapter) {
+        record_snapshot(root, relative_path, &current, policy)?;
+    }
+    project::atomic_write(&chapter, &content)?;
+    Ok(content)
+}
+
+/// Move a chapter's versions after the chapter was renamed or moved.
+pub fn move_versions(root: &Path, from: &str, to: &str) -> io::Result<()> {
+    let source = version_dir(root, from)?;
+    if !source.is_dir() {
+        return Ok(());
+    }
+    let target = version_dir(root, to)?;
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::rename(source, target)
+}
+
+/// Remove versions beyond the retention policy, always keeping the newest.
+fn prune(root: &Path, relative_path: &str, policy: &RetentionPolicy) -> io::Result<()> {
+    let dir = version_dir(root, relative_path)?;
+    let versions = list_versions(root, relative_path)?;
+    let now = now_ms();
+
+    for (index, version) in versions.iter().enumerate().skip(1) {
+        let too_many = policy.max_versions > 0 && index >= policy.max_versions;
+        let too_old = policy.max_age_days > 0
+            && now.saturating_sub(version.timestamp_ms) > policy.max_age_days * MILLIS_PER_DAY;
+        if too_many || too_old {
+            fs::remove_file(dir.join(version_filename(version.timestamp_ms)))?;
+        }
+    }
+    Ok(())
+}
+
+/// Folder holding the versions of one chapter.
+fn version_dir(root: &Path, relative_path: &str) -> io::Result<PathBuf> {
+    // Validates the relative path before it is used under the history folder
+    chapters::chapter_path(root, relative_path)?;
+    Ok(root.join(HISTORY_DIR).join(relative_path))
+}
+
+/// Path of one saved version. Version ids are numeric only.
+fn version_path(root: &Path, relative_path: &str, id: &str) -> io::Result<PathBuf> {
+    let timestamp_ms: u64 = id.parse().map_err(|_| {
+        io::Error::new(io::ErrorKind::InvalidInput, format!("Invalid version id: {}", id))
+    })?;
+    let path = version_dir(root, relative_path)?.join(version_filename(timestamp_ms));
+    if !path.is_file() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("Version {} not found for {}", id, relative_path),
+        ));
+    }
+    Ok(path)
+}
+
+fn version_filename(timestamp_ms: u64) -> String {
+    format!("{}.html", timestamp_ms)
+}
+
+/// Current time in milliseconds since the Unix epoch.
+pub fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshots_and_restore() {
+        let dir = std::env::temp_dir()
+            .join(format!("rhe_history_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("part1")).unwrap();
+        let policy = RetentionPolicy { max_versions: 3, max_age_days: 30 };
+
+        // Identical content is recorded once
+        assert!(record_snapshot(&dir, "part1/ch01.html", "v1", &policy).unwrap().is_some());
+        assert!(record_snapshot(&dir, "part1/ch01.html", "v1", &policy).unwrap().is_none());
+        for content in ["v2", "v3", "v4"] {
+            record_snapshot(&dir, "part1/ch01.html", content, &policy).unwrap();
+        }
+
+        // Pruned to the three newest, newest first
+        let versions = list_versions(&dir, "part1/ch01.html").unwrap();
+        assert_eq!(versions.len(), 3);
+        assert_eq!(read_version(&dir, "part1/ch01.html", &versions[0].id).unwrap(), "v4");
+        assert_eq!(read_version(&dir, "part1/ch01.html", &versions[2].id).unwrap(), "v2");
+
+        // Restore writes the old version and records the current one
+        fs::write(dir.join("part1/ch01.html"), "current").unwrap();
+        let restored = restore_version(&dir, "part1/ch01.html", &versions[2].id, &policy).unwrap();
+        assert_eq!(restored, "v2");
+        assert_eq!(fs::read_to_string(dir.join("part1/ch01.html")).unwrap(), "v2");
+        let newest = &list_versions(&dir, "part1/ch01.html").unwrap()[0];
+        assert_eq!(read_version(&dir, "part1/ch01.html", &newest.id).unwrap(), "current");
+
+        // Ids and paths are validated
+        assert!(read_version(&dir, "part1/ch01.html", "../../x").is_err());
+        assert!(list_versions(&dir, "../ch01.html").is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
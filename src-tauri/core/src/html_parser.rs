@@ -78,6 +78,53 @@ pub fn reassemble_html(doctype: &str, head: &str, body: &str, is_fragment: bool)
     result
 }
 
+/// Check that HTML read back after a save splits into the intended parts.
+/// Surrounding whitespace is ignored, since reassembly adds newlines.
+/// Returns a description of the first mismatch.
+pub fn verify_round_trip(written: &str, body: &str, head: &str, is_fragment: bool) -> Result<(), String> {
+    let split = split_html(written);
+    if split.is_fragment != is_fragment {
+        return Err(if is_fragment {
+            "expected a body fragment but found a full document".to_string()
+        } else {
+            "expected a full document but found a fragment".to_string()
+        });
+    }
+
+    compare_part("body", body.trim(), split.body_content.trim())?;
+    if !is_fragment {
+        compare_part("head", head.trim(), split.head_content.trim())?;
+    }
+    Ok(())
+}
+
+/// Compare an intended document part with what was read back.
+fn compare_part(name: &str, expected: &str, found: &str) -> Result<(), String> {
+    if expected == found {
+        return Ok(());
+    }
+    let offset = expected.bytes()
+        .zip(found.bytes())
+        .position(|(a, b)| a != b)
+        .unwrap_or(expected.len().min(found.len()));
+    Err(format!(
+        "{} differs at byte {} (expected {} bytes, found {})",
+        name, offset, expected.len(), found.len(),
+    ))
+}
+
+/// Escape text content for inclusion in HTML.
+pub fn escape_text(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Escape a value for a double-quoted HTML attribute.
+pub fn escape_attribute(value: &str) -> String {
+    escape_text(value).replace('"', "&quot;")
+}
+
 /// Extract the doctype declaration from the beginning of an HTML string.
 fn extract_doctype(raw: &str) -> String {
     let lower = raw.to_lowercase();
@@ -180,4 +227,18 @@ WeBWorK delivers automatically graded questions.
         let reassembled = reassemble_html("", "", &split.body_content, true);
         assert_eq!(reassembled, html);
     }
+
+    #[test]
+    fn test_verify_round_trip() {
+        let doc = reassemble_html("<!DOCTYPE html>", "<title>T</title>", "<p>Body</p>", false);
+        assert!(verify_round_trip(&doc, "<p>Body</p>", "<title>T</title>", false).is_ok());
+        assert!(verify_round_trip("<p>Body</p>", "<p>Body</p>", "", true).is_ok());
+
+        // Truncated write
+        let truncated = &doc[..doc.len() / 2];
+        assert!(verify_round_trip(truncated, "<p>Body</p>", "<title>T</title>", false).is_err());
+
+        let err = verify_round_trip("<p>Bod</p>", "<p>Body</p>", "", true).unwrap_err();
+        assert_eq!(err, "body differs at byte 6 (expected 11 bytes, found 10)");
+    }
 }
@@ -0,0 +1,82 @@
+//! Core of the HTML book editor: reading, splitting, and saving chapter
+//! files, project layout and history, and link and stylesheet handling.
+//!
+//! Nothing here depends on Tauri, so build scripts and command-line tools
+//! can share the logic the editor uses. The editor's commands are thin
+//! wrappers over these modules.
+//!
+//! ```no_run
+//! use std::path::Path;
+//! use editor_core::{html_parser, project};
+//!
+//! for chapter in project::list_html_files(Path::new("book")).unwrap() {
+//!     let raw = std::fs::read_to_string(&chapter.path).unwrap();
+//!     let split = html_parser::split_html(&raw);
+//!     println!("{}: {} bytes of body", chapter.relative_path, split.body_content.len());
+//! }
+//! ```
+
+/// Rewriting chapter and stylesheet URLs to and from the `book://` asset scheme.
+pub mod asset_urls;
+/// Per-session `.bak` backups before the first save of each file.
+pub mod backup;
+/// Loading chapters for editing and saving them with backup, verification, and history.
+pub mod chapter_io;
+/// Chapter create, rename, duplicate, delete-to-trash, and asset moves.
+pub mod chapters;
+/// Longest-common-subsequence diffs over lines, words, and tokens.
+pub mod diff;
+/// Word (`.docx`) import into chapters, with headings, lists, tables, notes, and style mapping.
+pub mod docx_import;
+/// EPUB 3 export of a project, with package and navigation documents.
+pub mod epub;
+/// Structural checks of EPUB files: packaging, manifest, spine, navigation, well-formedness.
+pub mod epub_check;
+/// EPUB 2 and 3 import into a project folder, and package document parsing.
+pub mod epub_import;
+/// `EditorError`, the error type shared by all modules that report to the user.
+pub mod error;
+/// Whole-project export of chapters as complete HTML documents.
+pub mod export;
+/// Whitespace normalization of chapter files.
+pub mod format;
+/// Local git status, commits of selected chapters, log, word diff, and revert.
+pub mod git;
+/// Timestamped chapter versions in `.editor-history/` with retention.
+pub mod history;
+/// Splitting HTML into doctype, head, and body, and reassembling it.
+pub mod html_parser;
+/// Scanning URL attributes and rewriting relative links after moves.
+pub mod links;
+/// Advisory chapter lock files with heartbeat and stale detection.
+pub mod lock;
+/// The project manifest (`manifest.json`): chapter order and settings.
+pub mod manifest;
+/// Markdown export with GFM extensions and YAML front matter.
+pub mod markdown;
+/// Markdown import: a CommonMark and GFM parser producing HTML.
+pub mod markdown_import;
+/// File media types from extensions, for HTTP responses and EPUB manifests.
+pub mod media_type;
+/// Block-level three-way merge of chapter bodies.
+pub mod merge;
+/// Local HTTP preview server with live reload.
+pub mod preview;
+/// Filesystem operations: chapter listing, durable atomic writes, backups, temp files.
+pub mod project;
+/// Crash recovery journal of unsaved chapter bodies.
+pub mod recovery;
+/// The set of paths file operations may touch: the project and picked files.
+pub mod scope;
+/// Self-contained single-file HTML export of a chapter or the whole book.
+pub mod single_file;
+/// Static website export with a layout template and navigation.
+pub mod site;
+/// Word, character, and element counts per chapter and project.
+pub mod stats;
+/// Chapter-linked stylesheets and `@import` chains in cascade order.
+pub mod stylesheets;
+/// Project checks: encoding, reassembly, relative links, and the manifest.
+pub mod validate;
+/// Serializing HTML fragments as well-formed XHTML, with heading ids.
+pub mod xhtml;
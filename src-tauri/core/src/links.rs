@@ -0,0 +1,408 @@
+use std::path::Path;
+
+use crate::backup::BackupTracker;
+use crate::error::{EditorError, EditorResult};
+use crate::project;
+
+/// Attributes that hold URLs to other project files.
+const URL_ATTRIBUTES: [&str; 2] = ["href", "src"];
+
+/// A rewritten chapter and how many of its links changed.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct LinkChange {
+    pub relative_path: String,
+    pub links_rewritten: usize,
+}
+
+/// Summary of a link rewrite across the project.
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct LinkRewriteSummary {
+    pub files_changed: Vec<LinkChange>,
+}
+
+/// Location of an attribute value inside an HTML string.
+#[derive(Debug, Clone)]
+pub struct AttrSpan {
+    /// Lowercased attribute name
+    pub name: String,
+    /// Byte range of the value, excluding quotes
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Rewrite `href`/`src` references after a file or folder moved from `from`
+/// to `to` (both project-relative). Scans every chapter, keeps fragments and
+/// query strings, and backs up each changed chapter through the tracker.
+/// Chapters that symlinks place outside the project are left alone.
+/// Must be called after the move, so the moved chapter is found at `to`.
+pub fn rewrite_moved_links(
+    root: &Path,
+    from: &str,
+    to: &str,
+    tracker: &BackupTracker,
+) -> EditorResult<LinkRewriteSummary> {
+    let chapters = project::list_html_files(root)
+        .map_err(|e| EditorError::io("Failed to list chapters", e).with_path(root))?;
+
+    let mut summary = LinkRewriteSummary::default();
+    for chapter in chapters {
+        let doc = chapter.relative_path.replace('\\', "/");
+        let path = Path::new(&chapter.path);
+        if project::ensure_inside(root, path).is_err() {
+            continue;
+        }
+
+        let html = std::fs::read_to_string(path)
+            .map_err(|e| EditorError::io(format!("Failed to read {}", chapter.path), e).with_path(path))?;
+        let (rewritten, count) = rewrite_html(&html, &doc, from, to);
+        if count == 0 {
+            continue;
+        }
+
+        tracker.backup_if_needed(path, Some(root))?;
+        project::atomic_write(path, &rewritten)
+            .map_err(|e| EditorError::write(format!("Failed to write {}", chapter.path), e).with_path(path))?;
+        summary.files_changed.push(LinkChange {
+            relative_path: chapter.relative_path,
+            links_rewritten: count,
+        });
+    }
+    Ok(summary)
+}
+
+/// Rewrite the links in one document for a move from `from` to `to`.
+/// `doc` is the document's project-relative path after the move.
+/// Only URLs that no longer resolve to their target are rewritten, so
+/// unrelated links keep their original text.
+/// Returns the new HTML and the number of links changed.
+pub fn rewrite_html(html: &str, doc: &str, from: &str, to: &str) -> (String, usize) {
+    // Where the document lived before the move (it may have moved itself)
+    let old_doc = map_path(doc, to, from).unwrap_or_else(|| doc.to_string());
+
+    let mut replacements: Vec<(usize, usize, String)> = Vec::new();
+    for span in attribute_spans(html, &URL_ATTRIBUTES) {
+        let url = &html[span.start..span.end];
+        let (path_part, suffix) = split_url(url);
+        if path_part.is_empty() || !is_relative_url(path_part) {
+            continue;
+        }
+
+        let Some(old_target) = resolve_relative(&old_doc, &percent_decode(path_part)) else {
+            continue;
+        };
+        let new_target = map_path(&old_target, from, to).unwrap_or(old_target);
+        // Leave the author's URL text alone wherever it still points at its target
+        if resolve_relative(doc, &percent_decode(path_part)).as_deref() == Some(new_target.as_str()) {
+            continue;
+        }
+        let new_url = format!("{}{}", relative_url(doc, &new_target), suffix);
+        if new_url != url {
+            replacements.push((span.start, span.end, new_url));
+        }
+    }
+
+    let count = replacements.len();
+    (replace_spans(html, replacements), count)
+}
+
+/// Find the values of the named attributes in every tag of an HTML string.
+/// Names are matched case-insensitively; comments are skipped.
+pub fn attribute_spans(html: &str, names: &[&str]) -> Vec<AttrSpan> {
+    let bytes = html.as_bytes();
+    let mut spans = Vec::new();
+    let mut i = 0;
+
+    while i < bytes.len() {
+        // Byte-wise, since i may sit inside a multi-byte character
+        if bytes[i..].starts_with(b"<!--") {
+            i = bytes[i..].windows(3)
+                .position(|w| w == b"-->")
+                .map(|p| i + p + 3)
+                .unwrap_or(bytes.len());
+            continue;
+        }
+        if bytes[i] != b'<' || !bytes.get(i + 1).is_some_and(|b| b.is_ascii_alphabetic()) {
+            i += 1;
+            continue;
+        }
+
+        // Skip the tag name
+        i += 1;
+        while i < bytes.len() && !bytes[i].is_ascii_whitespace() && bytes[i] != b'>' && bytes[i] != b'/' {
+            i += 1;
+        }
+
+        // Attributes until the end of the tag
+        loop {
+            while i < bytes.len() && (bytes[i].is_ascii_whitespace() || bytes[i] == b'/') {
+                i += 1;
+            }
+            if i >= bytes.len() || bytes[i] == b'>' {
+                i += 1;
+                break;
+            }
+
+            let name_start = i;
+            while i < bytes.len()
+                && !bytes[i].is_ascii_whitespace()
+                && !matches!(bytes[i], b'=' | b'>' | b'/')
+            {
+                i += 1;
+            }
+            let name = html[name_start..i].to_ascii_lowercase();
+
+            while i < bytes.len() && bytes[i].is_ascii_whitespace() {
+                i += 1;
+            }
+            if i >= bytes.len() || bytes[i] != b'=' {
+                // Attribute without a value
+                continue;
+            }
+            i += 1;
+            while i < bytes.len() && bytes[i].is_ascii_whitespace() {
+                i += 1;
+            }
+
+            let (start, end) = match bytes.get(i) {
+                Some(&quote) if quote == b'"' || quote == b'\'' => {
+                    let start = i + 1;
+                    let end = html[start..].find(quote as char)
+                        .map(|p| start + p)
+                        .unwrap_or(bytes.len());
+                    i = (end + 1).min(bytes.len());
+                    (start, end)
+                }
+                _ => {
+                    let start = i;
+                    while i < bytes.len() && !bytes[i].is_ascii_whitespace() && bytes[i] != b'>' {
+                        i += 1;
+                    }
+                    (start, i)
+                }
+            };
+
+            if names.contains(&name.as_str()) {
+                spans.push(AttrSpan { name, start, end });
+            }
+        }
+    }
+    spans
+}
+
+/// Replace byte ranges in a string. Ranges must be sorted and not overlap.
+pub fn replace_spans(text: &str, replacements: Vec<(usize, usize, String)>) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut last = 0;
+    for (start, end, value) in replacements {
+        result += &text[last..start];
+        result += &value;
+        last = end;
+    }
+    result += &text[last..];
+    result
+}
+
+/// Split a URL into its path and its `?query#fragment` suffix.
+pub fn split_url(url: &str) -> (&str, &str) {
+    let cut = url.find(['?', '#']).unwrap_or(url.len());
+    url.split_at(cut)
+}
+
+/// True for relative URLs: no scheme, not root-relative or protocol-relative.
+pub fn is_relative_url(url: &str) -> bool {
+    if url.starts_with('/') || url.starts_with('\\') {
+        return false;
+    }
+    // A scheme is letters/digits/+-. followed by ':' before any '/'
+    match url.find(':') {
+        Some(colon) => {
+            let scheme = &url[..colon];
+            scheme.is_empty()
+                || !scheme.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+        }
+        None => true,
+    }
+}
+
+/// Resolve a relative URL path against a document's project-relative path.
+/// Returns the normalized project-relative target, or None if it escapes
+/// the project root.
+pub fn resolve_relative(doc: &str, url_path: &str) -> Option<String> {
+    let mut parts: Vec<&str> = doc.split('/').collect();
+    // Drop the document filename to get its directory
+    parts.pop();
+
+    for segment in url_path.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                parts.pop()?;
+            }
+            _ => parts.push(segment),
+        }
+    }
+    Some(parts.join("/"))
+}
+
+/// Relative URL from a document to a project-relative target.
+pub fn relative_url(doc: &str, target: &str) -> String {
+    let doc_dir: Vec<&str> = {
+        let mut parts: Vec<&str> = doc.split('/').collect();
+        parts.pop();
+        parts
+    };
+    let target_parts: Vec<&str> = target.split('/').collect();
+
+    // Shared leading directories (the target filename never counts)
+    let common = doc_dir.iter()
+        .zip(target_parts.iter().take(target_parts.len().saturating_sub(1)))
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let mut segments: Vec<&str> = vec![".."; doc_dir.len() - common];
+    segments.extend(&target_parts[common..]);
+    encode_path(&segments.join("/"))
+}
+
+/// Map a path under `from` (the path itself or a descendant) to `to`.
+fn map_path(path: &str, from: &str, to: &str) -> Option<String> {
+    if path == from {
+        return Some(to.to_string());
+    }
+    path.strip_prefix(from)
+        .and_then(|rest| rest.strip_prefix('/'))
+        .map(|rest| format!("{}/{}", to, rest))
+}
+
+/// Decode %XX escapes in a URL path.
+pub fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut out: Vec<u8> = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).unwrap_or("");
+            if let Ok(byte) = u8::from_str_radix(hex, 16) {
+                out.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Escape characters that cannot appear literally in a URL path.
+pub fn encode_path(path: &str) -> String {
+    let mut out = String::with_capacity(path.len());
+    for c in path.chars() {
+        match c {
+            ' ' => out += "%20",
+            '%' => out += "%25",
+            '#' => out += "%23",
+            '?' => out += "%3F",
+            '"' => out += "%22",
+            '\'' => out += "%27",
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_attribute_spans() {
+        // Non-ASCII text before a comment must not split a character
+        let html = &r#"<p>cafe <a HREF="ch03.html#sec2">x</a><!-- <a href="no.html"> -->
+<img alt=fig src=images/fig1.png><a name='top' href='a.html'>y</a></p>"#.replace("cafe", "caf\u{e9}");
+        let values: Vec<&str> = attribute_spans(html, &URL_ATTRIBUTES)
+            .iter()
+            .map(|s| &html[s.start..s.end])
+            .collect();
+        assert_eq!(values, ["ch03.html#sec2", "images/fig1.png", "a.html"]);
+    }
+
+    #[test]
+    fn test_relative_paths() {
+        assert_eq!(resolve_relative("part1/a.html", "../ch03.html"), Some("ch03.html".into()));
+        assert_eq!(resolve_relative("a.html", "./img/x.png"), Some("img/x.png".into()));
+        assert_eq!(resolve_relative("a.html", "../x.html"), None);
+
+        assert_eq!(relative_url("part1/a.html", "ch03.html"), "../ch03.html");
+        assert_eq!(relative_url("a.html", "part2/b.html"), "part2/b.html");
+        assert_eq!(relative_url("part1/a.html", "part1/my file.html"), "my%20file.html");
+
+        assert!(is_relative_url("ch03.html"));
+        assert!(!is_relative_url("https://example.com/"));
+        assert!(!is_relative_url("mailto:someone@example.com"));
+        assert!(!is_relative_url("/abs.html"));
+    }
+
+    #[test]
+    fn test_rewrite_moved_chapter() {
+        // Another chapter linking to the moved one keeps its fragment
+        let html = r##"<a href="ch03.html#sec2">3</a> <a href="ch04.html">4</a> <a href="#local">l</a>"##;
+        let (out, count) = rewrite_html(html, "ch01.html", "ch03.html", "part2/three.html");
+        assert_eq!(count, 1);
+        assert!(out.contains(r#"href="part2/three.html#sec2""#));
+        assert!(out.contains(r#"href="ch04.html""#));
+        assert!(out.contains(r##"href="#local""##));
+
+        // URLs that would only change by normalization are left as written
+        let html = r#"<a href="./ch04.html">4</a> <img src="my%20fig.png"> <a href="x/../ch05.html">5</a>"#;
+        assert_eq!(rewrite_html(html, "ch01.html", "ch03.html", "part2/three.html"), (html.to_string(), 0));
+
+        // The moved chapter's own relative links follow it
+        let html = r#"<img src="images/fig.png"><a href="ch04.html?x=1">4</a>"#;
+        let (out, count) = rewrite_html(html, "part2/three.html", "ch03.html", "part2/three.html");
+        assert_eq!(count, 2);
+        assert!(out.contains(r#"src="../images/fig.png""#));
+        assert!(out.contains(r#"href="../ch04.html?x=1""#));
+
+        // A chapter renamed within its folder keeps links that still resolve
+        let html = r#"<img src="./images/fig.png"><a href="ch03.html#top">self</a>"#;
+        let (out, count) = rewrite_html(html, "three.html", "ch03.html", "three.html");
+        assert_eq!(count, 1);
+        assert_eq!(out, r#"<img src="./images/fig.png"><a href="three.html#top">self</a>"#);
+    }
+
+    #[test]
+    fn test_rewrite_moved_folder() {
+        let html = r#"<img src="images/fig1.png">"#;
+        let (out, count) = rewrite_html(html, "ch01.html", "images", "assets/img");
+        assert_eq!(count, 1);
+        assert_eq!(out, r#"<img src="assets/img/fig1.png">"#);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_rewrite_skips_symlinked_folders() {
+        let base = std::env::temp_dir()
+            .join(format!("rhe_links_symlink_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&base);
+        let project = base.join("book");
+        let outside = base.join("outside");
+        std::fs::create_dir_all(&project).unwrap();
+        std::fs::create_dir_all(&outside).unwrap();
+        let link = r#"<a href="../ch02.html">2</a>"#;
+        std::fs::write(outside.join("other.html"), link).unwrap();
+        std::os::unix::fs::symlink(&outside, project.join("linked")).unwrap();
+        std::fs::write(project.join("ch01.html"), r#"<a href="ch02.html">2</a>"#).unwrap();
+        std::fs::write(project.join("two.html"), "<p>2</p>").unwrap();
+
+        let tracker = BackupTracker::new();
+        let summary = rewrite_moved_links(&project, "ch02.html", "two.html", &tracker).unwrap();
+        let changed: Vec<&str> = summary.files_changed.iter()
+            .map(|c| c.relative_path.as_str())
+            .collect();
+        assert_eq!(changed, ["ch01.html"]);
+        assert_eq!(std::fs::read_to_string(outside.join("other.html")).unwrap(), link);
+
+        std::fs::remove_dir_all(&base).unwrap();
+    }
+}
@@ -0,0 +1,347 @@
+use std::collections::HashSet;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::error::{EditorError, EditorResult, ErrorKind};
+use crate::history;
+use crate::project;
+
+/// A lock whose heartbeat is older than this is considered abandoned.
+pub const STALE_AFTER_MS: u64 = 10 * 60 * 1000;
+
+/// How often held locks are refreshed.
+pub const HEARTBEAT_INTERVAL_MS: u64 = 60 * 1000;
+
+/// Contents of a chapter lock file.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct LockInfo {
+    /// User name of the lock holder
+    pub owner: String,
+    pub host: String,
+    pub pid: u32,
+    /// Identifies the app instance, so a restarted instance with the
+    /// same pid does not mistake an old lock for its own
+    pub instance: String,
+    pub acquired_ms: u64,
+    pub heartbeat_ms: u64,
+}
+
+/// Result of trying to lock a chapter.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct LockStatus {
+    /// True if this instance now holds the lock
+    pub acquired: bool,
+    /// The other instance holding the lock, if not acquired
+    pub holder: Option<LockInfo>,
+}
+
+/// Advisory locks held by this app instance on chapter files.
+/// A lock is a `.<filename>.lock` file beside the chapter, so it is
+/// visible to other machines on a shared drive.
+pub struct LockManager {
+    instance: String,
+    held: Mutex<HashSet<PathBuf>>,
+}
+
+impl Default for LockManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LockManager {
+    /// Create a new manager holding no locks.
+    pub fn new() -> Self {
+        LockManager {
+            instance: format!("{}-{}", std::process::id(), history::now_ms()),
+            held: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// Lock a chapter for this instance. If another live instance holds
+    /// the lock, it is left alone and reported as the holder. Stale locks
+    /// (dead process on this host, or no heartbeat) are taken over, as are
+    /// lock files that do not parse and have not changed for as long.
+    pub fn acquire(&self, path: &Path) -> EditorResult<LockStatus> {
+        let lock_path = lock_path(path);
+        let mut held = self.lock_held()?;
+
+        let stale = match read_lock(&lock_path) {
+            Some(existing) if existing.instance == self.instance => {
+                held.insert(path.to_path_buf());
+                return Ok(LockStatus { acquired: true, holder: None });
+            }
+            Some(existing) if !is_stale(&existing) => {
+                return Ok(LockStatus { acquired: false, holder: Some(existing) });
+            }
+            Some(existing) => Some(Some(existing)),
+            // Left by a crash between creating the lock and writing it
+            None if is_abandoned(&lock_path) => Some(None),
+            None => None,
+        };
+        if let Some(stale) = stale {
+            let displaced = remove_stale_lock(&lock_path, stale.as_ref(), &self.instance)
+                .map_err(|e| {
+                    EditorError::io(format!("Failed to remove stale lock {}", lock_path.display()), e)
+                        .with_path(path)
+                })?;
+            // Another instance replaced the stale lock first
+            if let Some(holder) = displaced {
+                return Ok(LockStatus { acquired: false, holder: Some(holder) });
+            }
+        }
+
+        let now = history::now_ms();
+        let info = LockInfo {
+            owner: current_user(),
+            host: host_name(),
+            pid: std::process::id(),
+            instance: self.instance.clone(),
+            acquired_ms: now,
+            heartbeat_ms: now,
+        };
+        match create_lock(&lock_path, &info) {
+            Ok(()) => {
+                held.insert(path.to_path_buf());
+                Ok(LockStatus { acquired: true, holder: None })
+            }
+            // Another instance created the lock first
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => Ok(LockStatus {
+                acquired: false,
+                holder: read_lock(&lock_path),
+            }),
+            Err(e) => Err(EditorError::io(format!("Failed to lock {}", path.display()), e).with_path(path)),
+        }
+    }
+
+    /// Release a lock held by this instance. Locks held by others are kept.
+    pub fn release(&self, path: &Path) -> EditorResult<()> {
+        let mut held = self.lock_held()?;
+        held.remove(path);
+        self.remove_own_lock(path)
+    }
+
+    /// Release every lock held by this instance, e.g. on exit.
+    pub fn release_all(&self) {
+        let Ok(mut held) = self.held.lock() else {
+            return;
+        };
+        for path in held.drain() {
+            let _ = self.remove_own_lock(&path);
+        }
+    }
+
+    /// Refresh the heartbeat of every held lock, so others see them as live.
+    pub fn refresh_all(&self) {
+        let Ok(held) = self.held.lock() else {
+            return;
+        };
+        for path in held.iter() {
+            let lock_path = lock_path(path);
+            if let Some(mut info) = read_lock(&lock_path).filter(|i| i.instance == self.instance) {
+                info.heartbeat_ms = history::now_ms();
+                let _ = write_lock(&lock_path, &info);
+            }
+        }
+    }
+
+    /// The holder of a chapter's lock if it is another live instance.
+    pub fn held_by_other(&self, path: &Path) -> Option<LockInfo> {
+        read_lock(&lock_path(path))
+            .filter(|info| info.instance != self.instance && !is_stale(info))
+    }
+
+    fn remove_own_lock(&self, path: &Path) -> EditorResult<()> {
+        let lock_path = lock_path(path);
+        match read_lock(&lock_path) {
+            Some(info) if info.instance == self.instance => fs::remove_file(&lock_path)
+                .map_err(|e| EditorError::io(format!("Failed to unlock {}", path.display()), e).with_path(path)),
+            _ => Ok(()),
+        }
+    }
+
+    fn lock_held(&self) -> EditorResult<std::sync::MutexGuard<'_, HashSet<PathBuf>>> {
+        self.held.lock()
+            .map_err(|e| EditorError::new(ErrorKind::Io, format!("Lock error: {}", e)))
+    }
+}
+
+/// Lock file for a chapter: hidden, beside it, named after the full filename.
+pub fn lock_path(path: &Path) -> PathBuf {
+    let name = path.file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    path.with_file_name(format!(".{}.lock", name))
+}
+
+/// Create a lock file, failing if one already exists.
+fn create_lock(lock_path: &Path, info: &LockInfo) -> io::Result<()> {
+    let text = serde_json::to_string_pretty(info)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(lock_path)?;
+    file.write_all(text.as_bytes())?;
+    file.sync_all()
+}
+
+/// Remove a stale lock without racing other instances taking it over.
+/// The lock file is first renamed aside to a name unique to `instance`,
+/// so only one instance gets it. If it no longer holds the stale lock
+/// (another instance already replaced it), it is put back and returned.
+/// `stale` is None for an abandoned lock file that does not parse.
+fn remove_stale_lock(lock_path: &Path, stale: Option<&LockInfo>, instance: &str) -> io::Result<Option<LockInfo>> {
+    let name = lock_path.file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let aside = lock_path.with_file_name(format!("{}.{}", name, instance));
+
+    match fs::rename(lock_path, &aside) {
+        Ok(()) => {}
+        // Already removed; creating the new lock decides who wins
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    }
+
+    let taken = read_lock(&aside);
+    let is_same = match (&taken, stale) {
+        (Some(t), Some(stale)) => t.instance == stale.instance && t.acquired_ms == stale.acquired_ms,
+        (None, None) => true,
+        _ => false,
+    };
+    if !is_same {
+        // Restore without replacing a lock created in the meantime
+        match fs::hard_link(&aside, lock_path) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {}
+            Err(e) => return Err(e),
+        }
+    }
+    fs::remove_file(&aside)?;
+    Ok(if is_same { None } else { taken })
+}
+
+/// Overwrite a lock file held by this instance.
+fn write_lock(lock_path: &Path, info: &LockInfo) -> io::Result<()> {
+    let text = serde_json::to_string_pretty(info)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    project::atomic_write(lock_path, &text)
+}
+
+/// Read a lock file. None if it is missing, unreadable, or corrupt.
+fn read_lock(lock_path: &Path) -> Option<LockInfo> {
+    let text = fs::read_to_string(lock_path).ok()?;
+    serde_json::from_str(&text).ok()
+}
+
+/// True if a lock file exists but has not been modified for longer than a
+/// lock may go without a heartbeat. Used for lock files that do not parse.
+fn is_abandoned(lock_path: &Path) -> bool {
+    fs::symlink_metadata(lock_path)
+        .and_then(|meta| meta.modified())
+        .is_ok_and(|modified| {
+            modified.elapsed().is_ok_and(|age| age.as_millis() > u128::from(STALE_AFTER_MS))
+        })
+}
+
+/// A lock is stale if its heartbeat is too old, or its process on this
+/// host has exited.
+fn is_stale(info: &LockInfo) -> bool {
+    if history::now_ms().saturating_sub(info.heartbeat_ms) > STALE_AFTER_MS {
+        return true;
+    }
+    info.host == host_name() && process_exited(info.pid)
+}
+
+/// True if no process with this pid is running. Only known on Linux;
+/// elsewhere the heartbeat decides.
+fn process_exited(pid: u32) -> bool {
+    if cfg!(target_os = "linux") {
+        !Path::new(&format!("/proc/{}", pid)).exists()
+    } else {
+        false
+    }
+}
+
+fn current_user() -> String {
+    std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .unwrap_or_else(|_| "unknown".to_string())
+}
+
+fn host_name() -> String {
+    if let Ok(name) = fs::read_to_string("/etc/hostname") {
+        if !name.trim().is_empty() {
+            return name.trim().to_string();
+        }
+    }
+    std::env::var("HOSTNAME")
+        .or_else(|_| std::env::var("COMPUTERNAME"))
+        .unwrap_or_else(|_| "unknown".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lock_lifecycle() {
+        let dir = std::env::temp_dir()
+            .join(format!("rhe_lock_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let chapter = dir.join("ch01.html");
+        fs::write(&chapter, "<p>1</p>").unwrap();
+
+        let first = LockManager::new();
+        let mut second = LockManager::new();
+        second.instance = format!("{}-other", second.instance);
+
+        assert!(first.acquire(&chapter).unwrap().acquired);
+        assert!(lock_path(&chapter).exists());
+
+        // A second instance sees the holder and cannot take the lock
+        let status = second.acquire(&chapter).unwrap();
+        assert!(!status.acquired);
+        assert_eq!(status.holder.unwrap().instance, first.instance);
+        assert!(second.held_by_other(&chapter).is_some());
+        assert!(first.held_by_other(&chapter).is_none());
+
+        // Releasing by a non-holder keeps the lock
+        second.release(&chapter).unwrap();
+        assert!(lock_path(&chapter).exists());
+
+        // An old heartbeat makes the lock stale, so it can be taken over
+        let mut info = read_lock(&lock_path(&chapter)).unwrap();
+        info.heartbeat_ms -= STALE_AFTER_MS + 1;
+        write_lock(&lock_path(&chapter), &info).unwrap();
+        let stale = info.clone();
+        assert!(second.acquire(&chapter).unwrap().acquired);
+
+        // A takeover that loses the race leaves the new holder's lock alone
+        let displaced = remove_stale_lock(&lock_path(&chapter), Some(&stale), "third").unwrap();
+        assert_eq!(displaced.unwrap().instance, second.instance);
+        assert_eq!(read_lock(&lock_path(&chapter)).unwrap().instance, second.instance);
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+
+        second.release_all();
+        assert!(!lock_path(&chapter).exists());
+
+        // An empty lock file left by a crash blocks others only until it is old
+        fs::write(lock_path(&chapter), "").unwrap();
+        let status = first.acquire(&chapter).unwrap();
+        assert!(!status.acquired && status.holder.is_none());
+        let old = std::time::SystemTime::now() - std::time::Duration::from_millis(STALE_AFTER_MS + 1000);
+        fs::File::options().write(true).open(lock_path(&chapter)).unwrap().set_modified(old).unwrap();
+        assert!(first.acquire(&chapter).unwrap().acquired);
+        assert_eq!(read_lock(&lock_path(&chapter)).unwrap().instance, first.instance);
+        assert!(second.held_by_other(&chapter).is_some());
+        first.release_all();
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,130 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::docx_import::DocxConfig;
+use crate::epub::EpubMetadata;
+use crate::history::RetentionPolicy;
+use crate::project::{self, ChapterMeta};
+use crate::site::SiteConfig;
+
+/// Filename of the project manifest in the project root.
+pub const MANIFEST_FILENAME: &str = "manifest.json";
+
+/// Project manifest: chapter order and project-level settings.
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct Manifest {
+    /// Chapter relative paths in reading order
+    #[serde(default)]
+    pub chapters: Vec<String>,
+    /// Retention of saved versions in the history folder
+    #[serde(default)]
+    pub history: RetentionPolicy,
+    /// Project-relative folder for `.bak` backups; beside each file if unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backup_dir: Option<String>,
+    /// Book metadata for EPUB export
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub epub: Option<EpubMetadata>,
+    /// Layout and title for static site export
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub site: Option<SiteConfig>,
+    /// Style mapping, chapter splitting, and image folder for Word import
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub docx: Option<DocxConfig>,
+}
+
+impl Manifest {
+    /// Load the manifest from the project root.
+    /// Returns an empty manifest if the file does not exist.
+    pub fn load(dir: &Path) -> io::Result<Manifest> {
+        let path = dir.join(MANIFEST_FILENAME);
+        if !path.exists() {
+            return Ok(Manifest::default());
+        }
+        let text = fs::read_to_string(&path)?;
+        serde_json::from_str(&text)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// The configured backup folder, checked to be a plain project-relative
+    /// path with no hidden, `..`, or absolute components.
+    pub fn backup_dir(&self) -> io::Result<Option<&str>> {
+        match self.backup_dir.as_deref() {
+            Some(dir) if !project::is_safe_relative(dir) => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("backup_dir must be a folder inside the project: {}", dir),
+            )),
+            dir => Ok(dir),
+        }
+    }
+
+    /// Save the manifest to the project root.
+    pub fn save(&self, dir: &Path) -> io::Result<()> {
+        let mut text = serde_json::to_string_pretty(self)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        text.push('\n');
+        project::atomic_write(&dir.join(MANIFEST_FILENAME), &text)
+    }
+
+    /// Load the manifest for editing. If it lists no chapters yet, seed the
+    /// order from the files currently on disk so edits keep that order.
+    pub fn load_for_update(dir: &Path) -> io::Result<Manifest> {
+        let mut manifest = Manifest::load(dir)?;
+        if manifest.chapters.is_empty() {
+            manifest.chapters = project::list_html_files(dir)?
+                .into_iter()
+                .map(|c| c.relative_path)
+                .collect();
+        }
+        Ok(manifest)
+    }
+
+    /// Sort chapters into manifest order.
+    /// Chapters missing from the manifest follow in relative path order;
+    /// manifest entries with no file on disk are skipped.
+    pub fn order(&self, chapters: Vec<ChapterMeta>) -> Vec<ChapterMeta> {
+        let mut listed: Vec<ChapterMeta> = Vec::new();
+        let mut rest = chapters;
+
+        for entry in &self.chapters {
+            if let Some(pos) = rest.iter().position(|c| &c.relative_path == entry) {
+                listed.push(rest.remove(pos));
+            }
+        }
+
+        // rest keeps the relative path order from list_html_files
+        listed.extend(rest);
+        listed
+    }
+
+    /// Replace a chapter entry after a rename or move.
+    pub fn rename(&mut self, from: &str, to: &str) {
+        for entry in self.chapters.iter_mut() {
+            if entry == from {
+                *entry = to.to_string();
+            }
+        }
+    }
+
+    /// Insert a chapter after another entry, or at the end.
+    pub fn insert_after(&mut self, after: Option<&str>, entry: &str) {
+        let pos = after.and_then(|a| self.chapters.iter().position(|c| c == a));
+        match pos {
+            Some(i) => self.chapters.insert(i + 1, entry.to_string()),
+            None => self.chapters.push(entry.to_string()),
+        }
+    }
+
+    /// Remove a chapter entry.
+    pub fn remove(&mut self, entry: &str) {
+        self.chapters.retain(|c| c != entry);
+    }
+}
+
+/// List chapters in the project directory in manifest order.
+pub fn list_chapters(dir: &Path) -> io::Result<Vec<ChapterMeta>> {
+    let chapters = project::list_html_files(dir)?;
+    let manifest = Manifest::load(dir)?;
+    Ok(manifest.order(chapters))
+}
@@ -0,0 +1,896 @@
+use ego_tree::NodeRef;
+use scraper::{ElementRef, Html, Node, Selector};
+
+use crate::html_parser::{escape_attribute, escape_text};
+
+/// Inline elements; any other element is written as a block.
+const INLINE_ELEMENTS: [&str; 33] = [
+    "a", "abbr", "b", "bdi", "bdo", "br", "cite", "code", "data", "del", "dfn", "em",
+    "font", "i", "img", "input", "ins", "kbd", "label", "mark", "q", "s", "samp",
+    "small", "span", "strike", "strong", "sub", "sup", "time", "u", "var", "wbr",
+];
+
+/// Containers written as their own tags around Markdown content.
+const CONTAINERS: [&str; 8] = ["article", "aside", "div", "footer", "header", "main", "nav", "section"];
+
+/// YAML front matter of a Markdown file, limited to `key: value` pairs.
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize)]
+pub struct FrontMatter {
+    /// Entries in file order; list values are joined with ", "
+    pub entries: Vec<(String, String)>,
+}
+
+impl FrontMatter {
+    /// Split front matter, fenced by `---` lines, off the start of a
+    /// Markdown text. Returns the entries, the rest of the text, and
+    /// warnings for lines that are not simple `key: value` pairs or lists.
+    pub fn split(markdown: &str) -> (FrontMatter, &str, Vec<String>) {
+        let text = markdown.strip_prefix('\u{feff}').unwrap_or(markdown);
+        let mut lines = text.split_inclusive('\n');
+        let Some(first) = lines.next().filter(|line| line.trim_end() == "---") else {
+            return (FrontMatter::default(), text, Vec::new());
+        };
+        let mut offset = first.len();
+        let mut yaml = Vec::new();
+        for line in lines {
+            offset += line.len();
+            let trimmed = line.trim_end();
+            if trimmed == "---" || trimmed == "..." {
+                let (front_matter, warnings) = FrontMatter::parse(&yaml);
+                return (front_matter, &text[offset..], warnings);
+            }
+            yaml.push(trimmed);
+        }
+        // An unclosed fence is a thematic break, not front matter
+        (FrontMatter::default(), text, Vec::new())
+    }
+
+    fn parse(lines: &[&str]) -> (FrontMatter, Vec<String>) {
+        let mut entries = Vec::new();
+        let mut warnings = Vec::new();
+        let mut k = 0;
+        while k < lines.len() {
+            let line = lines[k];
+            k += 1;
+            if line.trim().is_empty() || line.trim_start().starts_with('#') {
+                continue;
+            }
+            let entry = line.split_once(':').filter(|(key, _)| {
+                !key.is_empty() && key.chars().all(|c| c.is_ascii_alphanumeric() || "_-.".contains(c))
+            });
+            let Some((key, value)) = entry else {
+                warnings.push(format!("Front matter line {} is not a key: value pair: {}", k + 1, line.trim()));
+                continue;
+            };
+
+            let value = value.trim();
+            let value = if value.is_empty() || value == "|" || value == ">" {
+                // A block list or block scalar on the following indented lines
+                let mut items = Vec::new();
+                let mut is_list = false;
+                while k < lines.len() && (lines[k].starts_with([' ', '-']) || lines[k].trim().is_empty()) {
+                    let item = lines[k].trim();
+                    if let Some(item) = item.strip_prefix("- ").or(if item == "-" { Some("") } else { None }) {
+                        is_list = true;
+                        items.push(scalar(item.trim()));
+                    } else if !item.is_empty() {
+                        items.push(item.to_string());
+                    }
+                    k += 1;
+                }
+                let separator = if is_list { ", " } else if value == "|" { "\n" } else { " " };
+                items.join(separator)
+            } else if let Some(list) = value.strip_prefix('[').and_then(|v| v.strip_suffix(']')) {
+                list.split(',')
+                    .map(|item| scalar(item.trim()))
+                    .filter(|item| !item.is_empty())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            } else {
+                scalar(value)
+            };
+            entries.push((key.to_string(), value));
+        }
+        (FrontMatter { entries }, warnings)
+    }
+
+    /// Value of an entry; keys match case-insensitively.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entries.iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(key))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Entries from a chapter head: `<title>` as `title`, and each
+    /// `<meta name content>` by its name.
+    pub fn from_head(head: &str) -> FrontMatter {
+        let document = Html::parse_document(&format!("<html><head>{}</head><body></body></html>", head));
+        let mut entries = Vec::new();
+        if let Some(title) = Selector::parse("head > title").ok()
+            .and_then(|selector| document.select(&selector).next())
+        {
+            let text = title.text().collect::<String>();
+            let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+            if !text.is_empty() {
+                entries.push(("title".to_string(), text));
+            }
+        }
+        if let Ok(selector) = Selector::parse("meta[name][content]") {
+            for meta in document.select(&selector) {
+                let name = meta.value().attr("name").unwrap_or_default();
+                let content = meta.value().attr("content").unwrap_or_default();
+                entries.push((name.to_string(), content.to_string()));
+            }
+        }
+        FrontMatter { entries }
+    }
+
+    /// Head markup: `<title>` for `title` and `<meta>` for other keys.
+    pub fn to_head(&self) -> String {
+        self.entries.iter()
+            .map(|(key, value)| {
+                if key.eq_ignore_ascii_case("title") {
+                    format!("<title>{}</title>\n", escape_text(value))
+                } else {
+                    format!("<meta name=\"{}\" content=\"{}\">\n", escape_attribute(key), escape_attribute(value))
+                }
+            })
+            .collect()
+    }
+
+    /// A chapter head with its `<title>` and same-named `<meta>` elements
+    /// replaced by the front matter, keeping everything else as written.
+    pub fn apply_to_head(&self, head: &str) -> String {
+        if self.entries.is_empty() {
+            return head.to_string();
+        }
+        let keys: Vec<String> = self.entries.iter().map(|(k, _)| k.to_ascii_lowercase()).collect();
+        let replaces_title = keys.iter().any(|k| k == "title");
+        let lower = head.to_ascii_lowercase();
+
+        let mut out = String::new();
+        let mut kept_from = 0;
+        let mut search = 0;
+        while let Some(found) = lower[search..].find('<') {
+            let start = search + found;
+            let Some(tag_len) = lower[start..].find('>') else {
+                break;
+            };
+            let tag_end = start + tag_len + 1;
+            let tag = &lower[start..tag_end];
+            let end = if replaces_title && tag.starts_with("<title") {
+                lower[tag_end..].find("</title>").map(|e| tag_end + e + "</title>".len())
+            } else if tag.starts_with("<meta") && meta_name(&head[start..tag_end])
+                .is_some_and(|name| keys.contains(&name.to_ascii_lowercase()))
+            {
+                Some(tag_end)
+            } else {
+                None
+            };
+            match end {
+                Some(end) => {
+                    out += head[kept_from..start].trim_end_matches([' ', '\t']);
+                    kept_from = if head[end..].starts_with('\n') { end + 1 } else { end };
+                    search = kept_from;
+                }
+                None => search = tag_end,
+            }
+        }
+        out += &head[kept_from..];
+
+        let mut out = out.trim_end().to_string();
+        if !out.is_empty() {
+            out.push('\n');
+        }
+        out + &self.to_head()
+    }
+
+    /// The front matter as a fenced YAML block followed by a blank line,
+    /// or nothing if there are no entries.
+    pub fn to_yaml(&self) -> String {
+        if self.entries.is_empty() {
+            return String::new();
+        }
+        let mut out = String::from("---\n");
+        for (key, value) in &self.entries {
+            out += &format!("{}: {}\n", key, yaml_scalar(value));
+        }
+        out += "---\n\n";
+        out
+    }
+}
+
+/// A YAML scalar without its quotes and escapes, or trailing comment.
+fn scalar(value: &str) -> String {
+    let mut chars = value.chars().peekable();
+    let mut out = String::new();
+    match chars.next() {
+        Some('"') => {
+            while let Some(c) = chars.next() {
+                match c {
+                    '"' => break,
+                    '\\' => match chars.next() {
+                        Some('n') => out.push('\n'),
+                        Some('t') => out.push('\t'),
+                        Some(other) => out.push(other),
+                        None => {}
+                    },
+                    _ => out.push(c),
+                }
+            }
+            out
+        }
+        Some('\'') => {
+            while let Some(c) = chars.next() {
+                match c {
+                    '\'' if chars.peek() == Some(&'\'') => {
+                        out.push('\'');
+                        chars.next();
+                    }
+                    '\'' => break,
+                    _ => out.push(c),
+                }
+            }
+            out
+        }
+        _ => match value.find(" #") {
+            Some(comment) => value[..comment].trim_end().to_string(),
+            None => value.to_string(),
+        },
+    }
+}
+
+/// A value as a YAML scalar, quoted only when a plain scalar would be
+/// read differently.
+fn yaml_scalar(value: &str) -> String {
+    let plain = !value.is_empty()
+        && value.trim() == value
+        && !value.contains(": ")
+        && !value.contains(" #")
+        && !value.contains('\n')
+        && !value.ends_with(':')
+        && !value.starts_with(|c: char| "-?:,[]{}#&*!|>'\"%@`".contains(c));
+    if plain {
+        return value.to_string();
+    }
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n"))
+}
+
+/// `name` attribute of a `<meta>` tag.
+fn meta_name(tag: &str) -> Option<String> {
+    let fragment = Html::parse_fragment(tag);
+    let selector = Selector::parse("meta").ok()?;
+    let meta = fragment.select(&selector).next()?;
+    meta.value().attr("name").map(str::to_string)
+}
+
+/// A chapter converted to Markdown.
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize)]
+pub struct MarkdownText {
+    pub markdown: String,
+    /// Elements kept as raw HTML because Markdown has no equivalent
+    pub warnings: Vec<String>,
+}
+
+/// Convert a chapter to GitHub Flavored Markdown.
+///
+/// The head's `<title>` and named `<meta>` elements become YAML front
+/// matter. Tables without merged cells become GFM tables, task list
+/// checkboxes become `[x]`, `language-*` classes on code become fence
+/// languages, and footnotes in the form `markdown_to_html` writes (or
+/// marked with `doc-noteref` and `doc-endnotes` roles) become `[^label]`
+/// references and definitions. Elements Markdown cannot express are kept
+/// as raw HTML.
+pub fn html_to_markdown(head: &str, body: &str) -> MarkdownText {
+    let fragment = Html::parse_fragment(body);
+    let mut writer = MarkdownWriter::default();
+    let blocks = writer.blocks(*fragment.root_element());
+
+    let mut markdown = FrontMatter::from_head(head).to_yaml();
+    markdown += &blocks.join("\n\n");
+    for (label, text) in &writer.notes {
+        markdown += &format!("\n\n[^{}]: {}", label, indent_lines(text, "    "));
+    }
+    if !markdown.is_empty() && !markdown.ends_with('\n') {
+        markdown.push('\n');
+    }
+    MarkdownText {
+        markdown,
+        warnings: writer.warnings,
+    }
+}
+
+/// Serializer state for one chapter body.
+#[derive(Default)]
+struct MarkdownWriter {
+    warnings: Vec<String>,
+    /// Footnote definitions found in the body: label and Markdown text
+    notes: Vec<(String, String)>,
+}
+
+impl MarkdownWriter {
+    /// Markdown blocks for the children of `parent`. Runs of inline
+    /// content between block elements become paragraphs.
+    fn blocks(&mut self, parent: NodeRef<Node>) -> Vec<String> {
+        let mut blocks = Vec::new();
+        let mut line = String::new();
+        for child in parent.children() {
+            match child.value() {
+                Node::Element(element) if !INLINE_ELEMENTS.contains(&element.name()) => {
+                    finish_paragraph(&mut line, &mut blocks);
+                    if let Some(block) = ElementRef::wrap(child).and_then(|el| self.block(el)) {
+                        blocks.push(block);
+                    }
+                }
+                Node::Comment(comment) if line.trim().is_empty() => {
+                    blocks.push(format!("<!--{}-->", &**comment));
+                }
+                _ => self.inline(child, &mut line),
+            }
+        }
+        finish_paragraph(&mut line, &mut blocks);
+        blocks
+    }
+
+    fn block(&mut self, el: ElementRef) -> Option<String> {
+        let name = el.value().name();
+        match name {
+            "p" => {
+                let mut line = String::new();
+                self.inline_children(el, &mut line);
+                let mut blocks = Vec::new();
+                finish_paragraph(&mut line, &mut blocks);
+                blocks.pop()
+            }
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                let mut text = String::new();
+                self.inline_children(el, &mut text);
+                let text = text.replace("\\\n", " ");
+                let mut text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+                if text.ends_with('#') {
+                    text.insert(text.len() - 1, '\\');
+                }
+                let level = name[1..].parse().unwrap_or(1);
+                Some(format!("{} {}", "#".repeat(level), text).trim_end().to_string())
+            }
+            "ul" | "ol" => Some(self.list(el)),
+            "blockquote" => {
+                let inner = self.blocks(*el).join("\n\n");
+                Some(inner.lines()
+                    .map(|line| if line.is_empty() { ">".to_string() } else { format!("> {}", line) })
+                    .collect::<Vec<_>>()
+                    .join("\n"))
+            }
+            "pre" => Some(code_block(el)),
+            "hr" => Some("---".to_string()),
+            "table" => Some(self.table(el).unwrap_or_else(|| self.raw(el))),
+            _ if is_footnotes(el) => {
+                self.footnotes(el);
+                None
+            }
+            _ if CONTAINERS.contains(&name) => {
+                let inner = self.blocks(*el).join("\n\n");
+                if el.value().attrs().next().is_none() {
+                    return (!inner.is_empty()).then_some(inner);
+                }
+                Some(format!("{}\n\n{}\n\n</{}>", open_tag(el), inner, name))
+            }
+            _ => Some(self.raw(el)),
+        }
+    }
+
+    fn inline_children(&mut self, el: ElementRef, out: &mut String) {
+        for child in el.children() {
+            self.inline(child, out);
+        }
+    }
+
+    fn inline(&mut self, node: NodeRef<Node>, out: &mut String) {
+        let el = match node.value() {
+            Node::Text(text) => {
+                push_text(out, &escape_markdown(&collapse_whitespace(text)));
+                return;
+            }
+            Node::Comment(comment) => {
+                *out += &format!("<!--{}-->", &**comment);
+                return;
+            }
+            Node::Element(_) => match ElementRef::wrap(node) {
+                Some(el) => el,
+                None => return,
+            },
+            _ => return,
+        };
+
+        if let Some(label) = note_ref_label(el) {
+            *out += &format!("[^{}]", label);
+            return;
+        }
+        let name = el.value().name();
+        match name {
+            "strong" | "b" => self.emphasis(el, "**", out),
+            "em" | "i" => self.emphasis(el, "*", out),
+            "del" | "s" | "strike" => self.emphasis(el, "~~", out),
+            "code" => *out += &code_span(&el.text().collect::<String>()),
+            "br" => {
+                let trimmed = out.trim_end_matches(' ').len();
+                out.truncate(trimmed);
+                *out += "\\\n";
+            }
+            "input" if el.value().attr("type") == Some("checkbox") => {
+                push_text(out, if el.value().attr("checked").is_some() { "[x] " } else { "[ ] " });
+            }
+            "img" => {
+                let plain = el.value().attrs().all(|(attr, _)| matches!(attr, "src" | "alt" | "title"));
+                if !plain {
+                    *out += &open_tag(el);
+                    return;
+                }
+                let src = el.value().attr("src").unwrap_or_default();
+                let alt = el.value().attr("alt").unwrap_or_default();
+                *out += &format!(
+                    "![{}]({}{})",
+                    escape_markdown(alt),
+                    link_destination(src),
+                    link_title(el.value().attr("title")),
+                );
+            }
+            "a" if is_backref(el) => {}
+            "a" => {
+                let Some(href) = el.value().attr("href") else {
+                    self.inline_children(el, out);
+                    return;
+                };
+                let mut text = String::new();
+                self.inline_children(el, &mut text);
+                let title = el.value().attr("title");
+                let is_autolink = title.is_none()
+                    && text == escape_markdown(href)
+                    && (href.starts_with("http://") || href.starts_with("https://"))
+                    && !href.contains(char::is_whitespace);
+                if is_autolink {
+                    *out += &format!("<{}>", href);
+                } else {
+                    *out += &format!("[{}]({}{})", text.trim(), link_destination(href), link_title(title));
+                }
+            }
+            _ if INLINE_ELEMENTS.contains(&name) => {
+                *out += &open_tag(el);
+                if name != "wbr" {
+                    self.inline_children(el, out);
+                    *out += &format!("</{}>", name);
+                }
+            }
+            // A block element inside inline content keeps only its text
+            _ => self.inline_children(el, out),
+        }
+    }
+
+    /// Wrap inline content in `delimiter`, moving surrounding spaces
+    /// outside so the delimiters stay attached to the text.
+    fn emphasis(&mut self, el: ElementRef, delimiter: &str, out: &mut String) {
+        let mut inner = String::new();
+        self.inline_children(el, &mut inner);
+        let trimmed = inner.trim();
+        if trimmed.is_empty() {
+            push_text(out, &inner);
+            return;
+        }
+        if inner.starts_with(' ') {
+            push_text(out, " ");
+        }
+        *out += &format!("{}{}{}", delimiter, trimmed, delimiter);
+        if inner.ends_with(' ') {
+            out.push(' ');
+        }
+    }
+
+    fn list(&mut self, el: ElementRef) -> String {
+        let ordered = el.value().name() == "ol";
+        let start: u64 = el.value().attr("start").and_then(|s| s.trim().parse().ok()).unwrap_or(1);
+        let items: Vec<ElementRef> = el.children()
+            .filter_map(ElementRef::wrap)
+            .filter(|child| child.value().name() == "li")
+            .collect();
+
+        // Tight unless an item holds more than one paragraph or other block
+        let tight = items.iter().all(|li| {
+            li.children()
+                .filter_map(ElementRef::wrap)
+                .filter(|c| !INLINE_ELEMENTS.contains(&c.value().name()) && !matches!(c.value().name(), "ul" | "ol"))
+                .count() <= 1
+        });
+
+        let mut out = Vec::new();
+        for (number, li) in (start..).zip(items) {
+            let marker = if ordered { format!("{}. ", number) } else { "- ".to_string() };
+            let body = self.blocks(*li).join(if tight { "\n" } else { "\n\n" });
+            let padding = " ".repeat(marker.len());
+            out.push(format!("{}{}", marker, indent_lines(&body, &padding)).trim_end().to_string());
+        }
+        out.join(if tight { "\n" } else { "\n\n" })
+    }
+
+    /// A GFM table, or None if the table has merged cells, block content
+    /// in cells, or no header row.
+    fn table(&mut self, el: ElementRef) -> Option<String> {
+        let nested = el.descendants().skip(1).any(|n| n.value().as_element().is_some_and(|e| e.name() == "table"));
+        if nested {
+            return None;
+        }
+        let rows: Vec<Vec<ElementRef>> = el.descendants()
+            .filter_map(ElementRef::wrap)
+            .filter(|e| e.value().name() == "tr")
+            .map(|tr| {
+                tr.children()
+                    .filter_map(ElementRef::wrap)
+                    .filter(|c| matches!(c.value().name(), "td" | "th"))
+                    .collect()
+            })
+            .collect();
+        let header = rows.first()?;
+        if header.is_empty() || !header.iter().all(|cell| cell.value().name() == "th") {
+            return None;
+        }
+        for cell in rows.iter().flatten() {
+            let merged = ["colspan", "rowspan"].iter()
+                .any(|attr| cell.value().attr(attr).is_some_and(|v| v.trim() != "1"));
+            let blocks = cell.children()
+                .filter_map(ElementRef::wrap)
+                .filter(|c| !INLINE_ELEMENTS.contains(&c.value().name()))
+                .collect::<Vec<_>>();
+            let simple_blocks = blocks.is_empty() || (blocks.len() == 1 && blocks[0].value().name() == "p");
+            if merged || !simple_blocks {
+                return None;
+            }
+        }
+
+        let columns = header.len();
+        let mut lines = Vec::new();
+        for (n, row) in rows.iter().enumerate() {
+            let mut cells: Vec<String> = row.iter().map(|cell| self.table_cell(*cell)).collect();
+            cells.resize(columns.max(cells.len()), String::new());
+            lines.push(format!("| {} |", cells.join(" | ")));
+            if n == 0 {
+                let aligns: Vec<&str> = header.iter()
+                    .map(|cell| match cell_alignment(*cell) {
+                        Some("left") => ":---",
+                        Some("center") => ":---:",
+                        Some("right") => "---:",
+                        _ => "---",
+                    })
+                    .collect();
+                lines.push(format!("| {} |", aligns.join(" | ")));
+            }
+        }
+        Some(lines.join("\n"))
+    }
+
+    fn table_cell(&mut self, cell: ElementRef) -> String {
+        let mut text = String::new();
+        let paragraph = cell.children().filter_map(ElementRef::wrap).find(|c| c.value().name() == "p");
+        self.inline_children(paragraph.unwrap_or(cell), &mut text);
+        text.trim().replace("\\\n", "<br>").replace('|', "\\|")
+    }
+
+    /// Collect the notes of a footnotes section as definitions.
+    fn footnotes(&mut self, el: ElementRef) {
+        for li in el.descendants().filter_map(ElementRef::wrap).filter(|e| e.value().name() == "li") {
+            let Some(id) = li.value().id() else {
+                continue;
+            };
+            let text = self.blocks(*li).join("\n\n");
+            self.notes.push((note_label(id), text));
+        }
+    }
+
+    /// An element as raw HTML, without blank lines, which would end it.
+    fn raw(&mut self, el: ElementRef) -> String {
+        let warning = format!("<{}> kept as HTML", el.value().name());
+        if !self.warnings.contains(&warning) {
+            self.warnings.push(warning);
+        }
+        el.html().lines().filter(|line| !line.trim().is_empty()).collect::<Vec<_>>().join("\n")
+    }
+}
+
+/// Add a paragraph of inline content to `blocks`, escaping characters
+/// that would start a block at the beginning of its lines.
+fn finish_paragraph(line: &mut String, blocks: &mut Vec<String>) {
+    let text = std::mem::take(line);
+    let text = text.trim().trim_end_matches("\\\n").trim_end();
+    if text.is_empty() {
+        return;
+    }
+    let escaped: Vec<String> = text.lines().map(|l| escape_line_start(l.trim_start())).collect();
+    blocks.push(escaped.join("\n"));
+}
+
+fn escape_line_start(line: &str) -> String {
+    if line.starts_with(['#', '>', '-', '+', '=']) {
+        return format!("\\{}", line);
+    }
+    let digits = line.bytes().take_while(u8::is_ascii_digit).count();
+    if digits > 0 && line[digits..].starts_with(['.', ')']) {
+        return format!("{}\\{}", &line[..digits], &line[digits..]);
+    }
+    line.to_string()
+}
+
+/// Append text, not doubling a space where two runs meet.
+fn push_text(out: &mut String, text: &str) {
+    if out.ends_with([' ', '\n']) || out.is_empty() {
+        *out += text.strip_prefix(' ').unwrap_or(text);
+    } else {
+        *out += text;
+    }
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut space = false;
+    for c in text.chars() {
+        if c.is_ascii_whitespace() {
+            space = true;
+        } else {
+            if space {
+                out.push(' ');
+                space = false;
+            }
+            out.push(c);
+        }
+    }
+    if space {
+        out.push(' ');
+    }
+    out
+}
+
+/// Escape characters that Markdown would read as syntax.
+fn escape_markdown(text: &str) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut out = String::with_capacity(text.len());
+    for (i, &c) in chars.iter().enumerate() {
+        match c {
+            '\\' | '`' | '*' | '[' | ']' | '<' | '~' => {
+                out.push('\\');
+                out.push(c);
+            }
+            '_' => {
+                // Underscores inside words cannot open or close emphasis
+                let inside_word = i > 0
+                    && chars[i - 1].is_alphanumeric()
+                    && chars.get(i + 1).is_some_and(|c| c.is_alphanumeric());
+                if !inside_word {
+                    out.push('\\');
+                }
+                out.push(c);
+            }
+            '&' if looks_like_entity(&chars[i..]) => out += "\\&",
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+fn looks_like_entity(chars: &[char]) -> bool {
+    let name: String = chars.iter().skip(1).take_while(|c| c.is_ascii_alphanumeric() || **c == '#').collect();
+    !name.is_empty() && chars.get(name.chars().count() + 1) == Some(&';')
+}
+
+/// A code span with a fence longer than any backtick run inside.
+fn code_span(code: &str) -> String {
+    let code = code.replace('\n', " ");
+    let fence = "`".repeat(longest_run(&code, '`') + 1);
+    let pad = code.starts_with('`') || code.ends_with('`')
+        || (code.starts_with(' ') && code.ends_with(' ') && !code.trim().is_empty());
+    let space = if pad { " " } else { "" };
+    format!("{}{}{}{}{}", fence, space, code, space, fence)
+}
+
+fn code_block(el: ElementRef) -> String {
+    let code = el.children().filter_map(ElementRef::wrap).find(|c| c.value().name() == "code");
+    let lang = code
+        .and_then(|c| c.value().classes().find_map(|class| {
+            class.strip_prefix("language-").or_else(|| class.strip_prefix("lang-")).map(str::to_string)
+        }))
+        .unwrap_or_default();
+    let mut text: String = el.text().collect();
+    if !text.ends_with('\n') {
+        text.push('\n');
+    }
+    let fence = "`".repeat((longest_run(&text, '`') + 1).max(3));
+    format!("{}{}\n{}{}", fence, lang, text, fence)
+}
+
+fn longest_run(text: &str, c: char) -> usize {
+    let mut longest = 0;
+    let mut run = 0;
+    for x in text.chars() {
+        run = if x == c { run + 1 } else { 0 };
+        longest = longest.max(run);
+    }
+    longest
+}
+
+/// A link destination with spaces encoded and parentheses escaped.
+fn link_destination(url: &str) -> String {
+    url.replace('\\', "\\\\")
+        .replace(' ', "%20")
+        .replace('(', "\\(")
+        .replace(')', "\\)")
+}
+
+fn link_title(title: Option<&str>) -> String {
+    match title {
+        Some(title) => format!(" \"{}\"", title.replace('\\', "\\\\").replace('"', "\\\"")),
+        None => String::new(),
+    }
+}
+
+fn open_tag(el: ElementRef) -> String {
+    let mut tag = format!("<{}", el.value().name());
+    for (attr, value) in el.value().attrs() {
+        tag += &format!(" {}=\"{}\"", attr, escape_attribute(value));
+    }
+    tag.push('>');
+    tag
+}
+
+/// `left`, `center`, or `right` from a cell's `style` or `align`.
+fn cell_alignment(cell: ElementRef) -> Option<&'static str> {
+    let style = cell.value().attr("style").unwrap_or_default().replace(' ', "").to_ascii_lowercase();
+    let align = cell.value().attr("align").unwrap_or_default().to_ascii_lowercase();
+    ["left", "center", "right"].into_iter()
+        .find(|a| style.contains(&format!("text-align:{}", a)) || align == *a)
+}
+
+fn indent_lines(text: &str, padding: &str) -> String {
+    text.lines()
+        .enumerate()
+        .map(|(n, line)| {
+            if n == 0 || line.is_empty() {
+                line.to_string()
+            } else {
+                format!("{}{}", padding, line)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn has_class(el: ElementRef, class: &str) -> bool {
+    el.value().classes().any(|c| c == class)
+}
+
+/// True for a section of footnotes: `class="footnotes"` or an endnotes role.
+fn is_footnotes(el: ElementRef) -> bool {
+    has_class(el, "footnotes")
+        || el.value().attr("role") == Some("doc-endnotes")
+        || el.value().attr("epub:type") == Some("footnotes")
+}
+
+/// Label of a footnote reference: a `<sup class="footnote-ref">` holding a
+/// link to the note, or such a link itself.
+fn note_ref_label(el: ElementRef) -> Option<String> {
+    let link = match el.value().name() {
+        "a" => el,
+        "sup" if has_class(el, "footnote-ref") => el.children().filter_map(ElementRef::wrap).find(|c| c.value().name() == "a")?,
+        _ => return None,
+    };
+    let is_ref = has_class(el, "footnote-ref")
+        || has_class(link, "footnote-ref")
+        || link.value().attr("role") == Some("doc-noteref")
+        || link.value().attr("epub:type") == Some("noteref");
+    let target = link.value().attr("href")?.strip_prefix('#')?;
+    is_ref.then(|| note_label(target))
+}
+
+/// A link from a footnote back to its reference.
+fn is_backref(el: ElementRef) -> bool {
+    has_class(el, "footnote-backref")
+        || el.value().attr("role") == Some("doc-backlink")
+        || el.value().attr("href").is_some_and(|href| href.starts_with("#fnref"))
+}
+
+/// Footnote label from a note id such as `fn-1` or `fn1`.
+fn note_label(id: &str) -> String {
+    let label = id.strip_prefix("fn-")
+        .or_else(|| id.strip_prefix("fn:"))
+        .or_else(|| id.strip_prefix("fn"))
+        .unwrap_or(id);
+    if label.is_empty() { id.to_string() } else { label.to_string() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::markdown_import::markdown_to_html;
+
+    #[test]
+    fn test_markdown_round_trip() {
+        let source = r##"---
+title: "Birds: A Field Guide"
+author: A. Writer
+---
+
+# Spring *arrivals*
+
+Some *emphasis*, **strong**, ~~gone~~, ``a ` b``, snake_case, a \*star\*, and [next](ch%202.html "Next").\
+Line two with ![a wren](img/wren.png) and <https://example.com>.
+
+- [x] Done
+- [ ] Open
+  - Nested
+
+3. Three
+
+   More about three.
+
+4. Four
+
+> Quoted
+>
+> \# not a heading
+
+```rust
+fn main() {}
+```
+
+| Name | Count | Note |
+| :--- | ---: | :---: |
+| Wren | 3 | a \| b |
+
+<div class="aside">
+
+Boxed <sub>text</sub>.
+
+</div>
+
+A claim.[^1]
+
+---
+
+[^1]: The source.
+"##;
+        let html = markdown_to_html(source);
+        assert!(html.warnings.is_empty(), "{:?}", html.warnings);
+        let back = html_to_markdown(&html.head, &html.body);
+        assert_eq!(back.markdown, source);
+        assert!(back.warnings.is_empty());
+
+        // Editor output: paragraphs in list items and header cells in tbody
+        let body = "<h2>Notes</h2><ul><li><p>One</p></li><li><p>Two</p></li></ul>\
+            <table><tbody><tr><th colspan=\"1\"><p>A</p></th></tr><tr><td><p>1</p></td></tr></tbody></table>\
+            <p>1. Not a list &amp; <span class=\"x\">kept</span></p><figure><img src=\"a.png\"></figure>";
+        let converted = html_to_markdown("<title>Notes</title>\n<link rel=\"stylesheet\" href=\"book.css\">", body);
+        assert_eq!(
+            converted.markdown,
+            "---\ntitle: Notes\n---\n\n## Notes\n\n- One\n- Two\n\n| A |\n| --- |\n| 1 |\n\n\
+             1\\. Not a list & <span class=\"x\">kept</span>\n\n<figure><img src=\"a.png\"></figure>\n",
+        );
+        assert_eq!(converted.warnings, vec!["<figure> kept as HTML"]);
+    }
+
+    #[test]
+    fn test_front_matter_head() {
+        let (front_matter, rest, warnings) = FrontMatter::split(
+            "---\ntitle: 'It''s here' # comment\ntags:\n  - a\n  - b\nnot valid\n---\nBody\n",
+        );
+        assert_eq!(rest, "Body\n");
+        assert_eq!(front_matter.get("TITLE"), Some("It's here"));
+        assert_eq!(front_matter.get("tags"), Some("a, b"));
+        assert_eq!(warnings, vec!["Front matter line 6 is not a key: value pair: not valid"]);
+
+        let head = "\n  <meta charset=\"utf-8\">\n  <title>Old</title>\n  <meta name=\"tags\" content=\"x\">\n  <link rel=\"stylesheet\" href=\"book.css\">\n";
+        assert_eq!(
+            front_matter.apply_to_head(head),
+            "\n  <meta charset=\"utf-8\">\n  <link rel=\"stylesheet\" href=\"book.css\">\n\
+             <title>It's here</title>\n<meta name=\"tags\" content=\"a, b\">\n",
+        );
+        assert_eq!(FrontMatter::from_head(head).entries, vec![
+            ("title".to_string(), "Old".to_string()),
+            ("tags".to_string(), "x".to_string()),
+        ]);
+    }
+}
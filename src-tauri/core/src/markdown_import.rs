@@ -0,0 +1,1845 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::epub_import::ensure_empty_dir;
+use crate::error::{EditorError, EditorResult, ErrorKind};
+use crate::html_parser::{self, escape_attribute, escape_text};
+use crate::links;
+use crate::manifest::Manifest;
+use crate::markdown::FrontMatter;
+use crate::project::is_safe_relative;
+
+/// Elements whose tags start a raw HTML block.
+const BLOCK_TAGS: [&str; 45] = [
+    "address", "article", "aside", "blockquote", "body", "caption", "center", "col",
+    "colgroup", "dd", "details", "dialog", "div", "dl", "dt", "fieldset", "figcaption",
+    "figure", "footer", "form", "h1", "h2", "h3", "h4", "h5", "h6", "header", "hr", "li",
+    "main", "nav", "ol", "p", "pre", "script", "section", "style", "summary", "table",
+    "tbody", "td", "tfoot", "th", "thead", "tr",
+];
+
+/// Raw HTML blocks that run to their closing tag rather than a blank line.
+const VERBATIM_TAGS: [&str; 4] = ["pre", "script", "style", "textarea"];
+
+/// A Markdown file converted to HTML.
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize)]
+pub struct MarkdownHtml {
+    /// YAML front matter entries
+    pub front_matter: FrontMatter,
+    /// `<title>` and `<meta>` elements from the front matter
+    pub head: String,
+    /// Body fragment
+    pub body: String,
+    /// Constructs that could not be converted as written
+    pub warnings: Vec<String>,
+}
+
+impl MarkdownHtml {
+    /// A complete HTML document with the front matter in its head.
+    pub fn document(&self) -> String {
+        let head = format!("<meta charset=\"utf-8\">\n{}", self.head);
+        html_parser::reassemble_html("<!DOCTYPE html>", &head, &self.body, false)
+    }
+}
+
+/// Convert GitHub Flavored Markdown to HTML.
+///
+/// Supports CommonMark blocks and inlines plus GFM tables (with column
+/// alignment), task lists, strikethrough, bare URL autolinks, and
+/// footnotes, which become a `<section class="footnotes">` at the end.
+/// Fenced code keeps its language as a `language-*` class. YAML front
+/// matter is read into `front_matter`, with `title` as the head's
+/// `<title>` and other keys as named `<meta>` elements.
+pub fn markdown_to_html(markdown: &str) -> MarkdownHtml {
+    let (front_matter, text, mut warnings) = FrontMatter::split(markdown);
+    let lines: Vec<String> = text.lines().map(expand_tabs).collect();
+
+    let mut parser = BlockParser::default();
+    let blocks = parser.parse(&lines);
+    let mut renderer = Renderer {
+        refs: parser.refs,
+        notes: parser.notes,
+        note_order: Vec::new(),
+        warnings: Vec::new(),
+    };
+    let mut body = renderer.blocks(&blocks, false);
+    body += &renderer.footnotes();
+
+    warnings.extend(parser.warnings);
+    warnings.extend(renderer.warnings);
+    MarkdownHtml {
+        head: front_matter.to_head(),
+        front_matter,
+        body,
+        warnings,
+    }
+}
+
+/// A block-level element of a Markdown document.
+#[derive(Debug)]
+enum Block {
+    Heading(u8, String),
+    Paragraph(String),
+    Code { lang: String, text: String },
+    Quote(Vec<Block>),
+    List(List),
+    Table(Table),
+    Rule,
+    Html(String),
+}
+
+#[derive(Debug)]
+struct List {
+    ordered: bool,
+    start: u64,
+    tight: bool,
+    items: Vec<Item>,
+}
+
+#[derive(Debug)]
+struct Item {
+    /// Checkbox state of a task list item
+    task: Option<bool>,
+    blocks: Vec<Block>,
+}
+
+#[derive(Debug)]
+struct Table {
+    aligns: Vec<Option<&'static str>>,
+    head: Vec<String>,
+    rows: Vec<Vec<String>>,
+}
+
+/// A list item marker at the start of a line.
+#[derive(Debug, Clone)]
+struct Marker {
+    ordered: bool,
+    start: u64,
+    /// `-`, `*`, `+`, `.`, or `)`
+    delim: char,
+    /// Column where the item's content starts
+    offset: usize,
+    /// The first line of content
+    content: String,
+}
+
+/// Splits lines into blocks, collecting link reference and footnote
+/// definitions on the way.
+#[derive(Default)]
+struct BlockParser {
+    /// Link references by normalized label: URL and title
+    refs: HashMap<String, (String, Option<String>)>,
+    /// Footnotes by normalized label: label as written and content
+    notes: HashMap<String, (String, Vec<Block>)>,
+    warnings: Vec<String>,
+}
+
+impl BlockParser {
+    fn parse(&mut self, lines: &[String]) -> Vec<Block> {
+        let mut blocks = Vec::new();
+        let mut i = 0;
+        while i < lines.len() {
+            let line = &lines[i];
+            let indent = indent_of(line);
+            let rest = line.trim_start();
+            if rest.is_empty() {
+                i += 1;
+                continue;
+            }
+
+            if indent >= 4 {
+                let mut end = i;
+                let mut j = i;
+                while j < lines.len() && (is_blank(&lines[j]) || indent_of(&lines[j]) >= 4) {
+                    if !is_blank(&lines[j]) {
+                        end = j + 1;
+                    }
+                    j += 1;
+                }
+                let text = lines[i..end].iter()
+                    .map(|l| format!("{}\n", strip_indent(l, 4)))
+                    .collect();
+                blocks.push(Block::Code { lang: String::new(), text });
+                i = end;
+                continue;
+            }
+
+            if let Some((fence, lang)) = fence_open(rest) {
+                let mut text = String::new();
+                let mut j = i + 1;
+                while j < lines.len() && !is_fence_close(&lines[j], &fence) {
+                    text += strip_indent(&lines[j], indent);
+                    text.push('\n');
+                    j += 1;
+                }
+                if j == lines.len() {
+                    self.warnings.push(format!("Code block opened with {} is not closed", fence));
+                }
+                blocks.push(Block::Code { lang, text });
+                i = j + 1;
+                continue;
+            }
+
+            if let Some(level) = atx_level(rest) {
+                blocks.push(Block::Heading(level, atx_text(rest, level)));
+                i += 1;
+                continue;
+            }
+            if is_rule(rest) {
+                blocks.push(Block::Rule);
+                i += 1;
+                continue;
+            }
+            if rest.starts_with('>') {
+                i = self.quote(lines, i, &mut blocks);
+                continue;
+            }
+            if let Some(marker) = list_marker(line) {
+                i = self.list(lines, i, marker, &mut blocks);
+                continue;
+            }
+            if let Some(verbatim) = html_block_start(rest) {
+                i = html_block(lines, i, verbatim, &mut blocks);
+                continue;
+            }
+            if let Some((label, content)) = footnote_definition(rest) {
+                i = self.footnote(lines, i, label, content);
+                continue;
+            }
+            if let Some((label, url, title)) = link_definition(rest) {
+                self.refs.entry(normalize_label(&label)).or_insert((url, title));
+                i += 1;
+                continue;
+            }
+            if let Some((table, end)) = table_at(lines, i) {
+                blocks.push(Block::Table(table));
+                i = end;
+                continue;
+            }
+
+            let mut text = rest.to_string();
+            let mut j = i + 1;
+            let mut heading = None;
+            while j < lines.len() && !is_blank(&lines[j]) {
+                let next = &lines[j];
+                if indent_of(next) < 4 {
+                    heading = setext_level(next.trim());
+                    if heading.is_some() {
+                        j += 1;
+                        break;
+                    }
+                    if interrupts_paragraph(next) {
+                        break;
+                    }
+                }
+                text.push('\n');
+                text += next.trim_start();
+                j += 1;
+            }
+            let text = text.trim_end().to_string();
+            blocks.push(match heading {
+                Some(level) => Block::Heading(level, text),
+                None => Block::Paragraph(text),
+            });
+            i = j;
+        }
+        blocks
+    }
+
+    /// Parse a block quote starting at line `start`; returns the next line.
+    fn quote(&mut self, lines: &[String], start: usize, blocks: &mut Vec<Block>) -> usize {
+        let mut inner = Vec::new();
+        let mut lazy = false;
+        let mut j = start;
+        while j < lines.len() {
+            let line = &lines[j];
+            let rest = line.trim_start();
+            if indent_of(line) < 4 && rest.starts_with('>') {
+                let stripped = &rest[1..];
+                let stripped = stripped.strip_prefix(' ').unwrap_or(stripped);
+                lazy = !stripped.trim().is_empty() && fence_open(stripped.trim_start()).is_none();
+                inner.push(stripped.to_string());
+            } else if lazy && !is_blank(line) && !interrupts_paragraph(line) {
+                inner.push(rest.to_string());
+            } else {
+                break;
+            }
+            j += 1;
+        }
+        blocks.push(Block::Quote(self.parse(&inner)));
+        j
+    }
+
+    /// Parse a list whose first item starts at line `start`; returns the
+    /// next line.
+    fn list(&mut self, lines: &[String], start: usize, first: Marker, blocks: &mut Vec<Block>) -> usize {
+        let mut items = Vec::new();
+        let mut loose = false;
+        let mut marker = first.clone();
+        let mut j = start;
+        loop {
+            let mut item_lines = vec![marker.content.clone()];
+            let mut blank_before = false;
+            let mut inner_blank = false;
+            j += 1;
+            while j < lines.len() {
+                let line = &lines[j];
+                if is_blank(line) {
+                    item_lines.push(String::new());
+                    blank_before = true;
+                } else if indent_of(line) >= marker.offset {
+                    item_lines.push(strip_indent(line, marker.offset).to_string());
+                    inner_blank |= blank_before;
+                    blank_before = false;
+                } else if !blank_before
+                    && item_lines.last().is_some_and(|l| !l.is_empty())
+                    && list_marker(line).is_none()
+                    && !interrupts_paragraph(line)
+                {
+                    // Lazy continuation of the item's paragraph
+                    item_lines.push(line.trim_start().to_string());
+                } else {
+                    break;
+                }
+                j += 1;
+            }
+            while item_lines.last().is_some_and(|l| l.is_empty()) {
+                item_lines.pop();
+            }
+
+            let mut task = None;
+            if let Some(first_line) = item_lines.first_mut() {
+                for (prefix, checked) in [("[ ] ", false), ("[x] ", true), ("[X] ", true)] {
+                    if let Some(content) = first_line.strip_prefix(prefix) {
+                        task = Some(checked);
+                        *first_line = content.to_string();
+                        break;
+                    }
+                }
+            }
+            let item_blocks = self.parse(&item_lines);
+            loose |= inner_blank && item_blocks.len() > 1;
+            items.push(Item { task, blocks: item_blocks });
+
+            match lines.get(j).and_then(|line| list_marker(line)) {
+                Some(next) if next.ordered == first.ordered && next.delim == first.delim => {
+                    loose |= blank_before;
+                    marker = next;
+                }
+                _ => break,
+            }
+        }
+        blocks.push(Block::List(List {
+            ordered: first.ordered,
+            start: first.start,
+            tight: !loose,
+            items,
+        }));
+        j
+    }
+
+    /// Parse a footnote definition starting at line `start`; returns the
+    /// next line.
+    fn footnote(&mut self, lines: &[String], start: usize, label: String, content: String) -> usize {
+        let mut note_lines = vec![content];
+        let mut blank_before = false;
+        let mut j = start + 1;
+        while j < lines.len() {
+            let line = &lines[j];
+            if is_blank(line) {
+                note_lines.push(String::new());
+                blank_before = true;
+            } else if indent_of(line) >= 4 {
+                note_lines.push(strip_indent(line, 4).to_string());
+                blank_before = false;
+            } else if !blank_before && !interrupts_paragraph(line) && footnote_definition(line.trim_start()).is_none() {
+                note_lines.push(line.trim_start().to_string());
+            } else {
+                break;
+            }
+            j += 1;
+        }
+        while note_lines.last().is_some_and(|l| l.is_empty()) {
+            note_lines.pop();
+        }
+
+        let key = normalize_label(&label);
+        if self.notes.contains_key(&key) {
+            self.warnings.push(format!("Footnote [^{}] is defined more than once; the first is used", label));
+        } else {
+            let blocks = self.parse(&note_lines);
+            self.notes.insert(key, (label, blocks));
+        }
+        j
+    }
+}
+
+/// Collect a raw HTML block starting at line `start`; returns the next line.
+fn html_block(lines: &[String], start: usize, verbatim: Option<String>, blocks: &mut Vec<Block>) -> usize {
+    let mut j = start;
+    let mut raw = Vec::new();
+    while j < lines.len() {
+        let line = &lines[j];
+        match &verbatim {
+            Some(end) => {
+                raw.push(line.as_str());
+                j += 1;
+                if line.to_ascii_lowercase().contains(end.as_str()) {
+                    break;
+                }
+            }
+            None if is_blank(line) => break,
+            None => {
+                raw.push(line.as_str());
+                j += 1;
+            }
+        }
+    }
+    blocks.push(Block::Html(raw.join("\n")));
+    j
+}
+
+/// Turns blocks into HTML, resolving links and numbering footnotes.
+struct Renderer {
+    refs: HashMap<String, (String, Option<String>)>,
+    notes: HashMap<String, (String, Vec<Block>)>,
+    /// Normalized labels of referenced footnotes, in order of first use
+    note_order: Vec<String>,
+    warnings: Vec<String>,
+}
+
+/// An inline token waiting for emphasis and link processing.
+enum Inline {
+    /// Finished HTML
+    Html(String),
+    /// A run of `*`, `_`, or `~` that may open or close emphasis
+    Delim { ch: char, count: usize, open: bool, close: bool },
+    /// `[` or `![` that may start a link or image, with the index of the
+    /// character after it
+    Bracket { image: bool, active: bool, text_start: usize },
+}
+
+impl Renderer {
+    fn blocks(&mut self, blocks: &[Block], tight: bool) -> String {
+        let mut out = String::new();
+        for block in blocks {
+            match block {
+                Block::Heading(level, text) => {
+                    out += &format!("<h{0}>{1}</h{0}>\n", level, self.inline(text));
+                }
+                Block::Paragraph(text) => {
+                    let html = self.inline(text);
+                    out += &paragraph(&html, tight);
+                }
+                Block::Code { lang, text } => {
+                    let class = if lang.is_empty() {
+                        String::new()
+                    } else {
+                        format!(" class=\"language-{}\"", escape_attribute(lang))
+                    };
+                    out += &format!("<pre><code{}>{}</code></pre>\n", class, escape_text(text));
+                }
+                Block::Quote(inner) => {
+                    out += "<blockquote>\n";
+                    out += &self.blocks(inner, false);
+                    out += "</blockquote>\n";
+                }
+                Block::List(list) => out += &self.list(list),
+                Block::Table(table) => out += &self.table(table),
+                Block::Rule => out += "<hr>\n",
+                Block::Html(raw) => {
+                    out += raw;
+                    out.push('\n');
+                }
+            }
+        }
+        out
+    }
+
+    fn list(&mut self, list: &List) -> String {
+        let mut out = match (list.ordered, list.start) {
+            (false, _) => "<ul>\n".to_string(),
+            (true, 1) => "<ol>\n".to_string(),
+            (true, start) => format!("<ol start=\"{}\">\n", start),
+        };
+        for item in &list.items {
+            let mut inner = String::new();
+            for (n, block) in item.blocks.iter().enumerate() {
+                match (block, item.task) {
+                    (Block::Paragraph(text), Some(checked)) if n == 0 => {
+                        let checkbox = if checked {
+                            "<input type=\"checkbox\" disabled checked> "
+                        } else {
+                            "<input type=\"checkbox\" disabled> "
+                        };
+                        let html = format!("{}{}", checkbox, self.inline(text));
+                        inner += &paragraph(&html, list.tight);
+                    }
+                    _ => inner += &self.blocks(std::slice::from_ref(block), list.tight),
+                }
+            }
+            let open = if item.task.is_some() { "<li class=\"task-list-item\">" } else { "<li>" };
+            let inner = inner.trim_end();
+            let bare_text = list.tight && matches!(item.blocks.first(), Some(Block::Paragraph(_)));
+            if inner.is_empty() || bare_text && item.blocks.len() == 1 {
+                out += &format!("{}{}</li>\n", open, inner);
+            } else if bare_text {
+                out += &format!("{}{}\n</li>\n", open, inner);
+            } else {
+                out += &format!("{}\n{}\n</li>\n", open, inner);
+            }
+        }
+        out += if list.ordered { "</ol>\n" } else { "</ul>\n" };
+        out
+    }
+
+    fn table(&mut self, table: &Table) -> String {
+        let mut out = String::from("<table>\n<thead>\n");
+        out += &self.table_row(&table.head, &table.aligns, "th");
+        out += "</thead>\n";
+        if !table.rows.is_empty() {
+            out += "<tbody>\n";
+            for row in &table.rows {
+                out += &self.table_row(row, &table.aligns, "td");
+            }
+            out += "</tbody>\n";
+        }
+        out += "</table>\n";
+        out
+    }
+
+    fn table_row(&mut self, cells: &[String], aligns: &[Option<&str>], tag: &str) -> String {
+        let mut out = String::from("<tr>\n");
+        for (cell, align) in cells.iter().zip(aligns) {
+            let style = align.map(|a| format!(" style=\"text-align: {}\"", a)).unwrap_or_default();
+            out += &format!("<{0}{1}>{2}</{0}>\n", tag, style, self.inline(cell));
+        }
+        out += "</tr>\n";
+        out
+    }
+
+    /// The footnotes section, numbered in order of first reference.
+    fn footnotes(&mut self) -> String {
+        let mut out = String::new();
+        let mut n = 0;
+        while n < self.note_order.len() {
+            let key = self.note_order[n].clone();
+            n += 1;
+            // Rendering a note may reference further notes, or itself
+            let Some((label, blocks)) = self.notes.get_mut(&key)
+                .map(|(label, blocks)| (label.clone(), std::mem::take(blocks)))
+            else {
+                continue;
+            };
+            let id = note_id(&label);
+            let backref = format!(
+                " <a href=\"#fnref-{}\" class=\"footnote-backref\" role=\"doc-backlink\">&#8617;</a>",
+                id,
+            );
+            let mut content = self.blocks(&blocks, false);
+            match content.rfind("</p>") {
+                Some(end) if content[end..].trim() == "</p>" => content.insert_str(end, &backref),
+                _ => content += &format!("<p>{}</p>\n", backref.trim_start()),
+            }
+            out += &format!("<li id=\"fn-{}\">\n{}</li>\n", id, content);
+        }
+
+        let mut unused: Vec<&String> = self.notes.iter()
+            .filter(|(key, _)| !self.note_order.contains(key))
+            .map(|(_, (label, _))| label)
+            .collect();
+        unused.sort();
+        for label in unused {
+            self.warnings.push(format!("Footnote [^{}] is never referenced and was left out", label));
+        }
+        if out.is_empty() {
+            return out;
+        }
+        format!("<section class=\"footnotes\" role=\"doc-endnotes\">\n<ol>\n{}</ol>\n</section>\n", out)
+    }
+
+    /// Convert inline Markdown to HTML.
+    fn inline(&mut self, text: &str) -> String {
+        let chars: Vec<char> = text.chars().collect();
+        let mut nodes: Vec<Inline> = Vec::new();
+        let mut buf = String::new();
+        let mut i = 0;
+        while i < chars.len() {
+            let c = chars[i];
+            match c {
+                '\\' => match chars.get(i + 1) {
+                    Some(&next) if next.is_ascii_punctuation() => {
+                        push_escaped(&mut buf, next);
+                        i += 2;
+                    }
+                    Some('\n') => {
+                        buf += "<br>\n";
+                        i = skip_spaces(&chars, i + 2);
+                    }
+                    _ => {
+                        buf.push('\\');
+                        i += 1;
+                    }
+                },
+                '`' => {
+                    let run = run_length(&chars, i, '`');
+                    match find_backtick_run(&chars, i + run, run) {
+                        Some(close) => {
+                            let code: String = chars[i + run..close].iter()
+                                .map(|&c| if c == '\n' { ' ' } else { c })
+                                .collect();
+                            let code = match code.strip_prefix(' ').and_then(|c| c.strip_suffix(' ')) {
+                                Some(inner) if !code.trim().is_empty() => inner.to_string(),
+                                _ => code,
+                            };
+                            buf += &format!("<code>{}</code>", escape_text(&code));
+                            i = close + run;
+                        }
+                        None => {
+                            buf += &"`".repeat(run);
+                            i += run;
+                        }
+                    }
+                }
+                '*' | '_' | '~' => {
+                    let run = run_length(&chars, i, c);
+                    if c == '~' && run > 2 {
+                        buf += &"~".repeat(run);
+                        i += run;
+                        continue;
+                    }
+                    let before = if i == 0 { ' ' } else { chars[i - 1] };
+                    let after = chars.get(i + run).copied().unwrap_or(' ');
+                    let left = !after.is_whitespace()
+                        && (!is_punctuation(after) || before.is_whitespace() || is_punctuation(before));
+                    let right = !before.is_whitespace()
+                        && (!is_punctuation(before) || after.is_whitespace() || is_punctuation(after));
+                    let (open, close) = if c == '_' {
+                        (left && (!right || is_punctuation(before)), right && (!left || is_punctuation(after)))
+                    } else {
+                        (left, right)
+                    };
+                    flush(&mut buf, &mut nodes);
+                    nodes.push(Inline::Delim { ch: c, count: run, open, close });
+                    i += run;
+                }
+                '!' if chars.get(i + 1) == Some(&'[') => {
+                    flush(&mut buf, &mut nodes);
+                    nodes.push(Inline::Bracket { image: true, active: true, text_start: i + 2 });
+                    i += 2;
+                }
+                '[' => {
+                    if chars.get(i + 1) == Some(&'^') {
+                        if let Some(end) = chars[i..].iter().position(|&c| c == ']') {
+                            let label: String = chars[i + 2..i + end].iter().collect();
+                            if let Some(html) = self.note_ref(&label) {
+                                buf += &html;
+                                i += end + 1;
+                                continue;
+                            }
+                            if !label.trim().is_empty() && !label.contains('[') {
+                                self.warnings.push(format!("Footnote [^{}] is referenced but not defined", label));
+                            }
+                        }
+                    }
+                    flush(&mut buf, &mut nodes);
+                    nodes.push(Inline::Bracket { image: false, active: true, text_start: i + 1 });
+                    i += 1;
+                }
+                ']' => {
+                    flush(&mut buf, &mut nodes);
+                    i = self.close_bracket(&chars, i, &mut nodes);
+                }
+                '<' => match autolink_or_tag(&chars, i) {
+                    Some((html, end)) => {
+                        buf += &html;
+                        i = end;
+                    }
+                    None => {
+                        buf += "&lt;";
+                        i += 1;
+                    }
+                },
+                '&' => {
+                    let rest: String = chars[i..chars.len().min(i + 40)].iter().collect();
+                    match entity_length(&rest) {
+                        Some(len) => {
+                            buf += &rest[..len];
+                            i += rest[..len].chars().count();
+                        }
+                        None => {
+                            buf += "&amp;";
+                            i += 1;
+                        }
+                    }
+                }
+                '\n' => {
+                    let trimmed = buf.trim_end_matches(' ').len();
+                    let hard = buf.len() - trimmed >= 2;
+                    buf.truncate(trimmed);
+                    buf += if hard { "<br>\n" } else { "\n" };
+                    i = skip_spaces(&chars, i + 1);
+                }
+                'h' | 'w' if (i == 0 || !chars[i - 1].is_alphanumeric()) && !in_link(&nodes) => {
+                    match bare_url(&chars, i) {
+                        Some(end) => {
+                            let url: String = chars[i..end].iter().collect();
+                            let href = if url.starts_with("www.") { format!("http://{}", url) } else { url.clone() };
+                            buf += &format!("<a href=\"{}\">{}</a>", escape_attribute(&href), escape_text(&url));
+                            i = end;
+                        }
+                        None => {
+                            buf.push(c);
+                            i += 1;
+                        }
+                    }
+                }
+                _ => {
+                    push_escaped(&mut buf, c);
+                    i += 1;
+                }
+            }
+        }
+        flush(&mut buf, &mut nodes);
+        process_emphasis(&mut nodes);
+        render_inline(&nodes)
+    }
+
+    /// Handle `]` at `chars[i]`: close a link or image if a destination
+    /// or reference follows. Returns the index after what was consumed.
+    fn close_bracket(&mut self, chars: &[char], i: usize, nodes: &mut Vec<Inline>) -> usize {
+        let opener = nodes.iter().rposition(|n| matches!(n, Inline::Bracket { .. }));
+        let Some(o) = opener else {
+            nodes.push(Inline::Html("]".to_string()));
+            return i + 1;
+        };
+        let Inline::Bracket { image, active, text_start } = nodes[o] else {
+            unreachable!();
+        };
+        let literal = if image { "![" } else { "[" };
+        if !active {
+            nodes[o] = Inline::Html(literal.to_string());
+            nodes.push(Inline::Html("]".to_string()));
+            return i + 1;
+        }
+
+        let mut target = None;
+        if chars.get(i + 1) == Some(&'(') {
+            target = link_destination(chars, i + 2);
+        }
+        if target.is_none() {
+            // Full, collapsed, or shortcut reference
+            let (label, end) = match chars.get(i + 1) {
+                Some('[') => match chars[i + 2..].iter().position(|&c| c == ']') {
+                    Some(0) => (chars[text_start..i].iter().collect::<String>(), i + 3),
+                    Some(len) => (chars[i + 2..i + 2 + len].iter().collect(), i + 3 + len),
+                    None => (chars[text_start..i].iter().collect(), i + 1),
+                },
+                _ => (chars[text_start..i].iter().collect(), i + 1),
+            };
+            target = self.refs.get(&normalize_label(&label))
+                .map(|(url, title)| (url.clone(), title.clone(), end));
+        }
+        let Some((url, title, end)) = target else {
+            nodes[o] = Inline::Html(literal.to_string());
+            nodes.push(Inline::Html("]".to_string()));
+            return i + 1;
+        };
+
+        let mut inner = nodes.split_off(o + 1);
+        nodes.truncate(o);
+        process_emphasis(&mut inner);
+        let content = render_inline(&inner);
+        let title = title.map(|t| format!(" title=\"{}\"", escape_attribute(&t))).unwrap_or_default();
+        if image {
+            nodes.push(Inline::Html(format!(
+                "<img src=\"{}\" alt=\"{}\"{}>",
+                escape_attribute(&url),
+                escape_attribute(&strip_tags(&content)),
+                title,
+            )));
+        } else {
+            nodes.push(Inline::Html(format!("<a href=\"{}\"{}>{}</a>", escape_attribute(&url), title, content)));
+            // Links may not contain links
+            for node in nodes.iter_mut() {
+                if let Inline::Bracket { image: false, active, .. } = node {
+                    *active = false;
+                }
+            }
+        }
+        end
+    }
+
+    /// A footnote reference, numbered by first use, or None if the note
+    /// is not defined.
+    fn note_ref(&mut self, label: &str) -> Option<String> {
+        let key = normalize_label(label);
+        let (written, _) = self.notes.get(&key)?;
+        let id = note_id(written);
+        let (number, first) = match self.note_order.iter().position(|k| *k == key) {
+            Some(pos) => (pos + 1, false),
+            None => {
+                self.note_order.push(key);
+                (self.note_order.len(), true)
+            }
+        };
+        let id_attr = if first { format!(" id=\"fnref-{}\"", id) } else { String::new() };
+        Some(format!(
+            "<sup class=\"footnote-ref\"><a href=\"#fn-{}\"{} role=\"doc-noteref\">{}</a></sup>",
+            id, id_attr, number,
+        ))
+    }
+}
+
+/// A paragraph, or bare content in a tight list item.
+fn paragraph(html: &str, tight: bool) -> String {
+    if tight {
+        format!("{}\n", html)
+    } else {
+        format!("<p>{}</p>\n", html)
+    }
+}
+
+/// Match emphasis delimiters, innermost first, replacing each matched
+/// pair and what lies between with finished HTML.
+fn process_emphasis(nodes: &mut Vec<Inline>) {
+    let mut c = 0;
+    while c < nodes.len() {
+        let Inline::Delim { ch, count: close_count, open: close_open, close: true } = nodes[c] else {
+            c += 1;
+            continue;
+        };
+        if close_count == 0 {
+            c += 1;
+            continue;
+        }
+
+        let opener = (0..c).rev().find(|&o| match nodes[o] {
+            Inline::Delim { ch: och, count, open: true, close: oclose } if och == ch && count > 0 => {
+                if ch == '~' {
+                    count == close_count
+                } else {
+                    // The "multiple of 3" rule for runs that can both open and close
+                    !((oclose || close_open) && (count + close_count) % 3 == 0
+                        && !(count % 3 == 0 && close_count % 3 == 0))
+                }
+            }
+            _ => false,
+        });
+        let Some(o) = opener else {
+            c += 1;
+            continue;
+        };
+        let Inline::Delim { count: open_count, .. } = nodes[o] else {
+            unreachable!();
+        };
+
+        let used = if ch == '~' || (open_count >= 2 && close_count >= 2) { 2.min(close_count) } else { 1 };
+        let tag = match (ch, used) {
+            ('~', _) => "del",
+            (_, 2) => "strong",
+            _ => "em",
+        };
+        let inner: Vec<Inline> = nodes.drain(o + 1..c).collect();
+        nodes.insert(o + 1, Inline::Html(format!("<{0}>{1}</{0}>", tag, render_inline(&inner))));
+        for index in [o, o + 2] {
+            if let Inline::Delim { count, .. } = &mut nodes[index] {
+                *count -= used.min(*count);
+            }
+        }
+        c = o + 2;
+    }
+}
+
+fn render_inline(nodes: &[Inline]) -> String {
+    let mut out = String::new();
+    for node in nodes {
+        match node {
+            Inline::Html(html) => out += html,
+            Inline::Delim { ch, count, .. } => out += &ch.to_string().repeat(*count),
+            Inline::Bracket { image: true, .. } => out += "![",
+            Inline::Bracket { image: false, .. } => out.push('['),
+        }
+    }
+    out
+}
+
+fn flush(buf: &mut String, nodes: &mut Vec<Inline>) {
+    if !buf.is_empty() {
+        nodes.push(Inline::Html(std::mem::take(buf)));
+    }
+}
+
+/// True inside the text of a link being parsed, where bare URLs stay text.
+fn in_link(nodes: &[Inline]) -> bool {
+    nodes.iter().any(|n| matches!(n, Inline::Bracket { image: false, active: true, .. }))
+}
+
+fn push_escaped(buf: &mut String, c: char) {
+    match c {
+        '&' => *buf += "&amp;",
+        '<' => *buf += "&lt;",
+        '>' => *buf += "&gt;",
+        '"' => *buf += "&quot;",
+        _ => buf.push(c),
+    }
+}
+
+fn is_punctuation(c: char) -> bool {
+    c.is_ascii_punctuation() || (!c.is_ascii() && !c.is_alphanumeric() && !c.is_whitespace())
+}
+
+fn run_length(chars: &[char], start: usize, c: char) -> usize {
+    chars[start..].iter().take_while(|&&x| x == c).count()
+}
+
+fn skip_spaces(chars: &[char], mut i: usize) -> usize {
+    while chars.get(i) == Some(&' ') {
+        i += 1;
+    }
+    i
+}
+
+/// Start of the next run of exactly `len` backticks at or after `from`.
+fn find_backtick_run(chars: &[char], from: usize, len: usize) -> Option<usize> {
+    let mut i = from;
+    while i < chars.len() {
+        if chars[i] == '`' {
+            let run = run_length(chars, i, '`');
+            if run == len {
+                return Some(i);
+            }
+            i += run;
+        } else {
+            i += 1;
+        }
+    }
+    None
+}
+
+/// Parse `dest "title")` of an inline link, starting after `(`.
+/// Returns the URL, title, and the index after `)`.
+fn link_destination(chars: &[char], start: usize) -> Option<(String, Option<String>, usize)> {
+    let mut j = skip_whitespace(chars, start);
+    let mut url = String::new();
+    if chars.get(j) == Some(&'<') {
+        j += 1;
+        loop {
+            match chars.get(j)? {
+                '>' => break,
+                '\n' | '<' => return None,
+                '\\' if chars.get(j + 1).is_some_and(|c| c.is_ascii_punctuation()) => {
+                    url.push(chars[j + 1]);
+                    j += 1;
+                }
+                &c => url.push(c),
+            }
+            j += 1;
+        }
+        j += 1;
+    } else {
+        let mut depth = 0;
+        while let Some(&c) = chars.get(j) {
+            match c {
+                '\\' if chars.get(j + 1).is_some_and(|c| c.is_ascii_punctuation()) => {
+                    url.push(chars[j + 1]);
+                    j += 1;
+                }
+                _ if c.is_whitespace() || c.is_control() => break,
+                '(' => {
+                    depth += 1;
+                    url.push(c);
+                }
+                ')' if depth == 0 => break,
+                ')' => {
+                    depth -= 1;
+                    url.push(c);
+                }
+                _ => url.push(c),
+            }
+            j += 1;
+        }
+    }
+
+    let before_title = j;
+    j = skip_whitespace(chars, j);
+    let mut title = None;
+    if j > before_title {
+        if let Some(close) = match chars.get(j) {
+            Some('"') => Some('"'),
+            Some('\'') => Some('\''),
+            Some('(') => Some(')'),
+            _ => None,
+        } {
+            let mut text = String::new();
+            j += 1;
+            loop {
+                match chars.get(j)? {
+                    '\\' if chars.get(j + 1).is_some_and(|c| c.is_ascii_punctuation()) => {
+                        text.push(chars[j + 1]);
+                        j += 1;
+                    }
+                    &c if c == close => break,
+                    &c => text.push(c),
+                }
+                j += 1;
+            }
+            title = Some(text);
+            j = skip_whitespace(chars, j + 1);
+        }
+    }
+    (chars.get(j) == Some(&')')).then_some((url, title, j + 1))
+}
+
+fn skip_whitespace(chars: &[char], mut i: usize) -> usize {
+    while chars.get(i).is_some_and(|c| c.is_whitespace()) {
+        i += 1;
+    }
+    i
+}
+
+/// An autolink such as `<https://example.com>` or an inline HTML tag or
+/// comment at `chars[start]`, with the index after it.
+fn autolink_or_tag(chars: &[char], start: usize) -> Option<(String, usize)> {
+    let close = start + chars[start..].iter().position(|&c| c == '>' || c == '\n')?;
+    if chars[close] == '>' {
+        let inner: String = chars[start + 1..close].iter().collect();
+        if !inner.contains(' ') && !inner.contains('<') {
+            if let Some((scheme, _)) = inner.split_once(':') {
+                let valid_scheme = (2..=32).contains(&scheme.len())
+                    && scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+                    && scheme.chars().all(|c| c.is_ascii_alphanumeric() || "+.-".contains(c));
+                if valid_scheme {
+                    let link = format!("<a href=\"{}\">{}</a>", escape_attribute(&inner), escape_text(&inner));
+                    return Some((link, close + 1));
+                }
+            }
+            if let Some((user, domain)) = inner.split_once('@') {
+                if !user.is_empty() && domain.contains('.') && !domain.contains('@') {
+                    let link = format!(
+                        "<a href=\"mailto:{}\">{}</a>",
+                        escape_attribute(&inner),
+                        escape_text(&inner),
+                    );
+                    return Some((link, close + 1));
+                }
+            }
+        }
+    }
+
+    // Raw inline HTML: a comment, or an opening or closing tag
+    let rest: String = chars[start..].iter().collect();
+    if rest.starts_with("<!--") {
+        let end = rest.find("-->")? + 3;
+        return Some((rest[..end].to_string(), start + rest[..end].chars().count()));
+    }
+    let name_start = if rest.starts_with("</") { 2 } else { 1 };
+    if !rest[name_start..].starts_with(|c: char| c.is_ascii_alphabetic()) {
+        return None;
+    }
+    let mut quote = None;
+    for (offset, c) in rest.char_indices().skip(name_start) {
+        match (quote, c) {
+            (Some(q), _) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '>') => {
+                let tag = &rest[..=offset];
+                return Some((tag.to_string(), start + tag.chars().count()));
+            }
+            (None, '<') => return None,
+            _ => {}
+        }
+    }
+    None
+}
+
+/// End of a bare `http://`, `https://`, or `www.` URL at `start`.
+fn bare_url(chars: &[char], start: usize) -> Option<usize> {
+    let rest: String = chars[start..chars.len().min(start + 8)].iter().collect();
+    let prefix = ["https://", "http://", "www."].into_iter().find(|p| rest.starts_with(p))?;
+    let mut end = start;
+    while end < chars.len() && !chars[end].is_whitespace() && chars[end] != '<' {
+        end += 1;
+    }
+    // Trailing punctuation ends the sentence, not the URL
+    loop {
+        match chars[end - 1] {
+            '?' | '!' | '.' | ',' | ':' | '*' | '_' | '~' | '\'' | '"' => end -= 1,
+            ')' if chars[start..end].iter().filter(|&&c| c == '(').count()
+                < chars[start..end].iter().filter(|&&c| c == ')').count() => end -= 1,
+            _ => break,
+        }
+    }
+    (end > start + prefix.len()).then_some(end)
+}
+
+/// Length of a character reference such as `&amp;` or `&#169;` at the
+/// start of `text`.
+fn entity_length(text: &str) -> Option<usize> {
+    let end = text.find(';')?;
+    let name = &text[1..end];
+    let valid = if let Some(hex) = name.strip_prefix("#x").or_else(|| name.strip_prefix("#X")) {
+        (1..=6).contains(&hex.len()) && hex.chars().all(|c| c.is_ascii_hexdigit())
+    } else if let Some(digits) = name.strip_prefix('#') {
+        (1..=7).contains(&digits.len()) && digits.chars().all(|c| c.is_ascii_digit())
+    } else {
+        (2..=32).contains(&name.len())
+            && name.starts_with(|c: char| c.is_ascii_alphabetic())
+            && name.chars().all(|c| c.is_ascii_alphanumeric())
+    };
+    valid.then_some(end + 1)
+}
+
+fn strip_tags(html: &str) -> String {
+    let mut out = String::new();
+    let mut in_tag = false;
+    for c in html.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => in_tag = false,
+            _ if !in_tag => out.push(c),
+            _ => {}
+        }
+    }
+    out
+}
+
+/// Labels match case-insensitively with whitespace collapsed.
+fn normalize_label(label: &str) -> String {
+    label.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase()
+}
+
+/// A footnote label made safe for use in an id.
+fn note_id(label: &str) -> String {
+    label.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '-' })
+        .collect()
+}
+
+fn expand_tabs(line: &str) -> String {
+    if !line.contains('\t') {
+        return line.to_string();
+    }
+    let mut out = String::new();
+    for c in line.chars() {
+        if c == '\t' {
+            let width = 4 - out.chars().count() % 4;
+            out += &" ".repeat(width);
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+fn indent_of(line: &str) -> usize {
+    line.len() - line.trim_start_matches(' ').len()
+}
+
+fn is_blank(line: &str) -> bool {
+    line.trim().is_empty()
+}
+
+/// `line` without up to `n` leading spaces.
+fn strip_indent(line: &str, n: usize) -> &str {
+    &line[indent_of(line).min(n)..]
+}
+
+/// A code fence and the first word of its info string.
+fn fence_open(rest: &str) -> Option<(String, String)> {
+    let c = rest.chars().next().filter(|&c| c == '`' || c == '~')?;
+    let len = rest.chars().take_while(|&x| x == c).count();
+    if len < 3 {
+        return None;
+    }
+    let info = rest[len..].trim();
+    if c == '`' && info.contains('`') {
+        return None;
+    }
+    let lang = info.split_whitespace().next().unwrap_or("");
+    Some((rest[..len].to_string(), lang.to_string()))
+}
+
+fn is_fence_close(line: &str, fence: &str) -> bool {
+    let rest = line.trim();
+    let c = fence.chars().next().unwrap_or('`');
+    indent_of(line) < 4 && rest.len() >= fence.len() && rest.chars().all(|x| x == c)
+}
+
+fn atx_level(rest: &str) -> Option<u8> {
+    let level = rest.chars().take_while(|&c| c == '#').count();
+    let after = &rest[level..];
+    ((1..=6).contains(&level) && (after.is_empty() || after.starts_with(' '))).then_some(level as u8)
+}
+
+/// Heading text without the opening and any closing `#` sequence.
+fn atx_text(rest: &str, level: u8) -> String {
+    let text = rest[level as usize..].trim();
+    let without_closing = text.trim_end_matches('#');
+    if without_closing.is_empty() {
+        String::new()
+    } else if without_closing.ends_with(' ') {
+        without_closing.trim_end().to_string()
+    } else {
+        text.to_string()
+    }
+}
+
+fn is_rule(rest: &str) -> bool {
+    let Some(c) = rest.chars().next().filter(|&c| c == '-' || c == '*' || c == '_') else {
+        return false;
+    };
+    rest.chars().all(|x| x == c || x == ' ') && rest.chars().filter(|&x| x == c).count() >= 3
+}
+
+fn setext_level(trimmed: &str) -> Option<u8> {
+    if !trimmed.is_empty() && trimmed.chars().all(|c| c == '=') {
+        Some(1)
+    } else if !trimmed.is_empty() && trimmed.chars().all(|c| c == '-') {
+        Some(2)
+    } else {
+        None
+    }
+}
+
+fn list_marker(line: &str) -> Option<Marker> {
+    let indent = indent_of(line);
+    if indent >= 4 {
+        return None;
+    }
+    let rest = &line[indent..];
+    let (ordered, start, delim, width) = match rest.chars().next()? {
+        c @ ('-' | '*' | '+') => (false, 0, c, 1),
+        _ => {
+            let digits = rest.bytes().take_while(u8::is_ascii_digit).count();
+            if digits == 0 || digits > 9 {
+                return None;
+            }
+            let delim = rest[digits..].chars().next().filter(|&c| c == '.' || c == ')')?;
+            (true, rest[..digits].parse().ok()?, delim, digits + 1)
+        }
+    };
+    let after = &rest[width..];
+    if !after.is_empty() && !after.starts_with(' ') {
+        return None;
+    }
+    let spaces = indent_of(after);
+    let (pad, content) = if after.trim().is_empty() {
+        (1, String::new())
+    } else if spaces > 4 {
+        (1, after[1..].to_string())
+    } else {
+        (spaces, after[spaces..].to_string())
+    };
+    Some(Marker { ordered, start, delim, offset: indent + width + pad, content })
+}
+
+/// True if `line` starts a block that ends a paragraph.
+fn interrupts_paragraph(line: &str) -> bool {
+    if indent_of(line) >= 4 {
+        return false;
+    }
+    let rest = line.trim_start();
+    fence_open(rest).is_some()
+        || atx_level(rest).is_some()
+        || is_rule(rest)
+        || rest.starts_with('>')
+        || html_block_start(rest).is_some()
+        || list_marker(line).is_some_and(|m| !m.content.is_empty() && (!m.ordered || m.start == 1))
+}
+
+/// If `rest` starts a raw HTML block, the closing tag it runs to, or None
+/// if it runs to a blank line.
+fn html_block_start(rest: &str) -> Option<Option<String>> {
+    if rest.starts_with("<!--") {
+        return Some(Some("-->".to_string()));
+    }
+    let tag = rest.strip_prefix("</").or_else(|| rest.strip_prefix('<'))?;
+    let name: String = tag.chars().take_while(|c| c.is_ascii_alphanumeric()).collect::<String>().to_lowercase();
+    let after = tag[name.len()..].chars().next();
+    if !matches!(after, None | Some(' ' | '>' | '/')) {
+        return None;
+    }
+    if VERBATIM_TAGS.contains(&name.as_str()) && !rest.starts_with("</") {
+        return Some(Some(format!("</{}>", name)));
+    }
+    BLOCK_TAGS.contains(&name.as_str()).then_some(None)
+}
+
+/// `[^label]: content` at the start of a footnote definition.
+fn footnote_definition(rest: &str) -> Option<(String, String)> {
+    let inner = rest.strip_prefix("[^")?;
+    let (label, content) = inner.split_once("]:")?;
+    if label.is_empty() || label.contains(char::is_whitespace) || label.contains('[') {
+        return None;
+    }
+    Some((label.to_string(), content.trim().to_string()))
+}
+
+/// `[label]: url "title"`, a link reference definition on one line.
+fn link_definition(rest: &str) -> Option<(String, String, Option<String>)> {
+    let inner = rest.strip_prefix('[')?;
+    if inner.starts_with('^') {
+        return None;
+    }
+    let (label, after) = inner.split_once("]:")?;
+    if label.trim().is_empty() || label.contains('[') {
+        return None;
+    }
+    let chars: Vec<char> = after.chars().collect();
+    let mut text: Vec<char> = vec![' '];
+    text.extend(&chars);
+    text.push(')');
+    let (url, title, end) = link_destination(&text, 0)?;
+    (end == text.len() && !url.is_empty()).then_some((label.to_string(), url, title))
+}
+
+/// A GFM table whose header is at line `start`, with the line after it.
+fn table_at(lines: &[String], start: usize) -> Option<(Table, usize)> {
+    let header = &lines[start];
+    let delimiter = lines.get(start + 1)?;
+    if !header.contains('|') || !delimiter.contains('|') {
+        return None;
+    }
+    let aligns = split_row(delimiter).iter()
+        .map(|cell| {
+            let dashes = cell.trim_start_matches(':').trim_end_matches(':');
+            if dashes.is_empty() || !dashes.chars().all(|c| c == '-') {
+                return None;
+            }
+            Some(match (cell.starts_with(':'), cell.ends_with(':')) {
+                (true, true) => Some("center"),
+                (true, false) => Some("left"),
+                (false, true) => Some("right"),
+                (false, false) => None,
+            })
+        })
+        .collect::<Option<Vec<_>>>()?;
+    let head = split_row(header);
+    if head.len() != aligns.len() {
+        return None;
+    }
+
+    let mut rows = Vec::new();
+    let mut j = start + 2;
+    while j < lines.len() && !is_blank(&lines[j]) && lines[j].contains('|') {
+        let mut cells = split_row(&lines[j]);
+        cells.resize(aligns.len(), String::new());
+        rows.push(cells);
+        j += 1;
+    }
+    Some((Table { aligns, head, rows }, j))
+}
+
+/// Cells of a table row, split on unescaped pipes, with `\|` unescaped.
+fn split_row(line: &str) -> Vec<String> {
+    let mut row = line.trim();
+    row = row.strip_prefix('|').unwrap_or(row);
+    if row.ends_with('|') && !row.ends_with("\\|") {
+        row = &row[..row.len() - 1];
+    }
+    let mut cells = Vec::new();
+    let mut cell = String::new();
+    let mut chars = row.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars.peek() == Some(&'|') => {
+                cell.push('|');
+                chars.next();
+            }
+            '|' => cells.push(std::mem::take(&mut cell).trim().to_string()),
+            _ => cell.push(c),
+        }
+    }
+    cells.push(cell.trim().to_string());
+    cells
+}
+
+/// Warnings for one file of a Markdown folder import.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct FileWarnings {
+    /// Markdown file, relative to the source folder
+    pub path: String,
+    pub warnings: Vec<String>,
+}
+
+/// Result of converting a folder of Markdown files into a project.
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct MarkdownFolderImport {
+    pub project_dir: String,
+    /// Chapters in manifest order, project-relative
+    pub chapters: Vec<String>,
+    /// Images and other referenced files copied from the source folder
+    pub resources: Vec<String>,
+    /// Files that converted with warnings
+    pub files: Vec<FileWarnings>,
+}
+
+/// Convert every Markdown file under `source` into an HTML chapter of a
+/// new project folder.
+///
+/// Chapters keep their paths relative to `source`, with an `.html`
+/// extension, and links between Markdown files are rewritten to match.
+/// Images and other files the chapters reference are copied alongside.
+/// The chapter order is that of the links in a `SUMMARY.md` at the top of
+/// the folder, as used by mdBook and GitBook, followed by any files it
+/// does not list; without one, files are ordered by path.
+pub fn import_markdown_folder(source: &Path, project_dir: &Path) -> EditorResult<MarkdownFolderImport> {
+    let mut sources = Vec::new();
+    collect_markdown_files(source, "", &mut sources)
+        .map_err(|e| EditorError::io("Failed to read the Markdown folder", e).with_path(source))?;
+    sources.sort();
+    let summary = sources.iter()
+        .position(|path| path.eq_ignore_ascii_case("SUMMARY.md"))
+        .map(|i| sources.remove(i));
+    if sources.is_empty() {
+        return Err(EditorError::new(
+            ErrorKind::NotFound,
+            format!("No Markdown files in {}", source.display()),
+        ).with_path(source));
+    }
+
+    if let Some(summary) = summary {
+        let text = fs::read_to_string(source.join(&summary))
+            .map_err(|e| EditorError::io("Failed to read SUMMARY.md", e).with_path(source.join(&summary)))?;
+        let listed = markdown_link_targets(&summary, &markdown_to_html(&text).body);
+        sources.sort_by_key(|path| listed.iter().position(|l| l == path).unwrap_or(listed.len()));
+    }
+
+    ensure_empty_dir(project_dir)?;
+    let mut result = MarkdownFolderImport {
+        project_dir: project_dir.to_string_lossy().to_string(),
+        ..MarkdownFolderImport::default()
+    };
+
+    // Chapter path for each Markdown file; a later file that would
+    // overwrite an earlier chapter is left out
+    let mut converted: Vec<(String, String)> = Vec::new();
+    for path in &sources {
+        let chapter = html_path(path);
+        if converted.iter().any(|(_, c)| c == &chapter) {
+            result.files.push(FileWarnings {
+                path: path.clone(),
+                warnings: vec![format!("{} is already written from another file; this one was skipped", chapter)],
+            });
+            continue;
+        }
+        converted.push((path.clone(), chapter));
+    }
+
+    let mut referenced: Vec<String> = Vec::new();
+    for (path, chapter) in &converted {
+        let file = source.join(path);
+        let mut warnings = Vec::new();
+        let markdown = match fs::read_to_string(&file) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::InvalidData => {
+                result.files.push(FileWarnings {
+                    path: path.clone(),
+                    warnings: vec!["Not valid UTF-8; the file was skipped".to_string()],
+                });
+                continue;
+            }
+            Err(e) => return Err(EditorError::io("Failed to read Markdown", e).with_path(&file)),
+        };
+
+        let mut html = markdown_to_html(&markdown);
+        warnings.append(&mut html.warnings);
+        html.body = rewrite_markdown_links(source, path, &html.body, &converted, &mut referenced, &mut warnings);
+
+        let target = project_dir.join(chapter);
+        write_new_file(&target, html.document().as_bytes())
+            .map_err(|e| EditorError::write("Failed to write chapter", e).with_path(&target))?;
+        result.chapters.push(chapter.clone());
+        if !warnings.is_empty() {
+            result.files.push(FileWarnings { path: path.clone(), warnings });
+        }
+    }
+
+    for path in referenced {
+        let from = source.join(&path);
+        if result.chapters.contains(&path) {
+            continue;
+        }
+        let target = project_dir.join(&path);
+        let copied = fs::read(&from).and_then(|bytes| write_new_file(&target, &bytes));
+        copied.map_err(|e| EditorError::write(format!("Failed to copy {}", path), e).with_path(&target))?;
+        result.resources.push(path);
+    }
+
+    let manifest = Manifest {
+        chapters: result.chapters.clone(),
+        ..Manifest::default()
+    };
+    manifest.save(project_dir)
+        .map_err(|e| EditorError::write("Failed to write manifest.json", e).with_path(project_dir))?;
+    Ok(result)
+}
+
+/// Collect `.md` and `.markdown` files below `dir` as `/`-separated paths,
+/// skipping hidden files and folders.
+fn collect_markdown_files(dir: &Path, prefix: &str, files: &mut Vec<String>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        if name.starts_with('.') {
+            continue;
+        }
+        let relative = format!("{}{}", prefix, name);
+        if entry.file_type()?.is_dir() {
+            collect_markdown_files(&entry.path(), &format!("{}/", relative), files)?;
+        } else if markdown_extension(&relative).is_some() {
+            files.push(relative);
+        }
+    }
+    Ok(())
+}
+
+/// The Markdown extension of a path, with its dot, if it has one.
+fn markdown_extension(path: &str) -> Option<&str> {
+    let dot = path.rfind('.')?;
+    let ext = &path[dot..];
+    (ext.eq_ignore_ascii_case(".md") || ext.eq_ignore_ascii_case(".markdown")).then_some(ext)
+}
+
+/// A Markdown path with its extension replaced by `.html`.
+fn html_path(path: &str) -> String {
+    let stem = markdown_extension(path).map_or(path, |ext| &path[..path.len() - ext.len()]);
+    format!("{}.html", stem)
+}
+
+/// Markdown files linked from an HTML body, in order, relative to the
+/// folder of `doc`.
+fn markdown_link_targets(doc: &str, body: &str) -> Vec<String> {
+    links::attribute_spans(body, &["href"]).into_iter()
+        .filter_map(|span| {
+            let (path, _) = links::split_url(&body[span.start..span.end]);
+            if !links::is_relative_url(path) || markdown_extension(path).is_none() {
+                return None;
+            }
+            links::resolve_relative(doc, &links::percent_decode(path))
+        })
+        .collect()
+}
+
+/// Point links to converted Markdown files at their chapters and collect
+/// the other relative files a chapter references.
+fn rewrite_markdown_links(
+    source: &Path,
+    doc: &str,
+    body: &str,
+    converted: &[(String, String)],
+    referenced: &mut Vec<String>,
+    warnings: &mut Vec<String>,
+) -> String {
+    let mut replacements = Vec::new();
+    for span in links::attribute_spans(body, &["href", "src"]) {
+        let url = &body[span.start..span.end];
+        let (path, suffix) = links::split_url(url);
+        if path.is_empty() || !links::is_relative_url(path) {
+            continue;
+        }
+        let Some(target) = links::resolve_relative(doc, &links::percent_decode(path)) else {
+            warnings.push(format!("{} is outside the Markdown folder and was not copied", path));
+            continue;
+        };
+
+        if let Some(ext) = markdown_extension(path) {
+            if converted.iter().any(|(source, _)| source == &target) {
+                let new_url = format!("{}.html{}", &path[..path.len() - ext.len()], suffix);
+                replacements.push((span.start, span.end, new_url));
+            } else {
+                warnings.push(format!("Link to {} has no Markdown file to convert", path));
+            }
+        } else if !is_safe_relative(&target) {
+            warnings.push(format!("{} is not a safe file path and was not copied", path));
+        } else if !source.join(&target).is_file() {
+            warnings.push(format!("{} is referenced but missing", path));
+        } else if !referenced.contains(&target) {
+            referenced.push(target);
+        }
+    }
+    links::replace_spans(body, replacements)
+}
+
+/// Write a file that must not exist yet, creating its folders.
+fn write_new_file(target: &Path, bytes: &[u8]) -> io::Result<()> {
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut out = fs::OpenOptions::new().write(true).create_new(true).open(target)?;
+    io::Write::write_all(&mut out, bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_markdown_to_html() {
+        let source = r##"---
+title: "Notes: Spring"
+keywords: [birds, field notes]
+---
+
+Setext Title
+============
+
+A *b* **c** ***d*** ~~e~~ `f | g` snake_case 2*3*4 \*lit\* &copy; & <br> <span class="x">h</span>\
+next [ref][Site] ![alt *text*](img.png "T") <https://a.org> https://b.org/x). [^n] [^missing]
+
+[site]: https://example.com/ "Home"
+
+1. one
+1. two
+   - [x] sub
+
+3) loose
+
+   more
+
+| L | C | R |
+|:--|:-:|--:|
+| `a\|b` | 2 |
+
+<div class="box">
+*raw*
+</div>
+
+```js title
+let x = 1 < 2;
+```
+
+[^n]: A note with [^n] again.
+[^unused]: Never cited.
+
+```
+open"##;
+        let result = markdown_to_html(source);
+        assert_eq!(result.head, "<title>Notes: Spring</title>\n<meta name=\"keywords\" content=\"birds, field notes\">\n");
+        let body = &result.body;
+
+        assert!(body.starts_with("<h1>Setext Title</h1>\n"));
+        assert!(body.contains(
+            "<p>A <em>b</em> <strong>c</strong> <em><strong>d</strong></em> <del>e</del> <code>f | g</code> \
+             snake_case 2<em>3</em>4 *lit* &copy; &amp; <br> <span class=\"x\">h</span><br>\n"
+        ));
+        assert!(body.contains(
+            "next <a href=\"https://example.com/\" title=\"Home\">ref</a> \
+             <img src=\"img.png\" alt=\"alt text\" title=\"T\"> <a href=\"https://a.org\">https://a.org</a> \
+             <a href=\"https://b.org/x\">https://b.org/x</a>). \
+             <sup class=\"footnote-ref\"><a href=\"#fn-n\" id=\"fnref-n\" role=\"doc-noteref\">1</a></sup> [^missing]</p>\n"
+        ));
+        assert!(body.contains(
+            "<ol>\n<li>one</li>\n<li>two\n<ul>\n<li class=\"task-list-item\"><input type=\"checkbox\" disabled checked> sub</li>\n</ul>\n</li>\n</ol>\n"
+        ));
+        assert!(body.contains("<ol start=\"3\">\n<li>\n<p>loose</p>\n<p>more</p>\n</li>\n</ol>\n"));
+        assert!(body.contains("<th style=\"text-align: left\">L</th>\n<th style=\"text-align: center\">C</th>"));
+        assert!(body.contains("<td style=\"text-align: left\"><code>a|b</code></td>\n<td style=\"text-align: center\">2</td>\n<td style=\"text-align: right\"></td>"));
+        assert!(body.contains("<div class=\"box\">\n*raw*\n</div>\n"));
+        assert!(body.contains("<pre><code class=\"language-js\">let x = 1 &lt; 2;\n</code></pre>\n"));
+        assert!(body.contains(
+            "<li id=\"fn-n\">\n<p>A note with <sup class=\"footnote-ref\"><a href=\"#fn-n\" role=\"doc-noteref\">1</a></sup> again. \
+             <a href=\"#fnref-n\" class=\"footnote-backref\" role=\"doc-backlink\">&#8617;</a></p>\n</li>"
+        ));
+        assert!(body.ends_with("<pre><code>open\n</code></pre>\n<section class=\"footnotes\" role=\"doc-endnotes\">\n<ol>\n<li id=\"fn-n\">\n<p>A note with <sup class=\"footnote-ref\"><a href=\"#fn-n\" role=\"doc-noteref\">1</a></sup> again. <a href=\"#fnref-n\" class=\"footnote-backref\" role=\"doc-backlink\">&#8617;</a></p>\n</li>\n</ol>\n</section>\n"));
+        assert_eq!(result.warnings, vec![
+            "Code block opened with ``` is not closed",
+            "Footnote [^missing] is referenced but not defined",
+            "Footnote [^unused] is never referenced and was left out",
+        ]);
+        assert!(result.document().starts_with("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>"));
+    }
+
+    /// Body HTML of a Markdown snippet.
+    fn body(markdown: &str) -> String {
+        markdown_to_html(markdown).body
+    }
+
+    #[test]
+    fn test_nested_lists() {
+        assert_eq!(
+            body("- a\n  - b\n    - c\n- d"),
+            "<ul>\n<li>a\n<ul>\n<li>b\n<ul>\n<li>c</li>\n</ul>\n</li>\n</ul>\n</li>\n<li>d</li>\n</ul>\n",
+        );
+        // A blank line anywhere in the list makes every item a paragraph
+        assert_eq!(
+            body("1. a\n\n   - b\n   - c\n2. d"),
+            "<ol>\n<li>\n<p>a</p>\n<ul>\n<li>b</li>\n<li>c</li>\n</ul>\n</li>\n<li>\n<p>d</p>\n</li>\n</ol>\n",
+        );
+        assert_eq!(
+            body("- a\n- b\n\n- c"),
+            "<ul>\n<li>\n<p>a</p>\n</li>\n<li>\n<p>b</p>\n</li>\n<li>\n<p>c</p>\n</li>\n</ul>\n",
+        );
+        // Changing the bullet character starts a new list
+        assert_eq!(body("* a\n+ b"), "<ul>\n<li>a</li>\n</ul>\n<ul>\n<li>b</li>\n</ul>\n");
+        // A marker followed by text is a list item even if it looks like emphasis
+        assert_eq!(body("* not a list*"), "<ul>\n<li>not a list*</li>\n</ul>\n");
+    }
+
+    #[test]
+    fn test_lazy_continuation() {
+        assert_eq!(body("para\nlazy"), "<p>para\nlazy</p>\n");
+        assert_eq!(
+            body("> quote line\ncontinued lazily"),
+            "<blockquote>\n<p>quote line\ncontinued lazily</p>\n</blockquote>\n",
+        );
+        assert_eq!(body("- item\nlazy continuation"), "<ul>\n<li>item\nlazy continuation</li>\n</ul>\n");
+        assert_eq!(
+            body("> - a\nb"),
+            "<blockquote>\n<ul>\n<li>a\nb</li>\n</ul>\n</blockquote>\n",
+        );
+    }
+
+    #[test]
+    fn test_emphasis_rules() {
+        assert_eq!(
+            body("*a* _b_ __c__ **d**"),
+            "<p><em>a</em> <em>b</em> <strong>c</strong> <strong>d</strong></p>\n",
+        );
+        // `*` works inside words, `_` does not
+        assert_eq!(
+            body("snake_case_word and 2*3*4 and foo*bar*"),
+            "<p>snake_case_word and 2<em>3</em>4 and foo<em>bar</em></p>\n",
+        );
+        assert_eq!(body("_foo_bar"), "<p>_foo_bar</p>\n");
+        // Nesting in either order, and triple delimiters
+        assert_eq!(body("*foo**bar**baz*"), "<p><em>foo<strong>bar</strong>baz</em></p>\n");
+        assert_eq!(body("**foo*bar*baz**"), "<p><strong>foo<em>bar</em>baz</strong></p>\n");
+        assert_eq!(body("*a **b** c*"), "<p><em>a <strong>b</strong> c</em></p>\n");
+        assert_eq!(
+            body("***both*** and ___both___"),
+            "<p><em><strong>both</strong></em> and <em><strong>both</strong></em></p>\n",
+        );
+        // Delimiters next to spaces cannot open or close
+        assert_eq!(body("** not strong **"), "<p>** not strong **</p>\n");
+        assert_eq!(body("\\*x\\* \\_y\\_ \\# \\\\"), "<p>*x* _y_ # \\</p>\n");
+    }
+
+    #[test]
+    fn test_html_blocks() {
+        // A block element runs to the next blank line
+        assert_eq!(body("<div>\n*not md*\n\n*md again*"), "<div>\n*not md*\n<p><em>md again</em></p>\n");
+        // Comments and <pre> run to their closing marker, across blank lines
+        assert_eq!(
+            body("<!-- comment\n\n*still comment* -->\nafter"),
+            "<!-- comment\n\n*still comment* -->\n<p>after</p>\n",
+        );
+        assert_eq!(body("<pre>\n  *x*\n\n</pre>"), "<pre>\n  *x*\n\n</pre>\n");
+        // Inline tags with text after them start a paragraph
+        assert_eq!(body("<span>inline</span> *em*"), "<p><span>inline</span> <em>em</em></p>\n");
+        assert_eq!(body("text <b>bold</b> *em*"), "<p>text <b>bold</b> <em>em</em></p>\n");
+    }
+
+    #[test]
+    fn test_gfm_tables() {
+        // Extra cells are dropped and missing cells are empty
+        assert_eq!(
+            body("| a | b |\n|---|---|\n| 1 | 2 | 3 |\n| 4 |"),
+            "<table>\n<thead>\n<tr>\n<th>a</th>\n<th>b</th>\n</tr>\n</thead>\n<tbody>\n\
+             <tr>\n<td>1</td>\n<td>2</td>\n</tr>\n<tr>\n<td>4</td>\n<td></td>\n</tr>\n</tbody>\n</table>\n",
+        );
+        assert_eq!(body("| a |\n|---|"), "<table>\n<thead>\n<tr>\n<th>a</th>\n</tr>\n</thead>\n</table>\n");
+        // Outer pipes are optional
+        assert!(body("a | b\n--|--\nc | d").contains("<td>c</td>\n<td>d</td>"));
+    }
+
+    #[test]
+    fn test_gfm_task_lists_and_strikethrough() {
+        assert_eq!(
+            body("- [ ] todo\n- [x] done\n- [X] Done\n- [ ]not task"),
+            "<ul>\n<li class=\"task-list-item\"><input type=\"checkbox\" disabled> todo</li>\n\
+             <li class=\"task-list-item\"><input type=\"checkbox\" disabled checked> done</li>\n\
+             <li class=\"task-list-item\"><input type=\"checkbox\" disabled checked> Done</li>\n\
+             <li>[ ]not task</li>\n</ul>\n",
+        );
+        assert_eq!(
+            body("1. [x] ordered task"),
+            "<ol>\n<li class=\"task-list-item\"><input type=\"checkbox\" disabled checked> ordered task</li>\n</ol>\n",
+        );
+        assert_eq!(body("~~del~~ and ~single~"), "<p><del>del</del> and <del>single</del></p>\n");
+    }
+
+    #[test]
+    fn test_gfm_autolinks_and_footnotes() {
+        // Trailing punctuation and closing parentheses stay outside the link
+        assert_eq!(
+            body("www.example.com and https://x.org/a_b_ and (https://y.org/p)"),
+            "<p><a href=\"http://www.example.com\">www.example.com</a> and \
+             <a href=\"https://x.org/a_b\">https://x.org/a_b</a>_ and \
+             (<a href=\"https://y.org/p\">https://y.org/p</a>)</p>\n",
+        );
+
+        // Footnotes are numbered in citation order; indented lines continue them
+        let result = markdown_to_html("Text[^1] and[^note].\n\n[^1]: One.\n[^note]: Two\n    continued.");
+        assert!(result.warnings.is_empty());
+        assert_eq!(
+            result.body,
+            "<p>Text<sup class=\"footnote-ref\"><a href=\"#fn-1\" id=\"fnref-1\" role=\"doc-noteref\">1</a></sup> \
+             and<sup class=\"footnote-ref\"><a href=\"#fn-note\" id=\"fnref-note\" role=\"doc-noteref\">2</a></sup>.</p>\n\
+             <section class=\"footnotes\" role=\"doc-endnotes\">\n<ol>\n\
+             <li id=\"fn-1\">\n<p>One. <a href=\"#fnref-1\" class=\"footnote-backref\" role=\"doc-backlink\">&#8617;</a></p>\n</li>\n\
+             <li id=\"fn-note\">\n<p>Two\ncontinued. <a href=\"#fnref-note\" class=\"footnote-backref\" role=\"doc-backlink\">&#8617;</a></p>\n</li>\n\
+             </ol>\n</section>\n",
+        );
+    }
+
+    #[test]
+    fn test_code_headings_and_breaks() {
+        assert_eq!(body("`` a ` b `` and `x`"), "<p><code>a ` b</code> and <code>x</code></p>\n");
+        assert_eq!(body("    indented code\n    line 2"), "<pre><code>indented code\nline 2\n</code></pre>\n");
+        assert_eq!(body("~~~python\nx = 1\n~~~"), "<pre><code class=\"language-python\">x = 1\n</code></pre>\n");
+        assert_eq!(body("Line one  \nLine two\\\nLine three"), "<p>Line one<br>\nLine two<br>\nLine three</p>\n");
+        assert_eq!(
+            body("# H1 #\n## H2\n###### H6\n####### no"),
+            "<h1>H1</h1>\n<h2>H2</h2>\n<h6>H6</h6>\n<p>####### no</p>\n",
+        );
+        assert_eq!(body("Title\n-----"), "<h2>Title</h2>\n");
+        assert_eq!(body("---\n***\n- - -"), "<hr>\n<hr>\n<hr>\n");
+    }
+
+    #[test]
+    fn test_import_markdown_folder() {
+        let dir = std::env::temp_dir().join(format!("rhe_markdown_folder_{}", std::process::id()));
+        let source = dir.join("notes");
+        fs::create_dir_all(source.join("part/img")).unwrap();
+        fs::create_dir_all(source.join(".git")).unwrap();
+        fs::write(source.join("SUMMARY.md"), "- [Two](part/two.md)\n- [One](one.md)\n").unwrap();
+        fs::write(source.join("one.md"), "# One\n\nSee [two](part/two.md#end) and [gone](gone.md).\n").unwrap();
+        fs::write(source.join("part/two.md"), "# Two\n\n![Map](img/map.png) ![Lost](img/lost.png)\n\n[Back](../one.md)\n").unwrap();
+        fs::write(source.join("part/img/map.png"), b"png").unwrap();
+        fs::write(source.join("three.markdown"), "Three\n").unwrap();
+        fs::write(source.join(".git/x.md"), "hidden\n").unwrap();
+
+        let project = dir.join("book");
+        let imported = import_markdown_folder(&source, &project).unwrap();
+        assert_eq!(imported.chapters, vec!["part/two.html", "one.html", "three.html"]);
+        assert_eq!(imported.resources, vec!["part/img/map.png"]);
+        assert_eq!(imported.files, vec![
+            FileWarnings {
+                path: "part/two.md".to_string(),
+                warnings: vec!["img/lost.png is referenced but missing".to_string()],
+            },
+            FileWarnings {
+                path: "one.md".to_string(),
+                warnings: vec!["Link to gone.md has no Markdown file to convert".to_string()],
+            },
+        ]);
+
+        let one = fs::read_to_string(project.join("one.html")).unwrap();
+        assert!(one.contains("<a href=\"part/two.html#end\">two</a> and <a href=\"gone.md\">gone</a>"));
+        let two = fs::read_to_string(project.join("part/two.html")).unwrap();
+        assert!(two.contains("<a href=\"../one.html\">Back</a>"));
+        assert_eq!(fs::read(project.join("part/img/map.png")).unwrap(), b"png");
+        let manifest = Manifest::load(&project).unwrap();
+        assert_eq!(manifest.chapters, imported.chapters);
+
+        // The project folder must be new or empty
+        let again = import_markdown_folder(&source, &project).unwrap_err();
+        assert!(matches!(again.kind, ErrorKind::AlreadyExists));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,60 @@
+use std::path::Path;
+
+/// MIME type for a file, from its extension, with a charset for text.
+pub fn mime_type(path: &Path) -> &'static str {
+    let ext = path.extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("")
+        .to_lowercase();
+
+    match ext.as_str() {
+        "html" | "htm" => "text/html; charset=utf-8",
+        "xhtml" => "application/xhtml+xml",
+        "css" => "text/css; charset=utf-8",
+        "js" => "text/javascript; charset=utf-8",
+        "json" => "application/json",
+        "txt" | "md" => "text/plain; charset=utf-8",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "svg" => "image/svg+xml",
+        "webp" => "image/webp",
+        "avif" => "image/avif",
+        "ico" => "image/x-icon",
+        "mp3" => "audio/mpeg",
+        "m4a" => "audio/mp4",
+        "ogg" | "oga" => "audio/ogg",
+        "wav" => "audio/wav",
+        "mp4" | "m4v" => "video/mp4",
+        "webm" => "video/webm",
+        "ogv" => "video/ogg",
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+        "ttf" => "font/ttf",
+        "otf" => "font/otf",
+        "pdf" => "application/pdf",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Bare media type for a file, without parameters, as EPUB manifests use.
+pub fn media_type(path: &Path) -> &'static str {
+    let mime = mime_type(path);
+    match mime.find(';') {
+        Some(end) => &mime[..end],
+        None => mime,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_media_types() {
+        assert_eq!(mime_type(Path::new("a/book.CSS")), "text/css; charset=utf-8");
+        assert_eq!(media_type(Path::new("a/book.css")), "text/css");
+        assert_eq!(media_type(Path::new("font.woff2")), "font/woff2");
+        assert_eq!(media_type(Path::new("README")), "application/octet-stream");
+    }
+}
@@ -0,0 +1,176 @@
+use scraper::{ElementRef, Html, Node};
+
+use crate::diff;
+use crate::html_parser;
+
+/// A region where both sides changed the same blocks differently.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ConflictRegion {
+    /// Index of the conflict, matching `data-conflict` in the merged body
+    pub index: usize,
+    pub base: String,
+    pub ours: String,
+    pub theirs: String,
+}
+
+/// Result of a three-way merge of chapter bodies.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct MergeResult {
+    /// Merged body HTML, with conflict regions wrapped in marker elements
+    pub merged_body: String,
+    pub conflicts: Vec<ConflictRegion>,
+}
+
+/// Three-way merge of chapter bodies at the block level.
+///
+/// `base` is the body as loaded, `ours` is the editor body, and `theirs`
+/// is the body currently on disk. Blocks changed on only one side are
+/// taken from that side. Blocks changed differently on both sides become
+/// conflict regions, wrapped in `<div class="merge-conflict">` with
+/// `merge-ours` and `merge-theirs` children so the user can pick.
+pub fn merge_bodies(base: &str, ours: &str, theirs: &str) -> MergeResult {
+    let base_blocks = split_blocks(base);
+    let our_blocks = split_blocks(ours);
+    let their_blocks = split_blocks(theirs);
+
+    let mut merged: Vec<String> = Vec::new();
+    let mut conflicts: Vec<ConflictRegion> = Vec::new();
+
+    // Map each base block to its match on either side
+    let ours_map = match_map(&base_blocks, &our_blocks);
+    let theirs_map = match_map(&base_blocks, &their_blocks);
+
+    let (mut i, mut j, mut k) = (0, 0, 0);
+    loop {
+        // Next base block kept unchanged on both sides
+        let stable = (i..base_blocks.len())
+            .find(|&b| ours_map[b].is_some() && theirs_map[b].is_some());
+        let (base_end, ours_end, theirs_end) = match stable {
+            Some(b) => (b, ours_map[b].unwrap(), theirs_map[b].unwrap()),
+            None => (base_blocks.len(), our_blocks.len(), their_blocks.len()),
+        };
+
+        // Resolve the unstable chunk before the stable block
+        let base_chunk = &base_blocks[i..base_end];
+        let our_chunk = &our_blocks[j..ours_end];
+        let their_chunk = &their_blocks[k..theirs_end];
+        if our_chunk == base_chunk {
+            merged.extend_from_slice(their_chunk);
+        } else if their_chunk == base_chunk || our_chunk == their_chunk {
+            merged.extend_from_slice(our_chunk);
+        } else {
+            let region = ConflictRegion {
+                index: conflicts.len(),
+                base: base_chunk.concat(),
+                ours: our_chunk.concat(),
+                theirs: their_chunk.concat(),
+            };
+            merged.push(conflict_markup(&region));
+            conflicts.push(region);
+        }
+
+        match stable {
+            Some(b) => {
+                merged.push(base_blocks[b].clone());
+                i = base_end + 1;
+                j = ours_end + 1;
+                k = theirs_end + 1;
+            }
+            None => break,
+        }
+    }
+
+    MergeResult {
+        merged_body: merged.join("\n"),
+        conflicts,
+    }
+}
+
+/// Split a body fragment into its top-level blocks, serialized as HTML.
+/// Whitespace-only text between blocks is dropped.
+fn split_blocks(body: &str) -> Vec<String> {
+    let fragment = Html::parse_fragment(body);
+    let mut blocks = Vec::new();
+
+    for child in fragment.root_element().children() {
+        match child.value() {
+            Node::Element(_) => {
+                if let Some(element) = ElementRef::wrap(child) {
+                    blocks.push(element.html());
+                }
+            }
+            Node::Text(text) => {
+                let trimmed = text.trim();
+                if !trimmed.is_empty() {
+                    blocks.push(html_parser::escape_text(trimmed));
+                }
+            }
+            Node::Comment(comment) => {
+                blocks.push(format!("<!--{}-->", &**comment));
+            }
+            _ => {}
+        }
+    }
+    blocks
+}
+
+/// For each block in `base`, the index of the matching block in `other`.
+fn match_map(base: &[String], other: &[String]) -> Vec<Option<usize>> {
+    let mut map = vec![None; base.len()];
+    for (b, o) in diff::lcs_pairs(base, other) {
+        map[b] = Some(o);
+    }
+    map
+}
+
+/// Wrap both sides of a conflict in marker elements.
+fn conflict_markup(region: &ConflictRegion) -> String {
+    format!(
+        "<div class=\"merge-conflict\" data-conflict=\"{}\">\n\
+         <div class=\"merge-ours\">{}</div>\n\
+         <div class=\"merge-theirs\">{}</div>\n\
+         </div>",
+        region.index, region.ours, region.theirs,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BASE: &str = "<h1>Title</h1>\n<p>One</p>\n<p>Two</p>\n<p>Three</p>";
+
+    #[test]
+    fn test_merge_separate_edits() {
+        let ours = "<h1>Title</h1>\n<p>One edited</p>\n<p>Two</p>\n<p>Three</p>";
+        let theirs = "<h1>Title</h1>\n<p>One</p>\n<p>Two</p>\n<p>Three</p>\n<p>Four</p>";
+
+        let result = merge_bodies(BASE, ours, theirs);
+        assert!(result.conflicts.is_empty());
+        assert!(result.merged_body.contains("<p>One edited</p>"));
+        assert!(result.merged_body.contains("<p>Four</p>"));
+        assert!(!result.merged_body.contains("<p>One</p>"));
+    }
+
+    #[test]
+    fn test_merge_conflicting_edits() {
+        let ours = "<h1>Title</h1>\n<p>Two ours</p>\n<p>Three</p>";
+        let theirs = "<h1>Title</h1>\n<p>One</p>\n<p>Two theirs</p>\n<p>Three</p>";
+
+        let result = merge_bodies(BASE, ours, theirs);
+        assert_eq!(result.conflicts.len(), 1);
+        let conflict = &result.conflicts[0];
+        assert!(conflict.ours.contains("Two ours"));
+        assert!(conflict.theirs.contains("Two theirs"));
+        assert!(result.merged_body.contains("data-conflict=\"0\""));
+        assert!(result.merged_body.ends_with("<p>Three</p>"));
+    }
+
+    #[test]
+    fn test_merge_identical_edits() {
+        let edited = "<h1>New title</h1>\n<p>One</p>\n<p>Two</p>\n<p>Three</p>";
+        let result = merge_bodies(BASE, edited, edited);
+        assert!(result.conflicts.is_empty());
+        assert!(result.merged_body.starts_with("<h1>New title</h1>"));
+    }
+}
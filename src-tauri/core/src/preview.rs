@@ -0,0 +1,512 @@
+use std::fs;
+use std::io::{self, Read, Write};
+use std::net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, UNIX_EPOCH};
+
+use crate::error::{EditorError, EditorResult, ErrorKind};
+use crate::export;
+use crate::html_parser;
+use crate::links;
+use crate::manifest;
+use crate::media_type;
+use crate::recovery;
+
+/// Path of the server-sent events stream that previewed pages listen on.
+pub const EVENTS_PATH: &str = "/__preview/events";
+
+/// How often the project folder is scanned for changed files.
+pub const POLL_INTERVAL_MS: u64 = 500;
+
+/// How long an idle event stream waits before sending a keep-alive.
+const KEEP_ALIVE: Duration = Duration::from_secs(15);
+
+/// Largest request head accepted.
+const MAX_REQUEST: usize = 16 * 1024;
+
+/// Unsaved editor content shown in place of a chapter's saved body.
+struct Overlay {
+    chapter: String,
+    body: String,
+}
+
+/// State shared by the server threads.
+struct Shared {
+    root: PathBuf,
+    /// `root` with symlinks resolved, which served files must lie under
+    canonical_root: PathBuf,
+    /// Port the server listens on, expected in the `Host` header
+    port: u16,
+    overlay: Mutex<Option<Overlay>>,
+    /// Bumped on every change; event streams wait on it
+    generation: Mutex<u64>,
+    changed: Condvar,
+    stopped: AtomicBool,
+}
+
+impl Shared {
+    /// Record a change and wake the event streams.
+    fn bump(&self) {
+        if let Ok(mut generation) = self.generation.lock() {
+            *generation += 1;
+            self.changed.notify_all();
+        }
+    }
+
+    fn generation(&self) -> u64 {
+        self.generation.lock().map(|g| *g).unwrap_or(0)
+    }
+}
+
+/// A local HTTP server previewing a project in any browser.
+///
+/// It serves the project folder on 127.0.0.1, only to requests addressed
+/// to `127.0.0.1:<port>` or `localhost:<port>` (so a page on another site
+/// cannot reach it through DNS rebinding), with chapters wrapped as
+/// complete documents and the unsaved body of the open chapter in place
+/// of its saved one. Each HTML page gets a small script that listens on
+/// a server-sent events stream and reloads the page when a project file
+/// or the unsaved content changes.
+pub struct PreviewServer {
+    shared: Arc<Shared>,
+    addr: SocketAddr,
+}
+
+impl PreviewServer {
+    /// Serve `root` on a local port; 0 picks a free one.
+    pub fn start(root: &Path, port: u16) -> io::Result<PreviewServer> {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))?;
+        let addr = listener.local_addr()?;
+        let shared = Arc::new(Shared {
+            root: root.to_path_buf(),
+            canonical_root: fs::canonicalize(root)?,
+            port: addr.port(),
+            overlay: Mutex::new(None),
+            generation: Mutex::new(0),
+            changed: Condvar::new(),
+            stopped: AtomicBool::new(false),
+        });
+
+        let accept_shared = Arc::clone(&shared);
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                if accept_shared.stopped.load(Ordering::SeqCst) {
+                    break;
+                }
+                let Ok(stream) = stream else {
+                    continue;
+                };
+                let shared = Arc::clone(&accept_shared);
+                std::thread::spawn(move || {
+                    // A client closing early is not an error worth reporting
+                    let _ = handle_connection(stream, &shared);
+                });
+            }
+        });
+
+        let watch_shared = Arc::clone(&shared);
+        std::thread::spawn(move || {
+            let mut last = fingerprint(&watch_shared.root);
+            while !watch_shared.stopped.load(Ordering::SeqCst) {
+                std::thread::sleep(Duration::from_millis(POLL_INTERVAL_MS));
+                let current = fingerprint(&watch_shared.root);
+                if current != last {
+                    last = current;
+                    watch_shared.bump();
+                }
+            }
+        });
+
+        Ok(PreviewServer { shared, addr })
+    }
+
+    /// Base URL of the server, ending in `/`.
+    pub fn url(&self) -> String {
+        format!("http://{}/", self.addr)
+    }
+
+    /// The project folder being served.
+    pub fn root(&self) -> &Path {
+        &self.shared.root
+    }
+
+    /// Show `body` as the content of a chapter instead of its saved body,
+    /// reloading pages if it changed. `chapter` is project-relative and
+    /// `body` uses relative URLs. Replaces any previous overlay.
+    pub fn set_overlay(&self, chapter: &str, body: &str) {
+        let Ok(mut overlay) = self.shared.overlay.lock() else {
+            return;
+        };
+        let unchanged = overlay.as_ref().is_some_and(|o| o.chapter == chapter && o.body == body);
+        if !unchanged {
+            *overlay = Some(Overlay {
+                chapter: chapter.to_string(),
+                body: body.to_string(),
+            });
+            drop(overlay);
+            self.shared.bump();
+        }
+    }
+
+    /// Serve saved content only.
+    pub fn clear_overlay(&self) {
+        let had_overlay = self.shared.overlay.lock().map(|mut o| o.take().is_some()).unwrap_or(false);
+        if had_overlay {
+            self.shared.bump();
+        }
+    }
+
+    /// Stop serving. Open event streams end and the port is released.
+    pub fn stop(&self) {
+        if self.shared.stopped.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        self.shared.bump();
+        // Wake the accept loop so it sees the stop flag
+        let _ = TcpStream::connect(self.addr);
+    }
+}
+
+impl Drop for PreviewServer {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// The preview server of the app, started on demand.
+pub struct PreviewManager {
+    server: Mutex<Option<PreviewServer>>,
+}
+
+impl Default for PreviewManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PreviewManager {
+    /// Create a manager with no server running.
+    pub fn new() -> Self {
+        PreviewManager { server: Mutex::new(None) }
+    }
+
+    /// Start serving a project, or keep the running server if it already
+    /// serves this folder. Returns the server's base URL.
+    pub fn start(&self, root: &Path) -> EditorResult<String> {
+        let mut server = self.lock()?;
+        if let Some(running) = server.as_ref() {
+            if running.root() == root {
+                return Ok(running.url());
+            }
+        }
+        // Dropping the old server stops it
+        *server = None;
+        let started = PreviewServer::start(root, 0)
+            .map_err(|e| EditorError::io("Failed to start the preview server", e))?;
+        let url = started.url();
+        *server = Some(started);
+        Ok(url)
+    }
+
+    /// Show unsaved content of a chapter, if a server is running.
+    pub fn update(&self, chapter: &str, body: &str) -> EditorResult<()> {
+        if let Some(server) = self.lock()?.as_ref() {
+            server.set_overlay(chapter, body);
+        }
+        Ok(())
+    }
+
+    /// Stop the server, if one is running.
+    pub fn stop(&self) -> EditorResult<()> {
+        *self.lock()? = None;
+        Ok(())
+    }
+
+    fn lock(&self) -> EditorResult<std::sync::MutexGuard<'_, Option<PreviewServer>>> {
+        self.server.lock()
+            .map_err(|e| EditorError::new(ErrorKind::Io, format!("Lock error: {}", e)))
+    }
+}
+
+/// Answer one HTTP request.
+fn handle_connection(mut stream: TcpStream, shared: &Shared) -> io::Result<()> {
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+    let mut request = Vec::new();
+    let mut buf = [0u8; 2048];
+    while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+        let n = stream.read(&mut buf)?;
+        if n == 0 || request.len() + n > MAX_REQUEST {
+            return Ok(());
+        }
+        request.extend_from_slice(&buf[..n]);
+    }
+
+    let request = String::from_utf8_lossy(&request);
+    let mut lines = request.lines();
+    let mut parts = lines.next().unwrap_or("").split_whitespace();
+    let method = parts.next().unwrap_or("");
+    let target = parts.next().unwrap_or("/");
+
+    let host = lines.take_while(|line| !line.is_empty())
+        .find_map(|line| {
+            let (name, value) = line.split_once(':')?;
+            name.trim().eq_ignore_ascii_case("host").then(|| value.trim())
+        });
+    if !host.is_some_and(|host| is_local_host(host, shared.port)) {
+        return respond(&mut stream, "403 Forbidden", "text/plain", b"Forbidden", false);
+    }
+    if method != "GET" && method != "HEAD" {
+        return respond(&mut stream, "405 Method Not Allowed", "text/plain", b"Method not allowed", false);
+    }
+    let head_only = method == "HEAD";
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+
+    if path == EVENTS_PATH {
+        let since = query.split('&')
+            .find_map(|pair| pair.strip_prefix("since="))
+            .and_then(|v| v.parse().ok())
+            .unwrap_or_else(|| shared.generation());
+        return event_stream(&mut stream, shared, since);
+    }
+
+    match page(shared, &links::percent_decode(path)) {
+        Some((content_type, body)) => respond(&mut stream, "200 OK", content_type, &body, head_only),
+        None => respond(&mut stream, "404 Not Found", "text/plain", b"Not found", head_only),
+    }
+}
+
+/// True if a `Host` header names this server by its loopback address.
+fn is_local_host(host: &str, port: u16) -> bool {
+    let host = host.to_ascii_lowercase();
+    host == format!("127.0.0.1:{}", port) || host == format!("localhost:{}", port)
+}
+
+/// Content type and body for a request path, or None if not found.
+fn page(shared: &Shared, path: &str) -> Option<(&'static str, Vec<u8>)> {
+    let generation = shared.generation();
+    let relative = path.trim_start_matches('/');
+    if relative.is_empty() {
+        return Some(("text/html; charset=utf-8", index_page(&shared.root, generation).into_bytes()));
+    }
+
+    // Only plain paths inside the project; hidden files stay private
+    let relative = links::resolve_relative("", relative)?;
+    if relative.split('/').any(|part| part.starts_with('.')) {
+        return None;
+    }
+    let file = shared.root.join(&relative);
+    if !file.is_file() {
+        return None;
+    }
+    // Symlinks may not lead out of the project
+    if !fs::canonicalize(&file).ok()?.starts_with(&shared.canonical_root) {
+        return None;
+    }
+
+    let content_type = media_type::mime_type(&file);
+    let ext = file.extension().and_then(|e| e.to_str()).unwrap_or("").to_lowercase();
+    if !matches!(ext.as_str(), "html" | "htm" | "xhtml") {
+        return fs::read(&file).ok().map(|bytes| (content_type, bytes));
+    }
+
+    let mut raw = fs::read_to_string(&file).ok()?;
+    if let Ok(overlay) = shared.overlay.lock() {
+        if let Some(overlay) = overlay.as_ref().filter(|o| o.chapter == relative) {
+            let split = html_parser::split_html(&raw);
+            raw = html_parser::reassemble_html(&split.doctype, &split.head_content, &overlay.body, split.is_fragment);
+        }
+    }
+    let html = with_reload_script(&export::standalone_document(&shared.root, &relative, &raw), generation);
+    Some(("text/html; charset=utf-8", html.into_bytes()))
+}
+
+/// A page linking every chapter in manifest order.
+fn index_page(root: &Path, generation: u64) -> String {
+    let mut list = String::from("<ul>\n");
+    for chapter in manifest::list_chapters(root).unwrap_or_default() {
+        let relative = chapter.relative_path.replace('\\', "/");
+        list += &format!(
+            "<li><a href=\"/{}\">{}</a></li>\n",
+            html_parser::escape_attribute(&links::encode_path(&relative)),
+            html_parser::escape_text(&relative),
+        );
+    }
+    list += "</ul>\n";
+    let title = root.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+    let head = format!("<meta charset=\"utf-8\">\n<title>{}</title>\n", html_parser::escape_text(&title));
+    let body = format!("<h1>{}</h1>\n{}", html_parser::escape_text(&title), list);
+    with_reload_script(&html_parser::reassemble_html("<!DOCTYPE html>", &head, &body, false), generation)
+}
+
+/// Add the script that reloads the page on change, before `</body>`.
+/// `generation` is the state the page was built from, so a change made
+/// before the script connects still reloads it.
+fn with_reload_script(html: &str, generation: u64) -> String {
+    let script = format!(
+        "<script>new EventSource(\"{}?since={}\").addEventListener(\"reload\", () => location.reload());</script>\n",
+        EVENTS_PATH, generation,
+    );
+    match html.to_ascii_lowercase().rfind("</body>") {
+        Some(end) => format!("{}{}{}", &html[..end], script, &html[end..]),
+        None => format!("{}{}", html, script),
+    }
+}
+
+/// Keep a server-sent events stream open, sending `reload` whenever the
+/// generation moves past `since`.
+fn event_stream(stream: &mut TcpStream, shared: &Shared, mut since: u64) -> io::Result<()> {
+    stream.write_all(
+        b"HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-store\r\n\
+          Connection: keep-alive\r\n\r\nretry: 1000\n\n",
+    )?;
+    loop {
+        let current = {
+            let generation = shared.generation.lock()
+                .map_err(|_| io::Error::other("preview state poisoned"))?;
+            let (generation, _) = shared.changed
+                .wait_timeout_while(generation, KEEP_ALIVE, |g| {
+                    *g == since && !shared.stopped.load(Ordering::SeqCst)
+                })
+                .map_err(|_| io::Error::other("preview state poisoned"))?;
+            *generation
+        };
+        if shared.stopped.load(Ordering::SeqCst) {
+            return Ok(());
+        }
+        if current != since {
+            since = current;
+            stream.write_all(format!("event: reload\ndata: {}\n\n", current).as_bytes())?;
+        } else {
+            stream.write_all(b": keep-alive\n\n")?;
+        }
+        stream.flush()?;
+    }
+}
+
+fn respond(stream: &mut TcpStream, status: &str, content_type: &str, body: &[u8], head_only: bool) -> io::Result<()> {
+    let head = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n",
+        status,
+        content_type,
+        body.len(),
+    );
+    stream.write_all(head.as_bytes())?;
+    if !head_only {
+        stream.write_all(body)?;
+    }
+    stream.flush()
+}
+
+/// A hash of the names, sizes, and times of the project's visible files,
+/// which changes when any of them is added, removed, or saved.
+fn fingerprint(root: &Path) -> u64 {
+    let mut listing = String::new();
+    collect_listing(root, &mut listing);
+    recovery::fnv1a(listing.as_bytes())
+}
+
+fn collect_listing(dir: &Path, listing: &mut String) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    let mut entries: Vec<_> = entries.flatten().collect();
+    entries.sort_by_key(|entry| entry.file_name());
+    for entry in entries {
+        let name = entry.file_name().to_string_lossy().to_string();
+        // Temp files, locks, and backups change without visible effect
+        if name.starts_with('.') || name.ends_with(".bak") {
+            continue;
+        }
+        let Ok(meta) = entry.metadata() else {
+            continue;
+        };
+        if meta.is_dir() {
+            collect_listing(&entry.path(), listing);
+            continue;
+        }
+        let modified = meta.modified().ok()
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map(|d| d.as_nanos())
+            .unwrap_or(0);
+        listing.push_str(&format!("{}|{}|{}\n", entry.path().display(), meta.len(), modified));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::BufRead;
+
+    /// Send a GET request and return the status line and body.
+    fn get(server: &PreviewServer, path: &str) -> (String, String) {
+        let mut stream = TcpStream::connect(server.addr).unwrap();
+        write!(stream, "GET {} HTTP/1.1\r\nHost: localhost:{}\r\n\r\n", path, server.addr.port()).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        (head.lines().next().unwrap().to_string(), body.to_string())
+    }
+
+    #[test]
+    fn test_preview_server() {
+        let root = std::env::temp_dir().join(format!("rhe_preview_{}", std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join("book.css"), "p { margin: 0; }").unwrap();
+        fs::write(root.join("my ch.html"), "<h1>Saved</h1>\n").unwrap();
+        fs::write(root.join(".secret"), "x").unwrap();
+
+        let server = PreviewServer::start(&root, 0).unwrap();
+        let (status, body) = get(&server, "/my%20ch.html");
+        assert_eq!(status, "HTTP/1.1 200 OK");
+        assert!(body.contains("<h1>Saved</h1>"));
+        assert!(body.contains("<link rel=\"stylesheet\" href=\"book.css\">"));
+        assert!(body.contains("<script>new EventSource(\"/__preview/events?since=0\")"));
+
+        assert_eq!(get(&server, "/book.css").1, "p { margin: 0; }");
+        assert!(get(&server, "/").1.contains("<a href=\"/my%20ch.html\">my ch.html</a>"));
+        assert_eq!(get(&server, "/../etc/passwd").0, "HTTP/1.1 404 Not Found");
+        assert_eq!(get(&server, "/.secret").0, "HTTP/1.1 404 Not Found");
+
+        // Symlinks out of the project are not served
+        #[cfg(unix)]
+        {
+            let outside = root.with_file_name(format!("rhe_preview_outside_{}", std::process::id()));
+            fs::create_dir_all(&outside).unwrap();
+            fs::write(outside.join("secret.txt"), "secret").unwrap();
+            std::os::unix::fs::symlink(&outside, root.join("linked")).unwrap();
+            assert_eq!(get(&server, "/linked/secret.txt").0, "HTTP/1.1 404 Not Found");
+            fs::remove_file(root.join("linked")).unwrap();
+            fs::remove_dir_all(&outside).unwrap();
+        }
+
+        // Requests for another host name are refused (DNS rebinding)
+        for host in [format!("evil.example:{}", server.addr.port()), "localhost".to_string()] {
+            let mut stream = TcpStream::connect(server.addr).unwrap();
+            write!(stream, "GET /book.css HTTP/1.1\r\nHost: {}\r\n\r\n", host).unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            assert!(response.starts_with("HTTP/1.1 403 Forbidden"), "{}", host);
+        }
+
+        // An open event stream hears about unsaved edits
+        let mut events = TcpStream::connect(server.addr).unwrap();
+        write!(events, "GET {}?since=0 HTTP/1.1\r\nHost: 127.0.0.1:{}\r\n\r\n", EVENTS_PATH, server.addr.port()).unwrap();
+        server.set_overlay("my ch.html", "<h1>Unsaved</h1>");
+        let mut reader = io::BufReader::new(events);
+        let mut line = String::new();
+        while !line.starts_with("event:") {
+            line.clear();
+            reader.read_line(&mut line).unwrap();
+        }
+        assert_eq!(line, "event: reload\n");
+        assert!(get(&server, "/my%20ch.html").1.contains("<h1>Unsaved</h1>"));
+
+        server.clear_overlay();
+        assert!(get(&server, "/my%20ch.html").1.contains("<h1>Saved</h1>"));
+
+        server.stop();
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
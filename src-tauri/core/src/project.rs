@@ -0,0 +1,452 @@
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Component, Path, PathBuf};
+use std::time::Duration;
+
+use crate::chapters;
+use crate::git::GitStatus;
+use crate::history;
+
+/// Metadata for a chapter file.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ChapterMeta {
+    pub filename: String,
+    pub path: String,
+    /// Relative path from the project root (includes subdirectory)
+    pub relative_path: String,
+    /// Git status of the file; None outside a git repository
+    pub git_status: Option<GitStatus>,
+}
+
+/// List HTML files in a directory, recursively scanning subdirectories.
+/// Sorted by relative path so chapters group by folder then filename.
+pub fn list_html_files(dir: &Path) -> io::Result<Vec<ChapterMeta>> {
+    let mut chapters: Vec<ChapterMeta> = Vec::new();
+    collect_html_files(dir, dir, &mut chapters)?;
+
+    // Sort by relative path for natural ordering
+    chapters.sort_by(|a, b| a.relative_path.cmp(&b.relative_path));
+    Ok(chapters)
+}
+
+/// Recursively collect HTML files from a directory tree.
+/// Symlinked folders are not followed; symlinked chapter files are listed.
+fn collect_html_files(
+    root: &Path,
+    dir: &Path,
+    chapters: &mut Vec<ChapterMeta>,
+) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+
+        // Skip hidden entries such as .git and the editor trash folder
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            // Recurse into subdirectories
+            collect_html_files(root, &path, chapters)?;
+            continue;
+        }
+        if file_type.is_symlink() && path.is_dir() {
+            continue;
+        }
+
+        // Check for .html or .htm extension
+        let ext = path.extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or("")
+            .to_lowercase();
+
+        if ext == "html" || ext == "htm" || ext == "xhtml" {
+            let filename = path.file_name()
+                .and_then(|n| n.to_str())
+                .unwrap_or("")
+                .to_string();
+
+            // Compute relative path from project root
+            let relative = path.strip_prefix(root)
+                .unwrap_or(&path)
+                .to_string_lossy()
+                .to_string();
+
+            chapters.push(ChapterMeta {
+                filename,
+                path: path.to_string_lossy().to_string(),
+                relative_path: relative,
+                git_status: None,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Find the project stylesheet among common locations like book.css
+/// in the root or a css/ or styles/ subdirectory.
+/// Used for chapters that link no stylesheet of their own.
+pub fn find_css(dir: &Path) -> Option<PathBuf> {
+    let candidates = [
+        dir.join("book.css"),
+        dir.join("style.css"),
+        dir.join("styles.css"),
+        dir.join("css/book.css"),
+        dir.join("styles/book.css"),
+    ];
+
+    candidates.into_iter().find(|candidate| candidate.is_file())
+}
+
+/// True if a relative path only has plain, non-hidden components, so it
+/// stays inside the folder it is joined to.
+pub fn is_safe_relative(path: &str) -> bool {
+    let path = Path::new(path);
+    !path.as_os_str().is_empty()
+        && path.components().all(|c| match c {
+            Component::Normal(part) => !part.to_string_lossy().starts_with('.'),
+            _ => false,
+        })
+}
+
+/// Check that `path` stays inside `root` once symlinks are resolved.
+/// A path that does not exist yet is checked through its nearest existing
+/// folder, so writes through a folder symlinked out of the project fail.
+pub fn ensure_inside(root: &Path, path: &Path) -> io::Result<()> {
+    let root = fs::canonicalize(root)?;
+    let outside = || {
+        io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("Path outside the project: {}", path.display()),
+        )
+    };
+
+    let mut existing = path;
+    let canonical = loop {
+        match fs::canonicalize(existing) {
+            Ok(canonical) => break canonical,
+            // A dangling symlink could point anywhere
+            Err(_) if fs::symlink_metadata(existing).is_ok() => return Err(outside()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => match existing.parent() {
+                Some(parent) => existing = parent,
+                None => return Err(e),
+            },
+            Err(e) => return Err(e),
+        }
+    };
+    if canonical.starts_with(&root) {
+        Ok(())
+    } else {
+        Err(outside())
+    }
+}
+
+/// Write content to a file atomically and durably.
+/// Symlinks are followed so the real target is replaced, the new file keeps
+/// the original's permissions (and, on Unix, ownership and extended
+/// attributes where allowed), and both the file and its folder are synced.
+/// On any failure the temp file is removed and the original is untouched.
+/// A failed folder sync after the rename is still returned as an error,
+/// though the new content is already in place. Dangling symlinks are not
+/// written through, since nothing here knows where they may safely point.
+pub fn atomic_write(path: &Path, content: &str) -> io::Result<()> {
+    let target = resolve_symlinks(path)?;
+    let tmp_path = temp_path(&target);
+
+    let result = write_temp(&target, &tmp_path, content)
+        // Rename temp file to target (atomic on most filesystems)
+        .and_then(|_| fs::rename(&tmp_path, &target));
+    if let Err(e) = result {
+        let _ = fs::remove_file(&tmp_path);
+        return Err(e);
+    }
+
+    sync_parent_dir(&target)
+}
+
+/// Write and sync the temp file, copying metadata from the current target.
+/// The temp file gets the target's permissions before any content is
+/// written, so a private file is never briefly readable by others.
+fn write_temp(target: &Path, tmp_path: &Path, content: &str) -> io::Result<()> {
+    let meta = fs::metadata(target).ok();
+
+    // A stale temp file (or a link planted in its place) is replaced
+    let _ = fs::remove_file(tmp_path);
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    if let Some(meta) = &meta {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+        options.mode(meta.permissions().mode());
+    }
+    let mut file = options.open(tmp_path)?;
+
+    if let Some(meta) = &meta {
+        // The creation mode is narrowed by the umask; set it exactly
+        #[cfg(unix)]
+        file.set_permissions(meta.permissions())?;
+        copy_owner_and_xattrs(target, tmp_path, meta);
+    }
+    file.write_all(content.as_bytes())?;
+
+    // Read-only files on other platforms must be written before the flag is set
+    #[cfg(not(unix))]
+    if let Some(meta) = &meta {
+        file.set_permissions(meta.permissions())?;
+    }
+
+    file.sync_all()
+}
+
+/// Copy ownership and extended attributes. Both are best effort: only
+/// root may give files away, and some filesystems lack xattr support.
+#[cfg(unix)]
+fn copy_owner_and_xattrs(from: &Path, to: &Path, meta: &fs::Metadata) {
+    use std::os::unix::fs::MetadataExt;
+
+    let _ = std::os::unix::fs::chown(to, Some(meta.uid()), Some(meta.gid()));
+    if let Ok(names) = xattr::list(from) {
+        for name in names {
+            if let Ok(Some(value)) = xattr::get(from, &name) {
+                let _ = xattr::set(to, &name, &value);
+            }
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn copy_owner_and_xattrs(_from: &Path, _to: &Path, _meta: &fs::Metadata) {}
+
+/// Sync a file's folder so a rename survives a power loss.
+#[cfg(unix)]
+fn sync_parent_dir(path: &Path) -> io::Result<()> {
+    match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => fs::File::open(dir)?.sync_all(),
+        _ => fs::File::open(".")?.sync_all(),
+    }
+}
+
+/// Folders cannot be opened for syncing on Windows; the rename is durable
+/// once the file itself is synced.
+#[cfg(not(unix))]
+fn sync_parent_dir(_path: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+/// Follow a chain of symlinks to the file they point at.
+/// A chain ending at a file that does not exist is refused.
+fn resolve_symlinks(path: &Path) -> io::Result<PathBuf> {
+    let mut current = path.to_path_buf();
+    // Same limit as the Linux kernel
+    for _ in 0..40 {
+        match fs::symlink_metadata(&current) {
+            Ok(meta) if meta.file_type().is_symlink() => {
+                let link = fs::read_link(&current)?;
+                current = match current.parent() {
+                    Some(parent) => parent.join(link),
+                    None => link,
+                };
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound && current != path => {
+                return Err(io::Error::new(
+                    io::ErrorKind::PermissionDenied,
+                    format!("Refusing to write through dangling symlink: {}", path.display()),
+                ));
+            }
+            _ => return Ok(current),
+        }
+    }
+    Err(io::Error::other(format!("Too many levels of symbolic links: {}", path.display())))
+}
+
+/// Temp file used while writing `path`: the full filename, hidden, plus .tmp
+/// (for example `.intro.htm.tmp`), so it never collides with or lists as a chapter.
+pub fn temp_path(path: &Path) -> PathBuf {
+    let name = path.file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    path.with_file_name(format!(".{}.tmp", name))
+}
+
+/// True for names of the form `temp_path` produces: `.` + filename + `.tmp`.
+fn is_temp_name(name: &str) -> bool {
+    name.strip_prefix('.')
+        .and_then(|rest| rest.strip_suffix(".tmp"))
+        .is_some_and(|original| !original.is_empty())
+}
+
+/// Backup file for `path`: the full filename plus .bak (for example
+/// `intro.htm.bak`), beside the original or inside `backup_dir` if given.
+pub fn backup_path(path: &Path, backup_dir: Option<&Path>) -> PathBuf {
+    let name = format!(
+        "{}.bak",
+        path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default(),
+    );
+    match backup_dir {
+        Some(dir) => dir.join(name),
+        None => path.with_file_name(name),
+    }
+}
+
+/// Create a backup copy of a file, beside it or inside `backup_dir`.
+pub fn create_backup(path: &Path, backup_dir: Option<&Path>) -> io::Result<PathBuf> {
+    let backup_path = backup_path(path, backup_dir);
+    if let Some(parent) = backup_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::copy(path, &backup_path)?;
+    Ok(backup_path)
+}
+
+/// Remove temp files left behind by interrupted writes.
+/// Matches only the hidden `.name.tmp` files written by `temp_path`,
+/// skipping files modified within `min_age` that may still be in use.
+/// Symlinks are not followed, and the git, history, and trash folders
+/// are left alone. Returns the removed paths.
+pub fn cleanup_temp_files(dir: &Path, min_age: Duration) -> io::Result<Vec<PathBuf>> {
+    let mut removed: Vec<PathBuf> = Vec::new();
+    collect_stale_temp_files(dir, min_age, &mut removed)?;
+    Ok(removed)
+}
+
+/// Folders whose contents are never cleaned up.
+const CLEANUP_SKIP_DIRS: [&str; 3] = [".git", history::HISTORY_DIR, chapters::TRASH_DIR];
+
+/// Recursively remove stale temp files from a directory tree.
+fn collect_stale_temp_files(
+    dir: &Path,
+    min_age: Duration,
+    removed: &mut Vec<PathBuf>,
+) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        let name = entry.file_name().to_string_lossy().to_string();
+        let file_type = entry.file_type()?;
+
+        if file_type.is_dir() {
+            if !CLEANUP_SKIP_DIRS.contains(&name.as_str()) {
+                collect_stale_temp_files(&path, min_age, removed)?;
+            }
+            continue;
+        }
+        if !file_type.is_file() || !is_temp_name(&name) {
+            continue;
+        }
+
+        let age = entry.metadata()?
+            .modified()?
+            .elapsed()
+            .unwrap_or_default();
+        if age >= min_age {
+            fs::remove_file(&path)?;
+            removed.push(path);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backup_and_temp_names() {
+        let dir = Path::new("/book/part1");
+        // Distinct extensions never share a backup or temp file
+        assert_eq!(backup_path(&dir.join("intro.htm"), None), dir.join("intro.htm.bak"));
+        assert_eq!(backup_path(&dir.join("intro.html"), None), dir.join("intro.html.bak"));
+        assert_eq!(temp_path(&dir.join("intro.xhtml")), dir.join(".intro.xhtml.tmp"));
+
+        let backups = Path::new("/book/.backups/part1");
+        assert_eq!(backup_path(&dir.join("intro.htm"), Some(backups)), backups.join("intro.htm.bak"));
+    }
+
+    #[test]
+    fn test_atomic_write_keeps_file_identity() {
+        let dir = std::env::temp_dir()
+            .join(format!("rhe_atomic_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("shared")).unwrap();
+        let real = dir.join("shared/ch01.html");
+        fs::write(&real, "old").unwrap();
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&real, fs::Permissions::from_mode(0o640)).unwrap();
+            std::os::unix::fs::symlink("shared/ch01.html", dir.join("ch01.html")).unwrap();
+
+            // Writing through the link replaces the target, keeping the link and mode
+            atomic_write(&dir.join("ch01.html"), "new").unwrap();
+            assert!(fs::symlink_metadata(dir.join("ch01.html")).unwrap().file_type().is_symlink());
+            assert_eq!(fs::read_to_string(&real).unwrap(), "new");
+            assert_eq!(fs::metadata(&real).unwrap().permissions().mode() & 0o777, 0o640);
+
+            // A link left at the temp path is replaced, not written through
+            fs::write(dir.join("decoy.txt"), "decoy").unwrap();
+            std::os::unix::fs::symlink(dir.join("decoy.txt"), temp_path(&real)).unwrap();
+            atomic_write(&real, "newer").unwrap();
+            assert_eq!(fs::read_to_string(dir.join("decoy.txt")).unwrap(), "decoy");
+            assert_eq!(fs::read_to_string(&real).unwrap(), "newer");
+            assert_eq!(fs::metadata(&real).unwrap().permissions().mode() & 0o777, 0o640);
+
+            // A dangling link is not followed to create its target
+            std::os::unix::fs::symlink("../outside.html", dir.join("dangling.html")).unwrap();
+            let err = atomic_write(&dir.join("dangling.html"), "x").unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+            assert!(!dir.with_file_name("outside.html").exists());
+        }
+
+        // A failed rename leaves no temp file behind
+        fs::create_dir_all(dir.join("folder.html")).unwrap();
+        assert!(atomic_write(&dir.join("folder.html"), "x").is_err());
+        assert!(!temp_path(&dir.join("folder.html")).exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_cleanup_temp_files() {
+        let dir = std::env::temp_dir()
+            .join(format!("rhe_cleanup_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join(".editor-history/ch01.html")).unwrap();
+        fs::create_dir_all(dir.join("part1")).unwrap();
+        fs::write(dir.join("ch01.html"), "keep").unwrap();
+        fs::write(dir.join(".ch01.html.tmp"), "stale").unwrap();
+        fs::write(dir.join("part1/.manifest.json.tmp"), "stale").unwrap();
+        fs::write(dir.join("ch02.html.tmp"), "not ours").unwrap();
+        fs::write(dir.join(".tmp"), "not ours").unwrap();
+        fs::write(dir.join(".editor-history/ch01.html/.1.html.tmp"), "history").unwrap();
+
+        // Recent temp files are left alone
+        assert!(cleanup_temp_files(&dir, Duration::from_secs(3600)).unwrap().is_empty());
+
+        // Symlinked folders are not followed
+        #[cfg(unix)]
+        {
+            let outside = dir.with_file_name(format!("rhe_cleanup_outside_{}", std::process::id()));
+            let _ = fs::remove_dir_all(&outside);
+            fs::create_dir_all(&outside).unwrap();
+            fs::write(outside.join(".ch09.html.tmp"), "elsewhere").unwrap();
+            std::os::unix::fs::symlink(&outside, dir.join("linked")).unwrap();
+
+            let mut removed = cleanup_temp_files(&dir, Duration::ZERO).unwrap();
+            removed.sort();
+            assert_eq!(removed, [dir.join(".ch01.html.tmp"), dir.join("part1/.manifest.json.tmp")]);
+            assert!(outside.join(".ch09.html.tmp").exists());
+            fs::remove_dir_all(&outside).unwrap();
+        }
+        #[cfg(not(unix))]
+        assert_eq!(cleanup_temp_files(&dir, Duration::ZERO).unwrap().len(), 2);
+
+        assert!(dir.join("ch01.html").exists());
+        assert!(dir.join("ch02.html.tmp").exists());
+        assert!(dir.join(".tmp").exists());
+        assert!(dir.join(".editor-history/ch01.html/.1.html.tmp").exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
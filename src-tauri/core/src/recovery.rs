@@ -0,0 +1,180 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::diff::{self, DiffChunk};
+use crate::history;
+use crate::html_parser;
+use crate::project;
+
+/// Folder under the app data directory holding the recovery journal.
+pub const RECOVERY_DIR: &str = "recovery";
+
+/// Unsaved editor content for one chapter, kept until the chapter is saved.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct JournalEntry {
+    /// Absolute path of the chapter file
+    pub file_path: String,
+    /// Project root the chapter was opened from
+    pub project_dir: Option<String>,
+    /// Editor body with chapter-relative URLs
+    pub body_html: String,
+    pub original_head: String,
+    pub is_fragment: bool,
+    /// Journal time in milliseconds since the Unix epoch
+    pub saved_ms: u64,
+}
+
+/// A journaled chapter offered for recovery, with its changes against disk.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct RecoverableChapter {
+    pub file_path: String,
+    pub project_dir: Option<String>,
+    pub saved_ms: u64,
+    /// True if the chapter file no longer exists
+    pub missing: bool,
+    /// Line diff from the body on disk to the journaled body
+    pub diff: Vec<DiffChunk>,
+}
+
+/// Store the unsaved content of a chapter, replacing any earlier entry.
+pub fn record(
+    journal_dir: &Path,
+    file_path: &str,
+    project_dir: Option<&str>,
+    body_html: &str,
+    original_head: &str,
+    is_fragment: bool,
+) -> io::Result<()> {
+    let entry = JournalEntry {
+        file_path: file_path.to_string(),
+        project_dir: project_dir.map(|d| d.to_string()),
+        body_html: body_html.to_string(),
+        original_head: original_head.to_string(),
+        is_fragment,
+        saved_ms: history::now_ms(),
+    };
+    let text = serde_json::to_string(&entry)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+    fs::create_dir_all(journal_dir)?;
+    project::atomic_write(&entry_path(journal_dir, file_path), &text)
+}
+
+/// Load the journal entry of a chapter, if there is one.
+pub fn load(journal_dir: &Path, file_path: &str) -> io::Result<Option<JournalEntry>> {
+    let path = entry_path(journal_dir, file_path);
+    if !path.is_file() {
+        return Ok(None);
+    }
+    let entry = read_entry(&path)?;
+    // Guard against hash collisions
+    Ok(Some(entry).filter(|e| e.file_path == file_path))
+}
+
+/// Remove the journal entry of a chapter, if there is one.
+pub fn discard(journal_dir: &Path, file_path: &str) -> io::Result<()> {
+    match fs::remove_file(entry_path(journal_dir, file_path)) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+/// Journaled chapters whose content differs from the file on disk, newest first.
+/// Entries that match the disk (saved elsewhere) or cannot be read are removed.
+pub fn list_recoverable(journal_dir: &Path) -> io::Result<Vec<RecoverableChapter>> {
+    if !journal_dir.is_dir() {
+        return Ok(Vec::new());
+    }
+
+    let mut recoverable: Vec<RecoverableChapter> = Vec::new();
+    for dir_entry in fs::read_dir(journal_dir)? {
+        let path = dir_entry?.path();
+        if path.extension().and_then(|e| e.to_str()) != Some("json") {
+            continue;
+        }
+        let Ok(entry) = read_entry(&path) else {
+            fs::remove_file(&path)?;
+            continue;
+        };
+
+        let disk_body = fs::read_to_string(&entry.file_path)
+            .ok()
+            .map(|raw| html_parser::split_html(&raw).body_content);
+        if disk_body.as_deref() == Some(entry.body_html.as_str()) {
+            fs::remove_file(&path)?;
+            continue;
+        }
+
+        recoverable.push(RecoverableChapter {
+            diff: diff::diff_lines(disk_body.as_deref().unwrap_or(""), &entry.body_html),
+            missing: disk_body.is_none(),
+            file_path: entry.file_path,
+            project_dir: entry.project_dir,
+            saved_ms: entry.saved_ms,
+        });
+    }
+
+    recoverable.sort_by_key(|r| std::cmp::Reverse(r.saved_ms));
+    Ok(recoverable)
+}
+
+fn read_entry(path: &Path) -> io::Result<JournalEntry> {
+    let text = fs::read_to_string(path)?;
+    serde_json::from_str(&text)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// Journal file of a chapter, named by a hash of its path.
+fn entry_path(journal_dir: &Path, file_path: &str) -> PathBuf {
+    journal_dir.join(format!("{:016x}.json", fnv1a(file_path.as_bytes())))
+}
+
+/// 64-bit FNV-1a hash; stable across runs, unlike the std hasher.
+pub(crate) fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in bytes {
+        hash ^= u64::from(*byte);
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_journal_lifecycle() {
+        let base = std::env::temp_dir()
+            .join(format!("rhe_recovery_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&base);
+        let journal = base.join(RECOVERY_DIR);
+        fs::create_dir_all(&base).unwrap();
+        let chapter = base.join("ch01.html");
+        fs::write(&chapter, "<p>saved</p>\n").unwrap();
+        let chapter = chapter.to_string_lossy().to_string();
+
+        record(&journal, &chapter, None, "<p>saved</p>\n<p>draft</p>\n", "", true).unwrap();
+        let entry = load(&journal, &chapter).unwrap().unwrap();
+        assert_eq!(entry.body_html, "<p>saved</p>\n<p>draft</p>\n");
+
+        let recoverable = list_recoverable(&journal).unwrap();
+        assert_eq!(recoverable.len(), 1);
+        assert!(!recoverable[0].missing);
+        assert!(recoverable[0].diff.iter()
+            .any(|c| c.kind == diff::ChangeKind::Insert && c.text == "<p>draft</p>\n"));
+
+        // Once the disk matches the journal, the entry is dropped
+        fs::write(&chapter, "<p>saved</p>\n<p>draft</p>\n").unwrap();
+        assert!(list_recoverable(&journal).unwrap().is_empty());
+        assert!(load(&journal, &chapter).unwrap().is_none());
+
+        record(&journal, &chapter, None, "<p>again</p>", "", true).unwrap();
+        discard(&journal, &chapter).unwrap();
+        discard(&journal, &chapter).unwrap();
+        assert!(load(&journal, &chapter).unwrap().is_none());
+
+        fs::remove_dir_all(&base).unwrap();
+    }
+}
@@ -0,0 +1,222 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::error::{EditorError, EditorResult, ErrorKind};
+
+/// Message prefix of scope violation errors, which have kind `outside_scope`.
+pub const SCOPE_ERROR: &str = "Path outside project scope";
+
+/// Paths the frontend may read and write: the opened project directory
+/// and files the user picked explicitly in a native dialog.
+pub struct PathScope {
+    state: Mutex<ScopeState>,
+}
+
+#[derive(Default)]
+struct ScopeState {
+    project_root: Option<PathBuf>,
+    picked_files: HashSet<PathBuf>,
+}
+
+impl Default for PathScope {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PathScope {
+    /// Create a new scope with nothing allowed.
+    pub fn new() -> Self {
+        PathScope {
+            state: Mutex::new(ScopeState::default()),
+        }
+    }
+
+    /// Make a user-picked directory the project root.
+    /// Returns the canonical root.
+    pub fn set_project(&self, dir: &Path) -> EditorResult<PathBuf> {
+        let canonical = fs::canonicalize(dir)
+            .map_err(|e| EditorError::io(format!("Failed to resolve {}", dir.display()), e).with_path(dir))?;
+        if !canonical.is_dir() {
+            return Err(not_a_directory(dir));
+        }
+        let mut state = self.lock()?;
+        state.project_root = Some(canonical.clone());
+        Ok(canonical)
+    }
+
+    /// Allow a single user-picked file outside the project.
+    pub fn allow_file(&self, path: &Path) -> EditorResult<()> {
+        let canonical = canonicalize_new(path)?;
+        let mut state = self.lock()?;
+        state.picked_files.insert(canonical);
+        Ok(())
+    }
+
+    /// The canonical project root, if a project is open.
+    pub fn project_root(&self) -> Option<PathBuf> {
+        self.state.lock().ok().and_then(|state| state.project_root.clone())
+    }
+
+    /// Canonicalize a path and check that it lies in scope.
+    /// The path may not exist yet, but its parent directory must.
+    /// Symlinks are resolved first, so links escaping the project are rejected.
+    pub fn resolve(&self, path: &Path) -> EditorResult<PathBuf> {
+        let canonical = canonicalize_new(path)?;
+        let state = self.lock()?;
+
+        let in_project = state.project_root.as_ref()
+            .is_some_and(|root| canonical.starts_with(root));
+        if in_project || state.picked_files.contains(&canonical) {
+            Ok(canonical)
+        } else {
+            Err(outside_scope(path))
+        }
+    }
+
+    /// Resolve a directory that must be the project root or inside it.
+    pub fn resolve_dir(&self, dir: &Path) -> EditorResult<PathBuf> {
+        let canonical = self.resolve(dir)?;
+        let in_project = self.project_root()
+            .is_some_and(|root| canonical.starts_with(root));
+        if !in_project {
+            return Err(outside_scope(dir));
+        }
+        if !canonical.is_dir() {
+            return Err(not_a_directory(dir));
+        }
+        Ok(canonical)
+    }
+
+    /// Path of a file relative to the project root, with `/` separators.
+    /// None if no project is open or the file lies outside it.
+    pub fn relative_path(&self, path: &Path) -> Option<String> {
+        let root = self.project_root()?;
+        let canonical = fs::canonicalize(path).ok()?;
+        let relative = canonical.strip_prefix(&root).ok()?;
+
+        let parts: Vec<String> = relative.components()
+            .map(|c| c.as_os_str().to_string_lossy().to_string())
+            .collect();
+        Some(parts.join("/"))
+    }
+
+    /// Project root and project-relative path of a file in the project.
+    pub fn project_location(&self, path: &Path) -> EditorResult<(PathBuf, String)> {
+        self.resolve(path)?;
+        let root = self.project_root()
+            .ok_or_else(|| EditorError::new(ErrorKind::NoProject, "No project is open"))?;
+        let relative = self.relative_path(path)
+            .ok_or_else(|| outside_scope(path))?;
+        Ok((root, relative))
+    }
+
+    fn lock(&self) -> EditorResult<std::sync::MutexGuard<'_, ScopeState>> {
+        self.state.lock()
+            .map_err(|e| EditorError::new(ErrorKind::Io, format!("Lock error: {}", e)))
+    }
+}
+
+/// Scope violation error for a path.
+pub fn outside_scope(path: &Path) -> EditorError {
+    EditorError::new(ErrorKind::OutsideScope, format!("{}: {}", SCOPE_ERROR, path.display()))
+        .with_path(path)
+}
+
+fn not_a_directory(dir: &Path) -> EditorError {
+    EditorError::new(ErrorKind::InvalidInput, format!("Not a directory: {}", dir.display()))
+        .with_path(dir)
+}
+
+/// Canonicalize a path that may not exist yet by resolving its parent.
+/// `..` in the final component is rejected since it cannot be resolved safely.
+/// A dangling symlink resolves to the file it would create, so the caller's
+/// scope check applies to where a write would really land.
+fn canonicalize_new(path: &Path) -> EditorResult<PathBuf> {
+    let mut current = path.to_path_buf();
+    // Same limit as the Linux kernel
+    for _ in 0..40 {
+        if let Ok(canonical) = fs::canonicalize(&current) {
+            return Ok(canonical);
+        }
+
+        let (Some(parent), Some(Component::Normal(name))) = (current.parent(), current.components().next_back()) else {
+            return Err(outside_scope(path));
+        };
+        let parent = if parent.as_os_str().is_empty() { Path::new(".") } else { parent };
+        let canonical_parent = fs::canonicalize(parent)
+            .map_err(|e| EditorError::io(format!("Failed to resolve {}", path.display()), e).with_path(path))?;
+        let candidate = canonical_parent.join(name);
+        match fs::read_link(&candidate) {
+            Ok(target) => current = canonical_parent.join(target),
+            Err(_) => return Ok(candidate),
+        }
+    }
+    Err(outside_scope(path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scope_rules() {
+        let base = std::env::temp_dir()
+            .join(format!("rhe_scope_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&base);
+        let project = base.join("book");
+        fs::create_dir_all(project.join("part1")).unwrap();
+        fs::write(project.join("part1/ch01.html"), "<p>1</p>").unwrap();
+        fs::write(base.join("secret.txt"), "secret").unwrap();
+        fs::write(base.join("notes.md"), "# Notes").unwrap();
+
+        let scope = PathScope::new();
+
+        // Nothing is allowed before a project is opened
+        let err = scope.resolve(&project.join("part1/ch01.html")).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::OutsideScope);
+
+        scope.set_project(&project).unwrap();
+        assert!(scope.resolve(&project.join("part1/ch01.html")).is_ok());
+        assert!(scope.resolve(&project.join("part1/new.html")).is_ok());
+        assert!(scope.resolve_dir(&project.join("part1")).is_ok());
+
+        // Traversal out of the project is rejected
+        let err = scope.resolve(&project.join("part1/../../secret.txt")).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::OutsideScope);
+        assert!(scope.resolve_dir(&base).is_err());
+
+        // Picked files are allowed individually
+        assert!(scope.resolve(&base.join("notes.md")).is_err());
+        scope.allow_file(&base.join("notes.md")).unwrap();
+        assert!(scope.resolve(&base.join("notes.md")).is_ok());
+
+        // Symlinks that escape the project are rejected
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(base.join("secret.txt"), project.join("link.html")).unwrap();
+            let err = scope.resolve(&project.join("link.html")).unwrap_err();
+            assert_eq!(err.kind, ErrorKind::OutsideScope);
+
+            // So are dangling ones, which a write would follow out of the project
+            std::os::unix::fs::symlink("../outside_target.html", project.join("dangling.html")).unwrap();
+            let err = scope.resolve(&project.join("dangling.html")).unwrap_err();
+            assert_eq!(err.kind, ErrorKind::OutsideScope);
+            assert!(crate::chapter_io::save_chapter(
+                &project.join("dangling.html"), "<p>pwn</p>", "", true, &crate::backup::BackupTracker::new(), &scope,
+            ).is_err());
+            assert!(!base.join("outside_target.html").exists());
+
+            // A dangling link inside the project resolves to the file it names
+            std::os::unix::fs::symlink("part1/new.html", project.join("pending.html")).unwrap();
+            assert_eq!(
+                scope.resolve(&project.join("pending.html")).unwrap(),
+                fs::canonicalize(project.join("part1")).unwrap().join("new.html"),
+            );
+        }
+
+        fs::remove_dir_all(&base).unwrap();
+    }
+}
@@ -0,0 +1,498 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+use base64::Engine;
+
+use crate::asset_urls;
+use crate::error::{EditorError, EditorResult};
+use crate::export;
+use crate::html_parser;
+use crate::links;
+use crate::manifest::{self, Manifest};
+use crate::media_type;
+use crate::project;
+use crate::stylesheets;
+
+/// Attributes whose files are embedded as data URIs.
+const EMBED_ATTRIBUTES: [&str; 4] = ["src", "poster", "srcset", "style"];
+
+/// What a single-file export wrote.
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct SingleFileSummary {
+    pub path: String,
+    /// Chapters included, project-relative
+    pub chapters: Vec<String>,
+    /// Stylesheets, images, fonts, and other files embedded, project-relative
+    pub embedded: Vec<String>,
+    /// References that could not be embedded
+    pub warnings: Vec<String>,
+}
+
+/// Write one chapter, or the whole book if `chapter` is None, as a single
+/// self-contained HTML file: linked stylesheets become `<style>` blocks
+/// with their imports inlined, and images, fonts, and media become data
+/// URIs. In the whole-book file each chapter is a `<section>` in manifest
+/// order and links between chapters become in-document anchors.
+/// `chapter` is project-relative.
+pub fn export_single_file(root: &Path, chapter: Option<&str>, output: &Path) -> EditorResult<SingleFileSummary> {
+    let mut embedder = Embedder::new(root);
+    let (html, chapters) = match chapter {
+        Some(doc) => {
+            let doc = doc.replace('\\', "/");
+            let raw = read_chapter(root, &doc)?;
+            let document = export::standalone_document(root, &doc, &raw);
+            (embedder.embed_document(&doc, &document), vec![doc])
+        }
+        None => book_document(root, &mut embedder)?,
+    };
+
+    project::atomic_write(output, &html)
+        .map_err(|e| EditorError::write(format!("Failed to write {}", output.display()), e).with_path(output))?;
+    Ok(SingleFileSummary {
+        path: output.to_string_lossy().to_string(),
+        chapters,
+        embedded: embedder.embedded,
+        warnings: embedder.warnings,
+    })
+}
+
+/// Every chapter in manifest order as one document.
+fn book_document(root: &Path, embedder: &mut Embedder) -> EditorResult<(String, Vec<String>)> {
+    let chapter_list = manifest::list_chapters(root)
+        .map_err(|e| EditorError::io("Failed to list chapters", e).with_path(root))?;
+    let mut chapters: Vec<BookChapter> = Vec::new();
+    for chapter in &chapter_list {
+        let doc = chapter.relative_path.replace('\\', "/");
+        let raw = read_chapter(root, &doc)?;
+        let split = html_parser::split_html(&export::standalone_document(root, &doc, &raw));
+        let ids = links::attribute_spans(&split.body_content, &["id"]).iter()
+            .map(|span| split.body_content[span.start..span.end].to_string())
+            .collect();
+        chapters.push(BookChapter {
+            doc,
+            head: split.head_content,
+            body: split.body_content,
+            ids,
+            section_id: String::new(),
+            renamed: HashMap::new(),
+        });
+    }
+
+    // Section ids, then ids that clash with an earlier chapter get the
+    // section id as a prefix
+    let mut taken: HashSet<String> = chapters.iter().flat_map(|c| c.ids.iter().cloned()).collect();
+    for (n, chapter) in chapters.iter_mut().enumerate() {
+        chapter.section_id = unique_id(&mut taken, &format!("chapter-{}", n + 1));
+    }
+    let mut seen: HashSet<String> = HashSet::new();
+    for chapter in &mut chapters {
+        for id in chapter.ids.clone() {
+            if !seen.insert(id.clone()) {
+                let new_id = unique_id(&mut taken, &format!("{}-{}", chapter.section_id, id));
+                chapter.renamed.insert(id, new_id);
+            }
+        }
+    }
+
+    let project_manifest = Manifest::load(root)
+        .map_err(|e| EditorError::io("Failed to read the project manifest", e).with_path(root))?;
+    let book = project_manifest.epub.unwrap_or_default();
+    let title = if book.title.is_empty() {
+        root.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default()
+    } else {
+        book.title
+    };
+
+    // Stylesheets shared by chapters are included once
+    let mut head = String::from("<meta charset=\"utf-8\">\n");
+    head += &format!("<title>{}</title>\n", html_parser::escape_text(&title));
+    let mut styles_seen: HashSet<String> = HashSet::new();
+    for chapter in &chapters {
+        for style in embedder.head_styles(&chapter.doc, &chapter.head) {
+            if styles_seen.insert(style.clone()) {
+                head += &style;
+                head.push('\n');
+            }
+        }
+    }
+
+    let mut body = String::new();
+    for chapter in &chapters {
+        let content = anchor_links(&chapter.body, chapter, &chapters);
+        let content = embedder.embed_attributes(&chapter.doc, &content);
+        body += &format!("<section class=\"chapter\" id=\"{}\">\n", chapter.section_id);
+        body += content.trim_matches('\n');
+        body += "\n</section>\n";
+    }
+
+    let mut html = String::from("<!DOCTYPE html>\n");
+    html += &match book.language.as_str() {
+        "" => "<html>\n".to_string(),
+        lang => format!("<html lang=\"{}\">\n", html_parser::escape_attribute(lang)),
+    };
+    html += &format!("<head>\n{}</head>\n<body>\n{}</body>\n</html>\n", head, body);
+    Ok((html, chapters.into_iter().map(|c| c.doc).collect()))
+}
+
+/// A chapter being merged into the whole-book document.
+struct BookChapter {
+    doc: String,
+    head: String,
+    body: String,
+    /// `id` values in the body, in order
+    ids: Vec<String>,
+    /// `id` of the chapter's `<section>`
+    section_id: String,
+    /// Body ids renamed because an earlier chapter uses them
+    renamed: HashMap<String, String>,
+}
+
+impl BookChapter {
+    /// The id an element has in the merged document.
+    fn final_id<'a>(&'a self, id: &'a str) -> &'a str {
+        self.renamed.get(id).map(String::as_str).unwrap_or(id)
+    }
+}
+
+/// Rename clashing ids in a chapter body and point links to chapters at
+/// their sections in the merged document.
+fn anchor_links(body: &str, chapter: &BookChapter, chapters: &[BookChapter]) -> String {
+    let mut replacements: Vec<(usize, usize, String)> = Vec::new();
+    for span in links::attribute_spans(body, &["id", "href"]) {
+        let value = &body[span.start..span.end];
+        if span.name == "id" {
+            if let Some(new_id) = chapter.renamed.get(value) {
+                replacements.push((span.start, span.end, new_id.clone()));
+            }
+            continue;
+        }
+
+        if let Some(fragment) = value.strip_prefix('#') {
+            let id = links::percent_decode(fragment);
+            if let Some(new_id) = chapter.renamed.get(&id) {
+                replacements.push((span.start, span.end, format!("#{}", new_id)));
+            }
+            continue;
+        }
+        let (url_path, suffix) = links::split_url(value);
+        if url_path.is_empty() || !links::is_relative_url(url_path) {
+            continue;
+        }
+        let Some(target) = links::resolve_relative(&chapter.doc, &links::percent_decode(url_path)) else {
+            continue;
+        };
+        let Some(target_chapter) = chapters.iter().find(|c| c.doc == target) else {
+            continue;
+        };
+        let fragment = suffix.find('#').map(|hash| links::percent_decode(&suffix[hash + 1..]));
+        let anchor = match fragment.as_deref() {
+            Some(id) if !id.is_empty() => target_chapter.final_id(id).to_string(),
+            _ => target_chapter.section_id.clone(),
+        };
+        replacements.push((span.start, span.end, format!("#{}", html_parser::escape_attribute(&anchor))));
+    }
+    links::replace_spans(body, replacements)
+}
+
+/// Turns project files into inline styles and data URIs.
+struct Embedder<'a> {
+    root: &'a Path,
+    /// Data URI of each project-relative file already read
+    cache: HashMap<String, String>,
+    embedded: Vec<String>,
+    warnings: Vec<String>,
+}
+
+impl<'a> Embedder<'a> {
+    fn new(root: &'a Path) -> Self {
+        Embedder {
+            root,
+            cache: HashMap::new(),
+            embedded: Vec::new(),
+            warnings: Vec::new(),
+        }
+    }
+
+    /// A complete document with its stylesheets and files embedded.
+    fn embed_document(&mut self, doc: &str, html: &str) -> String {
+        let html = self.embed_attributes(doc, html);
+        let mut replacements: Vec<(usize, usize, String)> = Vec::new();
+        for (start, end) in style_tag_spans(&html) {
+            if let Some(style) = self.tag_style(doc, &html[start..end]) {
+                replacements.push((start, end, style));
+            }
+        }
+        links::replace_spans(&html, replacements)
+    }
+
+    /// `<style>` blocks for the stylesheets and style elements of a head.
+    fn head_styles(&mut self, doc: &str, head: &str) -> Vec<String> {
+        style_tag_spans(head).into_iter()
+            .filter_map(|(start, end)| self.tag_style(doc, &head[start..end]))
+            .collect()
+    }
+
+    /// The inlined replacement for a `<link rel="stylesheet">` or
+    /// `<style>` element, or None for other elements and remote sheets.
+    fn tag_style(&mut self, doc: &str, tag: &str) -> Option<String> {
+        if tag.len() > 6 && tag[..6].eq_ignore_ascii_case("<style") {
+            let open_end = tag.find('>')? + 1;
+            let close = tag.len() - "</style>".len();
+            let css = self.inline_css(doc, &tag[open_end..close], &mut Vec::new());
+            return Some(format!("{}{}</style>", &tag[..open_end], css));
+        }
+
+        let mut rel = String::new();
+        let mut href = None;
+        for span in links::attribute_spans(tag, &["rel", "href"]) {
+            let value = &tag[span.start..span.end];
+            if span.name == "rel" {
+                rel = value.to_ascii_lowercase();
+            } else {
+                href = Some(value);
+            }
+        }
+        let tokens: Vec<&str> = rel.split_ascii_whitespace().collect();
+        if !tokens.contains(&"stylesheet") || tokens.contains(&"alternate") {
+            return None;
+        }
+        let target = self.resolve(doc, href?)?;
+        let css = self.read_text(doc, &target)?;
+        let css = self.inline_css(&target, &css, &mut vec![target.clone()]);
+        Some(format!("<style>\n{}\n</style>", css.trim_matches('\n')))
+    }
+
+    /// A stylesheet with its local imports inlined and its `url()`s as
+    /// data URIs. `sheet` is the project-relative path URLs are resolved
+    /// against; `chain` holds the sheets being inlined, to stop cycles.
+    fn inline_css(&mut self, sheet: &str, css: &str, chain: &mut Vec<String>) -> String {
+        let mut remote = String::new();
+        let mut imported = String::new();
+        for import in stylesheets::css_imports(css) {
+            if !links::is_relative_url(&import) {
+                remote += &format!("@import url(\"{}\");\n", import);
+                continue;
+            }
+            let Some(target) = self.resolve(sheet, &import) else {
+                continue;
+            };
+            if chain.contains(&target) {
+                continue;
+            }
+            if let Some(text) = self.read_text(sheet, &target) {
+                chain.push(target.clone());
+                imported += &self.inline_css(&target, &text, chain);
+                imported.push('\n');
+                chain.pop();
+            }
+        }
+
+        let rules = &css[stylesheets::import_block_end(css)..];
+        format!("{}{}{}", remote, imported, self.embed_css_urls(sheet, rules))
+    }
+
+    /// Replace relative `url()`s in CSS with data URIs.
+    fn embed_css_urls(&mut self, sheet: &str, css: &str) -> String {
+        let mut replacements: Vec<(usize, usize, String)> = Vec::new();
+        for (start, end) in asset_urls::css_url_spans(css) {
+            if let Some(uri) = self.data_uri(sheet, &css[start..end]) {
+                replacements.push((start, end, uri));
+            }
+        }
+        links::replace_spans(css, replacements)
+    }
+
+    /// Replace files referenced by `src`, `poster`, `srcset`, and `style`
+    /// attributes with data URIs.
+    fn embed_attributes(&mut self, doc: &str, html: &str) -> String {
+        let mut replacements: Vec<(usize, usize, String)> = Vec::new();
+        for span in links::attribute_spans(html, &EMBED_ATTRIBUTES) {
+            let value = &html[span.start..span.end];
+            let new_value = match span.name.as_str() {
+                "style" => self.embed_css_urls(doc, value),
+                "srcset" => value.split(',')
+                    .map(|candidate| {
+                        let candidate = candidate.trim();
+                        let (url, descriptor) = candidate.split_once(' ').unwrap_or((candidate, ""));
+                        match self.data_uri(doc, url) {
+                            Some(uri) if descriptor.is_empty() => uri,
+                            Some(uri) => format!("{} {}", uri, descriptor.trim()),
+                            None => candidate.to_string(),
+                        }
+                    })
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                _ => match self.data_uri(doc, value) {
+                    Some(uri) => uri,
+                    None => continue,
+                },
+            };
+            if new_value != value {
+                replacements.push((span.start, span.end, new_value));
+            }
+        }
+        links::replace_spans(html, replacements)
+    }
+
+    /// A data URI for a relative URL, keeping its fragment.
+    fn data_uri(&mut self, from: &str, url: &str) -> Option<String> {
+        let target = self.resolve(from, url)?;
+        let (_, suffix) = links::split_url(url);
+        let fragment = suffix.find('#').map(|hash| &suffix[hash..]).unwrap_or("");
+        if let Some(uri) = self.cache.get(&target) {
+            return Some(format!("{}{}", uri, fragment));
+        }
+
+        let path = self.root.join(&target);
+        let bytes = match fs::read(&path) {
+            Ok(bytes) => bytes,
+            Err(_) => {
+                self.warnings.push(format!("{}: {} not found", from, url));
+                return None;
+            }
+        };
+        let uri = format!(
+            "data:{};base64,{}",
+            media_type::media_type(&path),
+            base64::engine::general_purpose::STANDARD.encode(bytes),
+        );
+        self.cache.insert(target.clone(), uri.clone());
+        self.embedded.push(target);
+        Some(format!("{}{}", uri, fragment))
+    }
+
+    /// A stylesheet's text, recorded as embedded.
+    fn read_text(&mut self, from: &str, target: &str) -> Option<String> {
+        match fs::read_to_string(self.root.join(target)) {
+            Ok(text) => {
+                if !self.embedded.iter().any(|e| e == target) {
+                    self.embedded.push(target.to_string());
+                }
+                Some(text)
+            }
+            Err(_) => {
+                self.warnings.push(format!("{}: {} not found", from, target));
+                None
+            }
+        }
+    }
+
+    /// The project-relative target of a relative URL. Remote, data, and
+    /// fragment-only URLs give None; URLs above the project root give None
+    /// with a warning.
+    fn resolve(&mut self, from: &str, url: &str) -> Option<String> {
+        let (url_path, _) = links::split_url(url);
+        if url_path.is_empty() || !links::is_relative_url(url_path) {
+            return None;
+        }
+        let target = links::resolve_relative(from, &links::percent_decode(url_path));
+        if target.is_none() {
+            self.warnings.push(format!("{}: {} is outside the project", from, url));
+        }
+        target
+    }
+}
+
+/// Byte ranges of the `<link ...>` tags and whole `<style>...</style>`
+/// elements of an HTML string, in order.
+fn style_tag_spans(html: &str) -> Vec<(usize, usize)> {
+    let lower = html.to_ascii_lowercase();
+    let mut spans = Vec::new();
+    let mut search = 0;
+    while let Some(found) = lower[search..].find('<') {
+        let start = search + found;
+        let rest = &lower[start + 1..];
+        let is_tag = |name: &str| {
+            rest.starts_with(name)
+                && rest[name.len()..].starts_with(|c: char| c.is_ascii_whitespace() || c == '>' || c == '/')
+        };
+        if is_tag("link") {
+            let Some(end) = lower[start..].find('>').map(|p| start + p + 1) else {
+                break;
+            };
+            spans.push((start, end));
+            search = end;
+        } else if is_tag("style") {
+            let Some(end) = lower[start..].find("</style>").map(|p| start + p + "</style>".len()) else {
+                break;
+            };
+            spans.push((start, end));
+            search = end;
+        } else if rest.starts_with("!--") {
+            search = lower[start..].find("-->").map(|p| start + p + 3).unwrap_or(lower.len());
+        } else {
+            search = start + 1;
+        }
+    }
+    spans
+}
+
+/// `base`, or `base` with a number suffix, not yet in `taken`.
+fn unique_id(taken: &mut HashSet<String>, base: &str) -> String {
+    let mut id = base.to_string();
+    let mut n = 2;
+    while !taken.insert(id.clone()) {
+        id = format!("{}-{}", base, n);
+        n += 1;
+    }
+    id
+}
+
+fn read_chapter(root: &Path, doc: &str) -> EditorResult<String> {
+    let path = root.join(doc);
+    fs::read_to_string(&path)
+        .map_err(|e| EditorError::io(format!("Failed to read {}", doc), e).with_path(&path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_export_single_file() {
+        let dir = std::env::temp_dir().join(format!("rhe_single_file_{}", std::process::id()));
+        let root = dir.join("project");
+        fs::create_dir_all(root.join("fonts")).unwrap();
+        fs::create_dir_all(root.join("images")).unwrap();
+        fs::write(root.join("book.css"), "@import \"fonts.css\";\nh1 { color: red; }\n").unwrap();
+        fs::write(root.join("fonts.css"), "@font-face { src: url('fonts/a.woff2') format('woff2'); }\n").unwrap();
+        fs::write(root.join("fonts/a.woff2"), "wof").unwrap();
+        fs::write(root.join("images/a.png"), "png").unwrap();
+        fs::write(
+            root.join("ch1.html"),
+            "<h1 id=\"top\">One</h1><p><img src=\"images/a.png\" alt=\"\"><a href=\"ch2.html#top\">on</a>\
+             <a href=\"ch2.html\">two</a><img src=\"gone.png\" alt=\"\"></p>\n",
+        ).unwrap();
+        fs::write(
+            root.join("ch2.html"),
+            "<!DOCTYPE html><html><head><title>Two</title><link rel=\"stylesheet\" href=\"book.css\"></head>\
+             <body><h1 id=\"top\">Two</h1><a href=\"#top\">up</a><a href=\"ch1.html#top\">back</a></body></html>\n",
+        ).unwrap();
+        fs::write(root.join("manifest.json"), "{\"chapters\": [\"ch1.html\", \"ch2.html\"]}").unwrap();
+
+        // One chapter: the stylesheet and its import are inlined
+        let out = dir.join("ch2.single.html");
+        let summary = export_single_file(&root, Some("ch2.html"), &out).unwrap();
+        let html = fs::read_to_string(&out).unwrap();
+        assert!(html.contains("<style>\n@font-face { src: url('data:font/woff2;base64,d29m') format('woff2'); }\n\nh1 { color: red; }\n</style>"));
+        assert!(!html.contains("<link"));
+        assert!(html.contains("<a href=\"ch1.html#top\">back</a>"));
+        assert_eq!(summary.embedded, vec!["book.css", "fonts.css", "fonts/a.woff2"]);
+
+        // The whole book: sections, anchors, and a renamed duplicate id
+        let out = dir.join("book.html");
+        let summary = export_single_file(&root, None, &out).unwrap();
+        let html = fs::read_to_string(&out).unwrap();
+        assert_eq!(summary.chapters, vec!["ch1.html", "ch2.html"]);
+        assert_eq!(summary.warnings, vec!["ch1.html: gone.png not found"]);
+        assert_eq!(html.matches("<style>").count(), 1);
+        assert!(html.contains("<title>project</title>"));
+        assert!(html.contains("<section class=\"chapter\" id=\"chapter-1\">\n<h1 id=\"top\">One</h1>"));
+        assert!(html.contains("<img src=\"data:image/png;base64,cG5n\" alt=\"\">"));
+        assert!(html.contains("<a href=\"#chapter-2-top\">on</a><a href=\"#chapter-2\">two</a>"));
+        assert!(html.contains("<h1 id=\"chapter-2-top\">Two</h1><a href=\"#chapter-2-top\">up</a><a href=\"#top\">back</a>"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
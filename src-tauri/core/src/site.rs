@@ -0,0 +1,395 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::error::{EditorError, EditorResult, ErrorKind};
+use crate::export;
+use crate::html_parser;
+use crate::links;
+use crate::manifest::{self, Manifest, MANIFEST_FILENAME};
+use crate::project;
+use crate::stylesheets;
+
+/// Filename of the generated index page.
+pub const INDEX_FILENAME: &str = "index.html";
+
+/// Layout used when the project sets no template.
+const DEFAULT_TEMPLATE: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<title>{{title}} - {{site_title}}</title>
+{{head}}
+</head>
+<body>
+<nav class="breadcrumbs">{{breadcrumbs}}</nav>
+<nav class="toc">
+{{toc}}
+</nav>
+<main>
+{{content}}
+</main>
+<nav class="pager">{{prev}} {{next}}</nav>
+</body>
+</html>
+"#;
+
+/// Static site settings, from the `site` section of the project manifest.
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct SiteConfig {
+    /// Project-relative path of the layout template; a built-in layout if unset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub template: Option<String>,
+    /// Site title; the EPUB title or the project folder name if unset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+}
+
+/// What a site export wrote.
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct SiteSummary {
+    /// Chapter pages in manifest order, plus the generated index if any
+    pub pages: Vec<String>,
+    /// Stylesheets, images, and other files in the site
+    pub assets: Vec<String>,
+    /// Pages and assets written by this export; the rest were up to date
+    pub written: Vec<String>,
+}
+
+/// A chapter page of the site.
+struct Page {
+    doc: String,
+    title: String,
+    head: String,
+    body: String,
+}
+
+/// Export the project as a static website in `out`. Each chapter is
+/// wrapped in the layout template at its project-relative path, with a
+/// table of contents, previous and next links, and breadcrumbs; other
+/// project files are copied. An `index.html` listing the chapters is
+/// generated unless the project has one.
+///
+/// Pages are only written when their content changed and assets only when
+/// their source is newer or a different size, so re-exporting after an
+/// edit touches only what it affects.
+pub fn export_site(root: &Path, out: &Path) -> EditorResult<SiteSummary> {
+    let project_manifest = Manifest::load(root)
+        .map_err(|e| EditorError::io("Failed to read the project manifest", e).with_path(root))?;
+    let config = project_manifest.site.clone().unwrap_or_default();
+    let site_title = config.title.clone()
+        .or(project_manifest.epub.as_ref().map(|epub| epub.title.clone()).filter(|t| !t.is_empty()))
+        .unwrap_or_else(|| root.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default());
+    let template = match &config.template {
+        Some(template) => {
+            let path = root.join(template);
+            let text = fs::read_to_string(&path)
+                .map_err(|e| EditorError::io(format!("Failed to read template {}", template), e).with_path(&path))?;
+            if !text.contains("{{content}}") {
+                return Err(EditorError::new(
+                    ErrorKind::InvalidInput,
+                    format!("Template {} has no {{{{content}}}} placeholder", template),
+                ).with_path(&path));
+            }
+            text
+        }
+        None => DEFAULT_TEMPLATE.to_string(),
+    };
+
+    let chapters = manifest::list_chapters(root)
+        .map_err(|e| EditorError::io("Failed to list chapters", e).with_path(root))?;
+    let mut pages = Vec::new();
+    for chapter in &chapters {
+        let doc = chapter.relative_path.replace('\\', "/");
+        // An HTML template is not a chapter
+        if config.template.as_deref() == Some(doc.as_str()) {
+            continue;
+        }
+        let raw = fs::read_to_string(&chapter.path)
+            .map_err(|e| EditorError::io(format!("Failed to read {}", doc), e).with_path(&chapter.path))?;
+        pages.push(page(root, doc, &raw));
+    }
+
+    fs::create_dir_all(out)
+        .map_err(|e| EditorError::write("Failed to create the site folder", e).with_path(out))?;
+    let mut summary = SiteSummary::default();
+    let has_index = pages.iter().any(|p| p.doc == INDEX_FILENAME);
+
+    for (n, page) in pages.iter().enumerate() {
+        let neighbor = |i: Option<usize>, rel: &str, label: &str| match i.and_then(|i| pages.get(i)) {
+            Some(other) => format!(
+                "<a rel=\"{}\" href=\"{}\">{}: {}</a>",
+                rel,
+                html_parser::escape_attribute(&links::relative_url(&page.doc, &other.doc)),
+                label,
+                html_parser::escape_text(&other.title),
+            ),
+            None => String::new(),
+        };
+        let html = fill_template(&template, |name| match name {
+            "title" => Some(html_parser::escape_text(&page.title)),
+            "site_title" => Some(html_parser::escape_text(&site_title)),
+            "head" => Some(page.head.clone()),
+            "content" => Some(page.body.clone()),
+            "toc" => Some(toc(&pages, &page.doc)),
+            "prev" => Some(neighbor(n.checked_sub(1), "prev", "Previous")),
+            "next" => Some(neighbor(Some(n + 1), "next", "Next")),
+            "breadcrumbs" => Some(breadcrumbs(&page.doc, &page.title, &site_title)),
+            "root" => Some(root_prefix(&page.doc)),
+            _ => None,
+        });
+        write_if_changed(out, &page.doc, &html, &mut summary)?;
+        summary.pages.push(page.doc.clone());
+    }
+
+    if !has_index {
+        let list = toc(&pages, INDEX_FILENAME);
+        let content = format!("<h1>{}</h1>\n{}", html_parser::escape_text(&site_title), list);
+        let html = fill_template(&template, |name| match name {
+            "title" | "site_title" => Some(html_parser::escape_text(&site_title)),
+            "content" => Some(content.clone()),
+            "toc" => Some(list.clone()),
+            "head" | "prev" | "breadcrumbs" | "root" => Some(String::new()),
+            "next" => Some(match pages.first() {
+                Some(first) => format!(
+                    "<a rel=\"next\" href=\"{}\">Next: {}</a>",
+                    html_parser::escape_attribute(&links::encode_path(&first.doc)),
+                    html_parser::escape_text(&first.title),
+                ),
+                None => String::new(),
+            }),
+            _ => None,
+        });
+        write_if_changed(out, INDEX_FILENAME, &html, &mut summary)?;
+        summary.pages.push(INDEX_FILENAME.to_string());
+    }
+
+    let skip = fs::canonicalize(out)
+        .map_err(|e| EditorError::io("Failed to read the site folder", e).with_path(out))?;
+    let mut excluded = vec![MANIFEST_FILENAME.to_string()];
+    excluded.extend(config.template);
+    copy_assets(root, root, out, &skip, &excluded, &mut summary)
+        .map_err(|e| EditorError::write("Failed to copy assets", e).with_path(out))?;
+    summary.assets.sort();
+    Ok(summary)
+}
+
+/// Title, head, and body of a chapter page. The head keeps the chapter's
+/// stylesheet links and style elements, or links the project stylesheet.
+fn page(root: &Path, doc: String, raw: &str) -> Page {
+    let split = html_parser::split_html(&export::standalone_document(root, &doc, raw));
+    let stem = Path::new(&doc).file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("Chapter");
+    let title = export::head_title(&split.head_content)
+        .unwrap_or_else(|| export::chapter_title(&split.body_content, stem));
+
+    let mut head = String::new();
+    for href in stylesheets::stylesheet_hrefs(&split.head_content) {
+        head += &format!("<link rel=\"stylesheet\" href=\"{}\">\n", html_parser::escape_attribute(&href));
+    }
+    for style in stylesheets::head_styles(&split.head_content) {
+        head += &format!("<style>{}</style>\n", style);
+    }
+    Page {
+        doc,
+        title,
+        head: head.trim_end().to_string(),
+        body: split.body_content.trim_matches('\n').to_string(),
+    }
+}
+
+/// Replace each `{{name}}` in a template, in one pass so placeholders in
+/// the inserted content are left alone. Unknown names are kept.
+fn fill_template(template: &str, value: impl Fn(&str) -> Option<String>) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(open) = rest.find("{{") {
+        out += &rest[..open];
+        let after = &rest[open + 2..];
+        match after.find("}}") {
+            Some(close) => match value(after[..close].trim()) {
+                Some(text) => {
+                    out += &text;
+                    rest = &after[close + 2..];
+                }
+                None => {
+                    out += "{{";
+                    rest = after;
+                }
+            },
+            None => {
+                out += &rest[open..];
+                rest = "";
+            }
+        }
+    }
+    out += rest;
+    out
+}
+
+/// The chapter list as seen from `from`, marking the current page.
+fn toc(pages: &[Page], from: &str) -> String {
+    let mut html = String::from("<ol>\n");
+    for page in pages {
+        let href = html_parser::escape_attribute(&links::relative_url(from, &page.doc));
+        let title = html_parser::escape_text(&page.title);
+        if page.doc == from {
+            html += &format!("<li class=\"current\"><a href=\"{}\" aria-current=\"page\">{}</a></li>\n", href, title);
+        } else {
+            html += &format!("<li><a href=\"{}\">{}</a></li>\n", href, title);
+        }
+    }
+    html += "</ol>";
+    html
+}
+
+/// Links from the index through each folder of the page's path.
+fn breadcrumbs(doc: &str, title: &str, site_title: &str) -> String {
+    let mut crumbs = vec![format!(
+        "<a href=\"{}\">{}</a>",
+        html_parser::escape_attribute(&links::relative_url(doc, INDEX_FILENAME)),
+        html_parser::escape_text(site_title),
+    )];
+    let folders: Vec<&str> = doc.split('/').collect();
+    for folder in &folders[..folders.len() - 1] {
+        crumbs.push(format!("<span>{}</span>", html_parser::escape_text(folder)));
+    }
+    crumbs.push(format!("<span aria-current=\"page\">{}</span>", html_parser::escape_text(title)));
+    crumbs.join(" / ")
+}
+
+/// Relative path from a page to the site root, such as `../` or empty.
+fn root_prefix(doc: &str) -> String {
+    "../".repeat(doc.matches('/').count())
+}
+
+/// Write a page unless the file already has this content.
+fn write_if_changed(out: &Path, relative: &str, html: &str, summary: &mut SiteSummary) -> EditorResult<()> {
+    let target = out.join(relative);
+    if fs::read_to_string(&target).is_ok_and(|existing| existing == html) {
+        return Ok(());
+    }
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| EditorError::write("Failed to create the site folder", e).with_path(parent))?;
+    }
+    project::atomic_write(&target, html)
+        .map_err(|e| EditorError::write(format!("Failed to write {}", relative), e).with_path(&target))?;
+    summary.written.push(relative.to_string());
+    Ok(())
+}
+
+/// Recursively copy the project's non-chapter files. Hidden files,
+/// symlinks, `.bak` backups, the manifest, the template, and the site
+/// folder are skipped.
+fn copy_assets(
+    root: &Path,
+    dir: &Path,
+    out: &Path,
+    skip: &Path,
+    excluded: &[String],
+    summary: &mut SiteSummary,
+) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        let file_type = entry.file_type()?;
+        if entry.file_name().to_string_lossy().starts_with('.') || file_type.is_symlink() {
+            continue;
+        }
+        if file_type.is_dir() {
+            if fs::canonicalize(&path)? != skip {
+                copy_assets(root, &path, out, skip, excluded, summary)?;
+            }
+            continue;
+        }
+
+        let ext = path.extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or("")
+            .to_lowercase();
+        if matches!(ext.as_str(), "bak" | "html" | "htm" | "xhtml") {
+            continue;
+        }
+        let relative = path.strip_prefix(root)
+            .unwrap_or(&path)
+            .to_string_lossy()
+            .replace('\\', "/");
+        if excluded.contains(&relative) {
+            continue;
+        }
+
+        let target = out.join(&relative);
+        summary.assets.push(relative.clone());
+        let source_meta = entry.metadata()?;
+        let up_to_date = fs::metadata(&target).is_ok_and(|target_meta| {
+            target_meta.len() == source_meta.len()
+                && matches!(
+                    (source_meta.modified(), target_meta.modified()),
+                    (Ok(source), Ok(copied)) if copied >= source
+                )
+        });
+        if up_to_date {
+            continue;
+        }
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::copy(&path, &target)?;
+        summary.written.push(relative);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_export_site() {
+        let dir = std::env::temp_dir().join(format!("rhe_site_{}", std::process::id()));
+        let root = dir.join("book");
+        let out = dir.join("site");
+        fs::create_dir_all(root.join("part1")).unwrap();
+        fs::write(root.join("book.css"), "p { margin: 0; }").unwrap();
+        fs::write(root.join("intro.html"), "<h1>Intro &amp; Aims</h1><p>{{content}}</p>\n").unwrap();
+        fs::write(root.join("part1/ch01.html"), "<h1>First</h1>\n").unwrap();
+        fs::write(
+            root.join("layout.html"),
+            "<title>{{title}} | {{site_title}}</title>{{head}}<p>{{breadcrumbs}}</p>{{toc}}{{content}}{{prev}}{{next}}{{unknown}}",
+        ).unwrap();
+        fs::write(
+            root.join("manifest.json"),
+            "{\"chapters\": [\"intro.html\", \"part1/ch01.html\"], \"site\": {\"template\": \"layout.html\", \"title\": \"Guide\"}}",
+        ).unwrap();
+
+        let summary = export_site(&root, &out).unwrap();
+        assert_eq!(summary.pages, vec!["intro.html", "part1/ch01.html", "index.html"]);
+        assert_eq!(summary.assets, vec!["book.css"]);
+        assert_eq!(summary.written.len(), 4);
+
+        let ch01 = fs::read_to_string(out.join("part1/ch01.html")).unwrap();
+        assert_eq!(
+            ch01,
+            "<title>First | Guide</title><link rel=\"stylesheet\" href=\"../book.css\">\
+             <p><a href=\"../index.html\">Guide</a> / <span>part1</span> / <span aria-current=\"page\">First</span></p>\
+             <ol>\n<li><a href=\"../intro.html\">Intro &amp; Aims</a></li>\n\
+             <li class=\"current\"><a href=\"ch01.html\" aria-current=\"page\">First</a></li>\n</ol>\
+             <h1>First</h1><a rel=\"prev\" href=\"../intro.html\">Previous: Intro &amp; Aims</a>{{unknown}}",
+        );
+        let intro = fs::read_to_string(out.join("intro.html")).unwrap();
+        assert!(intro.contains("<p>{{content}}</p><a rel=\"next\" href=\"part1/ch01.html\">Next: First</a>"));
+        let index = fs::read_to_string(out.join("index.html")).unwrap();
+        assert!(index.contains("<h1>Guide</h1>\n<ol>"));
+
+        // Only the edited chapter is written again
+        fs::write(root.join("part1/ch01.html"), "<h1>First</h1>\n<p>More</p>\n").unwrap();
+        let summary = export_site(&root, &out).unwrap();
+        assert_eq!(summary.written, vec!["part1/ch01.html"]);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,101 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use scraper::{Html, Selector};
+
+use crate::html_parser;
+use crate::manifest;
+
+/// Counts for one chapter.
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize)]
+pub struct ChapterStats {
+    pub relative_path: String,
+    /// Whitespace-separated words of body text
+    pub words: usize,
+    /// Characters of body text, excluding whitespace
+    pub characters: usize,
+    pub paragraphs: usize,
+    pub headings: usize,
+    pub images: usize,
+    pub links: usize,
+}
+
+/// Counts for a whole project, chapters in manifest order.
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct ProjectStats {
+    pub chapters: Vec<ChapterStats>,
+    pub total_words: usize,
+    pub total_characters: usize,
+}
+
+/// Count words, characters, and elements in a chapter body.
+/// Text inside `<script>` and `<style>` is not counted.
+pub fn body_stats(body_html: &str) -> ChapterStats {
+    let fragment = Html::parse_fragment(body_html);
+    let count = |selector: &str| {
+        let selector = Selector::parse(selector).unwrap();
+        fragment.select(&selector).count()
+    };
+
+    let mut words = 0;
+    let mut characters = 0;
+    for node in fragment.root_element().descendants() {
+        let Some(text) = node.value().as_text() else {
+            continue;
+        };
+        let hidden = node.ancestors()
+            .filter_map(|a| a.value().as_element())
+            .any(|e| matches!(e.name(), "script" | "style"));
+        if hidden {
+            continue;
+        }
+        words += text.split_whitespace().count();
+        characters += text.chars().filter(|c| !c.is_whitespace()).count();
+    }
+
+    ChapterStats {
+        relative_path: String::new(),
+        words,
+        characters,
+        paragraphs: count("p"),
+        headings: count("h1, h2, h3, h4, h5, h6"),
+        images: count("img"),
+        links: count("a[href]"),
+    }
+}
+
+/// Statistics for every chapter of a project.
+pub fn project_stats(root: &Path) -> io::Result<ProjectStats> {
+    let mut stats = ProjectStats::default();
+    for chapter in manifest::list_chapters(root)? {
+        let raw = fs::read_to_string(&chapter.path)?;
+        let split = html_parser::split_html(&raw);
+        let mut chapter_stats = body_stats(&split.body_content);
+        chapter_stats.relative_path = chapter.relative_path;
+
+        stats.total_words += chapter_stats.words;
+        stats.total_characters += chapter_stats.characters;
+        stats.chapters.push(chapter_stats);
+    }
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_body_stats() {
+        let body = "<h1>Chapter One</h1>\n<p>It was a <em>dark</em> night.</p>\n\
+            <p><img src=\"a.png\"> <a href=\"ch02.html\">Next</a></p>\n\
+            <style>p { color: red; }</style>";
+        let stats = body_stats(body);
+        assert_eq!(stats.words, 8);
+        assert_eq!(stats.characters, "ChapterOneItwasadarknight.Next".len());
+        assert_eq!(stats.paragraphs, 2);
+        assert_eq!(stats.headings, 1);
+        assert_eq!(stats.images, 1);
+        assert_eq!(stats.links, 1);
+    }
+}
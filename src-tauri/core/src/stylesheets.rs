@@ -0,0 +1,221 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use scraper::{Html, Selector};
+
+use crate::links;
+
+/// A stylesheet applied to a chapter, with the file it came from.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Stylesheet {
+    pub path: String,
+    pub css: String,
+}
+
+/// Stylesheets linked from a chapter's head, in cascade order.
+///
+/// Each `<link rel="stylesheet">` is resolved relative to the chapter file.
+/// A sheet's `@import`s come before the sheet itself, as in the browser
+/// cascade. Alternate stylesheets, remote URLs, missing files, and sheets
+/// already loaded (including `@import` cycles) are skipped.
+pub fn chapter_stylesheets(chapter_path: &Path, head: &str) -> Vec<Stylesheet> {
+    let chapter_dir = chapter_path.parent().unwrap_or(Path::new(""));
+    let mut visited: HashSet<PathBuf> = HashSet::new();
+    let mut sheets: Vec<Stylesheet> = Vec::new();
+
+    for href in stylesheet_hrefs(head) {
+        if let Some(path) = resolve_url(chapter_dir, &href) {
+            load_with_imports(&path, &mut visited, &mut sheets);
+        }
+    }
+    sheets
+}
+
+/// A stylesheet file and everything it imports, in cascade order.
+pub fn stylesheet_chain(path: &Path) -> Vec<Stylesheet> {
+    let mut visited: HashSet<PathBuf> = HashSet::new();
+    let mut sheets: Vec<Stylesheet> = Vec::new();
+    load_with_imports(path, &mut visited, &mut sheets);
+    sheets
+}
+
+/// Load a stylesheet after recursively loading its imports.
+fn load_with_imports(path: &Path, visited: &mut HashSet<PathBuf>, sheets: &mut Vec<Stylesheet>) {
+    // Canonical paths make cycle detection independent of ../ spelling
+    let Ok(canonical) = fs::canonicalize(path) else {
+        return;
+    };
+    if !visited.insert(canonical.clone()) {
+        return;
+    }
+    let Ok(css) = fs::read_to_string(&canonical) else {
+        return;
+    };
+
+    let sheet_dir = canonical.parent().unwrap_or(Path::new(""));
+    for import in css_imports(&css) {
+        if let Some(import_path) = resolve_url(sheet_dir, &import) {
+            load_with_imports(&import_path, visited, sheets);
+        }
+    }
+
+    sheets.push(Stylesheet {
+        path: canonical.to_string_lossy().to_string(),
+        css,
+    });
+}
+
+/// The `href` of each non-alternate `<link rel="stylesheet">` in a head.
+pub fn stylesheet_hrefs(head: &str) -> Vec<String> {
+    let document = Html::parse_document(&format!("<html><head>{}</head></html>", head));
+    let selector = Selector::parse("link[rel][href]").unwrap();
+
+    document.select(&selector)
+        .filter(|link| {
+            let rel = link.value().attr("rel").unwrap_or("").to_lowercase();
+            let tokens: Vec<&str> = rel.split_ascii_whitespace().collect();
+            tokens.contains(&"stylesheet") && !tokens.contains(&"alternate")
+        })
+        .filter_map(|link| link.value().attr("href").map(str::to_string))
+        .collect()
+}
+
+/// The text of each `<style>` element in a document head.
+pub fn head_styles(head: &str) -> Vec<String> {
+    let document = Html::parse_document(&format!("<html><head>{}</head></html>", head));
+    let selector = Selector::parse("head style").unwrap();
+    document.select(&selector)
+        .map(|style| style.text().collect::<String>())
+        .collect()
+}
+
+/// URLs of the `@import` rules at the top of a stylesheet.
+/// Scanning stops at the first rule block, since later imports are ignored.
+pub fn css_imports(css: &str) -> Vec<String> {
+    scan_imports(css).0
+}
+
+/// Byte offset where the `@charset` and `@import` rules at the top of a
+/// stylesheet end, so they can be replaced by the imported sheets.
+pub fn import_block_end(css: &str) -> usize {
+    scan_imports(css).1
+}
+
+/// Import URLs and the end of the import block.
+fn scan_imports(css: &str) -> (Vec<String>, usize) {
+    let mut imports = Vec::new();
+    let mut rest = css;
+
+    loop {
+        rest = rest.trim_start();
+        if let Some(after) = rest.strip_prefix("/*") {
+            // Skip comments
+            match after.find("*/") {
+                Some(end) => rest = &after[end + 2..],
+                None => break,
+            }
+            continue;
+        }
+
+        let is_import = starts_with_ignore_case(rest, "@import");
+        if is_import {
+            let statement_end = rest.find(';').unwrap_or(rest.len());
+            if let Some(url) = import_url(&rest[7..statement_end]) {
+                imports.push(url);
+            }
+            rest = &rest[(statement_end + 1).min(rest.len())..];
+            continue;
+        }
+
+        // @charset may precede imports; anything else ends the import block
+        if starts_with_ignore_case(rest, "@charset") {
+            match rest.find(';') {
+                Some(end) => rest = &rest[end + 1..],
+                None => break,
+            }
+            continue;
+        }
+        break;
+    }
+    (imports, css.len() - rest.len())
+}
+
+/// Extract the URL from the body of an `@import` rule:
+/// `url("a.css")`, `url(a.css)`, or `"a.css"`, optionally followed by media.
+fn import_url(rule: &str) -> Option<String> {
+    let rule = rule.trim_start();
+    let value = if starts_with_ignore_case(rule, "url(") {
+        let inner = &rule[4..];
+        let close = inner.find(')')?;
+        inner[..close].trim()
+    } else {
+        let end = rule.find(char::is_whitespace).unwrap_or(rule.len());
+        &rule[..end]
+    };
+
+    let unquoted = value.trim_matches(|c| c == '"' || c == '\'');
+    if unquoted.is_empty() {
+        None
+    } else {
+        Some(unquoted.to_string())
+    }
+}
+
+/// ASCII case-insensitive prefix test.
+fn starts_with_ignore_case(text: &str, prefix: &str) -> bool {
+    text.get(..prefix.len())
+        .is_some_and(|start| start.eq_ignore_ascii_case(prefix))
+}
+
+/// Resolve a relative URL against a directory. Remote URLs give None.
+fn resolve_url(dir: &Path, url: &str) -> Option<PathBuf> {
+    let (path_part, _) = links::split_url(url);
+    if path_part.is_empty() || !links::is_relative_url(path_part) {
+        return None;
+    }
+    Some(dir.join(links::percent_decode(path_part)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_css_imports() {
+        let css = "@charset \"utf-8\";\n/* base */\n@import url(\"base.css\");\n\
+                   @import 'print.css' print;\n@import url(fonts.css);\n\
+                   body { color: red; }\n@import \"ignored.css\";";
+        assert_eq!(css_imports(css), ["base.css", "print.css", "fonts.css"]);
+        assert!(css[import_block_end(css)..].trim_start().starts_with("body {"));
+    }
+
+    #[test]
+    fn test_chapter_stylesheets_cascade() {
+        let dir = std::env::temp_dir()
+            .join(format!("rhe_stylesheets_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("css")).unwrap();
+        fs::create_dir_all(dir.join("part1")).unwrap();
+
+        // book.css imports base.css, which imports book.css again (a cycle)
+        fs::write(dir.join("css/book.css"), "@import \"base.css\";\nh1 { color: red; }").unwrap();
+        fs::write(dir.join("css/base.css"), "@import url(book.css);\nbody { margin: 0; }").unwrap();
+        fs::write(dir.join("extra.css"), "p { margin: 1em; }").unwrap();
+
+        let head = r#"<title>Ch</title>
+<link rel="stylesheet" href="../css/book.css">
+<link rel="alternate stylesheet" href="../extra.css">
+<link rel="stylesheet" href="https://example.com/remote.css">
+<link rel="stylesheet" href="../extra.css">"#;
+        let sheets = chapter_stylesheets(&dir.join("part1/ch01.html"), head);
+
+        let names: Vec<String> = sheets.iter()
+            .map(|s| Path::new(&s.path).file_name().unwrap().to_string_lossy().to_string())
+            .collect();
+        assert_eq!(names, ["base.css", "book.css", "extra.css"]);
+        assert!(sheets[1].css.contains("h1 { color: red; }"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
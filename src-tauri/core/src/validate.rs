@@ -0,0 +1,193 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::html_parser;
+use crate::links;
+use crate::manifest::{Manifest, MANIFEST_FILENAME};
+use crate::project;
+
+/// What is wrong with a project file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProblemKind {
+    /// The file could not be read
+    Unreadable,
+    /// The file is not valid UTF-8
+    Encoding,
+    /// Saving the file unchanged would not reproduce its head and body
+    RoundTrip,
+    /// A relative `href` or `src` points at a file that does not exist
+    BrokenLink,
+    /// A relative `href` or `src` climbs above the project root
+    LinkOutsideProject,
+    /// The manifest lists a chapter with no file on disk
+    MissingChapter,
+    /// The manifest cannot be parsed
+    InvalidManifest,
+}
+
+/// One problem found in a project.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct Problem {
+    /// Project-relative path of the file with the problem
+    pub relative_path: String,
+    pub kind: ProblemKind,
+    pub message: String,
+}
+
+/// Result of validating a project.
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct ValidationReport {
+    pub chapters_checked: usize,
+    pub problems: Vec<Problem>,
+}
+
+impl ValidationReport {
+    pub fn is_valid(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+/// Check every chapter of a project: encoding, split and reassembly,
+/// relative links, and the manifest's chapter list.
+pub fn validate_project(root: &Path) -> io::Result<ValidationReport> {
+    let chapters = project::list_html_files(root)?;
+    let mut report = ValidationReport::default();
+
+    match Manifest::load(root) {
+        Ok(manifest) => {
+            for entry in &manifest.chapters {
+                if !chapters.iter().any(|c| &c.relative_path == entry) {
+                    report.problems.push(Problem {
+                        relative_path: MANIFEST_FILENAME.to_string(),
+                        kind: ProblemKind::MissingChapter,
+                        message: format!("Chapter {} is listed but does not exist", entry),
+                    });
+                }
+            }
+        }
+        Err(e) => report.problems.push(Problem {
+            relative_path: MANIFEST_FILENAME.to_string(),
+            kind: ProblemKind::InvalidManifest,
+            message: e.to_string(),
+        }),
+    }
+
+    for chapter in &chapters {
+        report.chapters_checked += 1;
+        let doc = chapter.relative_path.replace('\\', "/");
+        let problem = |kind, message| Problem {
+            relative_path: doc.clone(),
+            kind,
+            message,
+        };
+
+        let bytes = match fs::read(&chapter.path) {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                report.problems.push(problem(ProblemKind::Unreadable, e.to_string()));
+                continue;
+            }
+        };
+        let raw = match String::from_utf8(bytes) {
+            Ok(raw) => raw,
+            Err(e) => {
+                let offset = e.utf8_error().valid_up_to();
+                report.problems.push(problem(
+                    ProblemKind::Encoding,
+                    format!("Invalid UTF-8 at byte {}", offset),
+                ));
+                continue;
+            }
+        };
+
+        let mut problems = check_chapter(root, &doc, &raw);
+        report.problems.append(&mut problems);
+    }
+    Ok(report)
+}
+
+/// Check one chapter's text: reassembly and relative links.
+pub fn check_chapter(root: &Path, doc: &str, raw: &str) -> Vec<Problem> {
+    let mut problems = Vec::new();
+    let problem = |kind, message| Problem {
+        relative_path: doc.to_string(),
+        kind,
+        message,
+    };
+
+    // A save without edits must give back the same head and body
+    let split = html_parser::split_html(raw);
+    let reassembled = html_parser::reassemble_html(
+        &split.doctype, &split.head_content, &split.body_content, split.is_fragment,
+    );
+    if let Err(mismatch) = html_parser::verify_round_trip(
+        &reassembled, &split.body_content, &split.head_content, split.is_fragment,
+    ) {
+        problems.push(problem(ProblemKind::RoundTrip, mismatch));
+    }
+
+    for span in links::attribute_spans(raw, &["href", "src"]) {
+        let url = &raw[span.start..span.end];
+        if !links::is_relative_url(url) {
+            continue;
+        }
+        let (url_path, _) = links::split_url(url);
+        if url_path.is_empty() {
+            // Same-document fragment or query
+            continue;
+        }
+        let decoded = links::percent_decode(url_path);
+        match links::resolve_relative(doc, &decoded) {
+            Some(target) if root.join(&target).exists() => {}
+            Some(target) => problems.push(problem(
+                ProblemKind::BrokenLink,
+                format!("{}=\"{}\" points at missing file {}", span.name, url, target),
+            )),
+            None => problems.push(problem(
+                ProblemKind::LinkOutsideProject,
+                format!("{}=\"{}\" leaves the project folder", span.name, url),
+            )),
+        }
+    }
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_project() {
+        let root = std::env::temp_dir().join(format!("rhe_validate_{}", std::process::id()));
+        fs::create_dir_all(root.join("images")).unwrap();
+        fs::write(root.join("images/cover.png"), b"png").unwrap();
+        fs::write(
+            root.join("ch01.html"),
+            "<p><img src=\"images/cover.png\"><a href=\"ch02.html#top\">Next</a>\
+             <a href=\"#notes\">Notes</a><a href=\"https://example.com/\">Web</a></p>",
+        ).unwrap();
+        fs::write(
+            root.join("ch02.html"),
+            "<p><img src=\"images/missing%20file.png\"><a href=\"../outside.html\">Out</a></p>",
+        ).unwrap();
+        fs::write(root.join("ch03.html"), b"<p>caf\xe9</p>").unwrap();
+        fs::write(root.join(MANIFEST_FILENAME), r#"{"chapters": ["ch01.html", "gone.html"]}"#).unwrap();
+
+        let report = validate_project(&root).unwrap();
+        let kinds: Vec<(&str, ProblemKind)> = report.problems.iter()
+            .map(|p| (p.relative_path.as_str(), p.kind))
+            .collect();
+        assert_eq!(report.chapters_checked, 3);
+        assert_eq!(kinds, vec![
+            (MANIFEST_FILENAME, ProblemKind::MissingChapter),
+            ("ch02.html", ProblemKind::BrokenLink),
+            ("ch02.html", ProblemKind::LinkOutsideProject),
+            ("ch03.html", ProblemKind::Encoding),
+        ]);
+        assert!(report.problems[1].message.contains("images/missing file.png"));
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
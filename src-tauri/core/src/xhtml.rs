@@ -0,0 +1,233 @@
+use std::collections::HashSet;
+
+use ego_tree::NodeRef;
+use scraper::{Html, Node};
+
+use crate::html_parser;
+
+/// XHTML namespace, declared on the root `<html>` element.
+pub const XHTML_NS: &str = "http://www.w3.org/1999/xhtml";
+
+/// Elements written as self-closing tags.
+const VOID_ELEMENTS: [&str; 14] = [
+    "area", "base", "br", "col", "embed", "hr", "img", "input",
+    "link", "meta", "param", "source", "track", "wbr",
+];
+
+/// A heading found while converting a chapter body.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct Heading {
+    /// 1 for `<h1>` through 6 for `<h6>`
+    pub level: u8,
+    /// The heading's `id`, generated if it had none
+    pub id: String,
+    /// Heading text with whitespace collapsed
+    pub text: String,
+}
+
+/// Convert an HTML fragment to well-formed XHTML markup: void elements
+/// self-close, attributes are quoted, text is escaped, and SVG and MathML
+/// roots declare their namespaces. Headings without an `id` get a
+/// generated one so tables of contents can link to them; all headings are
+/// returned in document order.
+pub fn fragment_to_xhtml(html: &str) -> (String, Vec<Heading>) {
+    let fragment = Html::parse_fragment(html);
+
+    // Ids already used in the fragment, so generated ones do not collide
+    let mut ids: HashSet<String> = fragment.root_element()
+        .descendants()
+        .filter_map(|node| node.value().as_element())
+        .filter_map(|element| element.id().map(str::to_string))
+        .collect();
+
+    let mut writer = XhtmlWriter {
+        out: String::with_capacity(html.len() + html.len() / 8),
+        headings: Vec::new(),
+        ids: &mut ids,
+    };
+    for child in fragment.root_element().children() {
+        writer.write_node(child, XHTML_NS);
+    }
+    (writer.out, writer.headings)
+}
+
+/// Assemble a complete XHTML document. `head` is XHTML markup for the
+/// head (without `<title>`, which is added from `title`).
+pub fn document(title: &str, lang: &str, head: &str, body: &str) -> String {
+    let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<!DOCTYPE html>\n");
+    out += &format!(
+        "<html xmlns=\"{}\" xmlns:epub=\"http://www.idpf.org/2007/ops\" xml:lang=\"{}\" lang=\"{}\">\n",
+        XHTML_NS,
+        html_parser::escape_attribute(lang),
+        html_parser::escape_attribute(lang),
+    );
+    out += "<head>\n<meta charset=\"utf-8\"/>\n";
+    out += &format!("<title>{}</title>\n", html_parser::escape_text(title));
+    out += head;
+    if !head.is_empty() && !head.ends_with('\n') {
+        out.push('\n');
+    }
+    out += "</head>\n<body>\n";
+    out += body;
+    if !body.ends_with('\n') {
+        out.push('\n');
+    }
+    out += "</body>\n</html>\n";
+    out
+}
+
+/// Serializer state for one fragment.
+struct XhtmlWriter<'a> {
+    out: String,
+    headings: Vec<Heading>,
+    ids: &'a mut HashSet<String>,
+}
+
+impl XhtmlWriter<'_> {
+    fn write_node(&mut self, node: NodeRef<Node>, parent_ns: &str) {
+        match node.value() {
+            Node::Text(text) => self.out += &html_parser::escape_text(text),
+            Node::Comment(comment) => {
+                // "--" may not appear inside an XML comment, nor "-" at its end
+                let mut text = comment.replace("--", "- -");
+                if text.ends_with('-') {
+                    text.push(' ');
+                }
+                self.out += &format!("<!--{}-->", text);
+            }
+            Node::Element(element) => {
+                let name = element.name();
+                let ns: &str = &element.name.ns;
+                self.out.push('<');
+                self.out += name;
+                if ns != parent_ns {
+                    self.out += &format!(" xmlns=\"{}\"", ns);
+                }
+
+                for (attr, value) in element.attrs.iter() {
+                    let attr_name = match &attr.prefix {
+                        Some(prefix) => {
+                            // Foreign attributes such as xlink:href need their prefix bound
+                            if &**prefix != "xml" && &**prefix != "xmlns" {
+                                self.out += &format!(" xmlns:{}=\"{}\"", prefix, &*attr.ns);
+                            }
+                            format!("{}:{}", prefix, attr.local)
+                        }
+                        None => attr.local.to_string(),
+                    };
+                    if attr_name == "xmlns" || !is_xml_name(&attr_name) {
+                        continue;
+                    }
+                    self.out += &format!(" {}=\"{}\"", attr_name, html_parser::escape_attribute(value));
+                }
+
+                let level = heading_level(name, ns);
+                if let Some(level) = level {
+                    let id = match element.id() {
+                        Some(id) => id.to_string(),
+                        None => {
+                            let id = self.unique_id();
+                            self.out += &format!(" id=\"{}\"", id);
+                            id
+                        }
+                    };
+                    let text = node.descendants()
+                        .filter_map(|n| n.value().as_text().map(|t| t.to_string()))
+                        .collect::<String>();
+                    self.headings.push(Heading {
+                        level,
+                        id,
+                        text: text.split_whitespace().collect::<Vec<_>>().join(" "),
+                    });
+                }
+
+                if ns == XHTML_NS && VOID_ELEMENTS.contains(&name) {
+                    self.out += "/>";
+                    return;
+                }
+                if !node.has_children() && ns != XHTML_NS {
+                    self.out += "/>";
+                    return;
+                }
+                self.out.push('>');
+                for child in node.children() {
+                    self.write_node(child, ns);
+                }
+                self.out += &format!("</{}>", name);
+            }
+            _ => {}
+        }
+    }
+
+    /// The next free generated heading id.
+    fn unique_id(&mut self) -> String {
+        let mut n = self.headings.len() + 1;
+        loop {
+            let id = format!("heading-{}", n);
+            if self.ids.insert(id.clone()) {
+                return id;
+            }
+            n += 1;
+        }
+    }
+}
+
+/// Heading level of an HTML `h1`-`h6` element.
+fn heading_level(name: &str, ns: &str) -> Option<u8> {
+    if ns != XHTML_NS {
+        return None;
+    }
+    match name {
+        "h1" => Some(1),
+        "h2" => Some(2),
+        "h3" => Some(3),
+        "h4" => Some(4),
+        "h5" => Some(5),
+        "h6" => Some(6),
+        _ => None,
+    }
+}
+
+/// True if a string is usable as an XML attribute name.
+fn is_xml_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c.is_alphabetic() || c == '_' => {}
+        _ => return false,
+    }
+    chars.all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '.' | ':'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fragment_to_xhtml() {
+        let html = "<h1>Intro <em>one</em></h1><p class=x>A &amp; B<br><img alt=\"\" src=\"a.png\"></p>\
+            <h2 id=\"heading-1\">Taken</h2><h2>Next</h2><!-- a -- b -->\
+            <svg viewBox=\"0 0 1 1\"><circle r=\"1\"></circle><use xlink:href=\"#c\"/></svg>";
+        let (xhtml, headings) = fragment_to_xhtml(html);
+
+        assert_eq!(
+            xhtml,
+            "<h1 id=\"heading-2\">Intro <em>one</em></h1>\
+             <p class=\"x\">A &amp; B<br/><img alt=\"\" src=\"a.png\"/></p>\
+             <h2 id=\"heading-1\">Taken</h2><h2 id=\"heading-3\">Next</h2><!-- a - - b -->\
+             <svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"0 0 1 1\"><circle r=\"1\"/>\
+             <use xmlns:xlink=\"http://www.w3.org/1999/xlink\" xlink:href=\"#c\"/></svg>",
+        );
+        let found: Vec<(u8, &str, &str)> = headings.iter()
+            .map(|h| (h.level, h.id.as_str(), h.text.as_str()))
+            .collect();
+        assert_eq!(found, vec![
+            (1, "heading-2", "Intro one"),
+            (2, "heading-1", "Taken"),
+            (2, "heading-3", "Next"),
+        ]);
+
+        let doc = document("A <b>", "en", "", &xhtml);
+        assert!(doc.starts_with("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<!DOCTYPE html>\n"));
+        assert!(doc.contains("<title>A &lt;b&gt;</title>"));
+    }
+}
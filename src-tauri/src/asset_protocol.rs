@@ -0,0 +1,159 @@
+use std::fs::{self, File};
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Component, Path, PathBuf};
+
+use tauri::http::{header, Request, Response, StatusCode};
+
+use editor_core::{links, media_type};
+
+/// URI scheme that serves files from the opened project to the webview.
+pub const SCHEME: &str = "book";
+
+/// Serve a request for a project file.
+/// Supports single `Range: bytes=` requests so audio and video can seek.
+pub fn handle_request(root: Option<&Path>, request: &Request<Vec<u8>>) -> Response<Vec<u8>> {
+    let Some(root) = root else {
+        return status_response(StatusCode::NOT_FOUND);
+    };
+    let path = match resolve_request_path(root, request.uri().path()) {
+        Ok(path) => path,
+        Err(status) => return status_response(status),
+    };
+
+    let Ok(mut file) = File::open(&path) else {
+        return status_response(StatusCode::NOT_FOUND);
+    };
+    let len = match file.metadata() {
+        Ok(meta) => meta.len(),
+        Err(_) => return status_response(StatusCode::INTERNAL_SERVER_ERROR),
+    };
+
+    let range_header = request.headers()
+        .get(header::RANGE)
+        .and_then(|value| value.to_str().ok());
+    let (status, start, end) = match range_header {
+        Some(value) => match parse_range(value, len) {
+            Some((start, end)) => (StatusCode::PARTIAL_CONTENT, start, end),
+            None => {
+                return Response::builder()
+                    .status(StatusCode::RANGE_NOT_SATISFIABLE)
+                    .header(header::CONTENT_RANGE, format!("bytes */{}", len))
+                    .body(Vec::new())
+                    .unwrap_or_else(|_| status_response(StatusCode::RANGE_NOT_SATISFIABLE));
+            }
+        },
+        None => (StatusCode::OK, 0, len.saturating_sub(1)),
+    };
+
+    // Read only the requested bytes
+    let mut body = Vec::new();
+    if len > 0 {
+        let read = file.seek(SeekFrom::Start(start))
+            .and_then(|_| (&mut file).take(end - start + 1).read_to_end(&mut body));
+        if read.is_err() {
+            return status_response(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    }
+
+    let mut builder = Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, media_type::mime_type(&path))
+        .header(header::CONTENT_LENGTH, body.len())
+        .header(header::ACCEPT_RANGES, "bytes");
+    if status == StatusCode::PARTIAL_CONTENT {
+        builder = builder.header(header::CONTENT_RANGE, format!("bytes {}-{}/{}", start, end, len));
+    }
+    builder.body(body)
+        .unwrap_or_else(|_| status_response(StatusCode::INTERNAL_SERVER_ERROR))
+}
+
+/// Map a request path onto a file inside the project root.
+/// Rejects `..` components and symlinks that lead outside the root.
+fn resolve_request_path(root: &Path, uri_path: &str) -> Result<PathBuf, StatusCode> {
+    let decoded = links::percent_decode(uri_path.trim_start_matches('/'));
+    let relative = Path::new(&decoded);
+    if decoded.is_empty() || !relative.components().all(|c| matches!(c, Component::Normal(_))) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let canonical = fs::canonicalize(root.join(relative))
+        .map_err(|_| StatusCode::NOT_FOUND)?;
+    if !canonical.starts_with(root) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    if !canonical.is_file() {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    Ok(canonical)
+}
+
+/// Parse a single `bytes=` range into inclusive byte offsets.
+/// Returns None if the range is malformed or not satisfiable.
+fn parse_range(value: &str, len: u64) -> Option<(u64, u64)> {
+    let spec = value.trim().strip_prefix("bytes=")?;
+    // Multiple ranges are not supported; serve the first
+    let spec = spec.split(',').next()?.trim();
+    let (start, end) = spec.split_once('-')?;
+
+    let (start, end) = if start.is_empty() {
+        // Suffix range: the last N bytes
+        let suffix: u64 = end.parse().ok()?;
+        if suffix == 0 {
+            return None;
+        }
+        (len.saturating_sub(suffix), len.checked_sub(1)?)
+    } else {
+        let start: u64 = start.parse().ok()?;
+        let end: u64 = if end.is_empty() {
+            len.checked_sub(1)?
+        } else {
+            end.parse::<u64>().ok()?.min(len.checked_sub(1)?)
+        };
+        (start, end)
+    };
+
+    if start > end || start >= len {
+        return None;
+    }
+    Some((start, end))
+}
+
+/// Empty response with a status code.
+fn status_response(status: StatusCode) -> Response<Vec<u8>> {
+    let mut response = Response::new(Vec::new());
+    *response.status_mut() = status;
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_range() {
+        assert_eq!(parse_range("bytes=0-99", 1000), Some((0, 99)));
+        assert_eq!(parse_range("bytes=500-", 1000), Some((500, 999)));
+        assert_eq!(parse_range("bytes=-100", 1000), Some((900, 999)));
+        assert_eq!(parse_range("bytes=900-2000", 1000), Some((900, 999)));
+        assert_eq!(parse_range("bytes=1000-", 1000), None);
+        assert_eq!(parse_range("bytes=5-1", 1000), None);
+        assert_eq!(parse_range("items=0-1", 1000), None);
+    }
+
+    #[test]
+    fn test_resolve_request_path() {
+        let dir = std::env::temp_dir()
+            .join(format!("rhe_assets_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("images")).unwrap();
+        fs::write(dir.join("images/fig 1.png"), b"png").unwrap();
+        let root = fs::canonicalize(&dir).unwrap();
+
+        assert!(resolve_request_path(&root, "/images/fig%201.png").is_ok());
+        assert_eq!(resolve_request_path(&root, "/../secret"), Err(StatusCode::FORBIDDEN));
+        assert_eq!(resolve_request_path(&root, "/images/%2E%2E/%2E%2E/x"), Err(StatusCode::FORBIDDEN));
+        assert_eq!(resolve_request_path(&root, "/missing.png"), Err(StatusCode::NOT_FOUND));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
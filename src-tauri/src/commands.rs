@@ -1,154 +1,644 @@
 use std::path::Path;
-use tauri::State;
-use tauri_plugin_dialog::DialogExt;
+use tauri::{Manager, State};
+use tauri_plugin_dialog::{DialogExt, MessageDialogButtons};
 
-use crate::backup::BackupTracker;
-use crate::html_parser;
-use crate::project;
+use editor_core::asset_urls;
+use editor_core::backup::BackupTracker;
+use editor_core::chapter_io::{self, ChapterData};
+use editor_core::chapters;
+use editor_core::diff;
+use editor_core::docx_import::{self, DocxImport};
+use editor_core::epub::{self, EpubMetadata, EpubSummary};
+use editor_core::epub_check::{self, EpubCheckReport};
+use editor_core::epub_import::{self, EpubImport};
+use editor_core::error::{EditorError, EditorResult, ErrorKind};
+use editor_core::git;
+use editor_core::history;
+use editor_core::html_parser;
+use editor_core::links;
+use editor_core::lock::{self, LockManager};
+use editor_core::manifest;
+use editor_core::markdown::{self, MarkdownText};
+use editor_core::markdown_import::{self, MarkdownFolderImport, MarkdownHtml};
+use editor_core::merge;
+use editor_core::preview::PreviewManager;
+use editor_core::project;
+use editor_core::recovery;
+use editor_core::scope::PathScope;
+use editor_core::single_file::{self, SingleFileSummary};
+use editor_core::site::{self, SiteSummary};
 
-/// Data returned when reading a chapter.
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
-pub struct ChapterData {
-    pub filename: String,
-    pub body_html: String,
-    pub css: String,
-    pub original_head: String,
-    /// True if the file is a body-only fragment (no <html>/<head>/<body> wrapper)
-    pub is_fragment: bool,
+/// A file picked with File > Open File.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct OpenedFile {
+    pub path: String,
+    /// The file's folder, if the user chose to open it as the project
+    pub project_dir: Option<String>,
+}
+
+/// Data returned after moving a chapter or asset.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct MoveResult {
+    pub chapters: Vec<project::ChapterMeta>,
+    /// Chapters whose links were rewritten to follow the move
+    pub links: links::LinkRewriteSummary,
 }
 
 /// Open a native file picker for HTML files and return the selected path.
+/// The picked file is added to the scope, and a native prompt offers to
+/// open its folder as the project so the files beside it are listed.
 #[tauri::command]
-pub async fn open_file(app: tauri::AppHandle) -> Result<String, String> {
+pub async fn open_file(app: tauri::AppHandle, scope: State<'_, PathScope>) -> EditorResult<OpenedFile> {
     let file = app.dialog()
         .file()
         .add_filter("HTML files", &["html", "htm", "xhtml"])
         .blocking_pick_file();
 
-    match file {
-        Some(path) => Ok(path.to_string()),
-        None => Err("No file selected".to_string()),
-    }
+    let Some(path) = file else {
+        return Err(EditorError::new(ErrorKind::Cancelled, "No file selected"));
+    };
+    let path_str = path.to_string();
+    let path = Path::new(&path_str);
+    scope.allow_file(path)?;
+
+    let Some(folder) = path.parent().filter(|p| !p.as_os_str().is_empty()) else {
+        return Ok(OpenedFile { path: path_str, project_dir: None });
+    };
+    let open_folder = app.dialog()
+        .message(format!(
+            "Open the folder {} as a project? The files beside this one are listed and can be edited.",
+            folder.display(),
+        ))
+        .title("Open File")
+        .buttons(MessageDialogButtons::OkCancelCustom("Open Folder".to_string(), "Just This File".to_string()))
+        .blocking_show();
+    let project_dir = if open_folder {
+        scope.set_project(folder)?;
+        Some(folder.to_string_lossy().to_string())
+    } else {
+        None
+    };
+    Ok(OpenedFile { path: path_str, project_dir })
 }
 
 /// Open a native folder picker and return the selected path.
+/// The folder becomes the project scope for file commands.
 #[tauri::command]
-pub async fn open_project(app: tauri::AppHandle) -> Result<String, String> {
+pub async fn open_project(app: tauri::AppHandle, scope: State<'_, PathScope>) -> EditorResult<String> {
     let folder = app.dialog()
         .file()
         .blocking_pick_folder();
 
     match folder {
-        Some(path) => Ok(path.to_string()),
-        None => Err("No folder selected".to_string()),
+        Some(path) => {
+            let path_str = path.to_string();
+            scope.set_project(Path::new(&path_str))?;
+            Ok(path_str)
+        }
+        None => Err(EditorError::new(ErrorKind::Cancelled, "No folder selected")),
     }
 }
 
-/// List HTML chapter files in the project directory.
+/// List HTML chapter files in the project directory, in manifest order.
 #[tauri::command]
-pub fn list_chapters(project_dir: String) -> Result<Vec<project::ChapterMeta>, String> {
-    let dir = Path::new(&project_dir);
-    if !dir.is_dir() {
-        return Err(format!("Not a directory: {}", project_dir));
-    }
-    project::list_html_files(dir)
-        .map_err(|e| format!("Failed to list chapters: {}", e))
+pub fn list_chapters(
+    project_dir: String,
+    scope: State<'_, PathScope>,
+) -> EditorResult<Vec<project::ChapterMeta>> {
+    let dir = scope.resolve_dir(Path::new(&project_dir))?;
+    chapter_list(&dir)
 }
 
-/// Read a chapter file, splitting into body HTML, CSS, and original head.
+/// Remove temp files left in the project by interrupted saves.
+/// Files younger than a minute are kept in case a save is in progress.
+/// Returns the removed paths.
 #[tauri::command]
-pub fn read_chapter(file_path: String, project_dir: String) -> Result<ChapterData, String> {
-    let path = Path::new(&file_path);
-    let dir = Path::new(&project_dir);
+pub fn cleanup_temp_files(
+    project_dir: String,
+    scope: State<'_, PathScope>,
+) -> EditorResult<Vec<String>> {
+    let dir = scope.resolve_dir(Path::new(&project_dir))?;
+    let removed = project::cleanup_temp_files(&dir, std::time::Duration::from_secs(60))
+        .map_err(|e| EditorError::io("Failed to clean up temp files", e).with_path(&dir))?;
+    Ok(removed.iter().map(|p| p.to_string_lossy().to_string()).collect())
+}
 
-    // Read the raw HTML
-    let raw_html = std::fs::read_to_string(path)
-        .map_err(|e| format!("Failed to read {}: {}", file_path, e))?;
+/// Chapters of a project directory in manifest order.
+fn chapter_list(dir: &Path) -> EditorResult<Vec<project::ChapterMeta>> {
+    let mut chapters = manifest::list_chapters(dir)
+        .map_err(|e| EditorError::io("Failed to list chapters", e).with_path(dir))?;
+    git::annotate_status(dir, &mut chapters);
+    Ok(chapters)
+}
 
-    // Split into head and body (handles both full docs and fragments)
-    let split = html_parser::split_html(&raw_html);
+/// Create a new chapter from the project template and return the updated list.
+#[tauri::command]
+pub fn create_chapter(
+    project_dir: String,
+    relative_path: String,
+    title: String,
+    after: Option<String>,
+    scope: State<'_, PathScope>,
+) -> EditorResult<Vec<project::ChapterMeta>> {
+    let dir = scope.resolve_dir(Path::new(&project_dir))?;
+    chapters::create_chapter(&dir, &relative_path, &title, after.as_deref())
+        .map_err(|e| EditorError::io(format!("Failed to create {}", relative_path), e).with_path(dir.join(&relative_path)))?;
+    chapter_list(&dir)
+}
 
-    // Read project CSS
-    let css = project::read_css(dir);
-
-    // Get filename
-    let filename = path.file_name()
-        .and_then(|n| n.to_str())
-        .unwrap_or("unknown")
-        .to_string();
-
-    Ok(ChapterData {
-        filename,
-        body_html: split.body_content,
-        css,
-        original_head: split.head_content,
-        is_fragment: split.is_fragment,
+/// Rename or move a chapter within the project, rewriting links to it.
+#[tauri::command]
+pub fn rename_chapter(
+    project_dir: String,
+    from: String,
+    to: String,
+    tracker: State<'_, BackupTracker>,
+    scope: State<'_, PathScope>,
+) -> EditorResult<MoveResult> {
+    let dir = scope.resolve_dir(Path::new(&project_dir))?;
+    chapters::rename_chapter(&dir, &from, &to)
+        .map_err(|e| EditorError::io(format!("Failed to rename {} to {}", from, to), e).with_path(dir.join(&from)))?;
+    history::move_versions(&dir, &from, &to)
+        .map_err(|e| EditorError::io(format!("Failed to move history of {}", from), e).with_path(dir.join(&from)))?;
+    let links = links::rewrite_moved_links(&dir, &from, &to, &tracker)?;
+
+    Ok(MoveResult {
+        chapters: chapter_list(&dir)?,
+        links,
     })
 }
 
+/// Move an asset file or folder within the project, rewriting links to it.
+#[tauri::command]
+pub fn move_asset(
+    project_dir: String,
+    from: String,
+    to: String,
+    tracker: State<'_, BackupTracker>,
+    scope: State<'_, PathScope>,
+) -> EditorResult<MoveResult> {
+    let dir = scope.resolve_dir(Path::new(&project_dir))?;
+    chapters::move_asset(&dir, &from, &to)
+        .map_err(|e| EditorError::io(format!("Failed to move {} to {}", from, to), e).with_path(dir.join(&from)))?;
+    let links = links::rewrite_moved_links(&dir, &from, &to, &tracker)?;
+
+    Ok(MoveResult {
+        chapters: chapter_list(&dir)?,
+        links,
+    })
+}
+
+/// Duplicate a chapter and return the updated list.
+#[tauri::command]
+pub fn duplicate_chapter(
+    project_dir: String,
+    from: String,
+    to: String,
+    scope: State<'_, PathScope>,
+) -> EditorResult<Vec<project::ChapterMeta>> {
+    let dir = scope.resolve_dir(Path::new(&project_dir))?;
+    chapters::duplicate_chapter(&dir, &from, &to)
+        .map_err(|e| EditorError::io(format!("Failed to duplicate {} to {}", from, to), e).with_path(dir.join(&from)))?;
+    chapter_list(&dir)
+}
+
+/// Move a chapter to the project trash folder and return the updated list.
+#[tauri::command]
+pub fn delete_chapter(
+    project_dir: String,
+    relative_path: String,
+    scope: State<'_, PathScope>,
+) -> EditorResult<Vec<project::ChapterMeta>> {
+    let dir = scope.resolve_dir(Path::new(&project_dir))?;
+    chapters::delete_chapter(&dir, &relative_path)
+        .map_err(|e| EditorError::io(format!("Failed to delete {}", relative_path), e).with_path(dir.join(&relative_path)))?;
+    chapter_list(&dir)
+}
+
+/// Read a chapter file, splitting into body HTML, CSS, and original head.
+/// `project_dir` is None for a single file opened on its own.
+#[tauri::command]
+pub fn read_chapter(
+    file_path: String,
+    project_dir: Option<String>,
+    scope: State<'_, PathScope>,
+) -> EditorResult<ChapterData> {
+    let path = scope.resolve(Path::new(&file_path))?;
+    let dir = match &project_dir {
+        Some(dir) => scope.resolve_dir(Path::new(dir))?,
+        None => path.parent().unwrap_or(&path).to_path_buf(),
+    };
+    chapter_io::load_chapter(&path, &dir, &scope)
+}
+
 /// Write edited body HTML back to a chapter file, preserving the original head.
-/// Creates a .bak backup on the first save per session.
+/// Creates a .bak backup on the first save per session. The written file is
+/// read back and verified; on a mismatch the previous content is restored.
 #[tauri::command]
+#[allow(clippy::too_many_arguments)]
 pub fn write_chapter(
+    app: tauri::AppHandle,
     file_path: String,
     body_html: String,
     original_head: String,
     is_fragment: bool,
     tracker: State<'_, BackupTracker>,
-) -> Result<(), String> {
+    locks: State<'_, LockManager>,
+    scope: State<'_, PathScope>,
+) -> EditorResult<()> {
     let path = Path::new(&file_path);
+    let resolved = scope.resolve(path)?;
 
-    // Create backup on first save per session
-    tracker.backup_if_needed(path)?;
+    // Another instance is editing this chapter
+    if let Some(holder) = locks.held_by_other(&resolved) {
+        let message = format!(
+            "{} is locked by {} on {} (pid {})",
+            file_path, holder.owner, holder.host, holder.pid,
+        );
+        return Err(EditorError::new(ErrorKind::Locked, message).with_path(path));
+    }
 
-    // Build the output content
-    let output = if is_fragment {
-        // Fragment: save body content directly
-        body_html
-    } else {
-        // Full document: read original for doctype, reassemble
-        let raw_html = std::fs::read_to_string(path)
-            .map_err(|e| format!("Failed to read {}: {}", file_path, e))?;
-        let split = html_parser::split_html(&raw_html);
-        html_parser::reassemble_html(
-            &split.doctype,
-            &original_head,
-            &body_html,
-            false,
-        )
-    };
-
-    // Write atomically
-    project::atomic_write(path, &output)
-        .map_err(|e| format!("Failed to write {}: {}", file_path, e))?;
+    chapter_io::save_chapter(path, &body_html, &original_head, is_fragment, &tracker, &scope)?;
+
+    // The saved content supersedes any recovery journal entry; a stale
+    // entry would be dropped on the next launch anyway since it matches disk
+    if let Ok(journal) = journal_dir(&app) {
+        recovery::discard(&journal, &file_path).ok();
+    }
 
     Ok(())
 }
 
+/// Take the advisory lock on a chapter when it is opened for editing.
+/// If another instance holds it, the holder is reported instead.
+#[tauri::command]
+pub fn lock_chapter(
+    file_path: String,
+    locks: State<'_, LockManager>,
+    scope: State<'_, PathScope>,
+) -> EditorResult<lock::LockStatus> {
+    let path = scope.resolve(Path::new(&file_path))?;
+    locks.acquire(&path)
+}
+
+/// Release this instance's lock on a chapter when it is closed.
+#[tauri::command]
+pub fn unlock_chapter(
+    file_path: String,
+    locks: State<'_, LockManager>,
+    scope: State<'_, PathScope>,
+) -> EditorResult<()> {
+    let path = scope.resolve(Path::new(&file_path))?;
+    locks.release(&path)
+}
+
+/// Data returned when recovering a journaled chapter.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct RecoveredChapter {
+    /// Project root reopened for the chapter; None for a file opened on its own
+    pub project_dir: Option<String>,
+    /// The chapter with its unsaved body from the journal
+    pub chapter: ChapterData,
+}
+
+/// Store the unsaved editor body of a chapter in the recovery journal.
+/// Called periodically while the chapter has unsaved changes.
+#[tauri::command]
+pub fn journal_chapter(
+    app: tauri::AppHandle,
+    file_path: String,
+    body_html: String,
+    original_head: String,
+    is_fragment: bool,
+    scope: State<'_, PathScope>,
+) -> EditorResult<()> {
+    let path = Path::new(&file_path);
+    scope.resolve(path)?;
+
+    // Journal relative URLs so recovery does not depend on the asset scheme
+    let body_html = match scope.relative_path(path) {
+        Some(chapter) => asset_urls::from_asset_urls(&body_html, &chapter),
+        None => body_html,
+    };
+    let project_dir = scope.project_root().map(|root| root.to_string_lossy().to_string());
+
+    recovery::record(
+        &journal_dir(&app)?,
+        &file_path,
+        project_dir.as_deref(),
+        &body_html,
+        &original_head,
+        is_fragment,
+    )
+    .map_err(|e| EditorError::io(format!("Failed to journal {}", file_path), e).with_path(path))
+}
+
+/// Chapters with unsaved edits left from an earlier session, each with a
+/// line diff against the file on disk.
+#[tauri::command]
+pub fn list_recoverable_chapters(
+    app: tauri::AppHandle,
+) -> EditorResult<Vec<recovery::RecoverableChapter>> {
+    recovery::list_recoverable(&journal_dir(&app)?)
+        .map_err(|e| EditorError::io("Failed to read recovery journal", e))
+}
+
+/// Reopen the project of a journaled chapter and load the chapter with
+/// its unsaved body. A chapter journaled without a project is allowed on
+/// its own. The journal entry is kept until the chapter is saved.
+#[tauri::command]
+pub fn recover_chapter(
+    app: tauri::AppHandle,
+    file_path: String,
+    scope: State<'_, PathScope>,
+) -> EditorResult<RecoveredChapter> {
+    let entry = recovery::load(&journal_dir(&app)?, &file_path)
+        .map_err(|e| EditorError::io("Failed to read recovery journal", e))?
+        .ok_or_else(|| {
+            EditorError::new(ErrorKind::NotFound, format!("No recovery data for {}", file_path))
+                .with_path(&file_path)
+        })?;
+
+    // The journal only holds chapters that were in scope when recorded
+    let path = Path::new(&file_path);
+    let root = match &entry.project_dir {
+        Some(dir) => Some(scope.set_project(Path::new(dir))?),
+        None => {
+            scope.allow_file(path)?;
+            None
+        }
+    };
+    let path = &scope.resolve(path)?;
+
+    let mut chapter = if path.exists() {
+        let dir = root.clone()
+            .unwrap_or_else(|| path.parent().unwrap_or(path).to_path_buf());
+        chapter_io::load_chapter(path, &dir, &scope)?
+    } else {
+        // The file was deleted since; recovering recreates it on save
+        ChapterData {
+            filename: path.file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            body_html: String::new(),
+            css: String::new(),
+            stylesheets: Vec::new(),
+            original_head: String::new(),
+            is_fragment: true,
+        }
+    };
+    chapter.body_html = match scope.relative_path(path) {
+        Some(relative) => asset_urls::to_asset_urls(&entry.body_html, &relative),
+        None => entry.body_html,
+    };
+    chapter.original_head = entry.original_head;
+    chapter.is_fragment = entry.is_fragment;
+
+    Ok(RecoveredChapter {
+        project_dir: root.map(|root| root.to_string_lossy().to_string()),
+        chapter,
+    })
+}
+
+/// Drop the journaled edits of a chapter.
+#[tauri::command]
+pub fn discard_recovery(app: tauri::AppHandle, file_path: String) -> EditorResult<()> {
+    recovery::discard(&journal_dir(&app)?, &file_path)
+        .map_err(|e| EditorError::io(format!("Failed to discard recovery data for {}", file_path), e).with_path(&file_path))
+}
+
+/// Recovery journal folder in the app data directory.
+fn journal_dir(app: &tauri::AppHandle) -> EditorResult<std::path::PathBuf> {
+    app.path()
+        .app_data_dir()
+        .map(|dir| dir.join(recovery::RECOVERY_DIR))
+        .map_err(|e| EditorError::new(ErrorKind::Io, format!("Failed to locate app data folder: {}", e)))
+}
+
+/// List the saved versions of a chapter, newest first.
+#[tauri::command]
+pub fn list_chapter_versions(
+    file_path: String,
+    scope: State<'_, PathScope>,
+) -> EditorResult<Vec<history::VersionInfo>> {
+    let (root, chapter) = scope.project_location(Path::new(&file_path))?;
+    history::list_versions(&root, &chapter)
+        .map_err(|e| EditorError::io(format!("Failed to list versions of {}", file_path), e).with_path(&file_path))
+}
+
+/// Read a saved version of a chapter for preview.
+#[tauri::command]
+pub fn read_chapter_version(
+    file_path: String,
+    version_id: String,
+    scope: State<'_, PathScope>,
+) -> EditorResult<String> {
+    let (root, chapter) = scope.project_location(Path::new(&file_path))?;
+    history::read_version(&root, &chapter, &version_id)
+        .map_err(|e| EditorError::io(format!("Failed to read version {} of {}", version_id, file_path), e).with_path(&file_path))
+}
+
+/// Line diff from a saved version to the chapter currently on disk.
+#[tauri::command]
+pub fn diff_chapter_version(
+    file_path: String,
+    version_id: String,
+    scope: State<'_, PathScope>,
+) -> EditorResult<Vec<diff::DiffChunk>> {
+    let (root, chapter) = scope.project_location(Path::new(&file_path))?;
+    let path = scope.resolve(Path::new(&file_path))?;
+    let old = history::read_version(&root, &chapter, &version_id)
+        .map_err(|e| EditorError::io(format!("Failed to read version {} of {}", version_id, file_path), e).with_path(&file_path))?;
+    let current = std::fs::read_to_string(&path)
+        .map_err(|e| EditorError::io(format!("Failed to read {}", file_path), e).with_path(&file_path))?;
+    Ok(diff::diff_lines(&old, &current))
+}
+
+/// Restore a saved version over the chapter file and return its content.
+/// The current content is recorded as a version first.
+#[tauri::command]
+pub fn restore_chapter_version(
+    file_path: String,
+    version_id: String,
+    tracker: State<'_, BackupTracker>,
+    scope: State<'_, PathScope>,
+) -> EditorResult<String> {
+    let (root, chapter) = scope.project_location(Path::new(&file_path))?;
+    let path = scope.resolve(Path::new(&file_path))?;
+    tracker.backup_if_needed(&path, Some(&root))?;
+    let policy = manifest::Manifest::load(&root)
+        .map(|m| m.history)
+        .unwrap_or_default();
+    history::restore_version(&root, &chapter, &version_id, &policy)
+        .map_err(|e| EditorError::io(format!("Failed to restore version {} of {}", version_id, file_path), e).with_path(&file_path))
+}
+
+/// Commit the selected chapters to the project's git repository.
+/// Other changes in the working tree or index are not committed.
+#[tauri::command]
+pub fn git_commit_chapters(
+    project_dir: String,
+    file_paths: Vec<String>,
+    message: String,
+    scope: State<'_, PathScope>,
+) -> EditorResult<git::CommitInfo> {
+    let dir = scope.resolve_dir(Path::new(&project_dir))?;
+    let paths = file_paths.iter()
+        .map(|p| scope.resolve(Path::new(p)))
+        .collect::<EditorResult<Vec<std::path::PathBuf>>>()?;
+    let repo = project_repository(&dir)?;
+    git::commit_files(&repo, &paths, &message)
+        .map_err(|e| EditorError::git("Git commit failed", e))
+}
+
+/// Commits that changed a chapter, newest first.
+#[tauri::command]
+pub fn git_chapter_log(
+    file_path: String,
+    limit: Option<usize>,
+    scope: State<'_, PathScope>,
+) -> EditorResult<Vec<git::CommitInfo>> {
+    let path = scope.resolve(Path::new(&file_path))?;
+    let repo = project_repository(&path)?;
+    git::file_log(&repo, &path, limit.unwrap_or(100))
+        .map_err(|e| EditorError::git(format!("Git log failed for {}", file_path), e))
+}
+
+/// Word-level diff of a chapter from HEAD to the file on disk.
+#[tauri::command]
+pub fn git_diff_chapter(
+    file_path: String,
+    scope: State<'_, PathScope>,
+) -> EditorResult<Vec<diff::DiffChunk>> {
+    let path = scope.resolve(Path::new(&file_path))?;
+    let repo = project_repository(&path)?;
+    git::diff_head(&repo, &path)
+        .map_err(|e| EditorError::git(format!("Git diff failed for {}", file_path), e))
+}
+
+/// Restore a chapter to its content at a commit and return that content.
+/// The current file is backed up and recorded in history first.
+#[tauri::command]
+pub fn git_revert_chapter(
+    file_path: String,
+    commit_id: String,
+    tracker: State<'_, BackupTracker>,
+    scope: State<'_, PathScope>,
+) -> EditorResult<String> {
+    let (root, chapter) = scope.project_location(Path::new(&file_path))?;
+    let path = scope.resolve(Path::new(&file_path))?;
+    let repo = project_repository(&path)?;
+
+    tracker.backup_if_needed(&path, Some(&root))?;
+    if let Ok(current) = std::fs::read_to_string(&path) {
+        let policy = manifest::Manifest::load(&root)
+            .map(|m| m.history)
+            .unwrap_or_default();
+        history::record_snapshot(&root, &chapter, &current, &policy)
+            .map_err(|e| EditorError::io(format!("Failed to record history for {}", file_path), e).with_path(&file_path))?;
+    }
+    git::revert_file(&repo, &path, &commit_id)
+        .map_err(|e| EditorError::git(format!("Git revert failed for {}", file_path), e))
+}
+
+/// The git repository containing a project path.
+fn project_repository(path: &Path) -> EditorResult<git::Repository> {
+    let dir = if path.is_dir() { path } else { path.parent().unwrap_or(path) };
+    git::open_repository(dir)
+        .ok_or_else(|| {
+            EditorError::new(ErrorKind::Git, format!("Not a git repository: {}", dir.display()))
+                .with_path(dir)
+        })
+}
+
+/// Three-way merge the editor body with the chapter currently on disk.
+/// `base_body` is the body as it was loaded; `ours_body` is the editor body.
+#[tauri::command]
+pub fn merge_chapter(
+    file_path: String,
+    base_body: String,
+    ours_body: String,
+    scope: State<'_, PathScope>,
+) -> EditorResult<merge::MergeResult> {
+    let path = scope.resolve(Path::new(&file_path))?;
+    let raw_html = std::fs::read_to_string(&path)
+        .map_err(|e| EditorError::io(format!("Failed to read {}", file_path), e).with_path(&file_path))?;
+    let split = html_parser::split_html(&raw_html);
+
+    // Compare against the disk body with the same URLs the editor sees
+    let theirs = match scope.relative_path(&path) {
+        Some(chapter) => asset_urls::to_asset_urls(&split.body_content, &chapter),
+        None => split.body_content,
+    };
+
+    Ok(merge::merge_bodies(&base_body, &ours_body, &theirs))
+}
+
 /// Open a native file picker for Markdown files and return the selected path.
+/// The picked file is added to the scope so it can be read.
 #[tauri::command]
-pub async fn open_markdown_file(app: tauri::AppHandle) -> Result<String, String> {
+pub async fn open_markdown_file(app: tauri::AppHandle, scope: State<'_, PathScope>) -> EditorResult<String> {
     let file = app.dialog()
         .file()
         .add_filter("Markdown files", &["md", "markdown"])
         .blocking_pick_file();
 
     match file {
-        Some(path) => Ok(path.to_string()),
-        None => Err("No file selected".to_string()),
+        Some(path) => {
+            let path_str = path.to_string();
+            scope.allow_file(Path::new(&path_str))?;
+            Ok(path_str)
+        }
+        None => Err(EditorError::new(ErrorKind::Cancelled, "No file selected")),
     }
 }
 
 /// Read a text file and return its contents as a string.
 #[tauri::command]
-pub fn read_text_file(file_path: String) -> Result<String, String> {
-    std::fs::read_to_string(&file_path)
-        .map_err(|e| format!("Failed to read {}: {}", file_path, e))
+pub fn read_text_file(file_path: String, scope: State<'_, PathScope>) -> EditorResult<String> {
+    let path = scope.resolve(Path::new(&file_path))?;
+    std::fs::read_to_string(&path)
+        .map_err(|e| EditorError::io(format!("Failed to read {}", file_path), e).with_path(&file_path))
+}
+
+/// Convert Markdown to HTML for the editor. Front matter replaces the
+/// `<title>` and matching `<meta>` elements of `original_head`, and the
+/// returned `head` is the result. With `file_path`, relative URLs in the
+/// body are resolved against that chapter for display.
+#[tauri::command]
+pub fn markdown_to_html(
+    markdown: String,
+    file_path: Option<String>,
+    original_head: String,
+    scope: State<'_, PathScope>,
+) -> EditorResult<MarkdownHtml> {
+    let mut converted = markdown_import::markdown_to_html(&markdown);
+    converted.head = converted.front_matter.apply_to_head(&original_head);
+    if let Some(chapter) = file_path.and_then(|path| scope.relative_path(Path::new(&path))) {
+        converted.body = asset_urls::to_asset_urls(&converted.body, &chapter);
+    }
+    Ok(converted)
+}
+
+/// Convert the editor's body and the chapter head to Markdown, with the
+/// head's title and named meta elements as YAML front matter.
+#[tauri::command]
+pub fn html_to_markdown(
+    body_html: String,
+    file_path: Option<String>,
+    original_head: String,
+    scope: State<'_, PathScope>,
+) -> EditorResult<MarkdownText> {
+    let body_html = match file_path.and_then(|path| scope.relative_path(Path::new(&path))) {
+        Some(chapter) => asset_urls::from_asset_urls(&body_html, &chapter),
+        None => body_html,
+    };
+    Ok(markdown::html_to_markdown(&original_head, &body_html))
 }
 
 /// Save Markdown content via a native save dialog.
 #[tauri::command]
-pub async fn save_markdown_file(app: tauri::AppHandle, content: String) -> Result<(), String> {
+pub async fn save_markdown_file(app: tauri::AppHandle, content: String) -> EditorResult<()> {
     let file = app.dialog()
         .file()
         .add_filter("Markdown files", &["md"])
@@ -158,24 +648,248 @@ pub async fn save_markdown_file(app: tauri::AppHandle, content: String) -> Resul
         Some(path) => {
             let path_str = path.to_string();
             std::fs::write(&path_str, &content)
-                .map_err(|e| format!("Failed to write {}: {}", path_str, e))?;
+                .map_err(|e| EditorError::write(format!("Failed to write {}", path_str), e).with_path(&path_str))?;
             Ok(())
         }
-        None => Err("No file selected".to_string()),
+        None => Err(EditorError::new(ErrorKind::Cancelled, "No file selected")),
+    }
+}
+
+/// Convert a folder of Markdown files, picked in a dialog, into chapters
+/// of an empty project folder picked in a second dialog. The new folder
+/// becomes the project scope.
+#[tauri::command]
+pub async fn import_markdown_folder(
+    app: tauri::AppHandle,
+    scope: State<'_, PathScope>,
+) -> EditorResult<MarkdownFolderImport> {
+    let Some(source) = app.dialog()
+        .file()
+        .blocking_pick_folder()
+    else {
+        return Err(EditorError::new(ErrorKind::Cancelled, "No folder selected"));
+    };
+    let Some(project) = app.dialog()
+        .file()
+        .blocking_pick_folder()
+    else {
+        return Err(EditorError::new(ErrorKind::Cancelled, "No folder selected"));
+    };
+
+    let project_dir = std::path::PathBuf::from(project.to_string());
+    let imported = markdown_import::import_markdown_folder(Path::new(&source.to_string()), &project_dir)?;
+    scope.set_project(&project_dir)?;
+    Ok(imported)
+}
+
+/// Import a Word document picked in a file dialog into the open project
+/// as new chapters.
+#[tauri::command]
+pub async fn import_docx(
+    app: tauri::AppHandle,
+    project_dir: String,
+    scope: State<'_, PathScope>,
+) -> EditorResult<DocxImport> {
+    let dir = scope.resolve_dir(Path::new(&project_dir))?;
+
+    let file = app.dialog()
+        .file()
+        .add_filter("Word documents", &["docx"])
+        .blocking_pick_file();
+
+    match file {
+        Some(path) => {
+            let path_str = path.to_string();
+            docx_import::import_docx(Path::new(&path_str), &dir)
+        }
+        None => Err(EditorError::new(ErrorKind::Cancelled, "No file selected")),
     }
 }
 
 /// Export a chapter by opening it in the default browser.
 #[tauri::command]
-pub fn export_chapter(file_path: String) -> Result<(), String> {
-    let path = Path::new(&file_path);
+pub fn export_chapter(file_path: String, scope: State<'_, PathScope>) -> EditorResult<()> {
+    let path = scope.resolve(Path::new(&file_path))?;
     if !path.exists() {
-        return Err(format!("File not found: {}", file_path));
+        return Err(EditorError::new(ErrorKind::NotFound, format!("File not found: {}", file_path)).with_path(&path));
     }
 
     // Open in default browser
-    open::that(path)
-        .map_err(|e| format!("Failed to open browser: {}", e))?;
+    open::that(&path)
+        .map_err(|e| EditorError::io("Failed to open browser", e).with_path(&path))?;
 
     Ok(())
 }
+
+/// Export the project as an EPUB 3 book to a path picked in a save dialog.
+/// `metadata` overrides the `epub` section of the project manifest.
+#[tauri::command]
+pub async fn export_epub(
+    app: tauri::AppHandle,
+    project_dir: String,
+    metadata: Option<EpubMetadata>,
+    scope: State<'_, PathScope>,
+) -> EditorResult<EpubSummary> {
+    let dir = scope.resolve_dir(Path::new(&project_dir))?;
+
+    let file = app.dialog()
+        .file()
+        .add_filter("EPUB books", &["epub"])
+        .blocking_save_file();
+
+    match file {
+        Some(path) => {
+            let path_str = path.to_string();
+            epub::export_epub(&dir, Path::new(&path_str), metadata.as_ref())
+        }
+        None => Err(EditorError::new(ErrorKind::Cancelled, "No file selected")),
+    }
+}
+
+/// Export one chapter, or the whole project if `file_path` is None, as a
+/// single self-contained HTML file at a path picked in a save dialog.
+#[tauri::command]
+pub async fn export_single_file(
+    app: tauri::AppHandle,
+    project_dir: String,
+    file_path: Option<String>,
+    scope: State<'_, PathScope>,
+) -> EditorResult<SingleFileSummary> {
+    let (root, chapter) = match &file_path {
+        Some(file_path) => {
+            let (root, chapter) = scope.project_location(Path::new(file_path))?;
+            (root, Some(chapter))
+        }
+        None => (scope.resolve_dir(Path::new(&project_dir))?, None),
+    };
+
+    let file = app.dialog()
+        .file()
+        .add_filter("HTML files", &["html", "htm"])
+        .blocking_save_file();
+
+    match file {
+        Some(path) => {
+            let path_str = path.to_string();
+            single_file::export_single_file(&root, chapter.as_deref(), Path::new(&path_str))
+        }
+        None => Err(EditorError::new(ErrorKind::Cancelled, "No file selected")),
+    }
+}
+
+/// Export the project as a static website into a folder picked in a
+/// dialog. Exporting again to the same folder only rewrites changed files.
+#[tauri::command]
+pub async fn export_site(
+    app: tauri::AppHandle,
+    project_dir: String,
+    scope: State<'_, PathScope>,
+) -> EditorResult<SiteSummary> {
+    let dir = scope.resolve_dir(Path::new(&project_dir))?;
+
+    let folder = app.dialog()
+        .file()
+        .blocking_pick_folder();
+
+    match folder {
+        Some(path) => {
+            let path_str = path.to_string();
+            site::export_site(&dir, Path::new(&path_str))
+        }
+        None => Err(EditorError::new(ErrorKind::Cancelled, "No folder selected")),
+    }
+}
+
+/// Serve the project on a local port and open it in the default browser.
+/// Pages reload when project files or the unsaved chapter change.
+/// Returns the URL opened: the chapter if given, else the chapter index.
+#[tauri::command]
+pub fn start_preview(
+    project_dir: String,
+    file_path: Option<String>,
+    scope: State<'_, PathScope>,
+    preview: State<'_, PreviewManager>,
+) -> EditorResult<String> {
+    let (root, chapter) = match &file_path {
+        Some(file_path) => {
+            let (root, chapter) = scope.project_location(Path::new(file_path))?;
+            (root, Some(chapter))
+        }
+        None => (scope.resolve_dir(Path::new(&project_dir))?, None),
+    };
+
+    let mut url = preview.start(&root)?;
+    if let Some(chapter) = chapter {
+        url += &links::encode_path(&chapter);
+    }
+    open::that(&url)
+        .map_err(|e| EditorError::io("Failed to open browser", e))?;
+    Ok(url)
+}
+
+/// Show the editor's unsaved body of a chapter in the live preview.
+/// Does nothing when no preview is running.
+#[tauri::command]
+pub fn update_preview(
+    file_path: String,
+    body_html: String,
+    scope: State<'_, PathScope>,
+    preview: State<'_, PreviewManager>,
+) -> EditorResult<()> {
+    let (_, chapter) = scope.project_location(Path::new(&file_path))?;
+    preview.update(&chapter, &asset_urls::from_asset_urls(&body_html, &chapter))
+}
+
+/// Stop the live preview server.
+#[tauri::command]
+pub fn stop_preview(preview: State<'_, PreviewManager>) -> EditorResult<()> {
+    preview.stop()
+}
+
+/// Check the structure of an EPUB book picked in a file dialog.
+#[tauri::command]
+pub async fn check_epub(app: tauri::AppHandle) -> EditorResult<EpubCheckReport> {
+    let file = app.dialog()
+        .file()
+        .add_filter("EPUB books", &["epub"])
+        .blocking_pick_file();
+
+    match file {
+        Some(path) => {
+            let path_str = path.to_string();
+            epub_check::check_epub(Path::new(&path_str))
+        }
+        None => Err(EditorError::new(ErrorKind::Cancelled, "No file selected")),
+    }
+}
+
+/// Import an EPUB book picked in a file dialog into a new project folder,
+/// named after the book file, inside a folder picked in a second dialog.
+/// The new folder becomes the project scope.
+#[tauri::command]
+pub async fn import_epub(app: tauri::AppHandle, scope: State<'_, PathScope>) -> EditorResult<EpubImport> {
+    let Some(book) = app.dialog()
+        .file()
+        .add_filter("EPUB books", &["epub"])
+        .blocking_pick_file()
+    else {
+        return Err(EditorError::new(ErrorKind::Cancelled, "No file selected"));
+    };
+    let Some(parent) = app.dialog()
+        .file()
+        .blocking_pick_folder()
+    else {
+        return Err(EditorError::new(ErrorKind::Cancelled, "No folder selected"));
+    };
+
+    let book_str = book.to_string();
+    let book_path = Path::new(&book_str);
+    let name = book_path.file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_else(|| "book".to_string());
+    let project_dir = Path::new(&parent.to_string()).join(name);
+
+    let imported = epub_import::import_epub(book_path, &project_dir)?;
+    scope.set_project(&project_dir)?;
+    Ok(imported)
+}
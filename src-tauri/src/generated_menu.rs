@@ -35,8 +35,26 @@ pub fn setup_menu(app: &tauri::App) -> Result<(), Box<dyn std::error::Error>> {
         .build(app)?;
     let export = MenuItemBuilder::with_id("export", "Export to Browser")
         .build(app)?;
+    let live_preview = MenuItemBuilder::with_id("live_preview", "Live Preview in Browser")
+        .build(app)?;
+    let export_epub = MenuItemBuilder::with_id("export_epub", "Export as EPUB...")
+        .build(app)?;
+    let export_single_chapter = MenuItemBuilder::with_id("export_single_chapter", "Export Chapter as Single File...")
+        .build(app)?;
+    let export_single_book = MenuItemBuilder::with_id("export_single_book", "Export Book as Single File...")
+        .build(app)?;
+    let export_site = MenuItemBuilder::with_id("export_site", "Export as Website...")
+        .build(app)?;
+    let import_epub = MenuItemBuilder::with_id("import_epub", "Import EPUB...")
+        .build(app)?;
+    let check_epub = MenuItemBuilder::with_id("check_epub", "Check EPUB...")
+        .build(app)?;
     let import_markdown = MenuItemBuilder::with_id("import_markdown", "Import Markdown...")
         .build(app)?;
+    let import_markdown_folder = MenuItemBuilder::with_id("import_markdown_folder", "Import Markdown Folder...")
+        .build(app)?;
+    let import_docx = MenuItemBuilder::with_id("import_docx", "Import Word Document...")
+        .build(app)?;
     let export_markdown = MenuItemBuilder::with_id("export_markdown", "Export as Markdown...")
         .build(app)?;
     let close_window = MenuItemBuilder::with_id("close_window", "Close Window")
@@ -49,8 +67,17 @@ pub fn setup_menu(app: &tauri::App) -> Result<(), Box<dyn std::error::Error>> {
         .separator()
         .item(&save)
         .item(&export)
+        .item(&live_preview)
+        .item(&export_epub)
+        .item(&export_single_chapter)
+        .item(&export_single_book)
+        .item(&export_site)
+        .item(&import_epub)
+        .item(&check_epub)
         .separator()
         .item(&import_markdown)
+        .item(&import_markdown_folder)
+        .item(&import_docx)
         .item(&export_markdown)
         .separator()
         .item(&close_window)
@@ -1,28 +1,82 @@
-mod backup;
+mod asset_protocol;
 mod commands;
-mod html_parser;
 mod generated_menu;
-mod project;
+
+use editor_core::{backup, lock, preview, scope};
+use tauri::Manager;
 
 pub fn run() {
     tauri::Builder::default()
         .plugin(tauri_plugin_dialog::init())
         .manage(backup::BackupTracker::new())
+        .manage(scope::PathScope::new())
+        .manage(lock::LockManager::new())
+        .manage(preview::PreviewManager::new())
+        .register_uri_scheme_protocol(asset_protocol::SCHEME, |ctx, request| {
+            let root = ctx.app_handle().state::<scope::PathScope>().project_root();
+            asset_protocol::handle_request(root.as_deref(), &request)
+        })
         .setup(|app| {
             generated_menu::setup_menu(app)?;
+
+            // Keep held chapter locks fresh so other instances see them as live
+            let handle = app.handle().clone();
+            std::thread::spawn(move || loop {
+                std::thread::sleep(std::time::Duration::from_millis(lock::HEARTBEAT_INTERVAL_MS));
+                handle.state::<lock::LockManager>().refresh_all();
+            });
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
             commands::open_file,
             commands::open_project,
             commands::list_chapters,
+            commands::cleanup_temp_files,
+            commands::create_chapter,
+            commands::rename_chapter,
+            commands::duplicate_chapter,
+            commands::delete_chapter,
+            commands::move_asset,
             commands::read_chapter,
             commands::write_chapter,
+            commands::lock_chapter,
+            commands::unlock_chapter,
+            commands::merge_chapter,
+            commands::journal_chapter,
+            commands::list_recoverable_chapters,
+            commands::recover_chapter,
+            commands::discard_recovery,
+            commands::list_chapter_versions,
+            commands::read_chapter_version,
+            commands::diff_chapter_version,
+            commands::restore_chapter_version,
+            commands::git_commit_chapters,
+            commands::git_chapter_log,
+            commands::git_diff_chapter,
+            commands::git_revert_chapter,
             commands::export_chapter,
+            commands::export_epub,
+            commands::export_single_file,
+            commands::export_site,
+            commands::start_preview,
+            commands::update_preview,
+            commands::stop_preview,
+            commands::import_epub,
+            commands::check_epub,
             commands::open_markdown_file,
             commands::read_text_file,
             commands::save_markdown_file,
+            commands::markdown_to_html,
+            commands::html_to_markdown,
+            commands::import_markdown_folder,
+            commands::import_docx,
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|app, event| {
+            // Release chapter locks when the app quits
+            if let tauri::RunEvent::Exit = event {
+                app.state::<lock::LockManager>().release_all();
+            }
+        });
 }
@@ -19,38 +19,38 @@ let currentFile = null;
 let originalHead = null;
 let isFragment = false;
 let chapters = [];
+let previewActive = false;
+let previewTimer = null;
+
+// How often unsaved edits are sent to the recovery journal
+const JOURNAL_INTERVAL_MS = 15000;
+
+// Pause in typing before unsaved edits are sent to the live preview
+const PREVIEW_DELAY_MS = 300;
 
 //============================================
 async function openFile() {
   /**
    * Open a single HTML file via native file picker.
-   * Populates the sidebar with sibling HTML files from the same directory.
+   * If the user also opens its folder, the sidebar lists the sibling
+   * files; otherwise only the picked file is in scope and listed.
    */
-  const filePath = await invoke("open_file");
+  const opened = await invoke("open_file");
+  if (opened.project_dir) {
+    await showProject(opened.project_dir, opened.path);
+    return;
+  }
 
-  // Derive the parent directory from the file path
-  const lastSlash = filePath.lastIndexOf("/");
-  const dir = filePath.substring(0, lastSlash);
-  const filename = filePath.substring(lastSlash + 1);
-  projectDir = dir;
+  const lastSlash = opened.path.lastIndexOf("/");
+  const filename = opened.path.substring(lastSlash + 1);
+  projectDir = null;
+  chapters = [{ path: opened.path, filename: filename, relative_path: filename, git_status: null }];
 
-  // List sibling HTML files in the same directory
-  chapters = await invoke("list_chapters", { projectDir: dir });
-
-  // Populate sidebar with siblings
   const sidebar = document.querySelector("#sidebar");
   initSidebar(sidebar, chapters, (chapter) => {
     loadChapter(chapter);
   });
-
-  // Load the selected file
-  const selected = chapters.find((c) => c.path === filePath);
-  if (selected) {
-    loadChapter(selected);
-  } else {
-    // File might not be in the flat listing; load it directly
-    loadChapter({ path: filePath, filename: filename, relative_path: filename });
-  }
+  loadChapter(chapters[0]);
 }
 
 //============================================
@@ -59,6 +59,15 @@ async function openProject() {
    * Open a project folder via native dialog and load chapters.
    */
   const dir = await invoke("open_project");
+  await showProject(dir);
+}
+
+//============================================
+async function showProject(dir, selectPath = null) {
+  /**
+   * Make a folder the open project and load its chapters.
+   * Loads the chapter at selectPath if given, else the first one.
+   */
   projectDir = dir;
 
   // List chapters in the project (recursively)
@@ -70,8 +79,17 @@ async function openProject() {
     loadChapter(chapter);
   });
 
-  // Auto-load the first chapter if any
-  if (chapters.length > 0) {
+  if (selectPath) {
+    const selected = chapters.find((c) => c.path === selectPath);
+    if (selected) {
+      loadChapter(selected);
+    } else {
+      // File might not be in the listing; load it directly
+      const filename = selectPath.substring(selectPath.lastIndexOf("/") + 1);
+      loadChapter({ path: selectPath, filename: filename, relative_path: filename });
+    }
+  } else if (chapters.length > 0) {
+    // Auto-load the first chapter if any
     loadChapter(chapters[0]);
   }
 }
@@ -87,6 +105,18 @@ async function loadChapter(chapter) {
       projectDir: projectDir,
     });
 
+    // Release the previous chapter and lock this one against other instances
+    if (currentFile && currentFile !== chapter.path) {
+      await invoke("unlock_chapter", { filePath: currentFile });
+    }
+    const lock = await invoke("lock_chapter", { filePath: chapter.path });
+    if (!lock.acquired && lock.holder) {
+      window.alert(
+        `${chapter.filename} is being edited by ${lock.holder.owner} ` +
+        `on ${lock.holder.host}. Saving is disabled until they close it.`
+      );
+    }
+
     currentFile = chapter.path;
     originalHead = data.original_head;
     isFragment = data.is_fragment;
@@ -100,6 +130,7 @@ async function loadChapter(chapter) {
     // Reset dirty state
     setDirty(false);
     updateStatusBar(data.filename, false, editor);
+    schedulePreviewUpdate();
   };
 
   if (getDirty()) {
@@ -139,6 +170,70 @@ async function saveCurrentChapter() {
   updateStatusBar(filename, false, editor);
 }
 
+//============================================
+async function journalCurrentChapter() {
+  /**
+   * Send the unsaved editor body to the Rust recovery journal.
+   */
+  if (!currentFile || !getDirty()) return;
+
+  await invoke("journal_chapter", {
+    filePath: currentFile,
+    bodyHtml: editor.getHTML(),
+    originalHead: originalHead,
+    isFragment: isFragment,
+  });
+}
+
+//============================================
+async function offerRecovery() {
+  /**
+   * On startup, offer to restore unsaved edits left by a crash.
+   */
+  const recoverable = await invoke("list_recoverable_chapters");
+  for (const entry of recoverable) {
+    const changed = entry.diff.filter((c) => c.kind !== "equal").length;
+    const filename = entry.file_path.split("/").pop();
+    const recover = window.confirm(
+      `Unsaved edits to ${filename} were found (${changed} changed sections). ` +
+      "Click OK to recover them, or Cancel to discard."
+    );
+    if (!recover) {
+      await invoke("discard_recovery", { filePath: entry.file_path });
+      continue;
+    }
+
+    const data = await invoke("recover_chapter", { filePath: entry.file_path });
+    projectDir = data.project_dir;
+    if (projectDir) {
+      chapters = await invoke("list_chapters", { projectDir: projectDir });
+    } else {
+      // Journaled without a project: only the file itself is in scope
+      chapters = [{
+        path: entry.file_path,
+        filename: data.chapter.filename,
+        relative_path: data.chapter.filename,
+        git_status: null,
+      }];
+    }
+    const sidebar = document.querySelector("#sidebar");
+    initSidebar(sidebar, chapters, (chapter) => {
+      loadChapter(chapter);
+    });
+
+    currentFile = entry.file_path;
+    originalHead = data.chapter.original_head;
+    isFragment = data.chapter.is_fragment;
+    editor.commands.setContent(data.chapter.body_html);
+    injectCSS(data.chapter.css);
+
+    // Recovered edits are unsaved until the user saves
+    setDirty(true);
+    updateStatusBar(data.chapter.filename, true, editor);
+    return;
+  }
+}
+
 //============================================
 function navigateChapter(direction) {
   /**
@@ -203,18 +298,25 @@ async function toggleFullscreen() {
 async function importMarkdown() {
   /**
    * Import a Markdown file, converting it to HTML in the editor.
+   * Front matter replaces the chapter's title and matching meta tags.
    */
-  const { marked } = await import("marked");
-
   const filePath = await invoke("open_markdown_file");
   const content = await invoke("read_text_file", { filePath: filePath });
 
   // Convert Markdown to HTML
-  const html = marked(content);
-  editor.commands.setContent(html);
+  const converted = await invoke("markdown_to_html", {
+    markdown: content,
+    filePath: currentFile,
+    originalHead: originalHead || "",
+  });
+  editor.commands.setContent(converted.body);
+  originalHead = converted.head;
   setDirty(true);
   const filename = filePath.split("/").pop();
   updateStatusBar(filename, true, editor);
+  if (converted.warnings.length > 0) {
+    alert("Imported with warnings:\n" + converted.warnings.join("\n"));
+  }
 }
 
 //============================================
@@ -222,17 +324,207 @@ async function exportMarkdown() {
   /**
    * Export the current editor content as a Markdown file.
    */
-  const TurndownService = (await import("turndown")).default;
-  const turndown = new TurndownService();
+  // Convert to Markdown, with the head's title and meta tags as front matter
+  const converted = await invoke("html_to_markdown", {
+    bodyHtml: editor.getHTML(),
+    filePath: currentFile,
+    originalHead: originalHead || "",
+  });
 
-  // Get HTML from editor
-  const html = editor.getHTML();
+  // Save via Tauri dialog
+  await invoke("save_markdown_file", { content: converted.markdown });
+  if (converted.warnings.length > 0) {
+    alert("Exported with warnings:\n" + converted.warnings.join("\n"));
+  }
+}
 
-  // Convert to Markdown
-  const markdown = turndown.turndown(html);
+//============================================
+async function exportEpub() {
+  /**
+   * Export the open project as an EPUB book, reporting skipped references.
+   */
+  if (!projectDir) {
+    return;
+  }
+  try {
+    const summary = await invoke("export_epub", { projectDir: projectDir });
+    if (summary.warnings.length > 0) {
+      alert("EPUB written with warnings:\n" + summary.warnings.join("\n"));
+    }
+  } catch (err) {
+    if (err.kind !== "cancelled") {
+      alert("EPUB export failed: " + err.message);
+    }
+  }
+}
 
-  // Save via Tauri dialog
-  await invoke("save_markdown_file", { content: markdown });
+//============================================
+async function exportSingleFile(filePath) {
+  /**
+   * Export the open chapter, or the whole project if filePath is null,
+   * as one HTML file with styles and images embedded.
+   */
+  if (!projectDir) {
+    return;
+  }
+  try {
+    const summary = await invoke("export_single_file", {
+      projectDir: projectDir,
+      filePath: filePath,
+    });
+    if (summary.warnings.length > 0) {
+      alert("File written with warnings:\n" + summary.warnings.join("\n"));
+    }
+  } catch (err) {
+    if (err.kind !== "cancelled") {
+      alert("Export failed: " + err.message);
+    }
+  }
+}
+
+//============================================
+async function exportSite() {
+  /**
+   * Export the open project as a static website into a chosen folder.
+   */
+  if (!projectDir) {
+    return;
+  }
+  try {
+    const summary = await invoke("export_site", { projectDir: projectDir });
+    alert(`Website exported: ${summary.written.length} files updated.`);
+  } catch (err) {
+    if (err.kind !== "cancelled") {
+      alert("Website export failed: " + err.message);
+    }
+  }
+}
+
+//============================================
+async function startPreview() {
+  /**
+   * Serve the project locally and open the current chapter in the browser.
+   * The page reloads on every save and, while the preview runs, on edits.
+   */
+  if (!projectDir) {
+    return;
+  }
+  try {
+    await invoke("start_preview", {
+      projectDir: projectDir,
+      filePath: currentFile,
+    });
+    previewActive = true;
+    schedulePreviewUpdate();
+  } catch (err) {
+    alert("Live preview failed: " + err.message);
+  }
+}
+
+//============================================
+function schedulePreviewUpdate() {
+  /**
+   * Send the unsaved chapter body to the live preview once typing pauses.
+   */
+  if (!previewActive) {
+    return;
+  }
+  clearTimeout(previewTimer);
+  previewTimer = setTimeout(() => {
+    if (!currentFile || isInSourceMode()) {
+      return;
+    }
+    invoke("update_preview", {
+      filePath: currentFile,
+      bodyHtml: editor.getHTML(),
+    }).catch((err) => console.warn("Preview update failed:", err));
+  }, PREVIEW_DELAY_MS);
+}
+
+//============================================
+async function importEpub() {
+  /**
+   * Unpack an EPUB book into a new project folder and open it.
+   */
+  try {
+    const result = await invoke("import_epub");
+    if (result.warnings.length > 0) {
+      alert("EPUB imported with warnings:\n" + result.warnings.join("\n"));
+    }
+    await showProject(result.project_dir);
+  } catch (err) {
+    if (err.kind !== "cancelled") {
+      alert("EPUB import failed: " + err.message);
+    }
+  }
+}
+
+//============================================
+async function importMarkdownFolder() {
+  /**
+   * Convert a folder of Markdown files into a new project and open it.
+   */
+  try {
+    const result = await invoke("import_markdown_folder");
+    const lines = result.files.map((file) => `${file.path}: ${file.warnings.join("; ")}`);
+    if (lines.length > 0) {
+      alert("Markdown imported with warnings:\n" + lines.join("\n"));
+    }
+    await showProject(result.project_dir);
+  } catch (err) {
+    if (err.kind !== "cancelled") {
+      alert("Markdown import failed: " + err.message);
+    }
+  }
+}
+
+//============================================
+async function importDocx() {
+  /**
+   * Import a Word document into the open project and show its first chapter.
+   */
+  if (!projectDir) {
+    return;
+  }
+  try {
+    const result = await invoke("import_docx", { projectDir: projectDir });
+    if (result.warnings.length > 0) {
+      alert("Word document imported with warnings:\n" + result.warnings.join("\n"));
+    }
+    chapters = await invoke("list_chapters", { projectDir: projectDir });
+    const sidebar = document.querySelector("#sidebar");
+    initSidebar(sidebar, chapters, (chapter) => {
+      loadChapter(chapter);
+    });
+    const first = chapters.find((c) => c.relative_path === result.chapters[0]);
+    if (first) {
+      loadChapter(first);
+    }
+  } catch (err) {
+    if (err.kind !== "cancelled") {
+      alert("Word import failed: " + err.message);
+    }
+  }
+}
+
+//============================================
+async function checkEpub() {
+  /**
+   * Check an EPUB book's structure and list the issues found.
+   */
+  try {
+    const report = await invoke("check_epub");
+    const lines = report.issues.map((issue) => `${issue.severity}: ${issue.message}`);
+    if (lines.length === 0) {
+      alert("No problems found.");
+    } else {
+      alert(lines.join("\n"));
+    }
+  } catch (err) {
+    if (err.kind !== "cancelled") {
+      alert("EPUB check failed: " + err.message);
+    }
+  }
 }
 
 //============================================
@@ -363,6 +655,7 @@ function init() {
     setDirty(true);
     const filename = currentFile ? currentFile.split("/").pop() : null;
     updateStatusBar(filename, true, editor);
+    schedulePreviewUpdate();
   });
 
   // Initialize toolbar: formatting buttons first, then Open/Save prepended
@@ -386,6 +679,12 @@ function init() {
 
   // Listen for native menu actions from Rust
   setupMenuListener();
+
+  // Journal unsaved edits so they survive a crash
+  setInterval(journalCurrentChapter, JOURNAL_INTERVAL_MS);
+  offerRecovery().catch((err) => {
+    alert("Recovering unsaved edits failed: " + (err.message || err));
+  });
 }
 
 //============================================
@@ -419,6 +718,29 @@ function setupMenuListener() {
           invoke("export_chapter", { filePath: currentFile });
         }
         break;
+      case "live_preview":
+        startPreview();
+        break;
+      case "export_epub":
+        exportEpub();
+        break;
+      case "export_single_chapter":
+        if (currentFile) {
+          exportSingleFile(currentFile);
+        }
+        break;
+      case "export_single_book":
+        exportSingleFile(null);
+        break;
+      case "export_site":
+        exportSite();
+        break;
+      case "import_epub":
+        importEpub();
+        break;
+      case "check_epub":
+        checkEpub();
+        break;
       case "close_window":
         getCurrentWindow().close();
         break;
@@ -452,6 +774,12 @@ function setupMenuListener() {
       case "import_markdown":
         importMarkdown();
         break;
+      case "import_markdown_folder":
+        importMarkdownFolder();
+        break;
+      case "import_docx":
+        importDocx();
+        break;
       case "export_markdown":
         exportMarkdown();
         break;